@@ -0,0 +1,245 @@
+use std::ops::{Add, Mul};
+
+use cgmath::{InnerSpace, Matrix4, Quaternion, Vector3, VectorSpace};
+
+use crate::{
+    deformation::DeformationId,
+    world::{Entity, World},
+};
+
+/// How values between keyframes are interpolated.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    /// Spherical for rotations.
+    Linear,
+    /// Hermite spline, with each value stored between its in- and out-tangent.
+    CubicSpline,
+}
+
+#[derive(Debug, Clone)]
+pub enum Keyframes {
+    Translation(Vec<Vector3<f32>>),
+    Rotation(Vec<Quaternion<f32>>),
+    Scale(Vec<Vector3<f32>>),
+    /// Morph target weights, `count` per keyframe.
+    Weights {
+        count: usize,
+        values: Vec<f32>,
+    },
+}
+
+/// The keyframes around a point in time, clamped to the ends.
+#[derive(Debug, Copy, Clone)]
+struct Keyframe {
+    previous: usize,
+    next: usize,
+    /// Position between the keyframes in [0, 1].
+    t: f32,
+    /// Seconds between the keyframes.
+    dt: f32,
+}
+
+/// Keyframes of one property of a node.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub node: usize,
+    /// Ascending, in seconds.
+    pub times: Vec<f32>,
+    pub keyframes: Keyframes,
+    pub interpolation: Interpolation,
+}
+
+impl Channel {
+    fn keyframe(&self, time: f32) -> Keyframe {
+        let last = self.times.len() - 1;
+        let next = self.times.partition_point(|&t| t <= time);
+        if next == 0 || next > last {
+            let index = next.min(last);
+            return Keyframe {
+                previous: index,
+                next: index,
+                t: 0.0,
+                dt: 0.0,
+            };
+        }
+        let dt = self.times[next] - self.times[next - 1];
+        Keyframe {
+            previous: next - 1,
+            next,
+            t: (time - self.times[next - 1]) / dt,
+            dt,
+        }
+    }
+
+    /// Writes the value at the given time into the node or the weights of its morphs.
+    fn sample(&self, time: f32, node: &mut Node, morphs: &mut [Morph]) {
+        let keyframe = self.keyframe(time);
+        match &self.keyframes {
+            Keyframes::Translation(values) => {
+                node.translation = self.interpolate(values, keyframe, Vector3::lerp);
+            }
+            Keyframes::Rotation(values) => {
+                node.rotation = self
+                    .interpolate(values, keyframe, Quaternion::slerp)
+                    .normalize();
+            }
+            Keyframes::Scale(values) => {
+                node.scale = self.interpolate(values, keyframe, Vector3::lerp);
+            }
+            Keyframes::Weights { count, values } => {
+                for morph in morphs.iter_mut().filter(|morph| morph.node == self.node) {
+                    for (target, weight) in morph.weights.iter_mut().enumerate().take(*count) {
+                        let values: Vec<_> = values
+                            .iter()
+                            .skip(target)
+                            .step_by(*count)
+                            .copied()
+                            .collect();
+                        *weight = self.interpolate(&values, keyframe, |a, b, t| a + (b - a) * t);
+                    }
+                }
+            }
+        }
+    }
+
+    fn interpolate<T: Copy + Add<Output = T> + Mul<f32, Output = T>>(
+        &self,
+        values: &[T],
+        Keyframe {
+            previous,
+            next,
+            t,
+            dt,
+        }: Keyframe,
+        lerp: impl Fn(T, T, f32) -> T,
+    ) -> T {
+        match self.interpolation {
+            Interpolation::Step => values[previous],
+            Interpolation::Linear => lerp(values[previous], values[next], t),
+            Interpolation::CubicSpline => {
+                let t2 = t * t;
+                let t3 = t2 * t;
+                values[3 * previous + 1] * (2.0 * t3 - 3.0 * t2 + 1.0)
+                    + values[3 * previous + 2] * (dt * (t3 - 2.0 * t2 + t))
+                    + values[3 * next + 1] * (-2.0 * t3 + 3.0 * t2)
+                    + values[3 * next] * (dt * (t3 - t2))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Clip {
+    pub name: String,
+    /// End of the last keyframe, in seconds.
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+/// A node of an imported hierarchy, with its transform relative to the parent.
+#[derive(Debug, Copy, Clone)]
+pub struct Node {
+    /// Comes before the node in the player's list.
+    pub parent: Option<usize>,
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Node {
+    fn local_transform(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+/// Joints of a skinned mesh.
+#[derive(Debug, Clone)]
+pub struct Skin {
+    pub deformation: DeformationId,
+    /// Node of each joint.
+    pub joints: Vec<usize>,
+    /// Transform the mesh into the space of each joint in the bind pose.
+    pub inverse_bind_matrices: Vec<Matrix4<f32>>,
+    /// The current pose, uploaded by the renderer.
+    pub joint_matrices: Vec<Matrix4<f32>>,
+}
+
+/// Morph target weights of a mesh, animated by its node.
+#[derive(Debug, Clone)]
+pub struct Morph {
+    pub deformation: DeformationId,
+    pub node: usize,
+    /// One per target, uploaded by the renderer.
+    pub weights: Vec<f32>,
+}
+
+/// Plays a clip on a node hierarchy, moving the objects attached to its nodes and posing the
+/// skins bound to it.
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    pub nodes: Vec<Node>,
+    /// An entity and the index of the node it is attached to. Loaders number the entities by
+    /// the model's objects, until the scene spawns them.
+    pub attachments: Vec<(Entity, usize)>,
+    pub skins: Vec<Skin>,
+    pub morphs: Vec<Morph>,
+    /// May be empty for models which are only deformed.
+    pub clips: Vec<Clip>,
+    /// Index of the clip being played.
+    pub clip: usize,
+    /// Seconds since the start of the clip.
+    pub time: f32,
+    pub playing: bool,
+}
+
+impl AnimationPlayer {
+    pub fn advance(&mut self, dt: f32) {
+        if self.playing {
+            self.scrub(dt);
+        }
+    }
+
+    /// Moves the time by the given number of seconds, wrapping around the end of the clip.
+    pub fn scrub(&mut self, seconds: f32) {
+        let duration = self.clips.get(self.clip).map_or(0.0, |clip| clip.duration);
+        self.time = if duration > 0.0 {
+            (self.time + seconds).rem_euclid(duration)
+        } else {
+            0.0
+        };
+    }
+
+    /// Samples the clip at the current time and updates the attached entities' transforms and
+    /// the skins' joint matrices.
+    pub fn apply(&mut self, world: &mut World) {
+        if let Some(clip) = self.clips.get(self.clip) {
+            for channel in &clip.channels {
+                channel.sample(self.time, &mut self.nodes[channel.node], &mut self.morphs);
+            }
+        }
+        let mut transforms: Vec<Matrix4<f32>> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let local = node.local_transform();
+            transforms.push(match node.parent {
+                Some(parent) => transforms[parent] * local,
+                None => local,
+            });
+        }
+        for &(entity, node) in &self.attachments {
+            if let Some(transform) = world.transforms.get_mut(entity) {
+                transform.0 = transforms[node];
+            }
+        }
+        for skin in &mut self.skins {
+            skin.joint_matrices = skin
+                .joints
+                .iter()
+                .zip(&skin.inverse_bind_matrices)
+                .map(|(&joint, inverse_bind_matrix)| transforms[joint] * inverse_bind_matrix)
+                .collect();
+        }
+    }
+}
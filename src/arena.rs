@@ -0,0 +1,328 @@
+use std::ops::Range;
+
+use wgpu::*;
+
+use crate::{mesh::Vertex, render::as_byte_slice};
+
+/// Vertices per shared page, about 15 MB.
+const PAGE_VERTICES: u32 = 1 << 18;
+/// Indices per shared page, three per vertex as in a typical triangle mesh.
+const PAGE_INDICES: u32 = 3 * PAGE_VERTICES;
+
+/// Where the vertices and indices of a mesh are in the arena.
+#[derive(Debug, Copy, Clone)]
+pub struct Allocation {
+    pub page: usize,
+    pub first_vertex: u32,
+    pub vertex_count: u32,
+    pub first_index: u32,
+    pub index_count: u32,
+}
+
+#[derive(Debug)]
+struct Page {
+    vertices: Buffer,
+    indices: Buffer,
+    /// The unused vertices and indices, or none for a page of a single mesh, which is dropped
+    /// once the mesh is freed.
+    free: Option<(FreeList, FreeList)>,
+}
+
+/// Unused ranges, sorted and never adjacent.
+#[derive(Debug)]
+struct FreeList(Vec<Range<u32>>);
+
+/// Packs the vertices and indices of the meshes into a few large buffers, drawn from at offsets,
+/// so that the GPU does not hold a pair of small buffers per mesh and consecutive draws from the
+/// same page need not rebind them. Meshes larger than a page get a page of their own, as do
+/// deformed meshes, whose vertices are written from the start of the buffer.
+#[derive(Debug, Default)]
+pub struct MeshArena {
+    /// Slots of dropped pages stay empty until reused, so that allocations keep their page.
+    pages: Vec<Option<Page>>,
+}
+
+impl MeshArena {
+    /// Uploads the mesh into the first shared page with room, or a new page.
+    pub fn allocate(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        vertices: &[Vertex],
+        indices: &[u32],
+        dedicated: bool,
+    ) -> Allocation {
+        let vertex_count = vertices.len() as u32;
+        let index_count = indices.len() as u32;
+        let shared = !dedicated && vertex_count <= PAGE_VERTICES && index_count <= PAGE_INDICES;
+
+        let found = self
+            .pages
+            .iter_mut()
+            .enumerate()
+            .filter(|_| shared)
+            .find_map(|(index, page)| {
+                let (vertices, indices) = page.as_mut()?.free.as_mut()?;
+                if !vertices.fits(vertex_count) || !indices.fits(index_count) {
+                    return None;
+                }
+                Some((
+                    index,
+                    vertices.take(vertex_count)?,
+                    indices.take(index_count)?,
+                ))
+            });
+        let (page, first_vertex, first_index) = found.unwrap_or_else(|| {
+            let mut page = if shared {
+                Page::new(device, PAGE_VERTICES, PAGE_INDICES, true)
+            } else {
+                Page::new(device, vertex_count, index_count, false)
+            };
+            if let Some((vertices, indices)) = &mut page.free {
+                vertices.take(vertex_count);
+                indices.take(index_count);
+            }
+            let index = match self.pages.iter().position(Option::is_none) {
+                Some(index) => {
+                    self.pages[index] = Some(page);
+                    index
+                }
+                None => {
+                    self.pages.push(Some(page));
+                    self.pages.len() - 1
+                }
+            };
+            (index, 0, 0)
+        });
+
+        if vertex_count > 0 {
+            queue.write_buffer(
+                self.vertices(page),
+                first_vertex as u64 * std::mem::size_of::<Vertex>() as u64,
+                as_byte_slice(vertices),
+            );
+        }
+        if index_count > 0 {
+            queue.write_buffer(
+                self.indices(page),
+                first_index as u64 * std::mem::size_of::<u32>() as u64,
+                as_byte_slice(indices),
+            );
+        }
+        Allocation {
+            page,
+            first_vertex,
+            vertex_count,
+            first_index,
+            index_count,
+        }
+    }
+
+    /// Returns the ranges to their page, dropping the page once nothing is left in it.
+    pub fn free(&mut self, allocation: &Allocation) {
+        let slot = &mut self.pages[allocation.page];
+        let Some((vertices, indices)) = slot.as_mut().and_then(|page| page.free.as_mut()) else {
+            *slot = None;
+            return;
+        };
+        vertices
+            .give_back(allocation.first_vertex..allocation.first_vertex + allocation.vertex_count);
+        indices.give_back(allocation.first_index..allocation.first_index + allocation.index_count);
+        if vertices.is_whole(PAGE_VERTICES) && indices.is_whole(PAGE_INDICES) {
+            *slot = None;
+        }
+    }
+
+    pub fn vertices(&self, page: usize) -> &Buffer {
+        &self.pages[page]
+            .as_ref()
+            .expect("Page has been dropped")
+            .vertices
+    }
+
+    pub fn indices(&self, page: usize) -> &Buffer {
+        &self.pages[page]
+            .as_ref()
+            .expect("Page has been dropped")
+            .indices
+    }
+
+    /// Binds the vertices and indices of the page for drawing meshes allocated in it.
+    pub fn bind<'a>(&'a self, pass: &mut impl util::RenderEncoder<'a>, page: usize) {
+        pass.set_vertex_buffer(0, self.vertices(page).slice(..));
+        pass.set_index_buffer(self.indices(page).slice(..), IndexFormat::Uint32);
+    }
+
+    pub fn memory(&self) -> u64 {
+        self.pages
+            .iter()
+            .flatten()
+            .map(|page| page.vertices.size() + page.indices.size())
+            .sum()
+    }
+}
+
+impl Page {
+    fn new(device: &Device, vertex_count: u32, index_count: u32, shared: bool) -> Self {
+        // Ray-traced shadows build acceleration structures from the buffers.
+        let blas_input = if device
+            .features()
+            .contains(Features::EXPERIMENTAL_RAY_TRACING_ACCELERATION_STRUCTURE)
+        {
+            BufferUsages::BLAS_INPUT
+        } else {
+            BufferUsages::empty()
+        };
+        Page {
+            vertices: device.create_buffer(&BufferDescriptor {
+                label: None,
+                size: vertex_count.max(1) as u64 * std::mem::size_of::<Vertex>() as u64,
+                // Deformation writes the skinned and morphed vertices in place.
+                usage: BufferUsages::VERTEX
+                    | BufferUsages::STORAGE
+                    | BufferUsages::COPY_DST
+                    | blas_input,
+                mapped_at_creation: false,
+            }),
+            indices: device.create_buffer(&BufferDescriptor {
+                label: None,
+                size: index_count.max(1) as u64 * std::mem::size_of::<u32>() as u64,
+                usage: BufferUsages::INDEX | BufferUsages::COPY_DST | blas_input,
+                mapped_at_creation: false,
+            }),
+            free: shared.then(|| (FreeList::new(vertex_count), FreeList::new(index_count))),
+        }
+    }
+}
+
+impl FreeList {
+    fn new(len: u32) -> Self {
+        FreeList(std::iter::once(0..len).collect())
+    }
+
+    fn fits(&self, count: u32) -> bool {
+        count == 0 || self.0.iter().any(|range| range.end - range.start >= count)
+    }
+
+    /// Whether nothing is taken out of the given length.
+    fn is_whole(&self, len: u32) -> bool {
+        self.0.len() == 1 && self.0[0] == (0..len)
+    }
+
+    /// Takes the start of the first free range long enough.
+    fn take(&mut self, count: u32) -> Option<u32> {
+        if count == 0 {
+            return Some(0);
+        }
+        let free = &mut self.0;
+        let index = free
+            .iter()
+            .position(|range| range.end - range.start >= count)?;
+        let start = free[index].start;
+        free[index].start += count;
+        if free[index].is_empty() {
+            free.remove(index);
+        }
+        Some(start)
+    }
+
+    /// Frees the range, merging it with the free ranges next to it.
+    fn give_back(&mut self, range: Range<u32>) {
+        if range.is_empty() {
+            return;
+        }
+        let free = &mut self.0;
+        let index = free.partition_point(|other| other.start < range.start);
+        let joins_previous = index > 0 && free[index - 1].end == range.start;
+        let joins_next = free.get(index).is_some_and(|next| next.start == range.end);
+        match (joins_previous, joins_next) {
+            (true, true) => {
+                free[index - 1].end = free[index].end;
+                free.remove(index);
+            }
+            (true, false) => free[index - 1].end = range.end,
+            (false, true) => free[index].start = range.start,
+            (false, false) => free.insert(index, range),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the free ranges are sorted, apart and disjoint from the taken ones, and that
+    /// together they cover the length.
+    fn check(free: &FreeList, taken: &[Range<u32>], len: u32) {
+        for pair in free.0.windows(2) {
+            assert!(pair[0].end < pair[1].start, "{:?}", free.0);
+        }
+        let mut all: Vec<_> = free.0.iter().chain(taken).cloned().collect();
+        all.sort_by_key(|range| range.start);
+        let mut end = 0;
+        for range in all {
+            assert_eq!(range.start, end, "overlap or gap at {range:?}");
+            end = range.end;
+        }
+        assert_eq!(end, len);
+    }
+
+    #[test]
+    fn coalesces() {
+        let mut free = FreeList::new(100);
+        let mut taken: Vec<_> = [10, 20, 30, 40]
+            .map(|count| {
+                let start = free.take(count).unwrap();
+                start..start + count
+            })
+            .to_vec();
+        assert!(!free.fits(1));
+        assert_eq!(free.take(1), None);
+        // Freed in an order that leaves gaps before merging them.
+        for start in [10, 60, 0, 30] {
+            let index = taken.iter().position(|range| range.start == start).unwrap();
+            free.give_back(taken.remove(index));
+            check(&free, &taken, 100);
+        }
+        assert!(free.is_whole(100));
+    }
+
+    #[test]
+    fn never_overlaps() {
+        const LEN: u32 = 1000;
+        let mut free = FreeList::new(LEN);
+        let mut taken: Vec<Range<u32>> = Vec::new();
+        // A fixed pseudo-random sequence of allocations and frees.
+        let mut state = 12345u32;
+        let mut random = move |bound: u32| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) % bound
+        };
+        for _ in 0..2000 {
+            if !taken.is_empty() && random(3) == 0 {
+                let range = taken.swap_remove(random(taken.len() as u32) as usize);
+                free.give_back(range);
+            } else {
+                let count = 1 + random(50);
+                match free.take(count) {
+                    Some(start) => taken.push(start..start + count),
+                    None => assert!(!free.fits(count)),
+                }
+            }
+            check(&free, &taken, LEN);
+        }
+        for range in taken.drain(..) {
+            free.give_back(range);
+        }
+        assert!(free.is_whole(LEN));
+    }
+
+    #[test]
+    fn empty_ranges() {
+        let mut free = FreeList::new(10);
+        assert!(free.fits(0));
+        assert_eq!(free.take(0), Some(0));
+        free.give_back(5..5);
+        assert!(free.is_whole(10));
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{
+    loader::{self, Model, Source, Unit},
+    render::Renderer,
+    texture::TextureId,
+};
+
+/// Refers to an asset of type `T`, which may still be loading.
+pub struct Handle<T> {
+    index: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Handle({})", self.index)
+    }
+}
+
+/// Quiet time after a change on disk before a model is read again, as editors and exporters
+/// often write files in several steps.
+const RELOAD_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+struct ModelAsset {
+    path: PathBuf,
+    unit: Option<Unit>,
+    /// When reading started, or none while the model is not being read.
+    started: Option<Instant>,
+    /// Whether a version of the model has been added before.
+    loaded: bool,
+    /// When a file next to the model last changed, if it has not been read since.
+    changed: Option<Instant>,
+    /// Textures of the current version, released when it is replaced.
+    textures: Vec<TextureId>,
+}
+
+/// A model read and added to the renderer.
+#[derive(Debug)]
+pub struct LoadedModel {
+    pub handle: Handle<Model>,
+    pub model: Result<Model, String>,
+    /// Replaces an earlier version of the model, which changed on disk.
+    pub reload: bool,
+}
+
+/// Reads model files on background threads, so that large files do not stall the event loop.
+/// Only adding the decoded resources to the renderer happens on the main thread, in `update`.
+///
+/// Models are read again whenever a file in their directory changes, which also catches their
+/// textures and buffers. The textures of the previous version are then released, so that only
+/// the changed ones are uploaded again.
+#[derive(Debug)]
+pub struct Assets {
+    models: Vec<ModelAsset>,
+    sender: Sender<(usize, Result<Source, String>)>,
+    receiver: Receiver<(usize, Result<Source, String>)>,
+    /// None if watching is not supported.
+    watcher: Option<RecommendedWatcher>,
+    watched: HashSet<PathBuf>,
+    changes: Receiver<notify::Result<Event>>,
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        let (change_sender, changes) = channel();
+        let watcher = notify::recommended_watcher(change_sender)
+            .inspect_err(|error| println!("Cannot watch assets: {error}"))
+            .ok();
+        Assets {
+            models: Vec::new(),
+            sender,
+            receiver,
+            watcher,
+            watched: HashSet::new(),
+            changes,
+        }
+    }
+}
+
+impl Assets {
+    /// Starts reading a model. It is handed out by `update` once read, and again whenever it
+    /// changes.
+    pub fn load_model(&mut self, path: &Path, unit: Option<Unit>) -> Handle<Model> {
+        // Changes are reported with absolute paths.
+        let path = std::fs::canonicalize(path).unwrap_or(path.to_path_buf());
+        if let (Some(watcher), Some(directory)) = (&mut self.watcher, path.parent()) {
+            if self.watched.insert(directory.to_path_buf()) {
+                if let Err(error) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+                    println!("Cannot watch {}: {error}", directory.display());
+                }
+            }
+        }
+        let index = self.models.len();
+        self.models.push(ModelAsset {
+            path,
+            unit,
+            started: None,
+            loaded: false,
+            changed: None,
+            textures: Vec::new(),
+        });
+        self.read(index);
+        Handle {
+            index,
+            marker: PhantomData,
+        }
+    }
+
+    fn read(&mut self, index: usize) {
+        let model = &mut self.models[index];
+        model.started = Some(Instant::now());
+        model.changed = None;
+        let sender = self.sender.clone();
+        let path = model.path.clone();
+        let unit = model.unit;
+        std::thread::spawn(move || {
+            // The receiver outlives all threads unless the app is quitting.
+            let _ = sender.send((index, loader::read(&path, unit)));
+        });
+    }
+
+    pub fn path(&self, handle: Handle<Model>) -> &Path {
+        &self.models[handle.index].path
+    }
+
+    /// Whether any model is still being read for the first time.
+    pub fn is_loading(&self) -> bool {
+        self.models
+            .iter()
+            .any(|model| !model.loaded && model.started.is_some())
+    }
+
+    /// Whether any model is being read, or changed on disk and is about to be read again.
+    pub fn is_busy(&self) -> bool {
+        self.models
+            .iter()
+            .any(|model| model.started.is_some() || model.changed.is_some())
+    }
+
+    /// Notes the models whose files changed on disk, to be read again by `update`.
+    pub fn poll_changes(&mut self) {
+        for event in self.changes.try_iter().flatten() {
+            if !(event.kind.is_create() || event.kind.is_modify()) {
+                continue;
+            }
+            for model in &mut self.models {
+                if event
+                    .paths
+                    .iter()
+                    .any(|path| path.parent() == model.path.parent())
+                {
+                    model.changed = Some(Instant::now());
+                }
+            }
+        }
+    }
+
+    /// Rereads the models which changed on disk and adds the models read since the last call to
+    /// the renderer. Called once per frame.
+    pub fn update(&mut self, renderer: &mut Renderer) -> Vec<LoadedModel> {
+        self.poll_changes();
+        for index in 0..self.models.len() {
+            let model = &self.models[index];
+            if model.started.is_none()
+                && model
+                    .changed
+                    .is_some_and(|changed| changed.elapsed() >= RELOAD_DELAY)
+            {
+                println!("Reloading {}", model.path.display());
+                self.read(index);
+            }
+        }
+
+        self.receiver
+            .try_iter()
+            .map(|(index, source)| {
+                let model = source.and_then(|source| source.add(renderer));
+                let asset = &mut self.models[index];
+                if let (Ok(_), Some(started)) = (&model, asset.started) {
+                    println!(
+                        "Loaded {} in {:.2} s",
+                        asset.path.display(),
+                        started.elapsed().as_secs_f32()
+                    );
+                }
+                asset.started = None;
+                if let Ok(model) = &model {
+                    // After adding the new version, so that unchanged textures are shared.
+                    for texture in std::mem::replace(&mut asset.textures, model.textures.clone()) {
+                        renderer.release_texture(texture);
+                    }
+                }
+                let reload = asset.loaded;
+                asset.loaded |= model.is_ok();
+                LoadedModel {
+                    handle: Handle {
+                        index,
+                        marker: PhantomData,
+                    },
+                    model,
+                    reload,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,173 @@
+use std::{collections::HashMap, num::NonZeroU32};
+
+use tracing::warn;
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::{
+    material::{Material, MaterialBinding, MaterialUniforms},
+    render::as_byte_slice,
+    texture::TextureCache,
+};
+
+/// Features required for bindless materials, which are bound per draw without them.
+pub const BINDLESS_FEATURES: Features = Features::TEXTURE_BINDING_ARRAY
+    .union(Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING);
+
+/// Sampled textures per shader stage left for the other bindings of the scene shader.
+const RESERVED_TEXTURES: u32 = 16;
+/// Fewest textures in the array for bindless materials to be worthwhile.
+const MIN_TEXTURES: u32 = 64;
+/// Most textures in the array, as the whole array is rebound whenever a material is added.
+const MAX_TEXTURES: u32 = 1024;
+
+/// The materials in one storage buffer and their textures in one binding array, bound once and
+/// indexed by the material of each object, so that drawing does not switch bind groups between
+/// objects. Unloaded materials keep their uniforms but are drawn white.
+#[derive(Debug)]
+pub struct BindlessMaterials {
+    pub layout: BindGroupLayout,
+    capacity: u32,
+    /// None until rebuilt after the materials changed.
+    bind_group: Option<BindGroup>,
+    /// Whether the array was found to be full, so that it is only reported once.
+    full: bool,
+}
+
+impl BindlessMaterials {
+    /// The textures the array can hold, or `None` if the adapter lacks large enough arrays of
+    /// textures. The device has to be created with `BINDLESS_FEATURES` and the limit of sampled
+    /// textures raised to the adapter's.
+    pub fn capacity(adapter: &Adapter) -> Option<u32> {
+        if !adapter.features().contains(BINDLESS_FEATURES) {
+            return None;
+        }
+        let capacity = adapter
+            .limits()
+            .max_sampled_textures_per_shader_stage
+            .saturating_sub(RESERVED_TEXTURES)
+            .min(MAX_TEXTURES);
+        (capacity >= MIN_TEXTURES).then_some(capacity)
+    }
+
+    pub fn new(device: &Device, capacity: u32) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: NonZeroU32::new(capacity),
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        BindlessMaterials {
+            layout,
+            capacity,
+            bind_group: None,
+            full: false,
+        }
+    }
+
+    /// The bind group of all materials, once `update` has built it.
+    pub fn bind_group(&self) -> Option<&BindGroup> {
+        self.bind_group.as_ref()
+    }
+
+    /// Rebuilds the bind group with `update`, after materials were added, loaded or unloaded.
+    pub fn invalidate(&mut self) {
+        self.bind_group = None;
+    }
+
+    /// Builds the bind group if it was invalidated. Textures beyond the capacity are drawn white.
+    pub fn update(
+        &mut self,
+        device: &Device,
+        materials: &[MaterialBinding],
+        textures: &TextureCache,
+        white_texture: &TextureView,
+        sampler: &Sampler,
+    ) {
+        if self.bind_group.is_some() {
+            return;
+        }
+        let mut views = vec![white_texture];
+        let mut slots = HashMap::new();
+        let mut uniforms: Vec<_> = materials
+            .iter()
+            .map(|binding| {
+                let texture = binding
+                    .material
+                    .base_color_texture
+                    .filter(|_| binding.bind_group.is_some())
+                    .map_or(0, |id| {
+                        *slots.entry(id).or_insert_with(|| {
+                            if views.len() < self.capacity as usize {
+                                views.push(textures.view(id));
+                                views.len() as u32 - 1
+                            } else {
+                                if !std::mem::replace(&mut self.full, true) {
+                                    warn!(
+                                        "More than {} textures, drawing the rest white",
+                                        self.capacity
+                                    );
+                                }
+                                0
+                            }
+                        })
+                    });
+                MaterialUniforms::with_texture(&binding.material, texture)
+            })
+            .collect();
+        // Storage buffers cannot be empty.
+        if uniforms.is_empty() {
+            uniforms.push(MaterialUniforms::from(&Material::default()));
+        }
+        views.resize(self.capacity as usize, white_texture);
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&uniforms),
+            usage: BufferUsages::STORAGE,
+        });
+        self.bind_group = Some(device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureViewArray(&views),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(sampler),
+                },
+            ],
+        }));
+    }
+}
@@ -1,5 +1,19 @@
-use cgmath::{Matrix4, Quaternion, Rotation3, Vector3};
+use std::f32::consts::{PI, TAU};
 
+use cgmath::{InnerSpace, Matrix4, Quaternion, Rad, Rotation3, SquareMatrix, Vector3, Vector4};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    render::{FAR, FOVY, NEAR},
+    world::Layers,
+};
+
+/// Radians within which the smoothed camera snaps to its target.
+const SETTLE_ANGLE: f32 = 1e-4;
+/// Fraction of the target's radius within which the smoothed camera snaps to it.
+const SETTLE_DISTANCE: f32 = 1e-4;
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Camera {
     pub yaw: f32,
     pub pitch: f32,
@@ -10,17 +24,105 @@ impl Camera {
     pub fn matrix(&self) -> Matrix4<f32> {
         let yaw = Quaternion::from_angle_y(cgmath::Rad(self.yaw));
         let pitch = Quaternion::from_angle_x(cgmath::Rad(self.pitch));
-        let translation = Matrix4::from_translation(Vector3::new(0.0, 0.0, -1.0 * self.radius));
+        let translation = Matrix4::from_translation(Vector3::new(0.0, 0.0, -self.radius));
         translation * Matrix4::from(pitch * yaw)
     }
 
+    /// Moves just far enough away to see all of a sphere around the origin.
+    pub fn frame(&mut self, radius: f32) {
+        self.radius = radius / (0.5 * Rad::from(FOVY).0).sin();
+    }
+
+    /// Turns to look at the origin from the given direction, the shortest way round. Looking from
+    /// straight above or below keeps the yaw.
+    pub fn look_from(&mut self, direction: Vector3<f32>) {
+        let direction = direction.normalize();
+        let pitch = direction.y.clamp(-1.0, 1.0).asin();
+        let yaw = if direction.x == 0.0 && direction.z == 0.0 {
+            self.yaw
+        } else {
+            (-direction.x).atan2(direction.z)
+        };
+        // Within half a turn, so that the smoothed camera does not spin around.
+        let nearest = |from: f32, to: f32| from + (to - from + PI).rem_euclid(TAU) - PI;
+        self.yaw = nearest(self.yaw, yaw);
+        self.pitch = nearest(self.pitch, pitch);
+    }
+
     /// Interpolate between this camera and another camera in a frame-rate independent way.
-    pub fn lerp_exp(&mut self, other: &Self, stiffness: f32, dt: f32) {
+    /// Snaps to the other camera once close enough, returning whether it is still moving.
+    pub fn lerp_exp(&mut self, other: &Self, stiffness: f32, dt: f32) -> bool {
         let rate = -60.0 * (1.0 - stiffness).ln();
         let interpolant = 1.0 - (-rate * dt).exp();
         self.yaw += interpolant * (other.yaw - self.yaw);
         self.pitch += interpolant * (other.pitch - self.pitch);
         self.radius += interpolant * (other.radius - self.radius);
+        let settled = (other.yaw - self.yaw).abs() < SETTLE_ANGLE
+            && (other.pitch - self.pitch).abs() < SETTLE_ANGLE
+            && (other.radius - self.radius).abs() < SETTLE_DISTANCE * other.radius;
+        if settled {
+            *self = *other;
+        }
+        !settled
+    }
+}
+
+/// A perspective projection, with the aspect ratio taken from the window.
+#[derive(Debug, Copy, Clone)]
+pub struct Projection {
+    pub fovy: Rad<f32>,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection {
+            fovy: FOVY.into(),
+            near: NEAR,
+            far: FAR,
+        }
+    }
+}
+
+impl Projection {
+    pub fn matrix(&self, aspect: f32) -> Matrix4<f32> {
+        let (near, far) = (self.near, self.far);
+        let tan_half_fovy = (0.5 * self.fovy.0).tan();
+        Matrix4::from_cols(
+            Vector4::new(1.0 / (aspect * tan_half_fovy), 0.0, 0.0, 0.0),
+            Vector4::new(0.0, 1.0 / tan_half_fovy, 0.0, 0.0),
+            Vector4::new(0.0, 0.0, -(far + near) / (far - near), -1.0),
+            Vector4::new(0.0, 0.0, -2.0 * far * near / (far - near), 0.0),
+        )
+    }
+}
+
+/// A view to return to, through the orbit camera or a scene camera.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub orbit_camera: Camera,
+    /// Index of the scene camera looked through, or none for the orbit camera.
+    pub camera: Option<usize>,
+}
+
+/// A camera placed in the scene, such as one imported from a model.
+#[derive(Debug, Clone)]
+pub struct SceneCamera {
+    pub name: String,
+    /// Places the camera, looking along -Z with +Y up.
+    pub transform: Matrix4<f32>,
+    pub projection: Projection,
+    /// Layers seen through the camera.
+    pub layers: Layers,
+}
+
+impl SceneCamera {
+    /// Everything but the editor's aids.
+    pub const LAYERS: Layers = Layers::ALL.without(Layers::EDITOR);
+
+    pub fn view(&self) -> Matrix4<f32> {
+        self.transform.invert().unwrap_or(Matrix4::identity())
     }
 }
 
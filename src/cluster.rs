@@ -0,0 +1,82 @@
+use wgpu::*;
+
+const CLUSTER_COUNT_X: u32 = 16;
+const CLUSTER_COUNT_Y: u32 = 9;
+const CLUSTER_COUNT_Z: u32 = 24;
+/// Lights beyond this many per cluster are dropped.
+const MAX_CLUSTER_LIGHTS: u32 = 128;
+
+/// Bins the point and spot lights into a grid of screen tiles and exponential depth slices,
+/// so that each fragment only evaluates the lights reaching its cluster.
+#[derive(Debug)]
+pub struct LightClusters {
+    pipeline: ComputePipeline,
+    /// Per cluster, the point and spot light counts followed by their indices.
+    pub buffer: Buffer,
+}
+
+impl LightClusters {
+    pub fn new(device: &Device) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(include_str!("cluster.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: &module,
+            entry_point: Some("cull"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            usage: BufferUsages::STORAGE,
+            size: (CLUSTER_COUNT_X * CLUSTER_COUNT_Y * CLUSTER_COUNT_Z) as u64
+                * (MAX_CLUSTER_LIGHTS as u64 + 2)
+                * std::mem::size_of::<u32>() as u64,
+            mapped_at_creation: false,
+        });
+
+        LightClusters { pipeline, buffer }
+    }
+
+    /// Rebuilds the clusters from the frame uniforms and the light storage buffers.
+    pub fn cull(
+        &self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        uniforms: &Buffer,
+        point_lights: &Buffer,
+        spot_lights: &Buffer,
+    ) {
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniforms.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: point_lights.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: spot_lights.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: self.buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(1, 1, CLUSTER_COUNT_Z);
+    }
+}
@@ -0,0 +1,94 @@
+/// A prefix of the frame uniforms in the main shader.
+struct Uniforms {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    environment: mat4x4<f32>,
+    camera_position: vec4<f32>,
+    viewport: vec4<f32>,
+    toon: u32,
+    point_light_count: u32,
+    spot_light_count: u32,
+    rect_light_count: u32,
+    inverse_view_projection: mat4x4<f32>,
+    near: f32,
+    far: f32,
+}
+
+struct PointLight {
+    position: vec3<f32>,
+    radius: f32,
+    color: vec3<f32>,
+    attenuation: f32,
+    shadow_index: i32,
+}
+
+struct SpotLight {
+    position: vec3<f32>,
+    radius: f32,
+    color: vec3<f32>,
+    attenuation: f32,
+    direction: vec3<f32>,
+    cos_inner_angle: f32,
+    cos_outer_angle: f32,
+}
+
+const CLUSTER_COUNT_X: u32 = 16u;
+const CLUSTER_COUNT_Y: u32 = 9u;
+const CLUSTER_COUNT_Z: u32 = 24u;
+const MAX_CLUSTER_LIGHTS: u32 = 128u;
+/// The point and spot light counts followed by their indices.
+const CLUSTER_STRIDE: u32 = MAX_CLUSTER_LIGHTS + 2u;
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(0) @binding(1) var<storage, read> point_lights: array<PointLight>;
+@group(0) @binding(2) var<storage, read> spot_lights: array<SpotLight>;
+@group(0) @binding(3) var<storage, read_write> clusters: array<u32>;
+
+/// View-space depth at which the slice begins, growing exponentially from the near plane.
+fn slice_depth(slice: u32) -> f32 {
+    return uniforms.near * pow(uniforms.far / uniforms.near, f32(slice) / f32(CLUSTER_COUNT_Z));
+}
+
+fn sphere_intersects_box(center: vec3<f32>, radius: f32, box_min: vec3<f32>, box_max: vec3<f32>) -> bool {
+    let offset = clamp(center, box_min, box_max) - center;
+    return dot(offset, offset) <= radius * radius;
+}
+
+/// Bins the point and spot lights by their spheres of influence into one view-space cluster.
+@compute @workgroup_size(16, 9, 1)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    // Tiles run downwards from the top of the screen, like pixel coordinates.
+    let ndc_min = vec2<f32>(
+        f32(id.x) / f32(CLUSTER_COUNT_X) * 2.0 - 1.0,
+        1.0 - f32(id.y + 1u) / f32(CLUSTER_COUNT_Y) * 2.0,
+    );
+    let ndc_max = ndc_min + 2.0 / vec2<f32>(f32(CLUSTER_COUNT_X), f32(CLUSTER_COUNT_Y));
+    let near = slice_depth(id.z);
+    let far = slice_depth(id.z + 1u);
+    // Undoes the perspective scaling at both depths, which bounds the frustum slice.
+    let scale = 1.0 / vec2<f32>(uniforms.projection[0][0], uniforms.projection[1][1]);
+    let xy_min = min(ndc_min * near, ndc_min * far) * scale;
+    let xy_max = max(ndc_max * near, ndc_max * far) * scale;
+    let box_min = vec3<f32>(xy_min, -far);
+    let box_max = vec3<f32>(xy_max, -near);
+
+    let cluster = ((id.z * CLUSTER_COUNT_Y + id.y) * CLUSTER_COUNT_X + id.x) * CLUSTER_STRIDE;
+    var count = 0u;
+    for (var i = 0u; i < uniforms.point_light_count && count < MAX_CLUSTER_LIGHTS; i++) {
+        let center = (uniforms.view * vec4<f32>(point_lights[i].position, 1.0)).xyz;
+        if sphere_intersects_box(center, point_lights[i].radius, box_min, box_max) {
+            clusters[cluster + 2u + count] = i;
+            count++;
+        }
+    }
+    let point_count = count;
+    for (var i = 0u; i < uniforms.spot_light_count && count < MAX_CLUSTER_LIGHTS; i++) {
+        let center = (uniforms.view * vec4<f32>(spot_lights[i].position, 1.0)).xyz;
+        if sphere_intersects_box(center, spot_lights[i].radius, box_min, box_max) {
+            clusters[cluster + 2u + count] = i;
+            count++;
+        }
+    }
+    clusters[cluster] = point_count;
+    clusters[cluster + 1u] = count - point_count;
+}
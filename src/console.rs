@@ -0,0 +1,111 @@
+use std::{
+    collections::VecDeque,
+    fmt::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Level, Metadata, Subscriber,
+};
+
+/// Lines kept for the console, dropping the oldest beyond.
+const MAX_LINES: usize = 500;
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub level: Level,
+    pub text: String,
+}
+
+static LINES: Mutex<VecDeque<Line>> = Mutex::new(VecDeque::new());
+
+/// Collects the events for the in-app console and prints those of info level and above. Spans,
+/// such as the one around each frame, are only counted, leaving their timing to profilers
+/// subscribing in its place.
+#[derive(Debug, Default)]
+pub struct Console {
+    next_span: AtomicU64,
+}
+
+/// Sets the console as the global subscriber.
+pub fn init() {
+    tracing::subscriber::set_global_default(Console::default())
+        .expect("Cannot set the tracing subscriber");
+}
+
+/// The recent lines up to the level of detail, oldest first.
+pub fn lines(level: Level) -> Vec<Line> {
+    LINES
+        .lock()
+        .unwrap_or_else(|error| error.into_inner())
+        .iter()
+        .filter(|line| line.level <= level)
+        .cloned()
+        .collect()
+}
+
+/// Formats the message followed by the other fields as `name=value`.
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let fields = std::mem::take(&mut self.0);
+            write!(self.0, "{value:?}{fields}").unwrap();
+        } else {
+            write!(self.0, " {}={value:?}", field.name()).unwrap();
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0.insert_str(0, value);
+        } else {
+            write!(self.0, " {}={value}", field.name()).unwrap();
+        }
+    }
+}
+
+impl Subscriber for Console {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+        span::Id::from_u64(self.next_span.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut message = Message::default();
+        event.record(&mut message);
+        let level = *event.metadata().level();
+        if level <= Level::INFO {
+            if level == Level::INFO {
+                println!("{}", message.0);
+            } else {
+                println!("{level}: {}", message.0);
+            }
+        }
+        let mut lines = LINES.lock().unwrap_or_else(|error| error.into_inner());
+        if lines.len() == MAX_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(Line {
+            level,
+            text: message.0,
+        });
+    }
+
+    fn enter(&self, _: &span::Id) {}
+
+    fn exit(&self, _: &span::Id) {}
+}
@@ -0,0 +1,20 @@
+/// World-space direction through texel coordinates `uv ∈ [-1, 1]²` of the given cube face.
+/// Mirrors `environment::face_direction`.
+fn face_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let u = uv.x;
+    let v = uv.y;
+    switch face {
+        case 0u: { return normalize(vec3<f32>(1.0, -v, -u)); }
+        case 1u: { return normalize(vec3<f32>(-1.0, -v, u)); }
+        case 2u: { return normalize(vec3<f32>(u, 1.0, v)); }
+        case 3u: { return normalize(vec3<f32>(u, -1.0, -v)); }
+        case 4u: { return normalize(vec3<f32>(u, -v, 1.0)); }
+        default: { return normalize(vec3<f32>(-u, -v, -1.0)); }
+    }
+}
+
+/// Direction through the center of a texel of a cubemap storage texture.
+fn texel_direction(id: vec3<u32>, size: u32) -> vec3<f32> {
+    let uv = 2.0 * (vec2<f32>(id.xy) + 0.5) / f32(size) - 1.0;
+    return face_direction(id.z, uv);
+}
@@ -0,0 +1,390 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix4, SquareMatrix, Vector4};
+use tracing::error;
+use wgpu::*;
+
+use crate::{
+    hiz::HiZ,
+    mesh::Mesh,
+    render::{as_byte_slice, stage, INDIRECT_ARGS_STRIDE},
+};
+
+/// Objects tested per workgroup.
+const WORKGROUP_SIZE: u32 = 64;
+/// Group of objects which are only drawn by their own arguments.
+const NO_GROUP: u32 = u32::MAX;
+
+/// Features required for compacting the drawn objects' arguments into a multi-draw per group,
+/// and for drawing the objects as the instance indexing their uniforms.
+pub const COMPACTION_FEATURES: Features = Features::MULTI_DRAW_INDIRECT
+    .union(Features::MULTI_DRAW_INDIRECT_COUNT)
+    .union(Features::INDIRECT_FIRST_INSTANCE);
+
+#[derive(Debug, Copy, Clone)]
+struct FrustumUniforms {
+    #[allow(dead_code)]
+    planes: [Vector4<f32>; 6],
+    #[allow(dead_code)]
+    object_count: u32,
+    #[allow(dead_code)]
+    occlusion: u32,
+    #[allow(dead_code)]
+    padding: [u32; 2],
+    #[allow(dead_code)]
+    occlusion_view_projection: Matrix4<f32>,
+}
+
+/// Why an object was not drawn.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    Frustum,
+    /// Hidden behind the depth of the previous frame.
+    Occlusion,
+}
+
+/// What the culling pass needs to know of an object besides its model matrix.
+#[derive(Debug, Copy, Clone)]
+pub struct CullBounds {
+    /// Center and radius in model space.
+    #[allow(dead_code)]
+    sphere: Vector4<f32>,
+    #[allow(dead_code)]
+    index_count: u32,
+    #[allow(dead_code)]
+    never_culled: u32,
+    /// Where the mesh is in its page of the arena.
+    #[allow(dead_code)]
+    first_index: u32,
+    #[allow(dead_code)]
+    base_vertex: i32,
+    #[allow(dead_code)]
+    first_instance: u32,
+    /// Multi-draw the object's arguments are compacted into, if any.
+    #[allow(dead_code)]
+    group: u32,
+    /// Where the group's arguments start in the compacted arguments.
+    #[allow(dead_code)]
+    group_offset: u32,
+    #[allow(dead_code)]
+    padding: u32,
+}
+
+impl CullBounds {
+    /// The sphere around the bounds of a mesh drawn at the level of detail, which is always
+    /// drawn if it is deformed beyond its bounds. The instance is drawn as given.
+    pub fn new(mesh: &Mesh, level: usize, never_culled: bool, first_instance: u32) -> Self {
+        let bounds = &mesh.bounds;
+        let arguments = mesh.indirect_args(level);
+        CullBounds {
+            sphere: bounds
+                .center()
+                .to_vec()
+                .extend(0.5 * (bounds.max - bounds.min).magnitude()),
+            index_count: arguments.index_count,
+            never_culled: never_culled as u32,
+            first_index: arguments.first_index,
+            base_vertex: arguments.base_vertex,
+            first_instance,
+            group: NO_GROUP,
+            group_offset: 0,
+            padding: 0,
+        }
+    }
+
+    /// Compacts the object's arguments into the group's multi-draw if drawn, whose arguments
+    /// start at the offset.
+    pub fn compact(&mut self, group: u32, offset: u32) {
+        self.group = group;
+        self.group_offset = offset;
+    }
+}
+
+/// Tests the objects' bounding spheres against the view frustum, and optionally the depth
+/// pyramid of the previous frame, in a compute pass writing the arguments of an indirect draw
+/// per object: one instance if it may be visible, none otherwise. The arguments of visible
+/// objects in a group are also appended to the group's range of compacted arguments and
+/// counted, for drawing the group with a single multi-draw.
+#[derive(Debug)]
+pub struct GpuCulling {
+    pipeline: ComputePipeline,
+    frustum_buffer: Buffer,
+    bounds_buffer: Buffer,
+    /// The draw arguments, in the order of the object uniforms.
+    pub draws: Buffer,
+    /// The arguments of each group's visible objects, from the group's offset on.
+    pub compacted: Buffer,
+    /// The number of compacted arguments per group.
+    pub counts: Buffer,
+    /// Built after drawing for the next frame's culling, if occlusion culling is enabled.
+    pub depth_pyramid: HiZ,
+    rejection_buffer: Buffer,
+    /// Reads the rejections back for debugging, a few frames late.
+    pub read_back: bool,
+    readback_buffer: Buffer,
+    mapped: Arc<AtomicBool>,
+    /// Whether the readback buffer is being copied to or mapped.
+    busy: bool,
+    /// Copied to this frame, to be mapped once submitted.
+    submitted: bool,
+    rejections: Vec<u32>,
+}
+
+impl GpuCulling {
+    pub fn new(device: &Device) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(include_str!("culling.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: &module,
+            entry_point: Some("cull"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let frustum_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            size: std::mem::size_of::<FrustumUniforms>() as u64,
+            mapped_at_creation: false,
+        });
+        let (bounds_buffer, draws, rejection_buffer) = create_buffers(device, 1);
+        let (compacted, counts) = create_compaction_buffers(device, 1);
+        GpuCulling {
+            pipeline,
+            frustum_buffer,
+            bounds_buffer,
+            draws,
+            compacted,
+            counts,
+            depth_pyramid: HiZ::new(device),
+            rejection_buffer,
+            read_back: false,
+            readback_buffer: create_readback_buffer(device, 1),
+            mapped: Arc::new(AtomicBool::new(false)),
+            busy: false,
+            submitted: false,
+            rejections: Vec::new(),
+        }
+    }
+
+    /// Why the object in the slot was not drawn, as last read back.
+    pub fn rejection(&self, slot: usize) -> Option<Rejection> {
+        match self.rejections.get(slot) {
+            Some(1) => Some(Rejection::Frustum),
+            Some(2) => Some(Rejection::Occlusion),
+            _ => None,
+        }
+    }
+
+    /// Records culling the objects against the frustum of the view-projection, and against the
+    /// depth pyramid if it is built. The objects are the object uniforms, whose buffer has to be
+    /// bindable as storage.
+    pub fn cull(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view_projection: Matrix4<f32>,
+        objects: &Buffer,
+        bounds: &[CullBounds],
+    ) {
+        if bounds.is_empty() {
+            return;
+        }
+        let count = bounds.len() as u64;
+        if count * INDIRECT_ARGS_STRIDE > self.draws.size() {
+            (self.bounds_buffer, self.draws, self.rejection_buffer) =
+                create_buffers(device, count.next_power_of_two());
+            (self.compacted, self.counts) =
+                create_compaction_buffers(device, count.next_power_of_two());
+        }
+        encoder.clear_buffer(&self.counts, 0, None);
+        let occlusion_view_projection = self.depth_pyramid.view_projection;
+
+        // Rows combined into the planes the clip-space bounds -w <= x, y <= w and 0 <= z <= w
+        // describe.
+        let row = |index| view_projection.row(index);
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(2),
+            row(3) - row(2),
+        ]
+        .map(|plane| plane / plane.truncate().magnitude());
+        stage(
+            device,
+            staging_belt,
+            encoder,
+            &self.frustum_buffer,
+            as_byte_slice(&[FrustumUniforms {
+                planes,
+                object_count: bounds.len() as u32,
+                occlusion: occlusion_view_projection.is_some() as u32,
+                padding: [0; 2],
+                occlusion_view_projection: occlusion_view_projection.unwrap_or(Matrix4::identity()),
+            }]),
+        );
+        stage(
+            device,
+            staging_belt,
+            encoder,
+            &self.bounds_buffer,
+            as_byte_slice(bounds),
+        );
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: self.frustum_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: objects.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.bounds_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: self.draws.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: self.rejection_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: BindingResource::TextureView(&self.depth_pyramid.view),
+                },
+                BindGroupEntry {
+                    binding: 6,
+                    resource: self.counts.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 7,
+                    resource: self.compacted.as_entire_binding(),
+                },
+            ],
+        });
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(bounds.len().div_ceil(WORKGROUP_SIZE as usize) as u32, 1, 1);
+        drop(pass);
+
+        if self.read_back && !self.busy {
+            let size = count * std::mem::size_of::<u32>() as u64;
+            if size > self.readback_buffer.size() {
+                self.readback_buffer = create_readback_buffer(device, count.next_power_of_two());
+            }
+            encoder.copy_buffer_to_buffer(
+                &self.rejection_buffer,
+                0,
+                &self.readback_buffer,
+                0,
+                size,
+            );
+            self.rejections.resize(count as usize, 0);
+            self.busy = true;
+            self.submitted = true;
+        } else if !self.read_back {
+            self.rejections.clear();
+        }
+    }
+
+    /// Starts mapping this frame's rejections, once submitted.
+    pub fn map(&mut self) {
+        if !std::mem::take(&mut self.submitted) {
+            return;
+        }
+        let mapped = self.mapped.clone();
+        self.readback_buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(error) => error!("Cannot read back the culling: {error}"),
+            });
+    }
+
+    /// Takes the rejections read back since, once the device has been polled.
+    pub fn collect(&mut self) {
+        if !self.mapped.load(Ordering::Acquire) {
+            return;
+        }
+        let count = self.rejections.len();
+        for (rejection, bytes) in self.rejections.iter_mut().zip(
+            self.readback_buffer
+                .slice(..)
+                .get_mapped_range()
+                .chunks_exact(4)
+                .take(count),
+        ) {
+            *rejection = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+        self.readback_buffer.unmap();
+        self.mapped.store(false, Ordering::Release);
+        self.busy = false;
+    }
+}
+
+/// The bounds, draw arguments and rejections of as many objects.
+fn create_buffers(device: &Device, capacity: u64) -> (Buffer, Buffer, Buffer) {
+    let bounds = device.create_buffer(&BufferDescriptor {
+        label: None,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        size: capacity * std::mem::size_of::<CullBounds>() as u64,
+        mapped_at_creation: false,
+    });
+    let draws = device.create_buffer(&BufferDescriptor {
+        label: None,
+        usage: BufferUsages::INDIRECT | BufferUsages::STORAGE,
+        size: capacity * INDIRECT_ARGS_STRIDE,
+        mapped_at_creation: false,
+    });
+    let rejections = device.create_buffer(&BufferDescriptor {
+        label: None,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        size: capacity * std::mem::size_of::<u32>() as u64,
+        mapped_at_creation: false,
+    });
+    (bounds, draws, rejections)
+}
+
+/// The compacted arguments and counts of groups of as many objects in total.
+fn create_compaction_buffers(device: &Device, capacity: u64) -> (Buffer, Buffer) {
+    let compacted = device.create_buffer(&BufferDescriptor {
+        label: None,
+        usage: BufferUsages::INDIRECT | BufferUsages::STORAGE,
+        size: capacity * INDIRECT_ARGS_STRIDE,
+        mapped_at_creation: false,
+    });
+    // Each object is in at most one group, so there are no more groups than objects.
+    let counts = device.create_buffer(&BufferDescriptor {
+        label: None,
+        usage: BufferUsages::INDIRECT | BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        size: capacity * std::mem::size_of::<u32>() as u64,
+        mapped_at_creation: false,
+    });
+    (compacted, counts)
+}
+
+fn create_readback_buffer(device: &Device, capacity: u64) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: None,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        size: capacity * std::mem::size_of::<u32>() as u64,
+        mapped_at_creation: false,
+    })
+}
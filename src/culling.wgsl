@@ -0,0 +1,143 @@
+struct Frustum {
+    /// Inward facing, with the distance from the origin in `w`.
+    planes: array<vec4<f32>, 6>,
+    object_count: u32,
+    /// Whether objects behind the depth pyramid are culled as well.
+    occlusion: u32,
+    /// What the depth pyramid was drawn through.
+    occlusion_view_projection: mat4x4<f32>,
+}
+
+/// The object uniforms, of which only the model matrix is read.
+struct Object {
+    @size(256) model: mat4x4<f32>,
+}
+
+struct Bounds {
+    /// Center and radius in model space.
+    sphere: vec4<f32>,
+    index_count: u32,
+    never_culled: u32,
+    first_index: u32,
+    base_vertex: i32,
+    /// The object's slot, if the scene shader indexes the objects by the instance.
+    first_instance: u32,
+    /// Multi-draw the object is compacted into, or `NO_GROUP`.
+    group: u32,
+    /// Where the group's arguments start in the compacted arguments.
+    group_offset: u32,
+}
+
+/// Objects drawn only by their own arguments.
+const NO_GROUP: u32 = 0xffffffffu;
+
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0) var<uniform> frustum: Frustum;
+@group(0) @binding(1) var<storage, read> objects: array<Object>;
+@group(0) @binding(2) var<storage, read> bounds: array<Bounds>;
+@group(0) @binding(3) var<storage, read_write> draws: array<DrawArgs>;
+/// Per object, 0 if drawn, 1 if outside the frustum and 2 if occluded.
+@group(0) @binding(4) var<storage, read_write> rejections: array<u32>;
+/// The farthest depth of each texel's area, at decreasing resolutions.
+@group(0) @binding(5) var depth_pyramid: texture_2d<f32>;
+/// Objects drawn per group, cleared before the pass.
+@group(0) @binding(6) var<storage, read_write> counts: array<atomic<u32>>;
+/// The arguments of the drawn objects, packed at the start of their group's range.
+@group(0) @binding(7) var<storage, read_write> compacted: array<DrawArgs>;
+
+/// Whether the sphere lies behind the depth pyramid. Its bounding box's screen rectangle is
+/// tested at the level where it spans at most two texels across, against their farthest depth.
+fn occluded(center: vec3<f32>, radius: f32) -> bool {
+    var uv_min = vec2<f32>(1.0);
+    var uv_max = vec2<f32>(0.0);
+    var nearest = 1.0;
+    for (var corner = 0u; corner < 8u; corner++) {
+        let offset = vec3<f32>(
+            select(-radius, radius, (corner & 1u) != 0u),
+            select(-radius, radius, (corner & 2u) != 0u),
+            select(-radius, radius, (corner & 4u) != 0u),
+        );
+        let clip = frustum.occlusion_view_projection * vec4<f32>(center + offset, 1.0);
+        // Reaching behind the camera, where the rectangle is unbounded.
+        if clip.w <= 0.0 {
+            return false;
+        }
+        let ndc = clip.xyz / clip.w;
+        let uv = vec2<f32>(0.5 * ndc.x + 0.5, 0.5 - 0.5 * ndc.y);
+        uv_min = min(uv_min, uv);
+        uv_max = max(uv_max, uv);
+        nearest = min(nearest, ndc.z);
+    }
+    uv_min = clamp(uv_min, vec2<f32>(0.0), vec2<f32>(1.0));
+    uv_max = clamp(uv_max, vec2<f32>(0.0), vec2<f32>(1.0));
+
+    let extent = (uv_max - uv_min) * vec2<f32>(textureDimensions(depth_pyramid));
+    let levels = textureNumLevels(depth_pyramid);
+    var level = min(u32(ceil(log2(max(max(extent.x, extent.y), 1.0)))), levels - 1u);
+    var low = vec2<u32>(0u);
+    var high = vec2<u32>(0u);
+    loop {
+        let size = textureDimensions(depth_pyramid, level);
+        low = min(vec2<u32>(uv_min * vec2<f32>(size)), size - 1u);
+        high = min(vec2<u32>(uv_max * vec2<f32>(size)), size - 1u);
+        // Levels halve rounding down, so the rectangle may still span three texels.
+        if all(high - low <= vec2<u32>(1u)) || level == levels - 1u {
+            break;
+        }
+        level++;
+    }
+    // Loads take a signed level.
+    let mip = i32(level);
+    let farthest = max(
+        max(
+            textureLoad(depth_pyramid, low, mip).r,
+            textureLoad(depth_pyramid, vec2<u32>(high.x, low.y), mip).r,
+        ),
+        max(
+            textureLoad(depth_pyramid, vec2<u32>(low.x, high.y), mip).r,
+            textureLoad(depth_pyramid, high, mip).r,
+        ),
+    );
+    return nearest > farthest;
+}
+
+/// Draws each object once if its bounding sphere reaches into the frustum and is not occluded,
+/// and not at all otherwise. Drawn objects of a group are appended to its compacted arguments.
+@compute @workgroup_size(64)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= frustum.object_count {
+        return;
+    }
+    let model = objects[index].model;
+    let object = bounds[index];
+    let center = (model * vec4<f32>(object.sphere.xyz, 1.0)).xyz;
+    let scale = max(length(model[0].xyz), max(length(model[1].xyz), length(model[2].xyz)));
+    let radius = object.sphere.w * scale;
+
+    var rejection = 0u;
+    if object.never_culled == 0u {
+        for (var plane = 0u; plane < 6u; plane++) {
+            let distance = dot(frustum.planes[plane].xyz, center) + frustum.planes[plane].w;
+            if distance < -radius {
+                rejection = 1u;
+            }
+        }
+        if rejection == 0u && frustum.occlusion != 0u && occluded(center, radius) {
+            rejection = 2u;
+        }
+    }
+    rejections[index] = rejection;
+    draws[index] = DrawArgs(object.index_count, select(0u, 1u, rejection == 0u), object.first_index, object.base_vertex, object.first_instance);
+    if rejection == 0u && object.group != NO_GROUP {
+        let position = object.group_offset + atomicAdd(&counts[object.group], 1u);
+        compacted[position] = DrawArgs(object.index_count, 1u, object.first_index, object.base_vertex, object.first_instance);
+    }
+}
@@ -0,0 +1,277 @@
+use std::sync::{Mutex, MutexGuard};
+
+use cgmath::{InnerSpace, Matrix4, Rad, Vector3, Vector4};
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::{mesh::Bounds, render::as_byte_slice};
+
+const CIRCLE_SEGMENTS: u32 = 32;
+
+#[derive(Debug, Copy, Clone)]
+struct LineVertex {
+    #[allow(dead_code)]
+    position: Vector3<f32>,
+    #[allow(dead_code)]
+    color: Vector4<f32>,
+}
+
+impl LineVertex {
+    const LAYOUT: VertexBufferLayout<'static> = VertexBufferLayout {
+        array_stride: std::mem::size_of::<LineVertex>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &vertex_attr_array![0 => Float32x3, 1 => Float32x4],
+    };
+}
+
+/// Text placed in the scene, drawn by the UI.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub position: Vector3<f32>,
+    pub text: String,
+    pub color: Vector4<f32>,
+}
+
+/// Lines collected over a frame and drawn on top of the shaded scene.
+#[derive(Debug, Default)]
+pub struct DebugDraw {
+    vertices: Vec<LineVertex>,
+    labels: Vec<Label>,
+}
+
+static FRAME: Mutex<DebugDraw> = Mutex::new(DebugDraw {
+    vertices: Vec::new(),
+    labels: Vec::new(),
+});
+
+/// Shapes and labels for the current frame, to be added from anywhere in the app. The lines are
+/// drawn with the renderer's own in a single draw call, and the labels by the UI.
+pub fn frame() -> MutexGuard<'static, DebugDraw> {
+    FRAME.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+impl DebugDraw {
+    pub fn line(&mut self, a: Vector3<f32>, b: Vector3<f32>, color: Vector4<f32>) {
+        self.vertices.push(LineVertex { position: a, color });
+        self.vertices.push(LineVertex { position: b, color });
+    }
+
+    /// A circle around `center` in the plane perpendicular to `normal`.
+    pub fn circle(
+        &mut self,
+        center: Vector3<f32>,
+        normal: Vector3<f32>,
+        radius: f32,
+        color: Vector4<f32>,
+    ) {
+        let (u, v) = orthonormal_basis(normal);
+        let point = |i: u32| {
+            let angle = 2.0 * std::f32::consts::PI * i as f32 / CIRCLE_SEGMENTS as f32;
+            center + radius * (angle.cos() * u + angle.sin() * v)
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+
+    /// The edges of the bounds, placed by the transform.
+    pub fn aabb(&mut self, bounds: &Bounds, transform: Matrix4<f32>, color: Vector4<f32>) {
+        let corners = bounds
+            .corners()
+            .map(|corner| (transform * corner.to_homogeneous()).truncate());
+        // Corners are indexed by their bits along x, y and z, so edges join those a bit apart.
+        for a in 0..8 {
+            for bit in [1, 2, 4] {
+                if a & bit == 0 {
+                    self.line(corners[a], corners[a | bit], color);
+                }
+            }
+        }
+    }
+
+    /// The transform's x, y and z axes in red, green and blue, `length` long in its units.
+    pub fn axis(&mut self, transform: Matrix4<f32>, length: f32) {
+        let origin = transform.w.truncate();
+        for (axis, color) in [
+            (transform.x, Vector4::new(1.0, 0.0, 0.0, 1.0)),
+            (transform.y, Vector4::new(0.0, 1.0, 0.0, 1.0)),
+            (transform.z, Vector4::new(0.0, 0.0, 1.0, 1.0)),
+        ] {
+            self.line(origin, origin + length * axis.truncate(), color);
+        }
+    }
+
+    /// Text centered on a point in the scene.
+    pub fn text(&mut self, position: Vector3<f32>, text: impl Into<String>, color: Vector4<f32>) {
+        self.labels.push(Label {
+            position,
+            text: text.into(),
+            color,
+        });
+    }
+
+    /// Three great circles around the axes.
+    pub fn sphere(&mut self, center: Vector3<f32>, radius: f32, color: Vector4<f32>) {
+        for axis in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+            self.circle(center, axis, radius, color);
+        }
+    }
+
+    /// A line from `from` to `to` with a four-sided head at `to`.
+    pub fn arrow(&mut self, from: Vector3<f32>, to: Vector3<f32>, color: Vector4<f32>) {
+        self.line(from, to, color);
+        let direction = to - from;
+        let length = direction.magnitude();
+        let head = 0.2 * length;
+        let (u, v) = orthonormal_basis(direction);
+        let base = to - direction * (head / length);
+        for side in [u, v, -u, -v] {
+            self.line(to, base + 0.5 * head * side, color);
+        }
+    }
+
+    /// A cone opening from `apex` along `direction`, with the given half-angle and length.
+    pub fn cone(
+        &mut self,
+        apex: Vector3<f32>,
+        direction: Vector3<f32>,
+        angle: Rad<f32>,
+        length: f32,
+        color: Vector4<f32>,
+    ) {
+        let direction = direction.normalize();
+        let center = apex + length * direction;
+        let radius = length * angle.0.tan();
+        self.circle(center, direction, radius, color);
+        let (u, v) = orthonormal_basis(direction);
+        for side in [u, v, -u, -v] {
+            self.line(apex, center + radius * side, color);
+        }
+    }
+
+    /// Moves the other's lines over, to be drawn together.
+    pub fn append(&mut self, other: &mut DebugDraw) {
+        self.vertices.append(&mut other.vertices);
+    }
+
+    /// Removes the labels, for the UI to draw.
+    pub fn take_labels(&mut self) -> Vec<Label> {
+        std::mem::take(&mut self.labels)
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.labels.clear();
+    }
+}
+
+/// Two unit vectors perpendicular to `normal` and to each other.
+fn orthonormal_basis(normal: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let normal = normal.normalize();
+    let helper = if normal.y.abs() < 0.99 {
+        Vector3::unit_y()
+    } else {
+        Vector3::unit_x()
+    };
+    let u = normal.cross(helper).normalize();
+    (u, normal.cross(u))
+}
+
+#[derive(Debug)]
+pub struct DebugDrawPipeline {
+    pipeline: RenderPipeline,
+    /// Ignores the depth, for lines which have to stay visible, such as the transform gizmo.
+    on_top_pipeline: RenderPipeline,
+}
+
+impl DebugDrawPipeline {
+    pub fn new(
+        device: &Device,
+        color_format: TextureFormat,
+        sample_count: u32,
+        bind_group_layouts: &[&BindGroupLayout],
+    ) -> Self {
+        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(include_str!("debug_draw.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            bind_group_layouts,
+            ..Default::default()
+        });
+
+        let create_pipeline = |depth_compare| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: None,
+                cache: None,
+                layout: Some(&layout),
+                vertex: VertexState {
+                    module: &shader_module,
+                    entry_point: None,
+                    buffers: &[LineVertex::LAYOUT],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(FragmentState {
+                    module: &shader_module,
+                    entry_point: None,
+                    targets: &[Some(ColorTargetState {
+                        format: color_format,
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                multisample: MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                depth_stencil: Some(DepthStencilState {
+                    format: TextureFormat::Depth24Plus,
+                    depth_write_enabled: false,
+                    depth_compare,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multiview: None,
+            })
+        };
+
+        DebugDrawPipeline {
+            pipeline: create_pipeline(CompareFunction::LessEqual),
+            on_top_pipeline: create_pipeline(CompareFunction::Always),
+        }
+    }
+
+    /// Draws all collected lines in a single draw call. Expects the frame uniforms to be bound.
+    pub fn draw(&self, device: &Device, pass: &mut RenderPass, debug_draw: &DebugDraw) {
+        Self::draw_with(&self.pipeline, device, pass, debug_draw);
+    }
+
+    /// Like `draw`, but over everything drawn before.
+    pub fn draw_on_top(&self, device: &Device, pass: &mut RenderPass, debug_draw: &DebugDraw) {
+        Self::draw_with(&self.on_top_pipeline, device, pass, debug_draw);
+    }
+
+    fn draw_with(
+        pipeline: &RenderPipeline,
+        device: &Device,
+        pass: &mut RenderPass,
+        debug_draw: &DebugDraw,
+    ) {
+        if debug_draw.vertices.is_empty() {
+            return;
+        }
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&debug_draw.vertices),
+            usage: BufferUsages::VERTEX,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        pass.draw(0..debug_draw.vertices.len() as u32, 0..1);
+    }
+}
@@ -0,0 +1,24 @@
+struct Uniforms {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+struct FragmentInput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vertex(@location(0) position: vec3<f32>, @location(1) color: vec4<f32>) -> FragmentInput {
+    var out: FragmentInput;
+    out.position = uniforms.projection * uniforms.view * vec4<f32>(position, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,217 @@
+use wgpu::*;
+
+use crate::mesh::Vertex;
+
+const ALBEDO_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+const NORMAL_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+const MATERIAL_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+const IRRADIANCE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// Surface attributes of the opaque and masked geometry, lit afterwards in a single full-screen pass.
+#[derive(Debug)]
+pub struct GBuffer {
+    albedo: TextureView,
+    normal: TextureView,
+    material: TextureView,
+    irradiance: TextureView,
+    /// Holds the G-buffer textures together with the depth buffer they were rendered with.
+    pub bind_group: BindGroup,
+}
+
+impl GBuffer {
+    pub fn bind_group_layout(device: &Device) -> BindGroupLayout {
+        let texture = |binding, sample_type| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type,
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                texture(0, TextureSampleType::Float { filterable: false }),
+                texture(1, TextureSampleType::Float { filterable: false }),
+                texture(2, TextureSampleType::Float { filterable: false }),
+                texture(3, TextureSampleType::Depth),
+                texture(4, TextureSampleType::Float { filterable: false }),
+            ],
+        })
+    }
+
+    /// Expects a single-sampled depth texture of the same size.
+    pub fn new(
+        device: &Device,
+        layout: &BindGroupLayout,
+        width: u32,
+        height: u32,
+        depth: &TextureView,
+    ) -> Self {
+        let create = |format| {
+            device
+                .create_texture(&TextureDescriptor {
+                    label: None,
+                    size: Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format,
+                    view_formats: &[],
+                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                })
+                .create_view(&Default::default())
+        };
+        let albedo = create(ALBEDO_FORMAT);
+        let normal = create(NORMAL_FORMAT);
+        let material = create(MATERIAL_FORMAT);
+        let irradiance = create(IRRADIANCE_FORMAT);
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&albedo),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&normal),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&material),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(depth),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: BindingResource::TextureView(&irradiance),
+                },
+            ],
+        });
+
+        GBuffer {
+            albedo,
+            normal,
+            material,
+            irradiance,
+            bind_group,
+        }
+    }
+
+    pub fn color_attachments(&self) -> [Option<RenderPassColorAttachment<'_>>; 4] {
+        [&self.albedo, &self.normal, &self.material, &self.irradiance].map(|view| {
+            Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: StoreOp::Store,
+                },
+            })
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct DeferredPipelines {
+    /// Writes the opaque and masked materials into the G-buffer.
+    pub geometry: RenderPipeline,
+    /// Shades the G-buffer into the color target.
+    pub lighting: RenderPipeline,
+}
+
+impl DeferredPipelines {
+    /// The geometry pipeline uses the forward pipeline layout, while the lighting pipeline binds
+    /// the G-buffer in place of the material.
+    pub fn new(
+        device: &Device,
+        shader_module: &ShaderModule,
+        geometry_layout: &PipelineLayout,
+        lighting_layout: &PipelineLayout,
+        format: TextureFormat,
+    ) -> Self {
+        let target = |format| {
+            Some(ColorTargetState {
+                format,
+                blend: Some(BlendState::REPLACE),
+                write_mask: ColorWrites::ALL,
+            })
+        };
+
+        let geometry = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            cache: None,
+            layout: Some(geometry_layout),
+            vertex: VertexState {
+                module: shader_module,
+                entry_point: Some("vertex"),
+                buffers: &[Vertex::LAYOUT],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(FragmentState {
+                module: shader_module,
+                entry_point: Some("gbuffer_fragment"),
+                targets: &[
+                    target(ALBEDO_FORMAT),
+                    target(NORMAL_FORMAT),
+                    target(MATERIAL_FORMAT),
+                    target(IRRADIANCE_FORMAT),
+                ],
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                polygon_mode: PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            multisample: Default::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth24Plus,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multiview: None,
+        });
+
+        let lighting = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            cache: None,
+            layout: Some(lighting_layout),
+            vertex: VertexState {
+                module: shader_module,
+                entry_point: Some("deferred_vertex"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(FragmentState {
+                module: shader_module,
+                entry_point: Some("deferred_fragment"),
+                targets: &[target(format)],
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            multisample: Default::default(),
+            depth_stencil: None,
+            multiview: None,
+        });
+
+        DeferredPipelines { geometry, lighting }
+    }
+}
@@ -0,0 +1,87 @@
+@group(2) @binding(0) var gbuffer_albedo: texture_2d<f32>;
+@group(2) @binding(1) var gbuffer_normal: texture_2d<f32>;
+@group(2) @binding(2) var gbuffer_material: texture_2d<f32>;
+@group(2) @binding(3) var gbuffer_depth: texture_depth_2d;
+@group(2) @binding(4) var gbuffer_irradiance: texture_2d<f32>;
+
+struct GBufferOutput {
+    /// Albedo, with alpha unused.
+    @location(0) albedo: vec4<f32>,
+    /// World-space normal and rim strength.
+    @location(1) normal: vec4<f32>,
+    /// Metallic, roughness, shading model and toon bands, the latter two divided by 255.
+    @location(2) material: vec4<f32>,
+    /// Diffuse irradiance, with alpha unused.
+    @location(3) irradiance: vec4<f32>,
+}
+
+/// Mirrors `shininess_from_roughness` in the material module.
+fn shininess_from_roughness(roughness: f32) -> f32 {
+    let alpha = max(roughness * roughness, 1e-3);
+    return 2.0 / (alpha * alpha) - 2.0;
+}
+
+@fragment
+fn gbuffer_fragment(in: FragmentInput) -> GBufferOutput {
+    load_object(in.instance);
+    let normal = normalize(in.normal);
+    let base_color = material().base_color * blend_vertex_color(sample_base_color(in, normal), in.color);
+    if material().alpha_cutoff > 0.0 && base_color.a < material().alpha_cutoff {
+        discard;
+    }
+    var out: GBufferOutput;
+    out.albedo = vec4<f32>(base_color.rgb, 1.0);
+    out.normal = vec4<f32>(normal, material().rim);
+    out.material = vec4<f32>(
+        material().metallic,
+        material().roughness,
+        f32(material().shading) / 255.0,
+        f32(material().toon_bands) / 255.0,
+    );
+    out.irradiance = vec4<f32>(surface_irradiance(in, normal), 1.0);
+    return out;
+}
+
+/// Covers the screen with a single triangle.
+@vertex
+fn deferred_vertex(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    let clip = vec2<f32>(f32(vertex_index & 1u) * 4.0 - 1.0, f32(vertex_index >> 1u) * 4.0 - 1.0);
+    return vec4<f32>(clip, 0.0, 1.0);
+}
+
+/// Lights every covered pixel of the G-buffer, reconstructing its position from the depth.
+@fragment
+fn deferred_fragment(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(position.xy);
+    let depth = textureLoad(gbuffer_depth, pixel, 0);
+    if depth >= 1.0 {
+        discard;
+    }
+    let ndc = vec2<f32>(2.0, -2.0) * position.xy * uniforms.viewport.zw + vec2<f32>(-1.0, 1.0);
+    let world = uniforms.inverse_view_projection * vec4<f32>(ndc, depth, 1.0);
+
+    let albedo = textureLoad(gbuffer_albedo, pixel, 0);
+    let normal = textureLoad(gbuffer_normal, pixel, 0);
+    let properties = textureLoad(gbuffer_material, pixel, 0);
+    let shading = u32(round(properties.z * 255.0));
+    var shininess = 0.0;
+    if shading == SHADING_BLINN_PHONG {
+        shininess = shininess_from_roughness(properties.y);
+    }
+    let color = shade(
+        Surface(
+            albedo.rgb,
+            normalize(normal.xyz),
+            world.xyz / world.w,
+            properties.x,
+            properties.y,
+            shading,
+            u32(round(properties.w * 255.0)),
+            normal.w,
+            shininess,
+            textureLoad(gbuffer_irradiance, pixel, 0).rgb,
+        ),
+        position.xy,
+    );
+    return vec4<f32>(color, 1.0);
+}
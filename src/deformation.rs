@@ -0,0 +1,229 @@
+use cgmath::{Matrix4, SquareMatrix, Vector3, Zero};
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::{
+    arena::MeshArena,
+    mesh::{Mesh, Vertex},
+    render::as_byte_slice,
+};
+
+/// Influence of up to four joints on a vertex.
+#[derive(Debug, Copy, Clone)]
+pub struct SkinVertex {
+    #[allow(dead_code)]
+    pub joints: [u32; 4],
+    /// Sum to one.
+    #[allow(dead_code)]
+    pub weights: [f32; 4],
+}
+
+/// Per-vertex displacements blended onto the bind pose by the target's weight.
+#[derive(Debug, Clone, Default)]
+pub struct MorphTarget {
+    pub positions: Vec<Vector3<f32>>,
+    /// Empty if the target leaves the normals alone.
+    pub normals: Vec<Vector3<f32>>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct DeformationId(pub usize);
+
+#[derive(Debug, Copy, Clone)]
+struct DeformationParams {
+    #[allow(dead_code)]
+    target_count: u32,
+    #[allow(dead_code)]
+    skinned: u32,
+    #[allow(dead_code)]
+    padding: [u32; 2],
+}
+
+/// A mesh whose vertex buffer is rewritten from its bind pose every frame.
+#[derive(Debug)]
+struct DeformedMesh {
+    /// Joint matrices, written by the animation.
+    joint_buffer: Buffer,
+    /// Morph target weights, written by the animation.
+    weight_buffer: Buffer,
+    bind_group: BindGroup,
+    vertex_count: u32,
+}
+
+/// Deforms meshes by morph targets and then by joints in a compute pre-pass, writing the
+/// deformed positions and normals into the meshes' own vertex buffers. Every pipeline then draws
+/// deformed meshes like any other, but the meshes' bounds and ray tracing structures keep the
+/// bind pose.
+#[derive(Debug)]
+pub struct Deformation {
+    pipeline: ComputePipeline,
+    meshes: Vec<DeformedMesh>,
+}
+
+impl Deformation {
+    pub fn new(device: &Device) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(include_str!("deformation.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: &module,
+            entry_point: Some("deform"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        Deformation {
+            pipeline,
+            meshes: Vec::new(),
+        }
+    }
+
+    /// The skin consists of the joint influences and the number of joints. The mesh must not be
+    /// shared with other objects, as its vertices are overwritten, must have a page of its own in
+    /// the arena, and must not be unloaded.
+    pub fn add(
+        &mut self,
+        device: &Device,
+        mesh: &Mesh,
+        arena: &MeshArena,
+        skin: Option<(&[SkinVertex], usize)>,
+        targets: &[MorphTarget],
+    ) -> DeformationId {
+        let vertex_count = mesh.data.vertices.len();
+        let storage = |contents: &[u8]| {
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                contents,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            })
+        };
+
+        // Positions and normals, padded to four components.
+        let bind_pose: Vec<_> = mesh
+            .data
+            .vertices
+            .iter()
+            .flat_map(|vertex: &Vertex| [vertex.position.extend(1.0), vertex.normal.extend(0.0)])
+            .collect();
+        let bind_pose_buffer = storage(as_byte_slice(&bind_pose));
+
+        // Bindings cannot be empty, so unused ones get a placeholder element.
+        let (skin_buffer, joint_count) = match skin {
+            Some((vertices, joint_count)) => (storage(as_byte_slice(vertices)), joint_count),
+            None => (storage(&[0; std::mem::size_of::<SkinVertex>()]), 1),
+        };
+        let joint_buffer = storage(as_byte_slice(&vec![
+            Matrix4::<f32>::identity();
+            joint_count
+        ]));
+
+        let displacements: Vec<_> = targets
+            .iter()
+            .flat_map(|target| {
+                (0..vertex_count).flat_map(|index| {
+                    let normal = target
+                        .normals
+                        .get(index)
+                        .copied()
+                        .unwrap_or(Vector3::zero());
+                    [target.positions[index].extend(0.0), normal.extend(0.0)]
+                })
+            })
+            .collect();
+        let target_buffer = if displacements.is_empty() {
+            storage(&[0; 16])
+        } else {
+            storage(as_byte_slice(&displacements))
+        };
+        let weight_buffer = storage(as_byte_slice(&vec![0.0f32; targets.len().max(1)]));
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[DeformationParams {
+                target_count: targets.len() as u32,
+                skinned: skin.is_some() as u32,
+                padding: [0; 2],
+            }]),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: bind_pose_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: skin_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: joint_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: arena
+                        .vertices(mesh.allocation.expect("Mesh has been unloaded").page)
+                        .as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: target_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: weight_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 6,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        self.meshes.push(DeformedMesh {
+            joint_buffer,
+            weight_buffer,
+            bind_group,
+            vertex_count: vertex_count as u32,
+        });
+        DeformationId(self.meshes.len() - 1)
+    }
+
+    /// Joint transforms in model space, each multiplied with its inverse bind matrix.
+    pub fn write_joints(
+        &self,
+        queue: &Queue,
+        deformation: DeformationId,
+        joint_matrices: &[Matrix4<f32>],
+    ) {
+        queue.write_buffer(
+            &self.meshes[deformation.0].joint_buffer,
+            0,
+            as_byte_slice(joint_matrices),
+        );
+    }
+
+    pub fn write_weights(&self, queue: &Queue, deformation: DeformationId, weights: &[f32]) {
+        queue.write_buffer(
+            &self.meshes[deformation.0].weight_buffer,
+            0,
+            as_byte_slice(weights),
+        );
+    }
+
+    pub fn run(&self, encoder: &mut CommandEncoder) {
+        if self.meshes.is_empty() {
+            return;
+        }
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(&self.pipeline);
+        for mesh in &self.meshes {
+            pass.set_bind_group(0, &mesh.bind_group, &[]);
+            pass.dispatch_workgroups(mesh.vertex_count.div_ceil(64), 1, 1);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+struct SkinVertex {
+    joints: vec4<u32>,
+    weights: vec4<f32>,
+}
+
+struct Params {
+    target_count: u32,
+    skinned: u32,
+}
+
+/// Position and normal of each vertex, before deformation.
+@group(0) @binding(0) var<storage, read> bind_pose: array<vec4<f32>>;
+@group(0) @binding(1) var<storage, read> skin_vertices: array<SkinVertex>;
+@group(0) @binding(2) var<storage, read> joints: array<mat4x4<f32>>;
+/// The mesh's vertex buffer, as tightly packed floats.
+@group(0) @binding(3) var<storage, read_write> vertices: array<f32>;
+/// Position and normal displacements of each vertex, target after target.
+@group(0) @binding(4) var<storage, read> targets: array<vec4<f32>>;
+@group(0) @binding(5) var<storage, read> weights: array<f32>;
+@group(0) @binding(6) var<uniform> params: Params;
+
+/// Floats per vertex: position, normal, color, uv and lightmap uv.
+const VERTEX_STRIDE: u32 = 14u;
+
+@compute @workgroup_size(64)
+fn deform(@builtin(global_invocation_id) id: vec3<u32>) {
+    let vertex_count = arrayLength(&bind_pose) / 2u;
+    let index = id.x;
+    if index >= vertex_count {
+        return;
+    }
+
+    var position = bind_pose[2u * index];
+    var normal = bind_pose[2u * index + 1u];
+    for (var morph = 0u; morph < params.target_count; morph++) {
+        let offset = 2u * (morph * vertex_count + index);
+        position += weights[morph] * targets[offset];
+        normal += weights[morph] * targets[offset + 1u];
+    }
+
+    if params.skinned != 0u {
+        let influence = skin_vertices[index];
+        let last_joint = arrayLength(&joints) - 1u;
+        var transform = mat4x4<f32>();
+        for (var i = 0u; i < 4u; i++) {
+            transform += influence.weights[i] * joints[min(influence.joints[i], last_joint)];
+        }
+        position = transform * position;
+        normal = transform * normal;
+    }
+
+    let normalized = normalize(normal.xyz);
+    let base = index * VERTEX_STRIDE;
+    vertices[base] = position.x;
+    vertices[base + 1u] = position.y;
+    vertices[base + 2u] = position.z;
+    vertices[base + 3u] = normalized.x;
+    vertices[base + 4u] = normalized.y;
+    vertices[base + 5u] = normalized.z;
+}
@@ -0,0 +1,85 @@
+/// Change of the render scale per adjustment.
+const SCALE_STEP: f32 = 0.1;
+/// Frames to wait after an adjustment before the frame time is judged again.
+const SETTLE_FRAMES: u32 = 30;
+/// Frames within the budget after which a higher scale is tried. Frames cannot get faster than
+/// the display refresh with vsync, so headroom is only visible by probing.
+const PROBE_FRAMES: u32 = 120;
+
+/// Lowers the resolution the scene is rendered at while frames exceed a time budget, and raises
+/// it again while they stay within.
+#[derive(Debug)]
+pub struct DynamicResolution {
+    pub enabled: bool,
+    /// Targeted frame time in seconds.
+    pub budget: f32,
+    /// Lowest fraction of the display resolution to render at.
+    pub min_scale: f32,
+    /// Fraction of the display resolution rendered at while disabled, and the highest while
+    /// enabled.
+    pub max_scale: f32,
+    /// Current fraction of the display resolution, in both dimensions.
+    pub scale: f32,
+    /// Exponential moving average of the frame time.
+    average_frame_time: f32,
+    /// Frames to skip before the next adjustment.
+    settle_frames: u32,
+    /// Consecutive frames within the budget.
+    frames_within_budget: u32,
+}
+
+impl Default for DynamicResolution {
+    fn default() -> Self {
+        DynamicResolution {
+            enabled: false,
+            budget: 1.0 / 60.0,
+            min_scale: 0.5,
+            max_scale: 1.0,
+            scale: 1.0,
+            average_frame_time: 1.0 / 60.0,
+            settle_frames: 0,
+            frames_within_budget: 0,
+        }
+    }
+}
+
+impl DynamicResolution {
+    /// Adjusts the scale to the duration of the last frame, returning whether it changed.
+    pub fn update(&mut self, delta_time: f32) -> bool {
+        let scale = if self.enabled {
+            self.adjusted_scale(delta_time)
+        } else {
+            self.max_scale
+        };
+        if scale == self.scale {
+            return false;
+        }
+        self.scale = scale;
+        self.settle_frames = SETTLE_FRAMES;
+        self.frames_within_budget = 0;
+        true
+    }
+
+    fn adjusted_scale(&mut self, delta_time: f32) -> f32 {
+        self.average_frame_time += 0.1 * (delta_time - self.average_frame_time);
+        if self.settle_frames > 0 {
+            self.settle_frames -= 1;
+            return self.scale;
+        }
+
+        let step = if self.average_frame_time > 1.1 * self.budget {
+            -SCALE_STEP
+        } else if self.average_frame_time < 0.85 * self.budget {
+            SCALE_STEP
+        } else {
+            self.frames_within_budget += 1;
+            if self.frames_within_budget < PROBE_FRAMES {
+                return self.scale;
+            }
+            SCALE_STEP
+        };
+        // Snap to whole steps, so that repeated adjustments do not drift.
+        (((self.scale + step) / SCALE_STEP).round() * SCALE_STEP)
+            .clamp(self.min_scale.min(self.max_scale), self.max_scale)
+    }
+}
@@ -0,0 +1,539 @@
+use std::path::Path;
+
+use cgmath::{InnerSpace, Vector3};
+use half::f16;
+use image::{imageops, GenericImageView, Rgba32FImage};
+use serde::{Deserialize, Serialize};
+use util::{BufferInitDescriptor, DeviceExt, TextureDataOrder};
+use wgpu::*;
+
+use crate::{
+    light::{smoothstep, sun_direction},
+    render::as_byte_slice,
+};
+
+pub const CUBEMAP_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+/// Face file names tried when loading a cubemap from a directory, in wgpu layer order (+X, -X, +Y, -Y, +Z, -Z).
+const FACE_NAMES: [[&str; 2]; 6] = [
+    ["px", "right"],
+    ["nx", "left"],
+    ["py", "top"],
+    ["ny", "bottom"],
+    ["pz", "front"],
+    ["nz", "back"],
+];
+
+#[derive(Debug)]
+pub struct Cubemap {
+    pub texture: Texture,
+    pub view: TextureView,
+}
+
+/// World-space direction through texel coordinates `u, v ∈ [-1, 1]` of the given cube face.
+pub fn face_direction(face: usize, u: f32, v: f32) -> Vector3<f32> {
+    match face {
+        0 => Vector3::new(1.0, -v, -u),
+        1 => Vector3::new(-1.0, -v, u),
+        2 => Vector3::new(u, 1.0, v),
+        3 => Vector3::new(u, -1.0, -v),
+        4 => Vector3::new(u, -v, 1.0),
+        _ => Vector3::new(-u, -v, -1.0),
+    }
+    .normalize()
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts an 8-bit sRGB image into linear floating point.
+fn linearize(image: &image::DynamicImage) -> Rgba32FImage {
+    let mut image = image.to_rgba32f();
+    for pixel in image.pixels_mut() {
+        for c in &mut pixel.0[..3] {
+            *c = srgb_to_linear(*c);
+        }
+    }
+    image
+}
+
+fn load_linear(path: &Path) -> image::ImageResult<Rgba32FImage> {
+    let image = image::open(path)?;
+    Ok(match image {
+        image::DynamicImage::ImageRgb32F(_) | image::DynamicImage::ImageRgba32F(_) => {
+            image.to_rgba32f()
+        }
+        _ => linearize(&image),
+    })
+}
+
+impl Cubemap {
+    /// Creates a cubemap from six square faces of equal size, ordered +X, -X, +Y, -Y, +Z, -Z.
+    pub fn from_faces(
+        device: &Device,
+        queue: &Queue,
+        faces: &[Rgba32FImage; 6],
+    ) -> Result<Self, String> {
+        let size = faces[0].width();
+        if faces
+            .iter()
+            .any(|f| f.width() != size || f.height() != size)
+        {
+            return Err("Cubemap faces must be square and of equal size".into());
+        }
+
+        let data: Vec<f16> = faces
+            .iter()
+            .flat_map(|face| face.as_raw().iter().map(|&c| f16::from_f32(c)))
+            .collect();
+
+        let texture = device.create_texture_with_data(
+            queue,
+            &TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: CUBEMAP_FORMAT,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            TextureDataOrder::LayerMajor,
+            as_byte_slice(&data),
+        );
+
+        Ok(Self::from_texture(texture))
+    }
+
+    /// Wraps a six-layer texture into a cube view.
+    pub fn from_texture(texture: Texture) -> Self {
+        let view = texture.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        Cubemap { texture, view }
+    }
+
+    /// Splits a horizontal (4×3) or vertical (3×4) cross into its six faces.
+    pub fn from_cross(
+        device: &Device,
+        queue: &Queue,
+        cross: &Rgba32FImage,
+    ) -> Result<Self, String> {
+        let (width, height) = cross.dimensions();
+        let face =
+            |x: u32, y: u32, size: u32| cross.view(x * size, y * size, size, size).to_image();
+
+        let faces = if width * 3 == height * 4 {
+            let size = width / 4;
+            [
+                face(2, 1, size),
+                face(0, 1, size),
+                face(1, 0, size),
+                face(1, 2, size),
+                face(1, 1, size),
+                face(3, 1, size),
+            ]
+        } else if width * 4 == height * 3 {
+            let size = width / 3;
+            [
+                face(2, 1, size),
+                face(0, 1, size),
+                face(1, 0, size),
+                face(1, 2, size),
+                face(1, 1, size),
+                imageops::rotate180(&face(1, 3, size)),
+            ]
+        } else {
+            return Err(format!(
+                "Cross image must have a 4:3 or 3:4 aspect ratio, got {width}×{height}"
+            ));
+        };
+
+        Self::from_faces(device, queue, &faces)
+    }
+
+    /// Projects an equirectangular panorama onto a cubemap on the GPU.
+    pub fn from_equirectangular(device: &Device, queue: &Queue, panorama: &Rgba32FImage) -> Self {
+        let data: Vec<f16> = panorama
+            .as_raw()
+            .iter()
+            .map(|&c| f16::from_f32(c))
+            .collect();
+
+        let panorama_texture = device.create_texture_with_data(
+            queue,
+            &TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width: panorama.width(),
+                    height: panorama.height(),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba16Float,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            TextureDataOrder::LayerMajor,
+            as_byte_slice(&data),
+        );
+
+        // A quarter of the panorama width keeps the texel density roughly equal at the horizon.
+        let size = (panorama.width() / 4).next_power_of_two().min(2048);
+        let texture = device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: CUBEMAP_FORMAT,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING,
+            view_formats: &[],
+        });
+
+        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(
+                concat!(include_str!("cube.wgsl"), include_str!("equirect.wgsl")).into(),
+            ),
+        });
+
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: &shader_module,
+            entry_point: None,
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(
+                        &panorama_texture.create_view(&Default::default()),
+                    ),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&device.create_sampler(
+                        &SamplerDescriptor {
+                            address_mode_u: AddressMode::Repeat,
+                            mag_filter: FilterMode::Linear,
+                            min_filter: FilterMode::Linear,
+                            ..Default::default()
+                        },
+                    )),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&texture.create_view(
+                        &TextureViewDescriptor {
+                            dimension: Some(TextureViewDimension::D2Array),
+                            ..Default::default()
+                        },
+                    )),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(size.div_ceil(8), size.div_ceil(8), 6);
+        drop(pass);
+        queue.submit(Some(encoder.finish()));
+
+        Self::from_texture(texture)
+    }
+
+    /// Loads a cubemap either from a directory containing six face images
+    /// (`px.png`, `nx.png`, … or `right.png`, `left.png`, …), from a single cross image,
+    /// or from an equirectangular panorama with a 2:1 aspect ratio such as an `.hdr` file.
+    pub fn load(device: &Device, queue: &Queue, path: &Path) -> Result<Self, String> {
+        if path.is_dir() {
+            let mut faces = Vec::with_capacity(6);
+            for names in FACE_NAMES {
+                let file = std::fs::read_dir(path)
+                    .map_err(|e| e.to_string())?
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .find(|file| {
+                        file.file_stem()
+                            .and_then(|stem| stem.to_str())
+                            .is_some_and(|stem| names.contains(&stem.to_lowercase().as_str()))
+                    })
+                    .ok_or_else(|| format!("Missing cubemap face {}", names[0]))?;
+                faces.push(load_linear(&file).map_err(|e| e.to_string())?);
+            }
+            Self::from_faces(device, queue, &faces.try_into().unwrap())
+        } else {
+            let image = load_linear(path).map_err(|e| e.to_string())?;
+            if image.width() == 2 * image.height() {
+                Ok(Self::from_equirectangular(device, queue, &image))
+            } else {
+                Self::from_cross(device, queue, &image)
+            }
+        }
+    }
+
+    /// A procedural sky gradient, used until an environment is loaded.
+    pub fn sky(device: &Device, queue: &Queue, sky: &SkyGradient) -> Self {
+        let size = 64;
+
+        let faces: [Rgba32FImage; 6] = std::array::from_fn(|face| {
+            Rgba32FImage::from_fn(size, size, |x, y| {
+                let u = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+                let v = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+                let color = sky.radiance(face_direction(face, u, v));
+                image::Rgba([color.x, color.y, color.z, 1.0])
+            })
+        });
+
+        Self::from_faces(device, queue, &faces).unwrap()
+    }
+}
+
+/// A sky blending from the horizon color up to the zenith and down to the ground.
+#[derive(Debug, Copy, Clone)]
+pub struct SkyGradient {
+    pub zenith: Vector3<f32>,
+    pub horizon: Vector3<f32>,
+    pub ground: Vector3<f32>,
+}
+
+impl Default for SkyGradient {
+    fn default() -> Self {
+        SkyGradient {
+            zenith: Vector3::new(0.25, 0.35, 0.55),
+            horizon: Vector3::new(0.6, 0.6, 0.62),
+            ground: Vector3::new(0.12, 0.11, 0.1),
+        }
+    }
+}
+
+impl SkyGradient {
+    /// The default sky darkened towards night and reddened around the horizon at dawn and dusk,
+    /// following the sun at the given hour.
+    pub fn at_time(hours: f32) -> Self {
+        let elevation = -sun_direction(hours).y;
+        let night = SkyGradient {
+            zenith: Vector3::new(0.005, 0.008, 0.02),
+            horizon: Vector3::new(0.02, 0.025, 0.04),
+            ground: Vector3::new(0.005, 0.005, 0.005),
+        };
+        let day = Self::default();
+        let daylight = smoothstep(-0.15, 0.25, elevation);
+        let twilight = (1.0 - elevation.abs() / 0.3).max(0.0);
+        let sunset = Vector3::new(0.9, 0.45, 0.25);
+        let mix = |a: Vector3<f32>, b: Vector3<f32>, t: f32| a + (b - a) * t;
+        SkyGradient {
+            zenith: mix(night.zenith, day.zenith, daylight),
+            horizon: mix(
+                mix(night.horizon, day.horizon, daylight),
+                sunset,
+                0.7 * twilight,
+            ),
+            ground: mix(night.ground, day.ground, daylight),
+        }
+    }
+
+    /// Radiance in the given normalized direction.
+    pub fn radiance(&self, direction: Vector3<f32>) -> Vector3<f32> {
+        if direction.y > 0.0 {
+            self.horizon + (self.zenith - self.horizon) * direction.y.powf(0.5)
+        } else {
+            self.horizon + (self.ground - self.horizon) * (-direction.y).powf(0.25)
+        }
+    }
+}
+
+/// What is drawn behind the geometry.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackgroundMode {
+    Solid,
+    /// Blends from the bottom of the screen to the top.
+    Gradient,
+    Skybox,
+}
+
+/// The background's mode and colors, in linear radiance like everything rendered into the HDR
+/// target.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Background {
+    pub mode: BackgroundMode,
+    /// Cleared to before drawing, and all that is seen of the solid background.
+    pub clear_color: Vector3<f32>,
+    pub gradient_top: Vector3<f32>,
+    pub gradient_bottom: Vector3<f32>,
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background {
+            mode: BackgroundMode::Skybox,
+            clear_color: Vector3::new(0.01, 0.01, 0.01),
+            gradient_top: Vector3::new(0.12, 0.14, 0.18),
+            gradient_bottom: Vector3::new(0.01, 0.01, 0.012),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct GradientUniforms {
+    #[allow(dead_code)]
+    top: [f32; 4],
+    #[allow(dead_code)]
+    bottom: [f32; 4],
+}
+
+#[derive(Debug)]
+pub struct Skybox {
+    pipeline: RenderPipeline,
+    gradient_pipeline: RenderPipeline,
+    gradient_buffer: Buffer,
+    gradient_bind_group: BindGroup,
+}
+
+impl Skybox {
+    pub fn new(
+        device: &Device,
+        color_format: TextureFormat,
+        sample_count: u32,
+        bind_group_layouts: &[&BindGroupLayout],
+    ) -> Self {
+        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(include_str!("skybox.wgsl").into()),
+        });
+
+        let gradient_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let gradient_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[GradientUniforms {
+                top: [0.0; 4],
+                bottom: [0.0; 4],
+            }]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let gradient_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &gradient_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: gradient_buffer.as_entire_binding(),
+            }],
+        });
+
+        let create_pipeline = |entry_point, bind_group_layouts: &[&BindGroupLayout]| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: None,
+                cache: None,
+                layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                    bind_group_layouts,
+                    ..Default::default()
+                })),
+                vertex: VertexState {
+                    module: &shader_module,
+                    entry_point: Some("vertex"),
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(FragmentState {
+                    module: &shader_module,
+                    entry_point: Some(entry_point),
+                    targets: &[Some(ColorTargetState {
+                        format: color_format,
+                        blend: Some(BlendState::REPLACE),
+                        write_mask: ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: PrimitiveState::default(),
+                multisample: MultisampleState {
+                    count: sample_count,
+                    ..Default::default()
+                },
+                depth_stencil: Some(DepthStencilState {
+                    format: TextureFormat::Depth24Plus,
+                    depth_write_enabled: false,
+                    depth_compare: CompareFunction::LessEqual,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multiview: None,
+            })
+        };
+        let pipeline = create_pipeline("fragment", bind_group_layouts);
+        // Bound after the frame's groups, which stay in place for what is drawn next.
+        let gradient_pipeline = create_pipeline(
+            "gradient_fragment",
+            &[bind_group_layouts, &[&gradient_bind_group_layout]].concat(),
+        );
+
+        Skybox {
+            pipeline,
+            gradient_pipeline,
+            gradient_buffer,
+            gradient_bind_group,
+        }
+    }
+
+    /// Draws the background behind all geometry, nothing for a solid one. Expects the frame
+    /// uniforms and environment to be bound.
+    pub fn draw(&self, queue: &Queue, pass: &mut RenderPass, background: &Background) {
+        match background.mode {
+            BackgroundMode::Solid => {}
+            BackgroundMode::Gradient => {
+                queue.write_buffer(
+                    &self.gradient_buffer,
+                    0,
+                    as_byte_slice(&[GradientUniforms {
+                        top: background.gradient_top.extend(1.0).into(),
+                        bottom: background.gradient_bottom.extend(1.0).into(),
+                    }]),
+                );
+                pass.set_pipeline(&self.gradient_pipeline);
+                pass.set_bind_group(2, &self.gradient_bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+            BackgroundMode::Skybox => {
+                pass.set_pipeline(&self.pipeline);
+                pass.draw(0..3, 0..1);
+            }
+        }
+    }
+}
@@ -0,0 +1,20 @@
+const PI: f32 = 3.14159265359;
+
+@group(0) @binding(0) var panorama: texture_2d<f32>;
+@group(0) @binding(1) var panorama_sampler: sampler;
+@group(0) @binding(2) var cubemap: texture_storage_2d_array<rgba16float, write>;
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(cubemap).x;
+    if id.x >= size || id.y >= size {
+        return;
+    }
+    let direction = texel_direction(id, size);
+    let uv = vec2<f32>(
+        atan2(direction.z, direction.x) / (2.0 * PI) + 0.5,
+        acos(clamp(direction.y, -1.0, 1.0)) / PI,
+    );
+    let color = textureSampleLevel(panorama, panorama_sampler, uv, 0.0);
+    textureStore(cubemap, id.xy, id.z, vec4<f32>(color.rgb, 1.0));
+}
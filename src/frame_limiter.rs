@@ -0,0 +1,56 @@
+use std::time::{Duration, Instant};
+
+/// The end of a wait spent spinning rather than sleeping, as sleeps overshoot by up to the
+/// scheduler's granularity.
+const SPIN: Duration = Duration::from_millis(2);
+
+/// Caps the frame rate by sleeping before each frame, independent of the present mode, so that
+/// frames are not rendered faster than needed with Mailbox or Immediate.
+#[derive(Debug)]
+pub struct FrameLimiter {
+    pub enabled: bool,
+    pub target_fps: f32,
+    /// When the next frame is due, unless the limiter was just enabled.
+    next_frame: Option<Instant>,
+}
+
+impl Default for FrameLimiter {
+    fn default() -> Self {
+        FrameLimiter {
+            enabled: false,
+            target_fps: 60.0,
+            next_frame: None,
+        }
+    }
+}
+
+impl FrameLimiter {
+    /// Waits until the next frame is due. A frame running late starts the schedule over rather
+    /// than having the next ones rush to catch up.
+    pub fn wait(&mut self) {
+        if !self.enabled {
+            self.next_frame = None;
+            return;
+        }
+        let interval = Duration::from_secs_f32(1.0 / self.target_fps.max(1.0));
+        let now = Instant::now();
+        let start = match self.next_frame {
+            Some(due) if due > now => {
+                sleep_until(due);
+                due
+            }
+            _ => now,
+        };
+        self.next_frame = Some(start + interval);
+    }
+}
+
+fn sleep_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline > now + SPIN {
+        std::thread::sleep(deadline - now - SPIN);
+    }
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
@@ -0,0 +1,308 @@
+use cgmath::{InnerSpace, Matrix3, Matrix4, Rad, Vector3, Vector4};
+
+use crate::{
+    camera::Projection,
+    debug_draw::DebugDraw,
+    history::Edit,
+    world::{Entity, Transform, World},
+};
+
+/// Length of the handles as a fraction of the viewport's height.
+const SCREEN_SIZE: f32 = 0.15;
+/// Distance from a handle within which the pointer grabs it, relative to the handles' length.
+const GRAB_DISTANCE: f32 = 0.06;
+/// Where the plane handles start and end along both of their axes, relative to the handles'
+/// length.
+const PLANE_HANDLE: [f32; 2] = [0.25, 0.45];
+
+const AXIS_COLORS: [Vector4<f32>; 3] = [
+    Vector4::new(1.0, 0.2, 0.2, 1.0),
+    Vector4::new(0.2, 1.0, 0.2, 1.0),
+    Vector4::new(0.2, 0.4, 1.0, 1.0),
+];
+const ACTIVE_COLOR: Vector4<f32> = Vector4::new(1.0, 0.9, 0.2, 1.0);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum GizmoMode {
+    #[default]
+    Translate,
+    Rotate,
+    /// Along the object's own axes.
+    Scale,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Handle {
+    /// Along one axis.
+    Axis(usize),
+    /// Within the plane perpendicular to one axis.
+    Plane(usize),
+    /// Around one axis.
+    Ring(usize),
+    /// Scales uniformly.
+    Center,
+}
+
+#[derive(Debug, Clone)]
+struct Drag {
+    entity: Entity,
+    handle: Handle,
+    /// The entity's transform when grabbed.
+    transform: Matrix4<f32>,
+    /// Normal of the plane the pointer is projected onto when the handle is not an axis.
+    normal: Vector3<f32>,
+    /// Where the handle was grabbed.
+    grab: Vector3<f32>,
+    edit: Edit,
+}
+
+/// Handles drawn over the selected object, which move, rotate or scale it when dragged.
+/// Their size on screen stays the same regardless of the distance.
+#[derive(Debug, Default)]
+pub struct Gizmo {
+    mode: GizmoMode,
+    drag: Option<Drag>,
+}
+
+impl Gizmo {
+    pub fn cycle_mode(&mut self) {
+        self.mode = match self.mode {
+            GizmoMode::Translate => GizmoMode::Rotate,
+            GizmoMode::Rotate => GizmoMode::Scale,
+            GizmoMode::Scale => GizmoMode::Translate,
+        };
+        println!(
+            "Gizmo: {}",
+            match self.mode {
+                GizmoMode::Translate => "translate",
+                GizmoMode::Rotate => "rotate",
+                GizmoMode::Scale => "scale",
+            }
+        );
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// The world's axes, or the object's when scaling.
+    fn axes(&self, transform: Matrix4<f32>) -> [Vector3<f32>; 3] {
+        match self.mode {
+            GizmoMode::Scale => {
+                [transform.x, transform.y, transform.z].map(|axis| axis.truncate().normalize())
+            }
+            _ => [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()],
+        }
+    }
+
+    fn handles(&self) -> &'static [Handle] {
+        use Handle::*;
+        match self.mode {
+            // Planes first, as they lie between the axes.
+            GizmoMode::Translate => &[Plane(0), Plane(1), Plane(2), Axis(0), Axis(1), Axis(2)],
+            GizmoMode::Rotate => &[Ring(0), Ring(1), Ring(2)],
+            GizmoMode::Scale => &[Center, Axis(0), Axis(1), Axis(2)],
+        }
+    }
+
+    pub fn draw(
+        &self,
+        debug_draw: &mut DebugDraw,
+        transform: Matrix4<f32>,
+        view: Matrix4<f32>,
+        projection: &Projection,
+    ) {
+        let center = transform.w.truncate();
+        let size = size(view, projection, center);
+        let axes = self.axes(transform);
+        for &handle in self.handles() {
+            let color = match handle {
+                _ if self.drag.as_ref().is_some_and(|drag| drag.handle == handle) => ACTIVE_COLOR,
+                Handle::Axis(axis) | Handle::Plane(axis) | Handle::Ring(axis) => AXIS_COLORS[axis],
+                Handle::Center => Vector4::new(1.0, 1.0, 1.0, 1.0),
+            };
+            match handle {
+                Handle::Axis(axis) if self.mode == GizmoMode::Scale => {
+                    let end = center + size * axes[axis];
+                    debug_draw.line(center, end, color);
+                    debug_draw.sphere(end, GRAB_DISTANCE * size, color);
+                }
+                Handle::Axis(axis) => debug_draw.arrow(center, center + size * axes[axis], color),
+                Handle::Plane(axis) => {
+                    let (u, v) = (axes[(axis + 1) % 3], axes[(axis + 2) % 3]);
+                    let [near, far] = PLANE_HANDLE.map(|extent| extent * size);
+                    let corners = [
+                        center + near * u + near * v,
+                        center + far * u + near * v,
+                        center + far * u + far * v,
+                        center + near * u + far * v,
+                    ];
+                    for i in 0..4 {
+                        debug_draw.line(corners[i], corners[(i + 1) % 4], color);
+                    }
+                }
+                Handle::Ring(axis) => debug_draw.circle(center, axes[axis], size, color),
+                Handle::Center => debug_draw.sphere(center, 2.0 * GRAB_DISTANCE * size, color),
+            }
+        }
+    }
+
+    /// Starts dragging the entity's handle under the ray, if there is one.
+    pub fn grab(
+        &mut self,
+        world: &World,
+        entity: Entity,
+        ray: (Vector3<f32>, Vector3<f32>),
+        view: Matrix4<f32>,
+        projection: &Projection,
+    ) -> bool {
+        let Some(transform) = world.transforms.get(entity).map(|transform| transform.0) else {
+            return false;
+        };
+        let center = transform.w.truncate();
+        let size = size(view, projection, center);
+        let axes = self.axes(transform);
+        let reach = GRAB_DISTANCE * size;
+        let (_, direction) = ray;
+        let verb = match self.mode {
+            GizmoMode::Translate => "move",
+            GizmoMode::Rotate => "rotate",
+            GizmoMode::Scale => "scale",
+        };
+        let edit = || Edit::begin(format!("{verb} {}", world.name(entity)), world, [entity]);
+        self.drag = self.handles().iter().find_map(|&handle| {
+            let normal = match handle {
+                Handle::Axis(axis) => {
+                    let (along, distance) = closest_on_axis(ray, center, axes[axis])?;
+                    return (distance < reach && (0.0..=size + reach).contains(&along)).then_some(
+                        Drag {
+                            entity,
+                            handle,
+                            transform,
+                            normal: axes[axis],
+                            grab: center + along * axes[axis],
+                            edit: edit(),
+                        },
+                    );
+                }
+                Handle::Plane(axis) | Handle::Ring(axis) => axes[axis],
+                Handle::Center => direction,
+            };
+            let grab = intersect_plane(ray, center, normal)?;
+            let offset = grab - center;
+            let grabbed = match handle {
+                Handle::Plane(axis) => [(axis + 1) % 3, (axis + 2) % 3].iter().all(|&other| {
+                    let extent = offset.dot(axes[other]) / size;
+                    (PLANE_HANDLE[0]..=PLANE_HANDLE[1]).contains(&extent)
+                }),
+                Handle::Ring(_) => (offset.magnitude() - size).abs() < reach,
+                // The plane faces the ray, so this is the ray's distance from the center.
+                _ => offset.magnitude() < 2.0 * reach,
+            };
+            grabbed.then_some(Drag {
+                entity,
+                handle,
+                transform,
+                normal,
+                grab,
+                edit: edit(),
+            })
+        });
+        self.drag.is_some()
+    }
+
+    /// Moves the dragged handle to the ray.
+    pub fn drag(&self, world: &mut World, ray: (Vector3<f32>, Vector3<f32>)) {
+        let Some(drag) = &self.drag else {
+            return;
+        };
+        let center = drag.transform.w.truncate();
+        let point = match drag.handle {
+            Handle::Axis(_) => closest_on_axis(ray, center, drag.normal)
+                .map(|(along, _)| center + along * drag.normal),
+            _ => intersect_plane(ray, center, drag.normal),
+        };
+        let Some(point) = point else {
+            return;
+        };
+        let (from, to) = (drag.grab - center, point - center);
+        let transform = match (self.mode, drag.handle) {
+            (GizmoMode::Translate, _) => Matrix4::from_translation(to - from) * drag.transform,
+            (GizmoMode::Rotate, _) => {
+                let angle = from.cross(to).dot(drag.normal).atan2(from.dot(to));
+                Matrix4::from_translation(center)
+                    * Matrix4::from(Matrix3::from_axis_angle(drag.normal, Rad(angle)))
+                    * Matrix4::from_translation(-center)
+                    * drag.transform
+            }
+            (GizmoMode::Scale, Handle::Axis(axis)) => {
+                let from = from.dot(drag.normal);
+                if from.abs() < f32::EPSILON {
+                    return;
+                }
+                let mut scale = [1.0; 3];
+                scale[axis] = to.dot(drag.normal) / from;
+                drag.transform * Matrix4::from_nonuniform_scale(scale[0], scale[1], scale[2])
+            }
+            (GizmoMode::Scale, _) => {
+                let from = from.magnitude();
+                if from < f32::EPSILON {
+                    return;
+                }
+                drag.transform * Matrix4::from_scale(to.magnitude() / from)
+            }
+        };
+        world.transforms.insert(drag.entity, Transform(transform));
+    }
+
+    /// Ends dragging, returning the edit made.
+    pub fn release(&mut self, world: &World) -> Option<Edit> {
+        Some(self.drag.take()?.edit.finish(world))
+    }
+}
+
+/// Length of the handles at a point, so that they cover the same part of the viewport anywhere.
+fn size(view: Matrix4<f32>, projection: &Projection, center: Vector3<f32>) -> f32 {
+    let depth = -(view * center.extend(1.0)).z;
+    SCREEN_SIZE * 2.0 * depth.max(projection.near) * (0.5 * projection.fovy.0).tan()
+}
+
+/// The point on the axis closest to the ray, as its distance from the center along the axis,
+/// and its distance to the ray. None if the ray runs parallel to the axis or away from it.
+fn closest_on_axis(
+    (origin, direction): (Vector3<f32>, Vector3<f32>),
+    center: Vector3<f32>,
+    axis: Vector3<f32>,
+) -> Option<(f32, f32)> {
+    let offset = origin - center;
+    let (a, b, c) = (
+        direction.dot(direction),
+        direction.dot(axis),
+        axis.dot(axis),
+    );
+    let (d, e) = (direction.dot(offset), axis.dot(offset));
+    let denominator = a * c - b * b;
+    if denominator.abs() < 1e-6 * a * c {
+        return None;
+    }
+    let t = (b * e - c * d) / denominator;
+    let along = (a * e - b * d) / denominator;
+    (t >= 0.0).then(|| {
+        let distance = (origin + t * direction - (center + along * axis)).magnitude();
+        (along, distance)
+    })
+}
+
+/// None if the ray runs parallel to the plane or away from it.
+fn intersect_plane(
+    (origin, direction): (Vector3<f32>, Vector3<f32>),
+    point: Vector3<f32>,
+    normal: Vector3<f32>,
+) -> Option<Vector3<f32>> {
+    let denominator = direction.dot(normal);
+    if denominator.abs() < 1e-6 * direction.magnitude() {
+        return None;
+    }
+    let t = (point - origin).dot(normal) / denominator;
+    (t >= 0.0).then(|| origin + t * direction)
+}
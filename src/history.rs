@@ -0,0 +1,94 @@
+use crate::world::{Entity, Snapshot, World};
+
+/// Edits kept for undoing.
+const MAX_EDITS: usize = 100;
+
+/// Changes of some entities, recorded as their components before and after. Entities which did
+/// not exist before were added, and entities which no longer exist after were deleted.
+#[derive(Debug, Clone)]
+pub struct Edit {
+    description: String,
+    changes: Vec<(Entity, Option<Snapshot>, Option<Snapshot>)>,
+}
+
+impl Edit {
+    /// Starts recording changes of the entities.
+    pub fn begin(
+        description: impl Into<String>,
+        world: &World,
+        entities: impl IntoIterator<Item = Entity>,
+    ) -> Self {
+        Edit {
+            description: description.into(),
+            changes: entities
+                .into_iter()
+                .map(|entity| (entity, world.snapshot(entity), None))
+                .collect(),
+        }
+    }
+
+    /// Records an entity spawned since `begin`.
+    pub fn spawned(&mut self, entity: Entity) {
+        self.changes.push((entity, None, None));
+    }
+
+    /// Takes the entities as they are now as the result of the edit.
+    pub fn finish(mut self, world: &World) -> Self {
+        for (entity, _, after) in &mut self.changes {
+            *after = world.snapshot(*entity);
+        }
+        self.changes.retain(|(_, before, after)| before != after);
+        self
+    }
+}
+
+/// Edits of the scene which can be undone and redone.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl History {
+    /// Records a finished edit, unless it changed nothing. Edits undone before are dropped.
+    pub fn push(&mut self, edit: Edit) {
+        if edit.changes.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        if self.undo.len() == MAX_EDITS {
+            self.undo.remove(0);
+        }
+        self.undo.push(edit);
+    }
+
+    /// Forgets all edits, once the entities they changed have been replaced.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, world: &mut World) {
+        let Some(edit) = self.undo.pop() else {
+            println!("Nothing to undo");
+            return;
+        };
+        for (entity, before, _) in edit.changes.iter().rev() {
+            world.restore(*entity, before.as_ref());
+        }
+        println!("Undo: {}", edit.description);
+        self.redo.push(edit);
+    }
+
+    pub fn redo(&mut self, world: &mut World) {
+        let Some(edit) = self.redo.pop() else {
+            println!("Nothing to redo");
+            return;
+        };
+        for (entity, _, after) in &edit.changes {
+            world.restore(*entity, after.as_ref());
+        }
+        println!("Redo: {}", edit.description);
+        self.undo.push(edit);
+    }
+}
@@ -0,0 +1,171 @@
+use cgmath::Matrix4;
+use wgpu::*;
+
+use crate::texture::texture_memory;
+
+/// Texels per workgroup along each axis.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// A hierarchical depth buffer: the depth buffer followed by mip levels each keeping the
+/// farthest depth of the texels below. A bounding rectangle is tested against a level where it
+/// spans at most two texels across.
+#[derive(Debug)]
+pub struct HiZ {
+    copy_pipelines: [ComputePipeline; 2],
+    downsample_pipeline: ComputePipeline,
+    texture: Texture,
+    levels: Vec<TextureView>,
+    /// All levels, for the culling pass.
+    pub view: TextureView,
+    /// The view-projection the pyramid was last built from, or `None` if it is not built.
+    pub view_projection: Option<Matrix4<f32>>,
+}
+
+impl HiZ {
+    pub fn new(device: &Device) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(include_str!("hiz.wgsl").into()),
+        });
+        let create_pipeline = |entry_point| {
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: None,
+                layout: None,
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        let copy_pipelines = [
+            create_pipeline("copy_depth"),
+            create_pipeline("copy_depth_multisampled"),
+        ];
+        let downsample_pipeline = create_pipeline("downsample");
+        let (texture, levels, view) = create_pyramid(device, 1, 1);
+        HiZ {
+            copy_pipelines,
+            downsample_pipeline,
+            texture,
+            levels,
+            view,
+            view_projection: None,
+        }
+    }
+
+    /// Records rebuilding the pyramid from the depth buffer, which was drawn through the
+    /// view-projection.
+    pub fn build(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        depth: &Texture,
+        view_projection: Matrix4<f32>,
+    ) {
+        if (depth.width(), depth.height()) != (self.texture.width(), self.texture.height()) {
+            (self.texture, self.levels, self.view) =
+                create_pyramid(device, depth.width(), depth.height());
+        }
+
+        let multisampled = depth.sample_count() > 1;
+        let pipeline = &self.copy_pipelines[multisampled as usize];
+        let depth_view = depth.create_view(&TextureViewDescriptor {
+            aspect: TextureAspect::DepthOnly,
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                BindGroupEntry {
+                    binding: multisampled as u32,
+                    resource: BindingResource::TextureView(&depth_view),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(&self.levels[0]),
+                },
+            ],
+        });
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        dispatch(&mut pass, self.texture.width(), self.texture.height());
+
+        pass.set_pipeline(&self.downsample_pipeline);
+        for level in 1..self.levels.len() {
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: None,
+                layout: &self.downsample_pipeline.get_bind_group_layout(0),
+                entries: &[
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::TextureView(&self.levels[level - 1]),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: BindingResource::TextureView(&self.levels[level]),
+                    },
+                ],
+            });
+            pass.set_bind_group(0, &bind_group, &[]);
+            let size = self
+                .texture
+                .size()
+                .mip_level_size(level as u32, TextureDimension::D2);
+            dispatch(&mut pass, size.width, size.height);
+        }
+        self.view_projection = Some(view_projection);
+    }
+
+    /// Forgets the pyramid, so that nothing is tested against it until it is built again.
+    pub fn invalidate(&mut self) {
+        self.view_projection = None;
+    }
+
+    pub fn memory(&self) -> u64 {
+        texture_memory(&self.texture)
+    }
+}
+
+fn dispatch(pass: &mut ComputePass, width: u32, height: u32) {
+    pass.dispatch_workgroups(
+        width.div_ceil(WORKGROUP_SIZE),
+        height.div_ceil(WORKGROUP_SIZE),
+        1,
+    );
+}
+
+/// The texture with a view per level, and one of all levels.
+fn create_pyramid(
+    device: &Device,
+    width: u32,
+    height: u32,
+) -> (Texture, Vec<TextureView>, TextureView) {
+    let size = Extent3d {
+        width,
+        height,
+        depth_or_array_layers: 1,
+    };
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("Hi-Z"),
+        size,
+        mip_level_count: size.max_mips(TextureDimension::D2),
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::R32Float,
+        view_formats: &[],
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING,
+    });
+    let levels = (0..texture.mip_level_count())
+        .map(|level| {
+            texture.create_view(&TextureViewDescriptor {
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect();
+    let view = texture.create_view(&Default::default());
+    (texture, levels, view)
+}
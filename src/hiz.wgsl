@@ -0,0 +1,45 @@
+@group(0) @binding(0) var depth: texture_depth_2d;
+@group(0) @binding(1) var depth_multisampled: texture_depth_multisampled_2d;
+@group(0) @binding(2) var source: texture_2d<f32>;
+@group(0) @binding(3) var destination: texture_storage_2d<r32float, write>;
+
+@compute @workgroup_size(8, 8)
+fn copy_depth(@builtin(global_invocation_id) id: vec3<u32>) {
+    if any(id.xy >= textureDimensions(destination)) {
+        return;
+    }
+    textureStore(destination, id.xy, vec4<f32>(textureLoad(depth, id.xy, 0)));
+}
+
+/// Keeps the farthest of the samples.
+@compute @workgroup_size(8, 8)
+fn copy_depth_multisampled(@builtin(global_invocation_id) id: vec3<u32>) {
+    if any(id.xy >= textureDimensions(destination)) {
+        return;
+    }
+    var farthest = 0.0;
+    for (var sample = 0u; sample < textureNumSamples(depth_multisampled); sample++) {
+        farthest = max(farthest, textureLoad(depth_multisampled, id.xy, i32(sample)));
+    }
+    textureStore(destination, id.xy, vec4<f32>(farthest));
+}
+
+/// Keeps the farthest depth of the source texels the destination texel overlaps, which are up to
+/// three across where the source size is odd.
+@compute @workgroup_size(8, 8)
+fn downsample(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(destination);
+    if any(id.xy >= size) {
+        return;
+    }
+    let source_size = textureDimensions(source);
+    let start = id.xy * source_size / size;
+    let end = ((id.xy + 1u) * source_size + size - 1u) / size;
+    var farthest = 0.0;
+    for (var y = start.y; y < end.y; y++) {
+        for (var x = start.x; x < end.x; x++) {
+            farthest = max(farthest, textureLoad(source, vec2<u32>(x, y), 0).r);
+        }
+    }
+    textureStore(destination, id.xy, vec4<f32>(farthest));
+}
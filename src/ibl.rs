@@ -0,0 +1,236 @@
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::{
+    environment::{Cubemap, CUBEMAP_FORMAT},
+    render::as_byte_slice,
+};
+
+const SOURCE_SIZE: u32 = 512;
+const IRRADIANCE_SIZE: u32 = 32;
+const SPECULAR_SIZE: u32 = 128;
+const SPECULAR_MIP_COUNT: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 256;
+
+/// Image-based lighting derived from an environment cubemap.
+#[derive(Debug)]
+pub struct Ibl {
+    /// Cosine-convolved environment for diffuse ambient lighting.
+    pub irradiance: Cubemap,
+    /// GGX-prefiltered environment, with roughness increasing linearly over the mip chain.
+    pub specular: Cubemap,
+}
+
+fn create_cube_texture(device: &Device, size: u32, mip_level_count: u32) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: None,
+        size: Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: CUBEMAP_FORMAT,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING,
+        view_formats: &[],
+    })
+}
+
+fn mip_view(texture: &Texture, mip: u32, dimension: TextureViewDimension) -> TextureView {
+    texture.create_view(&TextureViewDescriptor {
+        dimension: Some(dimension),
+        base_mip_level: mip,
+        mip_level_count: Some(1),
+        ..Default::default()
+    })
+}
+
+fn create_pipeline(device: &Device, module: &ShaderModule, entry_point: &str) -> ComputePipeline {
+    device.create_compute_pipeline(&ComputePipelineDescriptor {
+        label: None,
+        layout: None,
+        module,
+        entry_point: Some(entry_point),
+        compilation_options: Default::default(),
+        cache: None,
+    })
+}
+
+fn create_shader_module(device: &Device) -> ShaderModule {
+    device.create_shader_module(ShaderModuleDescriptor {
+        label: None,
+        source: ShaderSource::Wgsl(
+            concat!(include_str!("cube.wgsl"), include_str!("ibl.wgsl")).into(),
+        ),
+    })
+}
+
+fn dispatch_cube(
+    encoder: &mut CommandEncoder,
+    pipeline: &ComputePipeline,
+    bind_group: &BindGroup,
+    size: u32,
+) {
+    let mut pass = encoder.begin_compute_pass(&Default::default());
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.dispatch_workgroups(size.div_ceil(8), size.div_ceil(8), 6);
+}
+
+impl Ibl {
+    pub fn new(device: &Device, queue: &Queue, environment: &Cubemap) -> Self {
+        let module = create_shader_module(device);
+        let downsample = create_pipeline(device, &module, "downsample");
+        let irradiance_pipeline = create_pipeline(device, &module, "irradiance");
+        let prefilter = create_pipeline(device, &module, "prefilter");
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = |pipeline: &ComputePipeline,
+                          source: &TextureView,
+                          destination: &TextureView,
+                          params: Option<&Buffer>| {
+            let mut entries = vec![
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(source),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(destination),
+                },
+            ];
+            if let Some(params) = params {
+                entries.push(BindGroupEntry {
+                    binding: 3,
+                    resource: params.as_entire_binding(),
+                });
+            }
+            device.create_bind_group(&BindGroupDescriptor {
+                label: None,
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &entries,
+            })
+        };
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+
+        // Resample the environment into a power-of-two mip chain, which the convolutions
+        // sample from at lower resolutions to keep the sample counts low without aliasing.
+        let source_size = SOURCE_SIZE.min(prev_power_of_two(environment.texture.width()));
+        let source_mip_count = source_size.ilog2() + 1;
+        let source = create_cube_texture(device, source_size, source_mip_count);
+        for mip in 0..source_mip_count {
+            let input = if mip == 0 {
+                environment.view.clone()
+            } else {
+                mip_view(&source, mip - 1, TextureViewDimension::Cube)
+            };
+            let output = mip_view(&source, mip, TextureViewDimension::D2Array);
+            dispatch_cube(
+                &mut encoder,
+                &downsample,
+                &bind_group(&downsample, &input, &output, None),
+                source_size >> mip,
+            );
+        }
+        let source = Cubemap::from_texture(source);
+
+        let irradiance = create_cube_texture(device, IRRADIANCE_SIZE, 1);
+        dispatch_cube(
+            &mut encoder,
+            &irradiance_pipeline,
+            &bind_group(
+                &irradiance_pipeline,
+                &source.view,
+                &mip_view(&irradiance, 0, TextureViewDimension::D2Array),
+                None,
+            ),
+            IRRADIANCE_SIZE,
+        );
+
+        let specular = create_cube_texture(device, SPECULAR_SIZE, SPECULAR_MIP_COUNT);
+        for mip in 0..SPECULAR_MIP_COUNT {
+            let roughness = mip as f32 / (SPECULAR_MIP_COUNT - 1) as f32;
+            let params = device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                contents: as_byte_slice(&[roughness, 0.0, 0.0, 0.0]),
+                usage: BufferUsages::UNIFORM,
+            });
+            dispatch_cube(
+                &mut encoder,
+                &prefilter,
+                &bind_group(
+                    &prefilter,
+                    &source.view,
+                    &mip_view(&specular, mip, TextureViewDimension::D2Array),
+                    Some(&params),
+                ),
+                SPECULAR_SIZE >> mip,
+            );
+        }
+
+        queue.submit(Some(encoder.finish()));
+
+        Ibl {
+            irradiance: Cubemap::from_texture(irradiance),
+            specular: Cubemap::from_texture(specular),
+        }
+    }
+}
+
+fn prev_power_of_two(n: u32) -> u32 {
+    1 << n.max(1).ilog2()
+}
+
+/// Precomputes the split-sum BRDF lookup table, which is independent of the environment.
+pub fn create_brdf_lut(device: &Device, queue: &Queue) -> TextureView {
+    let module = create_shader_module(device);
+    let pipeline = create_pipeline(device, &module, "integrate_brdf");
+
+    let texture = device.create_texture(&TextureDescriptor {
+        label: None,
+        size: Extent3d {
+            width: BRDF_LUT_SIZE,
+            height: BRDF_LUT_SIZE,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::Rgba16Float,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&Default::default());
+
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout: &pipeline.get_bind_group_layout(0),
+        entries: &[BindGroupEntry {
+            binding: 4,
+            resource: BindingResource::TextureView(&view),
+        }],
+    });
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    let mut pass = encoder.begin_compute_pass(&Default::default());
+    pass.set_pipeline(&pipeline);
+    pass.set_bind_group(0, &bind_group, &[]);
+    pass.dispatch_workgroups(BRDF_LUT_SIZE / 8, BRDF_LUT_SIZE / 8, 1);
+    drop(pass);
+    queue.submit(Some(encoder.finish()));
+
+    view
+}
@@ -0,0 +1,149 @@
+const PI: f32 = 3.14159265359;
+
+struct PrefilterParams {
+    roughness: f32,
+}
+
+@group(0) @binding(0) var source: texture_cube<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+@group(0) @binding(2) var destination: texture_storage_2d_array<rgba16float, write>;
+@group(0) @binding(3) var<uniform> params: PrefilterParams;
+@group(0) @binding(4) var brdf_lut: texture_storage_2d<rgba16float, write>;
+
+fn hammersley(i: u32, count: u32) -> vec2<f32> {
+    return vec2<f32>(f32(i) / f32(count), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+/// Orthonormal basis around `n`, with `n` as the third column.
+fn tangent_frame(n: vec3<f32>) -> mat3x3<f32> {
+    var up = vec3<f32>(1.0, 0.0, 0.0);
+    if abs(n.z) < 0.999 {
+        up = vec3<f32>(0.0, 0.0, 1.0);
+    }
+    let tangent = normalize(cross(up, n));
+    let bitangent = cross(n, tangent);
+    return mat3x3<f32>(tangent, bitangent, n);
+}
+
+fn importance_sample_ggx(xi: vec2<f32>, n: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    let h = vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+    return normalize(tangent_frame(n) * h);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a2 = roughness * roughness * roughness * roughness;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+fn geometry_schlick_ggx(n_dot_v: f32, roughness: f32) -> f32 {
+    let k = roughness * roughness / 2.0;
+    return n_dot_v / (n_dot_v * (1.0 - k) + k);
+}
+
+/// Resamples the source cube into one mip of the destination. Bilinear filtering at the
+/// texel centers of a half-resolution target amounts to a 2×2 box filter.
+@compute @workgroup_size(8, 8, 1)
+fn downsample(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(destination).x;
+    if id.x >= size || id.y >= size {
+        return;
+    }
+    let color = textureSampleLevel(source, source_sampler, texel_direction(id, size), 0.0);
+    textureStore(destination, id.xy, id.z, color);
+}
+
+/// Cosine-weighted convolution of the hemisphere around each texel direction.
+@compute @workgroup_size(8, 8, 1)
+fn irradiance(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(destination).x;
+    if id.x >= size || id.y >= size {
+        return;
+    }
+    let frame = tangent_frame(texel_direction(id, size));
+
+    // Sample a mip whose resolution roughly matches the angular step to avoid aliasing.
+    let lod = max(log2(f32(textureDimensions(source).x) / 32.0), 0.0);
+    let delta = 0.05;
+    var sum = vec3<f32>(0.0);
+    var count = 0.0;
+    for (var phi = 0.0; phi < 2.0 * PI; phi += delta) {
+        for (var theta = 0.0; theta < 0.5 * PI; theta += delta) {
+            let tangent_sample = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let color = textureSampleLevel(source, source_sampler, frame * tangent_sample, lod).rgb;
+            sum += color * cos(theta) * sin(theta);
+            count += 1.0;
+        }
+    }
+    textureStore(destination, id.xy, id.z, vec4<f32>(PI * sum / count, 1.0));
+}
+
+/// GGX prefiltering for one roughness level, using filtered importance sampling
+/// to pick source mips that match each sample's solid angle.
+@compute @workgroup_size(8, 8, 1)
+fn prefilter(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(destination).x;
+    if id.x >= size || id.y >= size {
+        return;
+    }
+    let n = texel_direction(id, size);
+    let source_size = f32(textureDimensions(source).x);
+    let texel_solid_angle = 4.0 * PI / (6.0 * source_size * source_size);
+
+    let sample_count = 256u;
+    var sum = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < sample_count; i++) {
+        let h = importance_sample_ggx(hammersley(i, sample_count), n, params.roughness);
+        let l = normalize(2.0 * dot(n, h) * h - n);
+        let n_dot_l = dot(n, l);
+        if n_dot_l > 0.0 {
+            let n_dot_h = max(dot(n, h), 0.0);
+            let pdf = distribution_ggx(n_dot_h, params.roughness) / 4.0 + 0.0001;
+            let sample_solid_angle = 1.0 / (f32(sample_count) * pdf);
+            var lod = 0.0;
+            if params.roughness > 0.0 {
+                lod = 0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0;
+            }
+            sum += textureSampleLevel(source, source_sampler, l, lod).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+    textureStore(destination, id.xy, id.z, vec4<f32>(sum / weight, 1.0));
+}
+
+/// Split-sum BRDF integration, indexed by `n·v` horizontally and roughness vertically.
+@compute @workgroup_size(8, 8, 1)
+fn integrate_brdf(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(brdf_lut);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+    let n_dot_v = (f32(id.x) + 0.5) / f32(size.x);
+    let roughness = (f32(id.y) + 0.5) / f32(size.y);
+    let v = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    let n = vec3<f32>(0.0, 0.0, 1.0);
+
+    let sample_count = 1024u;
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < sample_count; i++) {
+        let h = importance_sample_ggx(hammersley(i, sample_count), n, roughness);
+        let l = normalize(2.0 * dot(v, h) * h - v);
+        let n_dot_l = max(l.z, 0.0);
+        if n_dot_l > 0.0 {
+            let n_dot_h = max(h.z, 0.0);
+            let v_dot_h = max(dot(v, h), 0.0);
+            let g = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+            let g_vis = g * v_dot_h / (n_dot_h * n_dot_v);
+            let fc = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fc) * g_vis;
+            bias += fc * g_vis;
+        }
+    }
+    textureStore(brdf_lut, id.xy, vec4<f32>(scale, bias, 0.0, 0.0) / f32(sample_count));
+}
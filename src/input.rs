@@ -0,0 +1,558 @@
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use crate::scene::Primitive;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    CycleAntiAliasing,
+    ToggleToon,
+    RotateLight,
+    ToggleLightOrbit,
+    ToggleCascadeDebug,
+    CycleShadowFilter,
+    ToggleLightGizmos,
+    ToggleDeferred,
+    ToggleHemisphere,
+    ToggleDayCycle,
+    TimeOfDayBackward,
+    TimeOfDayForward,
+    DecreaseShadowBias,
+    IncreaseShadowBias,
+    DecreaseShadowNormalOffset,
+    IncreaseShadowNormalOffset,
+    ToggleMotionBlur,
+    ToggleBloom,
+    CycleTonemapper,
+    ToggleAutoExposure,
+    DecreaseExposure,
+    IncreaseExposure,
+    ToggleStylize,
+    ToggleColorGrading,
+    ToggleChromaticAberration,
+    ToggleGammaDebug,
+    CycleFog,
+    CyclePixelation,
+    ToggleSharpening,
+    ToggleDithering,
+    TogglePainterly,
+    CycleBufferView,
+    ToggleDynamicResolution,
+    ToggleAnimation,
+    AnimationBackward,
+    AnimationForward,
+    CycleCamera,
+    ToggleCameraParent,
+    CycleGizmo,
+    SaveScene,
+    LoadScene,
+    DeleteSelected,
+    DuplicateSelected,
+    Undo,
+    Redo,
+    RegisterPrefab,
+    PlacePrefab,
+    KeySelected,
+    Spawn(Primitive),
+    PrintStats,
+    ToggleUi,
+    ToggleHelp,
+    ToggleConsole,
+    ToggleBounds,
+    ToggleDebugCamera,
+    TogglePause,
+    StepFrame,
+    CycleRenderMode,
+    Screenshot,
+    /// By index.
+    SwitchScene(usize),
+    BookmarkView,
+    NextBookmark,
+    #[cfg(feature = "physics")]
+    DropCubes,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct KeyBinding {
+    /// Held with the key. Cmd counts as Ctrl, and Shift only matters together with Ctrl.
+    pub modifiers: ModifiersState,
+    pub key: KeyCode,
+    pub action: Action,
+    pub description: &'static str,
+}
+
+pub const KEY_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyM,
+        action: Action::CycleAntiAliasing,
+        description: "Cycle anti-aliasing (off, MSAA, FXAA, TAA)",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyT,
+        action: Action::ToggleToon,
+        description: "Toggle toon shading",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyL,
+        action: Action::RotateLight,
+        description: "Rotate the light",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::KeyL,
+        action: Action::ToggleLightOrbit,
+        description: "Start or stop orbiting the point lights",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyC,
+        action: Action::ToggleCascadeDebug,
+        description: "Toggle shadow cascade debug view",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyF,
+        action: Action::CycleShadowFilter,
+        description: "Cycle shadow filtering",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyG,
+        action: Action::ToggleLightGizmos,
+        description: "Toggle light gizmos",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyD,
+        action: Action::ToggleDeferred,
+        description: "Toggle deferred rendering",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::KeyE,
+        action: Action::ToggleHemisphere,
+        description: "Toggle hemisphere ambient lighting",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyN,
+        action: Action::ToggleDayCycle,
+        description: "Start or pause the day-night cycle",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::BracketLeft,
+        action: Action::TimeOfDayBackward,
+        description: "Scrub the time of day backward",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::BracketRight,
+        action: Action::TimeOfDayForward,
+        description: "Scrub the time of day forward",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Minus,
+        action: Action::DecreaseShadowBias,
+        description: "Decrease the shadow depth bias",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Equal,
+        action: Action::IncreaseShadowBias,
+        description: "Increase the shadow depth bias",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Comma,
+        action: Action::DecreaseShadowNormalOffset,
+        description: "Decrease the shadow normal offset",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Period,
+        action: Action::IncreaseShadowNormalOffset,
+        description: "Increase the shadow normal offset",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyV,
+        action: Action::ToggleMotionBlur,
+        description: "Toggle motion blur",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyB,
+        action: Action::ToggleBloom,
+        description: "Toggle bloom",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyY,
+        action: Action::CycleTonemapper,
+        description: "Cycle tone mapping",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyE,
+        action: Action::ToggleAutoExposure,
+        description: "Toggle automatic exposure",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Digit9,
+        action: Action::DecreaseExposure,
+        description: "Decrease the exposure",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Digit0,
+        action: Action::IncreaseExposure,
+        description: "Increase the exposure",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyU,
+        action: Action::ToggleStylize,
+        description: "Toggle vignette and film grain",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyK,
+        action: Action::ToggleColorGrading,
+        description: "Toggle color grading",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyJ,
+        action: Action::ToggleChromaticAberration,
+        description: "Toggle chromatic aberration",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyI,
+        action: Action::ToggleGammaDebug,
+        description: "Toggle showing the right half without gamma encoding",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyO,
+        action: Action::CycleFog,
+        description: "Cycle fog",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyP,
+        action: Action::CyclePixelation,
+        description: "Cycle pixelation",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyS,
+        action: Action::ToggleSharpening,
+        description: "Toggle contrast-adaptive sharpening",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyX,
+        action: Action::ToggleDithering,
+        description: "Toggle dithering",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyQ,
+        action: Action::TogglePainterly,
+        description: "Toggle painterly filter",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyZ,
+        action: Action::CycleBufferView,
+        description: "Cycle showing depth, normals, velocity and shadow map",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyR,
+        action: Action::ToggleDynamicResolution,
+        description: "Toggle dynamic resolution",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Space,
+        action: Action::ToggleAnimation,
+        description: "Play or pause the model's animation and the timeline",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::ArrowLeft,
+        action: Action::AnimationBackward,
+        description: "Scrub the animation backward",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::ArrowRight,
+        action: Action::AnimationForward,
+        description: "Scrub the animation forward",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Tab,
+        action: Action::CycleCamera,
+        description: "Cycle through the model's cameras",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyA,
+        action: Action::ToggleCameraParent,
+        description: "Attach the orbit camera to the selected object, or detach it",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyW,
+        action: Action::CycleGizmo,
+        description: "Cycle the gizmo between moving, rotating and scaling",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::F5,
+        action: Action::SaveScene,
+        description: "Save the scene",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::F9,
+        action: Action::LoadScene,
+        description: "Load the saved scene",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Backspace,
+        action: Action::DeleteSelected,
+        description: "Delete the selected object",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::KeyD,
+        action: Action::DuplicateSelected,
+        description: "Duplicate the selected object",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::KeyZ,
+        action: Action::Undo,
+        description: "Undo the last edit",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL.union(ModifiersState::SHIFT),
+        key: KeyCode::KeyZ,
+        action: Action::Redo,
+        description: "Redo the last undone edit",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::KeyP,
+        action: Action::RegisterPrefab,
+        description: "Register the selected object as the prefab to place",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::KeyI,
+        action: Action::PlacePrefab,
+        description: "Place an instance of the prefab at the orbit camera's center",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::KeyK,
+        action: Action::KeySelected,
+        description: "Key the selected object's transform at the timeline's time",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::Digit1,
+        action: Action::Spawn(Primitive::Cube),
+        description: "Add a cube at the orbit camera's center",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::Digit2,
+        action: Action::Spawn(Primitive::Sphere),
+        description: "Add a sphere at the orbit camera's center",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::Digit3,
+        action: Action::Spawn(Primitive::Plane),
+        description: "Add a plane at the orbit camera's center",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::F3,
+        action: Action::PrintStats,
+        description: "Print the scene statistics",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::F1,
+        action: Action::ToggleUi,
+        description: "Toggle the debug UI",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::F2,
+        action: Action::ToggleBounds,
+        description: "Toggle object bounds, red where culled",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::KeyF,
+        action: Action::ToggleDebugCamera,
+        description: "Toggle the debug camera, showing the main camera's frustum",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::F6,
+        action: Action::TogglePause,
+        description: "Pause or resume time, leaving the camera free",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::F7,
+        action: Action::StepFrame,
+        description: "Advance a paused frame, pausing first if running",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::KeyR,
+        action: Action::CycleRenderMode,
+        description: "Cycle the render mode: albedo, normals, depth, UVs, wireframe, overdraw",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::F12,
+        action: Action::Screenshot,
+        description: "Save a screenshot",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::KeyH,
+        action: Action::ToggleHelp,
+        description: "Toggle this help",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Backquote,
+        action: Action::ToggleConsole,
+        description: "Toggle the log console",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Digit1,
+        action: Action::SwitchScene(0),
+        description: "Switch to scene 1",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Digit2,
+        action: Action::SwitchScene(1),
+        description: "Switch to scene 2",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Digit3,
+        action: Action::SwitchScene(2),
+        description: "Switch to scene 3",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Digit4,
+        action: Action::SwitchScene(3),
+        description: "Switch to scene 4",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Digit5,
+        action: Action::SwitchScene(4),
+        description: "Switch to scene 5",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Digit6,
+        action: Action::SwitchScene(5),
+        description: "Switch to scene 6",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Digit7,
+        action: Action::SwitchScene(6),
+        description: "Switch to scene 7",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::empty(),
+        key: KeyCode::Digit8,
+        action: Action::SwitchScene(7),
+        description: "Switch to scene 8",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::KeyM,
+        action: Action::BookmarkView,
+        description: "Bookmark the view in this scene",
+    },
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::KeyJ,
+        action: Action::NextBookmark,
+        description: "Jump to the scene's next bookmarked view",
+    },
+    #[cfg(feature = "physics")]
+    KeyBinding {
+        modifiers: ModifiersState::CONTROL,
+        key: KeyCode::KeyB,
+        action: Action::DropCubes,
+        description: "Drop 100 cubes onto the scene",
+    },
+];
+
+/// What the mouse and trackpad do, which is not configurable.
+pub const MOUSE_BINDINGS: &[(&str, &str)] = &[
+    ("Click", "Select an object, or grab a handle of the gizmo"),
+    ("Drag", "Move a grabbed handle of the gizmo"),
+    (
+        "Scroll",
+        "Orbit the camera, or the debug camera while active",
+    ),
+    ("Pinch", "Zoom the camera, or the debug camera while active"),
+    (
+        "Click an axis",
+        "View from that side, through the axes in the top right corner",
+    ),
+];
+
+impl KeyBinding {
+    /// Such as `Ctrl+Shift+KeyZ`.
+    pub fn shortcut(&self) -> String {
+        let mut shortcut = String::new();
+        if self.modifiers.control_key() {
+            shortcut += "Ctrl+";
+        }
+        if self.modifiers.shift_key() {
+            shortcut += "Shift+";
+        }
+        format!("{shortcut}{:?}", self.key)
+    }
+}
+
+pub fn action(key: KeyCode, modifiers: ModifiersState) -> Option<Action> {
+    let held = if modifiers.control_key() || modifiers.super_key() {
+        ModifiersState::CONTROL | (modifiers & ModifiersState::SHIFT)
+    } else {
+        ModifiersState::empty()
+    };
+    KEY_BINDINGS
+        .iter()
+        .find(|binding| binding.key == key && binding.modifiers == held)
+        .map(|binding| binding.action)
+}
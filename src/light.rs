@@ -0,0 +1,386 @@
+use cgmath::{Angle, Deg, InnerSpace, Matrix3, Rad, Vector3};
+use serde::{Deserialize, Serialize};
+
+/// A light infinitely far away, such as the sun.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct DirectionalLight {
+    /// Direction in which the light travels.
+    pub direction: Vector3<f32>,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        DirectionalLight {
+            direction: Vector3::new(-0.4, -1.0, -0.3).normalize(),
+            color: Vector3::new(1.0, 0.95, 0.9),
+            intensity: 3.0,
+        }
+    }
+}
+
+impl DirectionalLight {
+    /// Rotates the light around the vertical axis.
+    pub fn rotate(&mut self, angle: Rad<f32>) {
+        self.direction = Matrix3::from_angle_y(angle) * self.direction;
+    }
+
+    /// The sun at the given hour, reddening towards the horizon and fading out below it.
+    pub fn sun(hours: f32) -> Self {
+        let direction = sun_direction(hours);
+        let elevation = -direction.y;
+        DirectionalLight {
+            direction,
+            color: color_temperature(1800.0 + 4700.0 * smoothstep(0.0, 0.6, elevation)),
+            intensity: 3.0 * smoothstep(-0.02, 0.1, elevation),
+        }
+    }
+}
+
+/// Hours of the simulated day passing per second while the cycle is animated.
+const DAY_CYCLE_SPEED: f32 = 0.2;
+
+/// A time of day driving the sun and the sky.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct DayCycle {
+    /// Hours since midnight.
+    pub time: f32,
+    pub animate: bool,
+}
+
+impl DayCycle {
+    pub fn advance(&mut self, dt: f32) {
+        if self.animate {
+            self.scrub(DAY_CYCLE_SPEED * dt);
+        }
+    }
+
+    /// Moves the time by the given number of hours, wrapping around midnight.
+    pub fn scrub(&mut self, hours: f32) {
+        self.time = (self.time + hours).rem_euclid(24.0);
+    }
+}
+
+/// Direction in which sunlight travels at the given hour. The sun rises in the east (+X) at six,
+/// culminates at noon slightly south (+Z) of the zenith, and sets in the west at eighteen.
+pub fn sun_direction(hours: f32) -> Vector3<f32> {
+    let angle = Rad(std::f32::consts::PI * (hours - 6.0) / 12.0);
+    let tilt = Deg(20.0);
+    -Vector3::new(
+        angle.cos(),
+        angle.sin() * tilt.cos(),
+        angle.sin() * tilt.sin(),
+    )
+}
+
+/// Approximate color of a black body at the given temperature in kelvin, normalized to a
+/// maximum of one.
+pub fn color_temperature(kelvin: f32) -> Vector3<f32> {
+    let t = kelvin / 100.0;
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.7 * (t - 60.0).powf(-0.1332)
+    };
+    let green = if t <= 66.0 {
+        99.47 * t.ln() - 161.12
+    } else {
+        288.12 * (t - 60.0).powf(-0.0755)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.52 * (t - 10.0).ln() - 305.04
+    };
+    let color = Vector3::new(red, green, blue).map(|c| c.clamp(0.0, 255.0));
+    color / color.x.max(color.y).max(color.z)
+}
+
+pub fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Cheap ambient light from a sky and a ground color, blended by the normal's height.
+/// Replaces the irradiance from the environment.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct HemisphereLight {
+    pub sky_color: Vector3<f32>,
+    pub ground_color: Vector3<f32>,
+    pub intensity: f32,
+}
+
+impl Default for HemisphereLight {
+    fn default() -> Self {
+        HemisphereLight {
+            sky_color: Vector3::new(0.35, 0.45, 0.65),
+            ground_color: Vector3::new(0.15, 0.12, 0.1),
+            intensity: 1.0,
+        }
+    }
+}
+
+/// A light emitting in all directions from a point.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PointLight {
+    pub position: Vector3<f32>,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    /// Distance at which the light has faded out completely.
+    pub radius: f32,
+    /// Quadratic falloff coefficient, with one approximating the inverse-square law.
+    pub attenuation: f32,
+    /// Only the first few shadow-casting point lights get a shadow map.
+    pub cast_shadows: bool,
+}
+
+impl PointLight {
+    pub fn new(position: Vector3<f32>, color: Vector3<f32>, intensity: f32) -> Self {
+        PointLight {
+            position,
+            color,
+            intensity,
+            radius: 10.0,
+            attenuation: 1.0,
+            cast_shadows: false,
+        }
+    }
+}
+
+/// A point light restricted to a cone.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpotLight {
+    pub position: Vector3<f32>,
+    /// Axis of the cone.
+    pub direction: Vector3<f32>,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    /// Distance at which the light has faded out completely.
+    pub radius: f32,
+    /// Quadratic falloff coefficient, with one approximating the inverse-square law.
+    pub attenuation: f32,
+    /// Half-angle within which the light is at full intensity.
+    pub inner_angle: Rad<f32>,
+    /// Half-angle beyond which the light has faded out.
+    pub outer_angle: Rad<f32>,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Vector3<f32>,
+        direction: Vector3<f32>,
+        color: Vector3<f32>,
+        intensity: f32,
+    ) -> Self {
+        SpotLight {
+            position,
+            direction,
+            color,
+            intensity,
+            radius: 10.0,
+            attenuation: 1.0,
+            inner_angle: Deg(20.0).into(),
+            outer_angle: Deg(30.0).into(),
+        }
+    }
+}
+
+/// A rectangular emitter, shaded with linearly transformed cosines for soft highlights.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RectLight {
+    /// Center of the rectangle.
+    pub position: Vector3<f32>,
+    /// Direction the emitting side faces.
+    pub direction: Vector3<f32>,
+    pub width: f32,
+    pub height: f32,
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+    /// Emits from the back side as well.
+    pub two_sided: bool,
+}
+
+impl RectLight {
+    pub fn new(
+        position: Vector3<f32>,
+        direction: Vector3<f32>,
+        color: Vector3<f32>,
+        intensity: f32,
+    ) -> Self {
+        RectLight {
+            position,
+            direction,
+            width: 2.0,
+            height: 1.0,
+            color,
+            intensity,
+            two_sided: false,
+        }
+    }
+
+    /// Half extents along the horizontal and vertical edges, which are kept level where possible.
+    pub fn axes(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let normal = self.direction.normalize();
+        let up = if normal.y.abs() > 0.99 {
+            Vector3::unit_z()
+        } else {
+            Vector3::unit_y()
+        };
+        let right = up.cross(normal).normalize();
+        let up = normal.cross(right);
+        (0.5 * self.width * right, 0.5 * self.height * up)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct LightUniforms {
+    #[allow(dead_code)]
+    direction: Vector3<f32>,
+    #[allow(dead_code)]
+    intensity: f32,
+    #[allow(dead_code)]
+    color: Vector3<f32>,
+    /// Whether the hemisphere light replaces the environment's irradiance.
+    #[allow(dead_code)]
+    hemisphere: u32,
+    /// Hemisphere colors premultiplied with its intensity.
+    #[allow(dead_code)]
+    sky_color: Vector3<f32>,
+    #[allow(dead_code)]
+    padding_sky: f32,
+    #[allow(dead_code)]
+    ground_color: Vector3<f32>,
+    #[allow(dead_code)]
+    padding_ground: f32,
+}
+
+impl LightUniforms {
+    pub fn new(light: &DirectionalLight, hemisphere: Option<&HemisphereLight>) -> Self {
+        let (sky_color, ground_color) = hemisphere.map_or(
+            (Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0)),
+            |h| (h.sky_color * h.intensity, h.ground_color * h.intensity),
+        );
+        LightUniforms {
+            direction: light.direction.normalize(),
+            intensity: light.intensity,
+            color: light.color,
+            hemisphere: hemisphere.is_some() as u32,
+            sky_color,
+            padding_sky: 0.0,
+            ground_color,
+            padding_ground: 0.0,
+        }
+    }
+}
+
+/// Layout of a point light in the light storage buffer.
+#[derive(Debug, Copy, Clone)]
+pub struct PointLightUniforms {
+    #[allow(dead_code)]
+    position: Vector3<f32>,
+    #[allow(dead_code)]
+    radius: f32,
+    /// Color premultiplied with the intensity.
+    #[allow(dead_code)]
+    color: Vector3<f32>,
+    #[allow(dead_code)]
+    attenuation: f32,
+    /// Layer of the light's shadow cubemap, or -1 without shadows.
+    #[allow(dead_code)]
+    shadow_index: i32,
+    #[allow(dead_code)]
+    padding: [f32; 3],
+}
+
+impl PointLightUniforms {
+    pub fn new(light: &PointLight, shadow_index: Option<usize>) -> Self {
+        PointLightUniforms {
+            position: light.position,
+            radius: light.radius,
+            color: light.color * light.intensity,
+            attenuation: light.attenuation,
+            shadow_index: shadow_index.map_or(-1, |index| index as i32),
+            padding: [0.0; 3],
+        }
+    }
+}
+
+/// Layout of a spot light in the light storage buffer.
+#[derive(Debug, Copy, Clone)]
+pub struct SpotLightUniforms {
+    #[allow(dead_code)]
+    position: Vector3<f32>,
+    #[allow(dead_code)]
+    radius: f32,
+    /// Color premultiplied with the intensity.
+    #[allow(dead_code)]
+    color: Vector3<f32>,
+    #[allow(dead_code)]
+    attenuation: f32,
+    #[allow(dead_code)]
+    direction: Vector3<f32>,
+    #[allow(dead_code)]
+    cos_inner_angle: f32,
+    #[allow(dead_code)]
+    cos_outer_angle: f32,
+    #[allow(dead_code)]
+    padding: [f32; 3],
+}
+
+impl From<&SpotLight> for SpotLightUniforms {
+    fn from(light: &SpotLight) -> Self {
+        SpotLightUniforms {
+            position: light.position,
+            radius: light.radius,
+            color: light.color * light.intensity,
+            attenuation: light.attenuation,
+            direction: light.direction.normalize(),
+            cos_inner_angle: light.inner_angle.cos(),
+            cos_outer_angle: light.outer_angle.cos(),
+            padding: [0.0; 3],
+        }
+    }
+}
+
+/// Layout of a rectangular light in the light storage buffer.
+#[derive(Debug, Copy, Clone)]
+pub struct RectLightUniforms {
+    #[allow(dead_code)]
+    position: Vector3<f32>,
+    #[allow(dead_code)]
+    two_sided: u32,
+    /// Half extent along the horizontal edges.
+    #[allow(dead_code)]
+    right: Vector3<f32>,
+    #[allow(dead_code)]
+    padding_right: f32,
+    /// Half extent along the vertical edges.
+    #[allow(dead_code)]
+    up: Vector3<f32>,
+    #[allow(dead_code)]
+    padding_up: f32,
+    /// Color premultiplied with the intensity.
+    #[allow(dead_code)]
+    color: Vector3<f32>,
+    #[allow(dead_code)]
+    padding: f32,
+}
+
+impl From<&RectLight> for RectLightUniforms {
+    fn from(light: &RectLight) -> Self {
+        let (right, up) = light.axes();
+        RectLightUniforms {
+            position: light.position,
+            two_sided: light.two_sided as u32,
+            right,
+            padding_right: 0.0,
+            up,
+            padding_up: 0.0,
+            color: light.color * light.intensity,
+            padding: 0.0,
+        }
+    }
+}
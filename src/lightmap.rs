@@ -0,0 +1,482 @@
+use std::path::{Path, PathBuf};
+
+use cgmath::{ElementWise, InnerSpace, Matrix, Matrix4, SquareMatrix, Vector2, Vector3};
+use half::f16;
+use image::{Rgb, Rgb32FImage};
+use util::{DeviceExt, TextureDataOrder};
+use wgpu::*;
+
+use crate::{
+    environment::SkyGradient,
+    light::DirectionalLight,
+    material::{srgb_to_linear, AlphaMode},
+    mesh::intersect_triangle,
+    render::{as_byte_slice, Renderer},
+    scene::Scene,
+    world::Renderable,
+};
+
+/// Texels along each side of an object's lightmap.
+pub const LIGHTMAP_SIZE: u32 = 64;
+/// Hemisphere rays per texel.
+const SAMPLE_COUNT: u32 = 256;
+/// Distance by which rays start off the surface, avoiding self-intersections.
+const RAY_OFFSET: f32 = 1e-3;
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+/// The file holding the lightmap of the renderable entity at the given index, counting in the
+/// order of the world.
+pub fn lightmap_path(directory: &Path, object: usize) -> PathBuf {
+    directory.join(format!("lightmap_{object}.hdr"))
+}
+
+/// Baked lightmaps of a scene's objects, as the layers of one texture array.
+#[derive(Debug)]
+pub struct Lightmaps {
+    pub view: TextureView,
+    /// Per object in the scene, its layer, or -1 if it has no lightmap.
+    layers: Vec<i32>,
+}
+
+impl Lightmaps {
+    /// No lightmaps, with a single black layer to satisfy the binding.
+    pub fn empty(device: &Device, queue: &Queue) -> Self {
+        Self::from_layers(device, queue, 1, &[f16::ZERO; 4], Vec::new())
+    }
+
+    /// Loads the lightmaps baked for the scene's objects, skipping objects without one.
+    pub fn load(
+        device: &Device,
+        queue: &Queue,
+        directory: &Path,
+        object_count: usize,
+    ) -> Result<Self, String> {
+        let mut data = Vec::new();
+        let mut layers = Vec::with_capacity(object_count);
+        let mut count = 0;
+        for object in 0..object_count {
+            let path = lightmap_path(directory, object);
+            if !path.exists() {
+                layers.push(-1);
+                continue;
+            }
+            let image = image::open(&path)
+                .map_err(|error| format!("{}: {error}", path.display()))?
+                .to_rgba32f();
+            if image.dimensions() != (LIGHTMAP_SIZE, LIGHTMAP_SIZE) {
+                return Err(format!(
+                    "{}: Lightmaps must be {LIGHTMAP_SIZE}x{LIGHTMAP_SIZE}",
+                    path.display()
+                ));
+            }
+            data.extend(image.as_raw().iter().map(|&c| f16::from_f32(c)));
+            layers.push(count);
+            count += 1;
+        }
+        if count == 0 {
+            return Err(format!("No lightmaps found in {}", directory.display()));
+        }
+        Ok(Self::from_layers(
+            device,
+            queue,
+            LIGHTMAP_SIZE,
+            &data,
+            layers,
+        ))
+    }
+
+    fn from_layers(
+        device: &Device,
+        queue: &Queue,
+        size: u32,
+        data: &[f16],
+        layers: Vec<i32>,
+    ) -> Self {
+        let texture = device.create_texture_with_data(
+            queue,
+            &TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: (data.len() / (4 * (size * size) as usize)) as u32,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba16Float,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            TextureDataOrder::LayerMajor,
+            as_byte_slice(data),
+        );
+        let view = texture.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        Lightmaps { view, layers }
+    }
+
+    /// The layer of the object at the given index in the scene, or -1 if it has none.
+    pub fn layer(&self, object: usize) -> i32 {
+        self.layers.get(object).copied().unwrap_or(-1)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Triangle {
+    a: Vector3<f32>,
+    b: Vector3<f32>,
+    c: Vector3<f32>,
+    albedo: Vector3<f32>,
+}
+
+impl Triangle {
+    fn centroid(&self) -> Vector3<f32> {
+        (self.a + self.b + self.c) / 3.0
+    }
+
+    fn intersect(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<(f32, bool)> {
+        intersect_triangle(self.a, self.b, self.c, origin, direction)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Node {
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+    /// First triangle of a leaf, or the second child of an inner node whose first child follows it.
+    start: usize,
+    /// Zero for inner nodes.
+    count: usize,
+}
+
+/// Bounding volume hierarchy over the scene's triangles, for tracing rays on the CPU.
+#[derive(Debug, Default)]
+struct Bvh {
+    nodes: Vec<Node>,
+    triangles: Vec<Triangle>,
+}
+
+impl Bvh {
+    fn new(triangles: Vec<Triangle>) -> Self {
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            triangles,
+        };
+        if !bvh.triangles.is_empty() {
+            bvh.build(0, bvh.triangles.len());
+        }
+        bvh
+    }
+
+    /// Splits the triangles at the median along the longest axis of their centroids.
+    fn build(&mut self, start: usize, end: usize) -> usize {
+        let triangles = &mut self.triangles[start..end];
+        let mut min = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut max = -min;
+        let mut centroid_min = min;
+        let mut centroid_max = max;
+        for triangle in triangles.iter() {
+            for vertex in [triangle.a, triangle.b, triangle.c] {
+                min = min.zip(vertex, f32::min);
+                max = max.zip(vertex, f32::max);
+            }
+            centroid_min = centroid_min.zip(triangle.centroid(), f32::min);
+            centroid_max = centroid_max.zip(triangle.centroid(), f32::max);
+        }
+
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            min,
+            max,
+            start,
+            count: end - start,
+        });
+        if end - start <= MAX_LEAF_TRIANGLES {
+            return index;
+        }
+
+        let extent = centroid_max - centroid_min;
+        let axis = if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        };
+        let middle = (end - start) / 2;
+        triangles.select_nth_unstable_by(middle, |a, b| {
+            a.centroid()[axis].total_cmp(&b.centroid()[axis])
+        });
+
+        self.build(start, start + middle);
+        let second = self.build(start + middle, end);
+        self.nodes[index].start = second;
+        self.nodes[index].count = 0;
+        index
+    }
+
+    fn hits_box(
+        node: &Node,
+        origin: Vector3<f32>,
+        inverse_direction: Vector3<f32>,
+        far: f32,
+    ) -> bool {
+        let t0 = (node.min - origin).mul_element_wise(inverse_direction);
+        let t1 = (node.max - origin).mul_element_wise(inverse_direction);
+        let near = t0.zip(t1, f32::min);
+        let exit = t0.zip(t1, f32::max);
+        let enter = near.x.max(near.y).max(near.z).max(0.0);
+        enter <= exit.x.min(exit.y).min(exit.z).min(far)
+    }
+
+    /// The closest triangle along the ray, with its distance and whether its front face was hit.
+    fn trace(
+        &self,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+    ) -> Option<(f32, bool, &Triangle)> {
+        let inverse_direction = Vector3::new(1.0, 1.0, 1.0).div_element_wise(direction);
+        let mut closest: Option<(f32, bool, &Triangle)> = None;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let Some(node) = self.nodes.get(index) else {
+                break;
+            };
+            let far = closest.map_or(f32::INFINITY, |(t, ..)| t);
+            if !Self::hits_box(node, origin, inverse_direction, far) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.start);
+                stack.push(index + 1);
+                continue;
+            }
+            for triangle in &self.triangles[node.start..node.start + node.count] {
+                if let Some((t, front)) = triangle.intersect(origin, direction) {
+                    if t < closest.map_or(f32::INFINITY, |(t, ..)| t) {
+                        closest = Some((t, front, triangle));
+                    }
+                }
+            }
+        }
+        closest
+    }
+}
+
+/// A lightmap texel's surface point, found by rasterizing the mesh in lightmap space.
+#[derive(Debug, Copy, Clone)]
+struct Texel {
+    position: Vector3<f32>,
+    normal: Vector3<f32>,
+}
+
+/// Irradiance from the sun, with a shadow ray towards it.
+fn sun_irradiance(
+    bvh: &Bvh,
+    light: &DirectionalLight,
+    position: Vector3<f32>,
+    normal: Vector3<f32>,
+) -> Vector3<f32> {
+    let to_light = -light.direction.normalize();
+    let cosine = normal.dot(to_light);
+    if cosine <= 0.0
+        || bvh
+            .trace(position + RAY_OFFSET * normal, to_light)
+            .is_some()
+    {
+        return Vector3::new(0.0, 0.0, 0.0);
+    }
+    light.color * light.intensity * cosine
+}
+
+/// Average radiance over the cosine-weighted hemisphere, like the irradiance cubemap: the sky
+/// where rays escape, and the sun reflected once off diffuse surfaces where they do not.
+fn gather(bvh: &Bvh, sky: &SkyGradient, light: &DirectionalLight, texel: Texel) -> Vector3<f32> {
+    let tangent = if texel.normal.x.abs() > 0.9 {
+        Vector3::unit_y()
+    } else {
+        Vector3::unit_x()
+    }
+    .cross(texel.normal)
+    .normalize();
+    let bitangent = texel.normal.cross(tangent);
+    let origin = texel.position + RAY_OFFSET * texel.normal;
+
+    let mut sum = Vector3::new(0.0, 0.0, 0.0);
+    for i in 0..SAMPLE_COUNT {
+        // Hammersley point, mapped to a cosine-weighted direction.
+        let u = i as f32 / SAMPLE_COUNT as f32;
+        let v = i.reverse_bits() as f32 * 2.328_306_4e-10;
+        let radius = u.sqrt();
+        let phi = 2.0 * std::f32::consts::PI * v;
+        let direction = (radius * phi.cos()) * tangent
+            + (radius * phi.sin()) * bitangent
+            + (1.0 - u).sqrt() * texel.normal;
+
+        sum += match bvh.trace(origin, direction) {
+            None => sky.radiance(direction),
+            Some((t, true, triangle)) => {
+                let hit = origin + t * direction;
+                let normal = (triangle.b - triangle.a)
+                    .cross(triangle.c - triangle.a)
+                    .normalize();
+                sun_irradiance(bvh, light, hit, normal).mul_element_wise(triangle.albedo)
+                    / std::f32::consts::PI
+            }
+            Some((_, false, _)) => Vector3::new(0.0, 0.0, 0.0),
+        };
+    }
+    sum / SAMPLE_COUNT as f32
+}
+
+/// Finds the surface point at each texel center covered by a triangle in lightmap space.
+fn rasterize(renderer: &Renderer, object: &Renderable) -> Vec<Option<Texel>> {
+    let mesh = &renderer.mesh(object.mesh).data;
+    let normal_matrix = object
+        .transform
+        .invert()
+        .unwrap_or(Matrix4::identity())
+        .transpose();
+    let size = LIGHTMAP_SIZE as f32;
+
+    let mut texels = vec![None; (LIGHTMAP_SIZE * LIGHTMAP_SIZE) as usize];
+    for indices in mesh.indices.chunks_exact(3) {
+        let vertices = <[u32; 3]>::try_from(indices)
+            .unwrap()
+            .map(|index| mesh.vertices[index as usize]);
+        let corners = vertices.map(|vertex| vertex.lightmap_uv * size);
+        let area = (corners[1] - corners[0]).perp_dot(corners[2] - corners[0]);
+        if area.abs() < 1e-12 {
+            continue;
+        }
+
+        let min = corners[0]
+            .zip(corners[1], f32::min)
+            .zip(corners[2], f32::min);
+        let max = corners[0]
+            .zip(corners[1], f32::max)
+            .zip(corners[2], f32::max);
+        let x_range = (min.x.floor().max(0.0) as u32)..(max.x.ceil().min(size) as u32);
+        for y in (min.y.floor().max(0.0) as u32)..(max.y.ceil().min(size) as u32) {
+            for x in x_range.clone() {
+                let point = Vector2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let weights = [
+                    (corners[2] - corners[1]).perp_dot(point - corners[1]) / area,
+                    (corners[0] - corners[2]).perp_dot(point - corners[2]) / area,
+                    (corners[1] - corners[0]).perp_dot(point - corners[0]) / area,
+                ];
+                if weights.iter().any(|&weight| weight < -1e-4) {
+                    continue;
+                }
+                let position = (0..3).fold(Vector3::new(0.0, 0.0, 0.0), |sum, i| {
+                    sum + weights[i] * vertices[i].position
+                });
+                let normal = (0..3).fold(Vector3::new(0.0, 0.0, 0.0), |sum, i| {
+                    sum + weights[i] * vertices[i].normal
+                });
+                texels[(y * LIGHTMAP_SIZE + x) as usize] = Some(Texel {
+                    position: (object.transform * position.extend(1.0)).truncate(),
+                    normal: (normal_matrix * normal.extend(0.0)).truncate().normalize(),
+                });
+            }
+        }
+    }
+    texels
+}
+
+/// Fills texels outside of all triangles with the average of their covered neighbors,
+/// so that bilinear filtering along the chart borders does not bleed in black.
+fn dilate(image: &mut Rgb32FImage, covered: &mut [bool]) {
+    for _ in 0..2 {
+        let source = image.clone();
+        let was_covered = covered.to_vec();
+        for y in 0..LIGHTMAP_SIZE {
+            for x in 0..LIGHTMAP_SIZE {
+                if was_covered[(y * LIGHTMAP_SIZE + x) as usize] {
+                    continue;
+                }
+                let mut sum = [0.0; 3];
+                let mut count = 0;
+                for (dx, dy) in (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy))) {
+                    let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                    if !(0..LIGHTMAP_SIZE as i32).contains(&nx)
+                        || !(0..LIGHTMAP_SIZE as i32).contains(&ny)
+                        || !was_covered[(ny as u32 * LIGHTMAP_SIZE + nx as u32) as usize]
+                    {
+                        continue;
+                    }
+                    let Rgb(color) = source.get_pixel(nx as u32, ny as u32);
+                    for channel in 0..3 {
+                        sum[channel] += color[channel];
+                    }
+                    count += 1;
+                }
+                if count > 0 {
+                    image.put_pixel(x, y, Rgb(sum.map(|c| c / count as f32)));
+                    covered[(y * LIGHTMAP_SIZE + x) as usize] = true;
+                }
+            }
+        }
+    }
+}
+
+/// Bakes the static lighting of every opaque and masked object into an HDR lightmap
+/// in the directory, addressed by the meshes' second UV set.
+pub fn bake(renderer: &Renderer, scene: &Scene, directory: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(directory).map_err(|error| error.to_string())?;
+
+    let is_static =
+        |object: &Renderable| renderer.material(object.material).alpha_mode != AlphaMode::Blend;
+    let objects: Vec<_> = scene.world.renderables().collect();
+    let mut triangles = Vec::new();
+    for object in objects.iter().filter(|object| is_static(object)) {
+        let mesh = &renderer.mesh(object.mesh).data;
+        let albedo = srgb_to_linear(renderer.material(object.material).base_color).truncate();
+        for indices in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = <[u32; 3]>::try_from(indices).unwrap().map(|index| {
+                (object.transform * mesh.vertices[index as usize].position.extend(1.0)).truncate()
+            });
+            triangles.push(Triangle { a, b, c, albedo });
+        }
+    }
+    let bvh = Bvh::new(triangles);
+    let sky = scene.sky();
+    println!("Baking lightmaps over {} triangles", bvh.triangles.len());
+
+    let threads = std::thread::available_parallelism().map_or(1, |count| count.get());
+    for (index, object) in objects.iter().enumerate() {
+        if !is_static(object) {
+            continue;
+        }
+        let texels = rasterize(renderer, object);
+        let mut colors = vec![Vector3::new(0.0, 0.0, 0.0); texels.len()];
+        let chunk_size = texels.len().div_ceil(threads);
+        std::thread::scope(|scope| {
+            for (texels, colors) in texels.chunks(chunk_size).zip(colors.chunks_mut(chunk_size)) {
+                let bvh = &bvh;
+                scope.spawn(move || {
+                    for (texel, color) in texels.iter().zip(colors) {
+                        if let Some(texel) = texel {
+                            *color = gather(bvh, &sky, &scene.light, *texel);
+                        }
+                    }
+                });
+            }
+        });
+
+        let mut image = Rgb32FImage::from_fn(LIGHTMAP_SIZE, LIGHTMAP_SIZE, |x, y| {
+            let color = colors[(y * LIGHTMAP_SIZE + x) as usize];
+            Rgb([color.x, color.y, color.z])
+        });
+        let mut covered: Vec<bool> = texels.iter().map(Option::is_some).collect();
+        dilate(&mut image, &mut covered);
+
+        let path = lightmap_path(directory, index);
+        image.save(&path).map_err(|error| error.to_string())?;
+        println!("Baked {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use std::path::Path;
+
+use crate::{
+    animation::AnimationPlayer,
+    camera::SceneCamera,
+    light::{DirectionalLight, PointLight, SpotLight},
+    point_cloud::PointCloudId,
+    render::Renderer,
+    scene::Object,
+    texture::TextureId,
+};
+
+pub mod bundle;
+#[cfg(feature = "fbx")]
+pub mod fbx;
+pub mod gltf;
+mod meshopt;
+pub mod obj;
+pub mod ply;
+pub mod stl;
+mod vertex_cache;
+
+/// Objects, point clouds, animations, cameras and lights imported from a file.
+#[derive(Debug, Default, Clone)]
+pub struct Model {
+    pub objects: Vec<Object>,
+    pub point_clouds: Vec<PointCloudId>,
+    pub animation: Option<AnimationPlayer>,
+    pub cameras: Vec<SceneCamera>,
+    pub directional_light: Option<DirectionalLight>,
+    pub point_lights: Vec<PointLight>,
+    pub spot_lights: Vec<SpotLight>,
+    /// References held on the renderer's textures, released when the model is unloaded.
+    pub textures: Vec<TextureId>,
+}
+
+/// Unit of length of formats which do not specify one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Unit {
+    Millimeters,
+    Centimeters,
+    Meters,
+    Inches,
+}
+
+impl Unit {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "mm" => Some(Unit::Millimeters),
+            "cm" => Some(Unit::Centimeters),
+            "m" => Some(Unit::Meters),
+            "in" => Some(Unit::Inches),
+            _ => None,
+        }
+    }
+
+    pub fn meters(self) -> f32 {
+        match self {
+            Unit::Millimeters => 0.001,
+            Unit::Centimeters => 0.01,
+            Unit::Meters => 1.0,
+            Unit::Inches => 0.0254,
+        }
+    }
+}
+
+/// A model file read and decoded, but not yet added to the renderer. Reading is the slow part
+/// and may happen on any thread.
+#[derive(Debug)]
+pub enum Source {
+    Bundle(bundle::Source),
+    Gltf(Box<gltf::Source>),
+    #[cfg(feature = "fbx")]
+    Fbx(fbx::Source),
+    Obj(obj::Source),
+    Stl(stl::Source),
+    Ply(ply::Source),
+}
+
+/// Reads a model by its file extension. The unit applies to formats without one, which are
+/// otherwise scaled to a fixed size.
+pub fn read(path: &Path, unit: Option<Unit>) -> Result<Source, String> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("bundle") => bundle::read(path).map(Source::Bundle),
+        Some("gltf" | "glb") => gltf::read(path).map(|source| Source::Gltf(Box::new(source))),
+        #[cfg(feature = "fbx")]
+        Some("fbx") => fbx::read(path).map(Source::Fbx),
+        #[cfg(not(feature = "fbx"))]
+        Some("fbx") => Err("Built without the fbx feature".to_string()),
+        Some("obj") => obj::read(path).map(Source::Obj),
+        Some("stl") => stl::read(path, unit).map(Source::Stl),
+        Some("ply") => ply::read(path).map(Source::Ply),
+        _ => Err("Unsupported model format".to_string()),
+    }
+}
+
+impl Source {
+    /// Imports the model, adding its resources to the renderer.
+    pub fn add(self, renderer: &mut Renderer) -> Result<Model, String> {
+        let objects = match self {
+            Source::Bundle(source) => return bundle::add(renderer, source),
+            Source::Gltf(source) => return gltf::add(renderer, *source),
+            #[cfg(feature = "fbx")]
+            Source::Fbx(source) => return Ok(fbx::add(renderer, source)),
+            Source::Obj(source) => return Ok(obj::add(renderer, source)),
+            Source::Stl(source) => stl::add(renderer, source),
+            Source::Ply(source) => return Ok(ply::add(renderer, source)),
+        };
+        Ok(Model {
+            objects,
+            ..Default::default()
+        })
+    }
+}
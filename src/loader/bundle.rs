@@ -0,0 +1,449 @@
+use std::{borrow::Cow, collections::HashMap, fs::File, hash::Hash, path::Path};
+
+use cgmath::{Matrix4, Rad, Vector3, Vector4};
+use memmap2::Mmap;
+
+use super::Model;
+use crate::{
+    camera::{Projection, SceneCamera},
+    light::{DirectionalLight, PointLight, SpotLight},
+    material::{AlphaMode, Material},
+    mesh::{MeshData, Vertex},
+    render::{as_byte_slice, Renderer},
+    scene::Object,
+    texture::MipChain,
+};
+
+const MAGIC: &[u8; 4] = b"HWGB";
+
+/// Bumped whenever the layout changes, as bundles are rebuilt rather than migrated.
+const VERSION: u32 = 2;
+
+/// A bundle mapped into memory. Bundles hold the resources of models as the renderer uploads
+/// them: vertices in the layout of the vertex buffer and textures with their mip chain, so that
+/// adding them is little more than copying. Only the material properties which loaders import
+/// are kept, and neither animations nor point clouds.
+#[derive(Debug)]
+pub struct Source {
+    map: Mmap,
+}
+
+pub fn read(path: &Path) -> Result<Source, String> {
+    let file = File::open(path).map_err(|error| error.to_string())?;
+    // Truncating the file while it is mapped is undefined behavior, but the map only lives
+    // until the bundle has been added.
+    let map = unsafe { Mmap::map(&file) }.map_err(|error| error.to_string())?;
+    let mut reader = Reader {
+        bytes: &map,
+        position: 0,
+    };
+    if reader.bytes(4)? != MAGIC {
+        return Err("Not a bundle".to_string());
+    }
+    if reader.u32()? != VERSION || reader.u32()? as usize != size_of::<Vertex>() {
+        return Err("Bundle was written by another version, rebuild it".to_string());
+    }
+    Ok(Source { map })
+}
+
+pub fn add(renderer: &mut Renderer, source: Source) -> Result<Model, String> {
+    let mut reader = Reader {
+        bytes: &source.map,
+        // After the header.
+        position: 12,
+    };
+    let mut model = Model::default();
+
+    for _ in 0..reader.u32()? {
+        let width = reader.u32()?;
+        let height = reader.u32()?;
+        let level_count = reader.u32()?;
+        let srgb = reader.u32()? != 0;
+        let size = reader.u32()? as usize;
+        let mips = MipChain {
+            width,
+            height,
+            level_count,
+            srgb,
+            data: Cow::Borrowed(reader.bytes(size)?),
+        };
+        if !mips.is_valid() {
+            return Err("Texture does not match its size".to_string());
+        }
+        model.textures.push(renderer.add_mipped_texture(&mips));
+    }
+
+    let mut materials = Vec::new();
+    for _ in 0..reader.u32()? {
+        let base_color = reader.vector4()?;
+        let texture = reader.u32()?;
+        let material = Material {
+            base_color,
+            base_color_texture: match texture {
+                u32::MAX => None,
+                index => Some(
+                    *model
+                        .textures
+                        .get(index as usize)
+                        .ok_or("Texture index out of range")?,
+                ),
+            },
+            metallic: reader.f32()?,
+            roughness: reader.f32()?,
+            alpha_mode: match reader.u32()? {
+                0 => AlphaMode::Opaque,
+                1 => AlphaMode::Mask,
+                _ => AlphaMode::Blend,
+            },
+            alpha_cutoff: reader.f32()?,
+            ..Default::default()
+        };
+        materials.push(renderer.add_material(&material));
+    }
+
+    let mut meshes = Vec::new();
+    for _ in 0..reader.u32()? {
+        let vertex_count = reader.u32()? as usize;
+        let index_count = reader.u32()? as usize;
+        let data = MeshData {
+            vertices: reader.array(vertex_count)?,
+            indices: reader.array(index_count)?,
+        };
+        if data
+            .indices
+            .iter()
+            .any(|&index| index as usize >= vertex_count)
+        {
+            return Err("Vertex index out of range".to_string());
+        }
+        meshes.push(renderer.add_mesh(&data));
+    }
+
+    for _ in 0..reader.u32()? {
+        let name = reader.string()?;
+        let transform = reader.matrix()?;
+        let mesh = *meshes
+            .get(reader.u32()? as usize)
+            .ok_or("Mesh index out of range")?;
+        let material = *materials
+            .get(reader.u32()? as usize)
+            .ok_or("Material index out of range")?;
+        model.objects.push(Object {
+            name,
+            transform,
+            mesh,
+            material,
+        });
+    }
+
+    for _ in 0..reader.u32()? {
+        model.cameras.push(SceneCamera {
+            name: reader.string()?,
+            transform: reader.matrix()?,
+            projection: Projection {
+                fovy: Rad(reader.f32()?),
+                near: reader.f32()?,
+                far: reader.f32()?,
+            },
+            layers: SceneCamera::LAYERS,
+        });
+    }
+
+    if reader.u32()? != 0 {
+        model.directional_light = Some(DirectionalLight {
+            direction: reader.vector3()?,
+            color: reader.vector3()?,
+            intensity: reader.f32()?,
+        });
+    }
+    for _ in 0..reader.u32()? {
+        model.point_lights.push(PointLight {
+            position: reader.vector3()?,
+            color: reader.vector3()?,
+            intensity: reader.f32()?,
+            radius: reader.f32()?,
+            attenuation: reader.f32()?,
+            cast_shadows: reader.u32()? != 0,
+        });
+    }
+    for _ in 0..reader.u32()? {
+        model.spot_lights.push(SpotLight {
+            position: reader.vector3()?,
+            direction: reader.vector3()?,
+            color: reader.vector3()?,
+            intensity: reader.f32()?,
+            radius: reader.f32()?,
+            attenuation: reader.f32()?,
+            inner_angle: Rad(reader.f32()?),
+            outer_angle: Rad(reader.f32()?),
+        });
+    }
+    Ok(model)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Skips the padding to the next multiple of four bytes.
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position.saturating_add(count))
+            .ok_or("Unexpected end of bundle")?;
+        self.position += count.next_multiple_of(4);
+        Ok(bytes)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let size = self.u32()? as usize;
+        Ok(String::from_utf8_lossy(self.bytes(size)?).into_owned())
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn vector3(&mut self) -> Result<Vector3<f32>, String> {
+        Ok(Vector3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn vector4(&mut self) -> Result<Vector4<f32>, String> {
+        Ok(Vector4::new(
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+        ))
+    }
+
+    fn matrix(&mut self) -> Result<Matrix4<f32>, String> {
+        Ok(Matrix4::from_cols(
+            self.vector4()?,
+            self.vector4()?,
+            self.vector4()?,
+            self.vector4()?,
+        ))
+    }
+
+    /// Copies plain values stored as they are laid out in memory.
+    fn array<T: Copy>(&mut self, count: usize) -> Result<Vec<T>, String> {
+        let size = count
+            .checked_mul(size_of::<T>())
+            .ok_or("Unexpected end of bundle")?;
+        let bytes = self.bytes(size)?;
+        let mut values = Vec::with_capacity(count);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                values.as_mut_ptr() as *mut u8,
+                bytes.len(),
+            );
+            values.set_len(count);
+        }
+        Ok(values)
+    }
+}
+
+/// Resources shared between objects, which are stored once.
+struct Table<T> {
+    ids: Vec<T>,
+    indices: HashMap<T, u32>,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Table {
+            ids: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Copy + Eq + Hash> Table<T> {
+    /// Index of the resource in the bundle, adding it if needed.
+    fn index(&mut self, id: T) -> u32 {
+        *self.indices.entry(id).or_insert_with(|| {
+            self.ids.push(id);
+            self.ids.len() as u32 - 1
+        })
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    /// Pads to the next multiple of four bytes.
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+        self.bytes.resize(self.bytes.len().next_multiple_of(4), 0);
+    }
+
+    fn string(&mut self, string: &str) {
+        self.u32(string.len() as u32);
+        self.bytes(string.as_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.u32(value.to_bits());
+    }
+
+    fn floats(&mut self, values: &[f32]) {
+        for &value in values {
+            self.f32(value);
+        }
+    }
+
+    fn vector3(&mut self, vector: Vector3<f32>) {
+        let values: &[f32; 3] = vector.as_ref();
+        self.floats(values);
+    }
+
+    fn vector4(&mut self, vector: Vector4<f32>) {
+        let values: &[f32; 4] = vector.as_ref();
+        self.floats(values);
+    }
+
+    fn matrix(&mut self, matrix: &Matrix4<f32>) {
+        let columns: &[[f32; 4]; 4] = matrix.as_ref();
+        self.floats(columns.as_flattened());
+    }
+}
+
+/// Collects the resources of the models from the renderer into a bundle, reading their
+/// textures back from the GPU.
+pub fn write(path: &Path, renderer: &Renderer, models: &[&Model]) -> Result<(), String> {
+    if models.iter().any(|model| model.animation.is_some()) {
+        println!("Animations are not bundled, skinned and morphed meshes keep their bind pose");
+    }
+    if models.iter().any(|model| !model.point_clouds.is_empty()) {
+        println!("Point clouds are not bundled");
+    }
+    let objects: Vec<_> = models.iter().flat_map(|model| &model.objects).collect();
+    let mut meshes = Table::default();
+    let mut materials = Table::default();
+    let mut textures = Table::default();
+    let object_indices: Vec<_> = objects
+        .iter()
+        .map(|object| (meshes.index(object.mesh), materials.index(object.material)))
+        .collect();
+    for &id in &materials.ids {
+        if let Some(texture) = renderer.material(id).base_color_texture {
+            textures.index(texture);
+        }
+    }
+
+    let mut writer = Writer::default();
+    writer.bytes(MAGIC);
+    writer.u32(VERSION);
+    writer.u32(size_of::<Vertex>() as u32);
+
+    writer.u32(textures.ids.len() as u32);
+    for &texture in &textures.ids {
+        let mips = renderer.read_texture(texture);
+        writer.u32(mips.width);
+        writer.u32(mips.height);
+        writer.u32(mips.level_count);
+        writer.u32(mips.srgb as u32);
+        writer.u32(mips.data.len() as u32);
+        writer.bytes(&mips.data);
+    }
+
+    writer.u32(materials.ids.len() as u32);
+    for &id in &materials.ids {
+        let material = renderer.material(id);
+        writer.vector4(material.base_color);
+        writer.u32(
+            material
+                .base_color_texture
+                .map_or(u32::MAX, |texture| textures.index(texture)),
+        );
+        writer.f32(material.metallic);
+        writer.f32(material.roughness);
+        writer.u32(match material.alpha_mode {
+            AlphaMode::Opaque => 0,
+            AlphaMode::Mask => 1,
+            AlphaMode::Blend => 2,
+        });
+        writer.f32(material.alpha_cutoff);
+    }
+
+    writer.u32(meshes.ids.len() as u32);
+    for &id in &meshes.ids {
+        let data = &renderer.mesh(id).data;
+        writer.u32(data.vertices.len() as u32);
+        writer.u32(data.indices.len() as u32);
+        writer.bytes(as_byte_slice(&data.vertices));
+        writer.bytes(as_byte_slice(&data.indices));
+    }
+
+    writer.u32(objects.len() as u32);
+    for (object, (mesh, material)) in objects.iter().zip(&object_indices) {
+        writer.string(&object.name);
+        writer.matrix(&object.transform);
+        writer.u32(*mesh);
+        writer.u32(*material);
+    }
+
+    let cameras: Vec<_> = models.iter().flat_map(|model| &model.cameras).collect();
+    writer.u32(cameras.len() as u32);
+    for camera in cameras {
+        writer.string(&camera.name);
+        writer.matrix(&camera.transform);
+        writer.floats(&[
+            camera.projection.fovy.0,
+            camera.projection.near,
+            camera.projection.far,
+        ]);
+    }
+
+    match models.iter().find_map(|model| model.directional_light) {
+        Some(light) => {
+            writer.u32(1);
+            writer.vector3(light.direction);
+            writer.vector3(light.color);
+            writer.f32(light.intensity);
+        }
+        None => writer.u32(0),
+    }
+    let point_lights: Vec<_> = models
+        .iter()
+        .flat_map(|model| &model.point_lights)
+        .collect();
+    writer.u32(point_lights.len() as u32);
+    for light in point_lights {
+        writer.vector3(light.position);
+        writer.vector3(light.color);
+        writer.floats(&[light.intensity, light.radius, light.attenuation]);
+        writer.u32(light.cast_shadows as u32);
+    }
+    let spot_lights: Vec<_> = models.iter().flat_map(|model| &model.spot_lights).collect();
+    writer.u32(spot_lights.len() as u32);
+    for light in spot_lights {
+        writer.vector3(light.position);
+        writer.vector3(light.direction);
+        writer.vector3(light.color);
+        writer.floats(&[
+            light.intensity,
+            light.radius,
+            light.attenuation,
+            light.inner_angle.0,
+            light.outer_angle.0,
+        ]);
+    }
+
+    std::fs::write(path, writer.bytes).map_err(|error| error.to_string())
+}
@@ -0,0 +1,923 @@
+use std::{collections::HashMap, io::Read, path::Path};
+
+use cgmath::{Deg, Matrix4, SquareMatrix, Vector2, Vector3, Vector4, Zero};
+use flate2::read::ZlibDecoder;
+use image::RgbaImage;
+
+use super::{vertex_cache, Model};
+use crate::{
+    material::{linear_to_srgb, roughness_from_shininess, AlphaMode, Material, MaterialId},
+    mesh::{MeshData, Vertex},
+    render::Renderer,
+    scene::Object,
+};
+
+/// Start of binary FBX files. ASCII FBX is not supported.
+const MAGIC: &[u8] = b"Kaydara FBX Binary  \0";
+
+/// Size of the header, which ends with the version.
+const HEADER_SIZE: usize = 27;
+
+/// From this version on, node records use 64-bit offsets.
+const WIDE_VERSION: u32 = 7500;
+
+/// The meshes of a binary FBX file, split by material and placed by their node hierarchy, with
+/// the materials and their decoded textures.
+#[derive(Debug)]
+pub struct Source {
+    parts: Vec<Part>,
+    /// With the index of their base color image, if any.
+    materials: Vec<(Material, Option<usize>)>,
+    images: Vec<RgbaImage>,
+}
+
+/// The triangles of a mesh which use one material.
+#[derive(Debug)]
+struct Part {
+    name: String,
+    transform: Matrix4<f32>,
+    data: MeshData,
+    material: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum Property {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    Integers(Vec<i64>),
+    Floats(Vec<f64>),
+    String(String),
+    Raw(Vec<u8>),
+}
+
+/// A record of the file's tree, such as an object or one of its attributes.
+#[derive(Debug, Default)]
+struct Node {
+    name: String,
+    properties: Vec<Property>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Node> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn integer(&self, index: usize) -> Option<i64> {
+        match self.properties.get(index)? {
+            Property::Integer(value) => Some(*value),
+            Property::Bool(value) => Some(*value as i64),
+            _ => None,
+        }
+    }
+
+    fn float(&self, index: usize) -> Option<f64> {
+        match self.properties.get(index)? {
+            Property::Float(value) => Some(*value),
+            Property::Integer(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    fn string(&self, index: usize) -> Option<&str> {
+        match self.properties.get(index)? {
+            Property::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The array held by the child of the given name, such as a mesh's `Vertices`.
+    fn floats(&self, name: &str) -> Option<&[f64]> {
+        match self.child(name)?.properties.first()? {
+            Property::Floats(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    fn integers(&self, name: &str) -> Option<&[i64]> {
+        match self.child(name)?.properties.first()? {
+            Property::Integers(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// An entry of the `Properties70` table, which holds the animatable properties of objects.
+    fn property(&self, name: &str) -> Option<&Node> {
+        self.child("Properties70")?
+            .children("P")
+            .find(|property| property.string(0) == Some(name))
+    }
+
+    /// Values of a table entry start after its name, type, label and flags.
+    fn property_float(&self, name: &str) -> Option<f64> {
+        self.property(name)?.float(4)
+    }
+
+    fn property_vector(&self, name: &str) -> Option<Vector3<f32>> {
+        let property = self.property(name)?;
+        Some(Vector3::new(
+            property.float(4)? as f32,
+            property.float(5)? as f32,
+            property.float(6)? as f32,
+        ))
+    }
+
+    /// Object names are followed by a separator and the class, as in `Cube\0\x01Model`.
+    fn object_name(&self) -> &str {
+        let name = self.string(1).unwrap_or("");
+        name.split('\0').next().unwrap_or(name)
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    /// Whether offsets and counts of node records take 64 bits.
+    wide: bool,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position.saturating_add(count))
+            .ok_or("Unexpected end of file")?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn offset(&mut self) -> Result<usize, String> {
+        if self.wide {
+            Ok(self.u64()? as usize)
+        } else {
+            Ok(self.u32()? as usize)
+        }
+    }
+
+    /// Returns none for the empty record ending a list of nodes.
+    fn node(&mut self) -> Result<Option<Node>, String> {
+        let end = self.offset()?;
+        let property_count = self.offset()?;
+        let _property_list_size = self.offset()?;
+        let name_size = self.u8()? as usize;
+        if end == 0 {
+            return Ok(None);
+        }
+        let name = String::from_utf8_lossy(self.take(name_size)?).into_owned();
+        let properties = (0..property_count)
+            .map(|_| self.property())
+            .collect::<Result<_, _>>()?;
+        let mut children = Vec::new();
+        while self.position < end {
+            match self.node()? {
+                Some(child) => children.push(child),
+                None => break,
+            }
+        }
+        // Going back would read the same records again.
+        if end < self.position {
+            return Err(format!("Node {name} overruns its end"));
+        }
+        self.position = end;
+        Ok(Some(Node {
+            name,
+            properties,
+            children,
+        }))
+    }
+
+    fn property(&mut self) -> Result<Property, String> {
+        let take_array = |reader: &mut Self, element_size: usize| {
+            let count = reader.u32()? as usize;
+            let encoding = reader.u32()?;
+            let size = reader.u32()? as usize;
+            let data = reader.take(size)?;
+            let length = count
+                .checked_mul(element_size)
+                .ok_or("Array length out of range")?;
+            // The count is not trusted with allocating more than the data holds.
+            let data = match encoding {
+                0 => data.get(..length).ok_or("Truncated array")?.to_vec(),
+                1 => {
+                    let mut decoded = Vec::new();
+                    ZlibDecoder::new(data)
+                        .take(length as u64)
+                        .read_to_end(&mut decoded)
+                        .map_err(|error| error.to_string())?;
+                    if decoded.len() < length {
+                        return Err("Truncated array".to_string());
+                    }
+                    decoded
+                }
+                _ => return Err(format!("Unknown array encoding {encoding}")),
+            };
+            Ok(data)
+        };
+        let code = self.u8()?;
+        Ok(match code {
+            b'C' => Property::Bool(self.u8()? != 0),
+            b'Y' => Property::Integer(i16::from_le_bytes(self.take(2)?.try_into().unwrap()) as i64),
+            b'I' => Property::Integer(self.u32()? as i32 as i64),
+            b'L' => Property::Integer(self.u64()? as i64),
+            b'F' => Property::Float(f32::from_le_bytes(self.take(4)?.try_into().unwrap()) as f64),
+            b'D' => Property::Float(f64::from_bits(self.u64()?)),
+            b'b' => Property::Integers(
+                take_array(self, 1)?
+                    .into_iter()
+                    .map(|value| value as i64)
+                    .collect(),
+            ),
+            b'i' => Property::Integers(
+                take_array(self, 4)?
+                    .chunks_exact(4)
+                    .map(|value| i32::from_le_bytes(value.try_into().unwrap()) as i64)
+                    .collect(),
+            ),
+            b'l' => Property::Integers(
+                take_array(self, 8)?
+                    .chunks_exact(8)
+                    .map(|value| i64::from_le_bytes(value.try_into().unwrap()))
+                    .collect(),
+            ),
+            b'f' => Property::Floats(
+                take_array(self, 4)?
+                    .chunks_exact(4)
+                    .map(|value| f32::from_le_bytes(value.try_into().unwrap()) as f64)
+                    .collect(),
+            ),
+            b'd' => Property::Floats(
+                take_array(self, 8)?
+                    .chunks_exact(8)
+                    .map(|value| f64::from_le_bytes(value.try_into().unwrap()))
+                    .collect(),
+            ),
+            b'S' => {
+                let size = self.u32()? as usize;
+                Property::String(String::from_utf8_lossy(self.take(size)?).into_owned())
+            }
+            b'R' => {
+                let size = self.u32()? as usize;
+                Property::Raw(self.take(size)?.to_vec())
+            }
+            _ => return Err(format!("Unknown property type {:?}", code as char)),
+        })
+    }
+}
+
+/// Parses the nodes at the top of the file.
+fn parse(bytes: &[u8]) -> Result<Vec<Node>, String> {
+    if !bytes.starts_with(MAGIC) {
+        return Err("Only binary FBX is supported".to_string());
+    }
+    let mut reader = Reader {
+        bytes,
+        position: HEADER_SIZE - 4,
+        wide: false,
+    };
+    reader.wide = reader.u32()? >= WIDE_VERSION;
+    let mut nodes = Vec::new();
+    while let Some(node) = reader.node()? {
+        nodes.push(node);
+    }
+    Ok(nodes)
+}
+
+/// Objects linked by the file's connections.
+struct Graph<'a> {
+    objects: HashMap<i64, &'a Node>,
+    /// The children of each object in the order they were connected, with the property they
+    /// are connected to, if any.
+    children: HashMap<i64, Vec<(i64, Option<&'a str>)>>,
+    /// The parent model of each model.
+    parents: HashMap<i64, i64>,
+}
+
+impl<'a> Graph<'a> {
+    fn new(objects: &'a Node, connections: Option<&'a Node>) -> Result<Self, String> {
+        let objects: HashMap<_, _> = objects
+            .children
+            .iter()
+            .filter_map(|object| Some((object.integer(0)?, object)))
+            .collect();
+        let mut children: HashMap<_, Vec<_>> = HashMap::new();
+        let mut parents = HashMap::new();
+        for connection in connections.into_iter().flat_map(|node| node.children("C")) {
+            let (Some(child), Some(parent)) = (connection.integer(1), connection.integer(2)) else {
+                continue;
+            };
+            let is_model = |id| objects.get(&id).is_some_and(|node| node.name == "Model");
+            if is_model(child) && is_model(parent) {
+                // The parents form a forest as long as the child is not among the ancestors.
+                let mut ancestor = Some(parent);
+                while let Some(id) = ancestor {
+                    if id == child {
+                        return Err(format!("Model {child} is its own ancestor"));
+                    }
+                    ancestor = parents.get(&id).copied();
+                }
+                parents.insert(child, parent);
+            }
+            children
+                .entry(parent)
+                .or_default()
+                .push((child, connection.string(3)));
+        }
+        Ok(Graph {
+            objects,
+            children,
+            parents,
+        })
+    }
+
+    /// Children of the object which are of the given kind, such as `Geometry`.
+    fn children_of(&self, id: i64, kind: &'a str) -> impl Iterator<Item = (i64, &'a Node)> + '_ {
+        self.children
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .filter_map(move |&(child, _)| {
+                let node = *self.objects.get(&child)?;
+                (node.name == kind).then_some((child, node))
+            })
+    }
+
+    /// The child connected to the given property of the object.
+    fn property_child(&self, id: i64, property: &str) -> Option<(i64, &'a Node)> {
+        self.children.get(&id)?.iter().find_map(|&(child, name)| {
+            if name != Some(property) {
+                return None;
+            }
+            Some((child, *self.objects.get(&child)?))
+        })
+    }
+
+    /// Transform of a model relative to the scene root.
+    fn world_transform(&self, id: i64) -> Matrix4<f32> {
+        let local = self
+            .objects
+            .get(&id)
+            .map_or(Matrix4::identity(), |model| local_transform(model));
+        match self.parents.get(&id) {
+            Some(&parent) => self.world_transform(parent) * local,
+            None => local,
+        }
+    }
+}
+
+/// Rotation from Euler angles in degrees, applied in the order X, Y, Z.
+fn euler(angles: Vector3<f32>) -> Matrix4<f32> {
+    Matrix4::from_angle_z(Deg(angles.z))
+        * Matrix4::from_angle_y(Deg(angles.y))
+        * Matrix4::from_angle_x(Deg(angles.x))
+}
+
+/// The transform of a model relative to its parent. Pivots and offsets are ignored, as is the
+/// rotation order, which is XYZ unless changed in the authoring tool.
+fn local_transform(model: &Node) -> Matrix4<f32> {
+    let vector = |name, default| model.property_vector(name).unwrap_or(default);
+    let scale = vector("Lcl Scaling", Vector3::new(1.0, 1.0, 1.0));
+    let post_rotation = euler(vector("PostRotation", Vector3::zero()));
+    Matrix4::from_translation(vector("Lcl Translation", Vector3::zero()))
+        * euler(vector("PreRotation", Vector3::zero()))
+        * euler(vector("Lcl Rotation", Vector3::zero()))
+        * post_rotation.invert().unwrap_or(Matrix4::identity())
+        * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z)
+}
+
+/// Offset of a model's geometry, which does not apply to its children.
+fn geometric_transform(model: &Node) -> Matrix4<f32> {
+    let vector = |name, default| model.property_vector(name).unwrap_or(default);
+    let scale = vector("GeometricScaling", Vector3::new(1.0, 1.0, 1.0));
+    Matrix4::from_translation(vector("GeometricTranslation", Vector3::zero()))
+        * euler(vector("GeometricRotation", Vector3::zero()))
+        * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z)
+}
+
+/// Converts the file's unit and up axis to meters and Y-up.
+fn root_transform(settings: Option<&Node>) -> Matrix4<f32> {
+    let property = |name| settings.and_then(|settings| settings.property_float(name));
+    // The unit is given in centimeters.
+    let scale = property("UnitScaleFactor").unwrap_or(1.0) as f32 / 100.0;
+    let sign = property("UpAxisSign").unwrap_or(1.0).signum() as f32;
+    let up = match property("UpAxis").map(|axis| axis as i64) {
+        Some(0) => Matrix4::from_angle_z(Deg(90.0 * sign)),
+        Some(2) => Matrix4::from_angle_x(Deg(-90.0 * sign)),
+        _ if sign < 0.0 => Matrix4::from_angle_x(Deg(180.0)),
+        _ => Matrix4::identity(),
+    };
+    Matrix4::from_scale(scale) * up
+}
+
+/// The data of a layer element, such as the normals, with the index of one polygon corner's
+/// value.
+fn layer_data<'a>(
+    element: Option<&'a Node>,
+    data: &str,
+    indices: &str,
+    corner: usize,
+    vertex: usize,
+    polygon: usize,
+) -> Option<(&'a [f64], usize)> {
+    let element = element?;
+    let mapping = element.child("MappingInformationType")?.string(0)?;
+    let index = match mapping {
+        "ByPolygonVertex" => corner,
+        "ByVertex" | "ByVertice" => vertex,
+        "ByPolygon" => polygon,
+        "AllSame" => 0,
+        _ => return None,
+    };
+    let index = match element.child("ReferenceInformationType")?.string(0)? {
+        "IndexToDirect" | "Index" => {
+            usize::try_from(*element.integers(indices)?.get(index)?).ok()?
+        }
+        _ => index,
+    };
+    Some((element.floats(data)?, index))
+}
+
+/// Splits a mesh's polygons into triangle fans by material slot, giving each corner its own
+/// vertex.
+fn mesh_parts(geometry: &Node) -> Result<Vec<(usize, MeshData)>, String> {
+    let positions = geometry.floats("Vertices").ok_or("Mesh without vertices")?;
+    let polygon_indices = geometry
+        .integers("PolygonVertexIndex")
+        .ok_or("Mesh without polygons")?;
+    let normals = geometry.child("LayerElementNormal");
+    let uvs = geometry.child("LayerElementUV");
+    let colors = geometry.child("LayerElementColor");
+    let materials = geometry.child("LayerElementMaterial");
+
+    let vector = |data: &[f64], index: usize| {
+        Some(Vector3::new(
+            *data.get(3 * index)? as f32,
+            *data.get(3 * index + 1)? as f32,
+            *data.get(3 * index + 2)? as f32,
+        ))
+    };
+    let mut parts: HashMap<usize, MeshData> = HashMap::new();
+    let mut corners = Vec::new();
+    let mut polygon = 0;
+    for (corner, &index) in polygon_indices.iter().enumerate() {
+        // The last index of each polygon is stored as its bitwise complement.
+        let vertex = (if index < 0 { !index } else { index }) as usize;
+        let layer =
+            |element, data, indices| layer_data(element, data, indices, corner, vertex, polygon);
+        let uv = layer(uvs, "UV", "UVIndex")
+            .and_then(|(data, index)| {
+                // FBX texture coordinates start at the bottom left.
+                Some(Vector2::new(
+                    *data.get(2 * index)? as f32,
+                    1.0 - *data.get(2 * index + 1)? as f32,
+                ))
+            })
+            .unwrap_or(Vector2::zero());
+        let color = layer(colors, "Colors", "ColorIndex")
+            .and_then(|(data, index)| {
+                let color = data.get(4 * index..4 * index + 4)?;
+                Vector4::new(color[0], color[1], color[2], color[3]).cast::<f32>()
+            })
+            .unwrap_or(Vector4::new(1.0, 1.0, 1.0, 1.0));
+        corners.push(Vertex {
+            position: vector(positions, vertex).ok_or("Vertex index out of range")?,
+            normal: layer(normals, "Normals", "NormalsIndex")
+                .and_then(|(data, index)| vector(data, index))
+                .unwrap_or(Vector3::zero()),
+            color,
+            uv,
+            lightmap_uv: uv,
+        });
+        if index >= 0 {
+            continue;
+        }
+
+        let slot = materials
+            .and_then(|element| {
+                let slots = element.integers("Materials")?;
+                match element.child("MappingInformationType")?.string(0)? {
+                    "ByPolygon" => slots.get(polygon),
+                    _ => slots.first(),
+                }
+            })
+            .and_then(|&slot| usize::try_from(slot).ok())
+            .unwrap_or(0);
+        let part = parts.entry(slot).or_default();
+        let base = part.vertices.len() as u32;
+        for i in 1..corners.len().saturating_sub(1) as u32 {
+            part.indices.extend([base, base + i, base + i + 1]);
+        }
+        part.vertices.append(&mut corners);
+        polygon += 1;
+    }
+
+    let has_normals = normals.is_some_and(|element| element.floats("Normals").is_some());
+    let mut parts: Vec<_> = parts
+        .into_iter()
+        .filter(|(_, data)| !data.indices.is_empty())
+        .collect();
+    parts.sort_by_key(|&(slot, _)| slot);
+    if !has_normals {
+        for (_, data) in &mut parts {
+            data.compute_normals();
+        }
+    }
+    for (_, data) in &mut parts {
+        vertex_cache::optimize(data);
+    }
+    Ok(parts)
+}
+
+/// FBX stores colors linearly, and either opacity or transparency.
+fn to_material(material: &Node) -> Material {
+    let color = material
+        .property_vector("DiffuseColor")
+        .or_else(|| material.property_vector("Diffuse"))
+        .unwrap_or(Vector3::new(1.0, 1.0, 1.0))
+        * material.property_float("DiffuseFactor").unwrap_or(1.0) as f32;
+    let alpha = material
+        .property_float("Opacity")
+        .or_else(|| {
+            material
+                .property_float("TransparencyFactor")
+                .map(|transparency| 1.0 - transparency)
+        })
+        .unwrap_or(1.0) as f32;
+    let shininess = material
+        .property_float("ShininessExponent")
+        .or_else(|| material.property_float("Shininess"));
+    Material {
+        base_color: linear_to_srgb(color.extend(alpha)),
+        roughness: shininess.map_or(Material::default().roughness, |shininess| {
+            roughness_from_shininess(shininess as f32)
+        }),
+        alpha_mode: if alpha < 1.0 {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        },
+        ..Default::default()
+    }
+}
+
+/// Decodes a texture from the media embedded in the file, or else from the file it refers to,
+/// looked up next to the model.
+fn read_texture(graph: &Graph, id: i64, texture: &Node, directory: &Path) -> Option<RgbaImage> {
+    let embedded = graph.children_of(id, "Video").find_map(|(_, video)| {
+        match video.child("Content")?.properties.first()? {
+            Property::Raw(content) if !content.is_empty() => Some(content.as_slice()),
+            _ => None,
+        }
+    });
+    let name = texture.object_name();
+    let image = match embedded {
+        Some(content) => image::load_from_memory(content),
+        None => {
+            let relative = texture
+                .child("RelativeFilename")
+                .and_then(|node| node.string(0))
+                .filter(|file| !file.is_empty());
+            let absolute = texture.child("FileName").and_then(|node| node.string(0));
+            let path = relative
+                .map(|file| directory.join(file.replace('\\', "/")))
+                .filter(|path| path.exists())
+                .or_else(|| {
+                    // Absolute paths rarely survive the trip to another machine.
+                    let file = absolute?.replace('\\', "/");
+                    Some(directory.join(Path::new(&file).file_name()?))
+                });
+            match path {
+                Some(path) => image::open(path),
+                None => {
+                    println!("Texture {name} has no file");
+                    return None;
+                }
+            }
+        }
+    };
+    image
+        .inspect_err(|error| println!("Cannot load texture {name}: {error}"))
+        .ok()
+        .map(|image| image.to_rgba8())
+}
+
+/// Parses a binary FBX file, placing each mesh by the hierarchy of models it hangs off. Only the
+/// diffuse color and texture of materials are taken over.
+pub fn read(path: &Path) -> Result<Source, String> {
+    let bytes = std::fs::read(path).map_err(|error| error.to_string())?;
+    from_bytes(&bytes, path.parent().unwrap_or(Path::new("")))
+}
+
+/// Reads a binary FBX file's contents, with textures referred to by name looked up in the
+/// directory.
+fn from_bytes(bytes: &[u8], directory: &Path) -> Result<Source, String> {
+    let nodes = parse(bytes)?;
+    let top = |name| nodes.iter().find(|node| node.name == name);
+    let objects = top("Objects").ok_or("No objects")?;
+    let graph = Graph::new(objects, top("Connections"))?;
+    let root = root_transform(top("GlobalSettings"));
+
+    let mut source = Source {
+        parts: Vec::new(),
+        materials: Vec::new(),
+        images: Vec::new(),
+    };
+    let mut material_indices: HashMap<i64, usize> = HashMap::new();
+    let mut image_indices: HashMap<i64, Option<usize>> = HashMap::new();
+    // In file order, as the map of objects is not.
+    for model in objects.children("Model") {
+        let Some(id) = model.integer(0) else {
+            continue;
+        };
+        let transform = root * graph.world_transform(id) * geometric_transform(model);
+        let slots: Vec<_> = graph.children_of(id, "Material").collect();
+        for (_, geometry) in graph.children_of(id, "Geometry") {
+            if geometry.string(2) != Some("Mesh") {
+                continue;
+            }
+            let parts = mesh_parts(geometry)
+                .map_err(|error| format!("{} in {}", error, model.object_name()))?;
+            for (slot, data) in parts {
+                let material = slots.get(slot).map(|&(material_id, material)| {
+                    *material_indices.entry(material_id).or_insert_with(|| {
+                        let texture = graph
+                            .property_child(material_id, "DiffuseColor")
+                            .filter(|(_, node)| node.name == "Texture")
+                            .and_then(|(texture_id, texture)| {
+                                *image_indices.entry(texture_id).or_insert_with(|| {
+                                    let image =
+                                        read_texture(&graph, texture_id, texture, directory)?;
+                                    source.images.push(image);
+                                    Some(source.images.len() - 1)
+                                })
+                            });
+                        source.materials.push((to_material(material), texture));
+                        source.materials.len() - 1
+                    })
+                });
+                source.parts.push(Part {
+                    name: model.object_name().to_string(),
+                    transform,
+                    data,
+                    material,
+                });
+            }
+        }
+    }
+    if source.parts.is_empty() {
+        return Err("No meshes".to_string());
+    }
+    Ok(source)
+}
+
+pub fn add(renderer: &mut Renderer, source: Source) -> Model {
+    let textures: Vec<_> = source
+        .images
+        .iter()
+        .map(|image| renderer.add_texture(image, true))
+        .collect();
+    let materials: Vec<_> = source
+        .materials
+        .iter()
+        .map(|(material, texture)| {
+            renderer.add_material(&Material {
+                base_color_texture: texture.map(|index| textures[index]),
+                ..*material
+            })
+        })
+        .collect();
+    let mut default_material: Option<MaterialId> = None;
+    let objects = source
+        .parts
+        .iter()
+        .map(|part| Object {
+            name: part.name.clone(),
+            transform: part.transform,
+            mesh: renderer.add_mesh(&part.data),
+            material: match part.material {
+                Some(index) => materials[index],
+                None => *default_material
+                    .get_or_insert_with(|| renderer.add_material(&Material::default())),
+            },
+        })
+        .collect();
+    Model {
+        objects,
+        textures,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::*;
+
+    /// A node as written to the file, with its properties encoded.
+    struct Record {
+        name: &'static str,
+        properties: Vec<Vec<u8>>,
+        children: Vec<Record>,
+    }
+
+    impl Record {
+        fn new(name: &'static str, properties: Vec<Vec<u8>>, children: Vec<Record>) -> Self {
+            Record {
+                name,
+                properties,
+                children,
+            }
+        }
+
+        /// The record at the offset in the file, with the empty record ending its children.
+        fn write(&self, offset: usize) -> Vec<u8> {
+            let properties = self.properties.concat();
+            let header = 13 + self.name.len();
+            let mut body = properties.clone();
+            for child in &self.children {
+                let child = child.write(offset + header + body.len());
+                body.extend(child);
+            }
+            if !self.children.is_empty() {
+                body.extend([0; 13]);
+            }
+            let end = (offset + header + body.len()) as u32;
+            [
+                &end.to_le_bytes()[..],
+                &(self.properties.len() as u32).to_le_bytes(),
+                &(properties.len() as u32).to_le_bytes(),
+                &[self.name.len() as u8],
+                self.name.as_bytes(),
+                &body,
+            ]
+            .concat()
+        }
+    }
+
+    fn file(records: &[Record]) -> Vec<u8> {
+        let mut bytes = [MAGIC, &[0x1a, 0], &7400u32.to_le_bytes()].concat();
+        for record in records {
+            let record = record.write(bytes.len());
+            bytes.extend(record);
+        }
+        bytes.extend([0; 13]);
+        bytes
+    }
+
+    fn string(value: &str) -> Vec<u8> {
+        [
+            b"S",
+            &(value.len() as u32).to_le_bytes()[..],
+            value.as_bytes(),
+        ]
+        .concat()
+    }
+
+    fn long(value: i64) -> Vec<u8> {
+        [&b"L"[..], &value.to_le_bytes()].concat()
+    }
+
+    fn array(code: u8, count: usize, values: &[u8], compressed: bool) -> Vec<u8> {
+        let (encoding, data) = if compressed {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(values).unwrap();
+            (1u32, encoder.finish().unwrap())
+        } else {
+            (0, values.to_vec())
+        };
+        [
+            &[code][..],
+            &(count as u32).to_le_bytes(),
+            &encoding.to_le_bytes(),
+            &(data.len() as u32).to_le_bytes(),
+            &data,
+        ]
+        .concat()
+    }
+
+    /// A unit quad in the XY plane, hung off a model, next to an empty model. The models are
+    /// parented by the pairs of child and parent.
+    fn quad(compressed: bool, parents: &[(i64, i64)]) -> Vec<u8> {
+        let positions = [
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0f64,
+        ];
+        let polygons = [0, 1, 2, -4i32];
+        let geometry = Record::new(
+            "Geometry",
+            vec![long(1), string("Quad\0\x01Geometry"), string("Mesh")],
+            vec![
+                Record::new(
+                    "Vertices",
+                    vec![array(
+                        b'd',
+                        positions.len(),
+                        &positions.map(f64::to_le_bytes).concat(),
+                        compressed,
+                    )],
+                    vec![],
+                ),
+                Record::new(
+                    "PolygonVertexIndex",
+                    vec![array(
+                        b'i',
+                        polygons.len(),
+                        &polygons.map(i32::to_le_bytes).concat(),
+                        compressed,
+                    )],
+                    vec![],
+                ),
+            ],
+        );
+        let model = Record::new(
+            "Model",
+            vec![long(2), string("Quad\0\x01Model"), string("Mesh")],
+            vec![],
+        );
+        let empty = Record::new(
+            "Model",
+            vec![long(3), string("Empty\0\x01Model"), string("Null")],
+            vec![],
+        );
+        let connection =
+            |child, parent| Record::new("C", vec![string("OO"), long(child), long(parent)], vec![]);
+        file(&[
+            Record::new("Objects", vec![], vec![geometry, model, empty]),
+            Record::new(
+                "Connections",
+                vec![],
+                [(1, 2)]
+                    .iter()
+                    .chain(parents)
+                    .map(|&(child, parent)| connection(child, parent))
+                    .collect(),
+            ),
+        ])
+    }
+
+    #[test]
+    fn reads_quad() {
+        for compressed in [false, true] {
+            let source = from_bytes(&quad(compressed, &[]), Path::new("")).unwrap();
+            assert_eq!(source.parts.len(), 1);
+            let part = &source.parts[0];
+            assert_eq!(part.name, "Quad");
+            assert_eq!(part.data.vertices.len(), 4);
+            assert_eq!(part.data.indices.len(), 6);
+            assert_eq!(part.material, None);
+            // Centimeters, without settings.
+            assert_eq!(part.transform, Matrix4::from_scale(0.01));
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = quad(true, &[]);
+        for length in 0..bytes.len() {
+            assert!(from_bytes(&bytes[..length], Path::new("")).is_err());
+        }
+    }
+
+    #[test]
+    fn parent_cycles() {
+        assert!(from_bytes(&quad(false, &[(2, 3)]), Path::new("")).is_ok());
+        assert!(from_bytes(&quad(false, &[(2, 2)]), Path::new("")).is_err());
+        assert!(from_bytes(&quad(false, &[(2, 3), (3, 2)]), Path::new("")).is_err());
+    }
+
+    #[test]
+    fn array_longer_than_data() {
+        for compressed in [false, true] {
+            let bytes = array(b'd', u32::MAX as usize, &[0; 8], compressed);
+            let mut reader = Reader {
+                bytes: &bytes,
+                position: 0,
+                wide: false,
+            };
+            assert!(reader.property().is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_ascii() {
+        assert!(parse(b"; FBX 7.4.0 project file\nFBXHeaderExtension:  {\n}\n").is_err());
+    }
+}
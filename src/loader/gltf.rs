@@ -0,0 +1,702 @@
+use std::{collections::HashMap, path::Path};
+
+use cgmath::{InnerSpace, Matrix4, Quaternion, Rad, SquareMatrix, Vector2, Vector3, Vector4, Zero};
+use gltf::{
+    accessor::{DataType, Item, Iter},
+    animation::{util::ReadOutputs, Interpolation as GltfInterpolation},
+    camera::Projection as GltfProjection,
+    image::Format,
+    json::Value,
+    khr_lights_punctual::Kind,
+    material::AlphaMode as GltfAlphaMode,
+    Document, Semantic,
+};
+use image::RgbaImage;
+
+use super::{meshopt, vertex_cache, Model};
+use crate::{
+    animation::{AnimationPlayer, Channel, Clip, Interpolation, Keyframes, Morph, Node, Skin},
+    camera::{Projection, SceneCamera},
+    deformation::{DeformationId, MorphTarget, SkinVertex},
+    light::{DirectionalLight, PointLight, SpotLight},
+    material::{linear_to_srgb, AlphaMode, Material, MaterialId},
+    mesh::{MeshData, MeshId, Vertex},
+    render::Renderer,
+    render::FAR,
+    scene::Object,
+    texture::TextureId,
+    world::Entity,
+};
+
+/// A glTF file with its buffers and decoded images.
+#[derive(Debug)]
+pub struct Source {
+    document: Document,
+    buffers: Vec<gltf::buffer::Data>,
+    images: Vec<gltf::image::Data>,
+}
+
+/// Required extensions which are handled here rather than by the gltf crate.
+const DECODED_EXTENSIONS: &[&str] = &["EXT_meshopt_compression", "KHR_mesh_quantization"];
+
+/// Reads a glTF file with its buffers and images, decompressing the buffer views compressed with
+/// EXT_meshopt_compression.
+pub fn read(path: &Path) -> Result<Source, String> {
+    let bytes = std::fs::read(path).map_err(|error| error.to_string())?;
+    let gltf =
+        gltf::Gltf::from_slice_without_validation(&bytes).map_err(|error| error.to_string())?;
+    let mut json = gltf.document.into_json();
+    if json
+        .extensions_required
+        .iter()
+        .any(|extension| extension == "KHR_draco_mesh_compression")
+    {
+        return Err("Draco compression is not supported".to_string());
+    }
+    json.extensions_required
+        .retain(|extension| !DECODED_EXTENSIONS.contains(&extension.as_str()));
+    let document = Document::from_json(json).map_err(|error| error.to_string())?;
+    if document
+        .extensions_used()
+        .any(|extension| extension == "KHR_draco_mesh_compression")
+    {
+        println!("Ignoring Draco compression, using the uncompressed data");
+    }
+
+    let base = path.parent();
+    let mut blob = gltf.blob;
+    let mut buffers = Vec::new();
+    for buffer in document.buffers() {
+        // Fallback buffers of compressed views only reserve space for decompression.
+        let fallback = buffer
+            .extension_value("EXT_meshopt_compression")
+            .and_then(|extension| extension.get("fallback"))
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let data = if fallback {
+            gltf::buffer::Data(vec![0; buffer.length()])
+        } else {
+            gltf::buffer::Data::from_source_and_blob(buffer.source(), base, &mut blob)
+                .map_err(|error| error.to_string())?
+        };
+        if data.len() < buffer.length() {
+            return Err(format!("Buffer {} is too short", buffer.index()));
+        }
+        buffers.push(data);
+    }
+    for view in document.views() {
+        if let Some(extension) = view.extension_value("EXT_meshopt_compression") {
+            let decoded = decompress(extension, &buffers)?;
+            buffers[view.buffer().index()]
+                .0
+                .get_mut(view.offset()..view.offset() + decoded.len())
+                .ok_or("Compressed view outside of its buffer")?
+                .copy_from_slice(&decoded);
+        }
+    }
+    let images =
+        gltf::import_images(&document, base, &buffers).map_err(|error| error.to_string())?;
+    Ok(Source {
+        document,
+        buffers,
+        images,
+    })
+}
+
+/// Imports the default scene of a glTF file, one object per mesh primitive, its node animations,
+/// its perspective cameras and its punctual lights.
+pub fn add(renderer: &mut Renderer, source: Source) -> Result<Model, String> {
+    let Source {
+        document,
+        buffers,
+        images,
+    } = source;
+    let mut importer = Importer {
+        renderer,
+        buffers: &buffers,
+        images: &images,
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        meshes: HashMap::new(),
+        objects: Vec::new(),
+        nodes: Vec::new(),
+        node_indices: HashMap::new(),
+        attachments: Vec::new(),
+        skins: Vec::new(),
+        morphs: Vec::new(),
+        model: Model::default(),
+    };
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or("No scene")?;
+    for node in scene.nodes() {
+        importer.add_node(&node, Matrix4::identity(), None)?;
+    }
+
+    let skins = importer.skins(&document)?;
+    let clips = importer.clips(&document);
+    let deformed = !skins.is_empty() || !importer.morphs.is_empty();
+    let animation = (!clips.is_empty() || deformed).then(|| {
+        if !clips.is_empty() {
+            let names: Vec<_> = clips.iter().map(|clip| clip.name.as_str()).collect();
+            println!("Animations: {}", names.join(", "));
+        }
+        AnimationPlayer {
+            nodes: importer.nodes,
+            attachments: importer.attachments,
+            skins,
+            morphs: importer.morphs,
+            clips,
+            clip: 0,
+            time: 0.0,
+            playing: true,
+        }
+    });
+    Ok(Model {
+        objects: importer.objects,
+        animation,
+        textures: importer.textures.into_values().collect(),
+        ..importer.model
+    })
+}
+
+/// Resources added to the renderer so far, by their glTF index.
+struct Importer<'a> {
+    renderer: &'a mut Renderer,
+    buffers: &'a [gltf::buffer::Data],
+    images: &'a [gltf::image::Data],
+    textures: HashMap<usize, TextureId>,
+    /// Keyed by `None` for the default material.
+    materials: HashMap<Option<usize>, MaterialId>,
+    /// Keyed by mesh and primitive.
+    meshes: HashMap<(usize, usize), MeshId>,
+    objects: Vec<Object>,
+    /// The hierarchy, for animating the objects.
+    nodes: Vec<Node>,
+    node_indices: HashMap<usize, usize>,
+    attachments: Vec<(Entity, usize)>,
+    /// Skinned meshes with the index of their glTF skin.
+    skins: Vec<(DeformationId, usize)>,
+    morphs: Vec<Morph>,
+    /// Collects the cameras and lights.
+    model: Model,
+}
+
+impl Importer<'_> {
+    fn add_node(
+        &mut self,
+        node: &gltf::Node,
+        parent_transform: Matrix4<f32>,
+        parent: Option<usize>,
+    ) -> Result<(), String> {
+        let transform = parent_transform * Matrix4::from(node.transform().matrix());
+        let (translation, [x, y, z, w], scale) = node.transform().decomposed();
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            parent,
+            translation: translation.into(),
+            rotation: Quaternion::new(w, x, y, z),
+            scale: scale.into(),
+        });
+        self.node_indices.insert(node.index(), index);
+        if let Some(mesh) = node.mesh() {
+            let name = node.name().or(mesh.name()).unwrap_or_default();
+            for primitive in mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    println!(
+                        "Skipping primitive {} of mesh {}: not triangles",
+                        primitive.index(),
+                        mesh.index()
+                    );
+                    continue;
+                }
+                let material = self.material(&primitive.material());
+                // Deformation overwrites the vertices, so every skinned or morphed node gets its
+                // own mesh. Skinned ones are placed by their joints alone.
+                let targets = self.morph_targets(&primitive);
+                if node.skin().is_some() || !targets.is_empty() {
+                    // Deformed meshes keep their vertex order, which the skin and the morph
+                    // targets index.
+                    let data = self.mesh_data(&primitive)?;
+                    let skin_vertices = match node.skin() {
+                        Some(_) => Some(self.skin_vertices(&primitive)?),
+                        None => None,
+                    };
+                    let object_mesh = self.renderer.add_mesh(&data);
+                    let deformation = self.renderer.add_deformation(
+                        object_mesh,
+                        node.skin()
+                            .zip(skin_vertices.as_deref())
+                            .map(|(skin, vertices)| (vertices, skin.joints().count())),
+                        &targets,
+                    );
+                    if let Some(skin) = node.skin() {
+                        self.skins.push((deformation, skin.index()));
+                    } else {
+                        self.attachments.push((Entity(self.objects.len()), index));
+                    }
+                    if !targets.is_empty() {
+                        let defaults = node.weights().or(mesh.weights()).unwrap_or(&[]);
+                        self.morphs.push(Morph {
+                            deformation,
+                            node: index,
+                            weights: (0..targets.len())
+                                .map(|target| defaults.get(target).copied().unwrap_or(0.0))
+                                .collect(),
+                        });
+                    }
+                    self.objects.push(Object {
+                        name: name.to_string(),
+                        transform: if node.skin().is_some() {
+                            Matrix4::identity()
+                        } else {
+                            transform
+                        },
+                        mesh: object_mesh,
+                        material,
+                    });
+                    continue;
+                }
+                let key = (mesh.index(), primitive.index());
+                let mesh = match self.meshes.get(&key) {
+                    Some(&mesh) => mesh,
+                    None => {
+                        let mut data = self.mesh_data(&primitive)?;
+                        vertex_cache::optimize(&mut data);
+                        let mesh = self.renderer.add_mesh(&data);
+                        self.meshes.insert(key, mesh);
+                        mesh
+                    }
+                };
+                self.attachments.push((Entity(self.objects.len()), index));
+                self.objects.push(Object {
+                    name: name.to_string(),
+                    transform,
+                    mesh,
+                    material,
+                });
+            }
+        }
+        if let Some(camera) = node.camera() {
+            self.add_camera(&camera, node, transform);
+        }
+        if let Some(light) = node.light() {
+            self.add_light(&light, transform);
+        }
+        for child in node.children() {
+            self.add_node(&child, transform, Some(index))?;
+        }
+        Ok(())
+    }
+
+    /// Cameras keep their place in the bind pose, even if their nodes are animated.
+    fn add_camera(&mut self, camera: &gltf::Camera, node: &gltf::Node, transform: Matrix4<f32>) {
+        let name = camera
+            .name()
+            .or(node.name())
+            .map_or_else(|| format!("{}", self.model.cameras.len()), str::to_string);
+        let GltfProjection::Perspective(perspective) = camera.projection() else {
+            println!("Ignoring orthographic camera {name}");
+            return;
+        };
+        self.model.cameras.push(SceneCamera {
+            name,
+            transform: without_scale(transform),
+            projection: Projection {
+                fovy: Rad(perspective.yfov()),
+                near: perspective.znear(),
+                far: perspective.zfar().unwrap_or(FAR),
+            },
+            layers: SceneCamera::LAYERS,
+        });
+    }
+
+    /// Lights shine along their node's -Z axis. Their intensities are taken as they are, in lux
+    /// for directional and candela for point and spot lights.
+    fn add_light(&mut self, light: &gltf::khr_lights_punctual::Light, transform: Matrix4<f32>) {
+        let position = transform.w.truncate();
+        let direction = (transform * -Vector4::unit_z()).truncate().normalize();
+        let color = Vector3::from(light.color());
+        match light.kind() {
+            Kind::Directional => {
+                if self.model.directional_light.is_some() {
+                    println!("Ignoring additional directional light");
+                    return;
+                }
+                self.model.directional_light = Some(DirectionalLight {
+                    direction,
+                    color,
+                    intensity: light.intensity(),
+                });
+            }
+            Kind::Point => {
+                let point_light = PointLight::new(position, color, light.intensity());
+                self.model.point_lights.push(PointLight {
+                    radius: light.range().unwrap_or(point_light.radius),
+                    ..point_light
+                });
+            }
+            Kind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => {
+                let spot_light = SpotLight::new(position, direction, color, light.intensity());
+                self.model.spot_lights.push(SpotLight {
+                    radius: light.range().unwrap_or(spot_light.radius),
+                    inner_angle: Rad(inner_cone_angle),
+                    outer_angle: Rad(outer_cone_angle),
+                    ..spot_light
+                });
+            }
+        }
+    }
+
+    /// The joints of the skinned meshes, in the imported hierarchy.
+    fn skins(&self, document: &Document) -> Result<Vec<Skin>, String> {
+        self.skins
+            .iter()
+            .map(|&(id, index)| {
+                let skin = document.skins().nth(index).unwrap();
+                let joints = skin
+                    .joints()
+                    .map(|joint| self.node_indices.get(&joint.index()).copied())
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or("Joint outside of the scene")?;
+                let inverse_bind_matrices = match skin
+                    .reader(|buffer| Some(&self.buffers[buffer.index()]))
+                    .read_inverse_bind_matrices()
+                {
+                    Some(matrices) => matrices.map(Matrix4::from).collect(),
+                    None => vec![Matrix4::identity(); joints.len()],
+                };
+                Ok(Skin {
+                    deformation: id,
+                    joint_matrices: vec![Matrix4::identity(); joints.len()],
+                    joints,
+                    inverse_bind_matrices,
+                })
+            })
+            .collect()
+    }
+
+    /// The animations of the imported nodes' translations, rotations and scales.
+    fn clips(&self, document: &Document) -> Vec<Clip> {
+        document
+            .animations()
+            .map(|animation| {
+                let channels: Vec<_> = animation
+                    .channels()
+                    .filter_map(|channel| {
+                        let node = *self.node_indices.get(&channel.target().node().index())?;
+                        let reader = channel.reader(|buffer| Some(&self.buffers[buffer.index()]));
+                        let times: Vec<_> = reader.read_inputs()?.collect();
+                        let keyframes = match reader.read_outputs()? {
+                            ReadOutputs::Translations(values) => {
+                                Keyframes::Translation(values.map(Vector3::from).collect())
+                            }
+                            ReadOutputs::Rotations(values) => Keyframes::Rotation(
+                                values
+                                    .into_f32()
+                                    .map(|[x, y, z, w]| Quaternion::new(w, x, y, z))
+                                    .collect(),
+                            ),
+                            ReadOutputs::Scales(values) => {
+                                Keyframes::Scale(values.map(Vector3::from).collect())
+                            }
+                            ReadOutputs::MorphTargetWeights(values) => {
+                                let values: Vec<_> = values.into_f32().collect();
+                                let per_time = match channel.sampler().interpolation() {
+                                    GltfInterpolation::CubicSpline => 3 * times.len(),
+                                    _ => times.len(),
+                                };
+                                Keyframes::Weights {
+                                    count: values.len() / per_time.max(1),
+                                    values,
+                                }
+                            }
+                        };
+                        (!times.is_empty()).then_some(Channel {
+                            node,
+                            times,
+                            keyframes,
+                            interpolation: match channel.sampler().interpolation() {
+                                GltfInterpolation::Step => Interpolation::Step,
+                                GltfInterpolation::Linear => Interpolation::Linear,
+                                GltfInterpolation::CubicSpline => Interpolation::CubicSpline,
+                            },
+                        })
+                    })
+                    .collect();
+                Clip {
+                    name: animation
+                        .name()
+                        .map_or_else(|| format!("{}", animation.index()), str::to_string),
+                    duration: channels
+                        .iter()
+                        .map(|channel| *channel.times.last().unwrap())
+                        .fold(0.0, f32::max),
+                    channels,
+                }
+            })
+            .collect()
+    }
+
+    fn mesh_data(&self, primitive: &gltf::Primitive) -> Result<MeshData, String> {
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let attribute = |semantic| primitive.get(&semantic);
+        let positions: Vec<[f32; 3]> = attribute(Semantic::Positions)
+            .and_then(|accessor| read_floats(accessor, self.buffers))
+            .ok_or("Missing positions")?;
+        let normals: Option<Vec<[f32; 3]>> =
+            attribute(Semantic::Normals).and_then(|accessor| read_floats(accessor, self.buffers));
+        let colors: Option<Vec<_>> = reader
+            .read_colors(0)
+            .map(|colors| colors.into_rgba_f32().collect());
+        let uvs: Option<Vec<[f32; 2]>> = attribute(Semantic::TexCoords(0))
+            .and_then(|accessor| read_floats(accessor, self.buffers));
+        let lightmap_uvs: Option<Vec<[f32; 2]>> = attribute(Semantic::TexCoords(1))
+            .and_then(|accessor| read_floats(accessor, self.buffers));
+
+        let vertices = positions
+            .iter()
+            .enumerate()
+            .map(|(index, &position)| {
+                let uv = uvs
+                    .as_ref()
+                    .map_or(Vector2::zero(), |uvs| uvs[index].into());
+                Vertex {
+                    position: position.into(),
+                    normal: normals
+                        .as_ref()
+                        .map_or(Vector3::zero(), |normals| normals[index].into()),
+                    // glTF stores vertex colors linearly.
+                    color: colors
+                        .as_ref()
+                        .map_or(Vector4::new(1.0, 1.0, 1.0, 1.0), |colors| {
+                            linear_to_srgb(colors[index].into())
+                        }),
+                    uv,
+                    lightmap_uv: lightmap_uvs
+                        .as_ref()
+                        .map_or(uv, |lightmap_uvs| lightmap_uvs[index].into()),
+                }
+            })
+            .collect();
+        let indices = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+
+        let mut data = MeshData { vertices, indices };
+        if normals.is_none() {
+            data.compute_normals();
+        }
+        Ok(data)
+    }
+
+    fn morph_targets(&self, primitive: &gltf::Primitive) -> Vec<MorphTarget> {
+        let vertex_count = primitive
+            .get(&Semantic::Positions)
+            .map_or(0, |accessor| accessor.count());
+        let read = |accessor: Option<gltf::Accessor>| -> Vec<Vector3<f32>> {
+            accessor
+                .and_then(|accessor| read_floats::<3>(accessor, self.buffers))
+                .map_or(Vec::new(), |values| {
+                    values.into_iter().map(Vector3::from).collect()
+                })
+        };
+        primitive
+            .morph_targets()
+            .map(|target| {
+                let mut positions = read(target.positions());
+                positions.resize(vertex_count, Vector3::zero());
+                MorphTarget {
+                    positions,
+                    normals: read(target.normals()),
+                }
+            })
+            .collect()
+    }
+
+    fn skin_vertices(&self, primitive: &gltf::Primitive) -> Result<Vec<SkinVertex>, String> {
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let joints = reader.read_joints(0).ok_or("Missing joints")?.into_u16();
+        let weights = reader.read_weights(0).ok_or("Missing weights")?.into_f32();
+        Ok(joints
+            .zip(weights)
+            .map(|(joints, weights)| SkinVertex {
+                joints: joints.map(u32::from),
+                weights,
+            })
+            .collect())
+    }
+
+    fn material(&mut self, material: &gltf::Material) -> MaterialId {
+        if let Some(&id) = self.materials.get(&material.index()) {
+            return id;
+        }
+        let pbr = material.pbr_metallic_roughness();
+        let base_color_texture = pbr
+            .base_color_texture()
+            .map(|info| self.texture(info.texture().source().index()));
+        let id = self.renderer.add_material(&Material {
+            // glTF stores factors linearly.
+            base_color: linear_to_srgb(pbr.base_color_factor().into()),
+            base_color_texture,
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+            alpha_mode: match material.alpha_mode() {
+                GltfAlphaMode::Opaque => AlphaMode::Opaque,
+                GltfAlphaMode::Mask => AlphaMode::Mask,
+                GltfAlphaMode::Blend => AlphaMode::Blend,
+            },
+            alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+            ..Default::default()
+        });
+        self.materials.insert(material.index(), id);
+        id
+    }
+
+    fn texture(&mut self, image: usize) -> TextureId {
+        if let Some(&id) = self.textures.get(&image) {
+            return id;
+        }
+        let id = self
+            .renderer
+            .add_texture(&to_rgba(&self.images[image]), true);
+        self.textures.insert(image, id);
+        id
+    }
+}
+
+/// Expands the image to 8-bit RGBA, keeping the most significant byte of wider channels.
+fn to_rgba(image: &gltf::image::Data) -> RgbaImage {
+    let (channels, bytes_per_channel) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let channel = |pixel: &[u8], index: usize| -> u8 {
+        let bytes = &pixel[index * bytes_per_channel..][..bytes_per_channel];
+        match bytes_per_channel {
+            1 => bytes[0],
+            2 => bytes[1],
+            _ => {
+                let value = f32::from_le_bytes(bytes.try_into().unwrap());
+                (value.clamp(0.0, 1.0) * 255.0).round() as u8
+            }
+        }
+    };
+    let pixels = image
+        .pixels
+        .chunks_exact(channels * bytes_per_channel)
+        .flat_map(|pixel| match channels {
+            1 => {
+                let value = channel(pixel, 0);
+                [value, value, value, 255]
+            }
+            2 => {
+                let value = channel(pixel, 0);
+                [value, value, value, channel(pixel, 1)]
+            }
+            3 => [channel(pixel, 0), channel(pixel, 1), channel(pixel, 2), 255],
+            _ => [0, 1, 2, 3].map(|index| channel(pixel, index)),
+        })
+        .collect();
+    RgbaImage::from_raw(image.width, image.height, pixels).unwrap()
+}
+
+/// Decodes a buffer view compressed with EXT_meshopt_compression from the range of the buffer
+/// the extension names, undoing its filter.
+fn decompress(extension: &Value, buffers: &[gltf::buffer::Data]) -> Result<Vec<u8>, String> {
+    let field = |name: &str| {
+        extension
+            .get(name)
+            .and_then(Value::as_u64)
+            .map(|value| value as usize)
+            .ok_or(format!("Missing compression field {name}"))
+    };
+    let buffer = buffers
+        .get(field("buffer")?)
+        .ok_or("Invalid compressed buffer")?;
+    let offset = field("byteOffset").unwrap_or(0);
+    let data = buffer
+        .get(offset..offset + field("byteLength")?)
+        .ok_or("Compressed data outside of its buffer")?;
+    let mode = extension
+        .get("mode")
+        .and_then(Value::as_str)
+        .and_then(meshopt::Mode::parse)
+        .ok_or("Invalid compression mode")?;
+    let filter = match extension.get("filter").and_then(Value::as_str) {
+        Some(name) => meshopt::Filter::parse(name).ok_or(format!("Invalid filter {name}"))?,
+        None => meshopt::Filter::None,
+    };
+    meshopt::decode(data, field("count")?, field("byteStride")?, mode, filter)
+}
+
+/// Reads an accessor as floats. Besides floats, KHR_mesh_quantization allows plain and
+/// normalized integers for positions, normals and texture coordinates.
+fn read_floats<const N: usize>(
+    accessor: gltf::Accessor,
+    buffers: &[gltf::buffer::Data],
+) -> Option<Vec<[f32; N]>>
+where
+    [f32; N]: Item,
+    [i8; N]: Item,
+    [u8; N]: Item,
+    [i16; N]: Item,
+    [u16; N]: Item,
+{
+    let normalized = accessor.normalized();
+    let get = |buffer: gltf::Buffer| Some(&*buffers[buffer.index()]);
+    let convert = |value: f32, max: f32| {
+        if normalized {
+            (value / max).max(-1.0)
+        } else {
+            value
+        }
+    };
+    match accessor.data_type() {
+        DataType::F32 => Iter::<[f32; N]>::new(accessor, get).map(Iterator::collect),
+        DataType::I8 => Iter::<[i8; N]>::new(accessor, get).map(|values| {
+            values
+                .map(|value| value.map(|c| convert(c as f32, 127.0)))
+                .collect()
+        }),
+        DataType::U8 => Iter::<[u8; N]>::new(accessor, get).map(|values| {
+            values
+                .map(|value| value.map(|c| convert(c as f32, 255.0)))
+                .collect()
+        }),
+        DataType::I16 => Iter::<[i16; N]>::new(accessor, get).map(|values| {
+            values
+                .map(|value| value.map(|c| convert(c as f32, 32767.0)))
+                .collect()
+        }),
+        DataType::U16 => Iter::<[u16; N]>::new(accessor, get).map(|values| {
+            values
+                .map(|value| value.map(|c| convert(c as f32, 65535.0)))
+                .collect()
+        }),
+        DataType::U32 => None,
+    }
+}
+
+/// The transform with its axes normalized, keeping rotation and translation.
+fn without_scale(transform: Matrix4<f32>) -> Matrix4<f32> {
+    Matrix4::from_cols(
+        transform.x.truncate().normalize().extend(0.0),
+        transform.y.truncate().normalize().extend(0.0),
+        transform.z.truncate().normalize().extend(0.0),
+        transform.w,
+    )
+}
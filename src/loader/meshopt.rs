@@ -0,0 +1,540 @@
+//! Decoder for buffer views compressed with EXT_meshopt_compression, following the bitstream
+//! specification of the extension.
+
+/// How a compressed buffer view is encoded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// Vertex attributes, delta-encoded byte by byte.
+    Attributes,
+    /// Triangle list indices.
+    Triangles,
+    /// Any other indices.
+    Indices,
+}
+
+/// Transform applied to attributes after decoding.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Filter {
+    None,
+    /// Unit vectors in octahedral encoding.
+    Octahedral,
+    /// Rotations with the largest component left out.
+    Quaternion,
+    /// Floats with a shared exponent.
+    Exponential,
+}
+
+impl Mode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "ATTRIBUTES" => Some(Mode::Attributes),
+            "TRIANGLES" => Some(Mode::Triangles),
+            "INDICES" => Some(Mode::Indices),
+            _ => None,
+        }
+    }
+}
+
+impl Filter {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "NONE" => Some(Filter::None),
+            "OCTAHEDRAL" => Some(Filter::Octahedral),
+            "QUATERNION" => Some(Filter::Quaternion),
+            "EXPONENTIAL" => Some(Filter::Exponential),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes `count` elements of `stride` bytes each.
+pub fn decode(
+    data: &[u8],
+    count: usize,
+    stride: usize,
+    mode: Mode,
+    filter: Filter,
+) -> Result<Vec<u8>, String> {
+    let mut decoded = match mode {
+        Mode::Attributes => decode_vertices(data, count, stride)?,
+        Mode::Triangles => write_indices(&decode_triangles(data, count)?, stride)?,
+        Mode::Indices => write_indices(&decode_indices(data, count)?, stride)?,
+    };
+    match filter {
+        Filter::None => {}
+        Filter::Octahedral if stride == 4 => octahedral::<1>(&mut decoded),
+        Filter::Octahedral if stride == 8 => octahedral::<2>(&mut decoded),
+        Filter::Quaternion if stride == 8 => quaternion(&mut decoded),
+        Filter::Exponential if stride.is_multiple_of(4) => exponential(&mut decoded),
+        _ => return Err(format!("Invalid stride {stride} for filter {filter:?}")),
+    }
+    Ok(decoded)
+}
+
+/// Reads through the encoded bytes, failing at the end instead of panicking.
+struct Cursor<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Cursor<'_> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self
+            .data
+            .get(self.position)
+            .ok_or("Truncated compressed data")?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&[u8], String> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + count)
+            .ok_or("Truncated compressed data")?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    /// Variable-length integer, seven bits per byte with the high bit marking continuation.
+    fn vbyte(&mut self) -> Result<u32, String> {
+        let mut result = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            result |= u32::from(byte & 127) << shift;
+            if byte < 128 {
+                break;
+            }
+        }
+        Ok(result)
+    }
+}
+
+fn unzigzag(value: u32) -> u32 {
+    (value >> 1) ^ (value & 1).wrapping_neg()
+}
+
+/// Values per byte group, which share a bit width.
+const BYTE_GROUP_SIZE: usize = 16;
+
+fn decode_vertices(data: &[u8], count: usize, stride: usize) -> Result<Vec<u8>, String> {
+    if stride == 0 || stride > 256 || !stride.is_multiple_of(4) {
+        return Err(format!("Invalid vertex stride {stride}"));
+    }
+    // The tail holds the vertex the first block is delta-encoded against.
+    let tail_size = stride.max(32);
+    if data.len() < 1 + tail_size {
+        return Err("Truncated compressed data".to_string());
+    }
+    if data[0] != 0xa0 {
+        return Err(format!("Unsupported vertex encoding {:#x}", data[0]));
+    }
+    let mut last_vertex = data[data.len() - stride..].to_vec();
+    let mut cursor = Cursor {
+        data: &data[..data.len() - tail_size],
+        position: 1,
+    };
+
+    // Blocks are limited to 8 KiB and 256 vertices, in whole byte groups.
+    let block_size = ((8192 / stride) & !(BYTE_GROUP_SIZE - 1)).min(256);
+    let mut vertices = vec![0; count * stride];
+    let mut deltas = vec![0; block_size];
+    for block_start in (0..count).step_by(block_size) {
+        let block_count = block_size.min(count - block_start);
+        let aligned_count = block_count.next_multiple_of(BYTE_GROUP_SIZE);
+        for (byte, last) in last_vertex.iter_mut().enumerate() {
+            decode_bytes(&mut cursor, &mut deltas[..aligned_count])?;
+            for (vertex, &delta) in deltas[..block_count].iter().enumerate() {
+                *last = last.wrapping_add(unzigzag(u32::from(delta)) as u8);
+                vertices[(block_start + vertex) * stride + byte] = *last;
+            }
+        }
+    }
+    if cursor.position != cursor.data.len() {
+        return Err("Trailing compressed data".to_string());
+    }
+    Ok(vertices)
+}
+
+/// Byte groups of 16 values each, stored with 0, 2, 4 or 8 bits per value as given by a header
+/// of two bits per group. The largest narrow value is an escape for a full byte that follows
+/// the group.
+fn decode_bytes(cursor: &mut Cursor, values: &mut [u8]) -> Result<(), String> {
+    let group_count = values.len() / BYTE_GROUP_SIZE;
+    let header = cursor.bytes(group_count.div_ceil(4))?.to_vec();
+    for (group, values) in values.chunks_exact_mut(BYTE_GROUP_SIZE).enumerate() {
+        let bits = match (header[group / 4] >> (group % 4 * 2)) & 3 {
+            0 => 0,
+            1 => 2,
+            2 => 4,
+            _ => 8,
+        };
+        match bits {
+            0 => values.fill(0),
+            8 => values.copy_from_slice(cursor.bytes(BYTE_GROUP_SIZE)?),
+            _ => {
+                let packed = cursor.bytes(BYTE_GROUP_SIZE * bits / 8)?.to_vec();
+                let escape = (1 << bits) - 1;
+                for (index, value) in values.iter_mut().enumerate() {
+                    let bit = index * bits;
+                    let narrow = (packed[bit / 8] >> (8 - bits - bit % 8)) & escape;
+                    *value = if narrow == escape {
+                        cursor.byte()?
+                    } else {
+                        narrow
+                    };
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Ring buffer of the last 16 entries.
+struct Fifo<T> {
+    entries: [T; 16],
+    offset: usize,
+}
+
+impl<T: Copy> Fifo<T> {
+    fn new(entry: T) -> Self {
+        Fifo {
+            entries: [entry; 16],
+            offset: 0,
+        }
+    }
+
+    /// The entry pushed the given number of pushes ago.
+    fn get(&self, age: usize) -> T {
+        self.entries[self.offset.wrapping_sub(age) & 15]
+    }
+
+    /// Writes the next slot, which is only kept if the FIFO advances.
+    fn push(&mut self, entry: T, advance: bool) {
+        self.entries[self.offset] = entry;
+        self.offset = (self.offset + advance as usize) & 15;
+    }
+}
+
+/// Triangles are encoded one code byte each, referring to recent edges and vertices through
+/// FIFOs, with new vertices numbered sequentially and any others delta-encoded.
+fn decode_triangles(data: &[u8], count: usize) -> Result<Vec<u32>, String> {
+    if !count.is_multiple_of(3) {
+        return Err(format!("Index count {count} is not a multiple of three"));
+    }
+    let triangle_count = count / 3;
+    // A table of common auxiliary codes closes the data.
+    if data.len() < 1 + triangle_count + 16 {
+        return Err("Truncated compressed data".to_string());
+    }
+    let version = match data[0] {
+        0xe0 => 0,
+        0xe1 => 1,
+        header => return Err(format!("Unsupported index encoding {header:#x}")),
+    };
+    let codes = &data[1..1 + triangle_count];
+    let aux_table = &data[data.len() - 16..];
+    let mut cursor = Cursor {
+        data: &data[..data.len() - 16],
+        position: 1 + triangle_count,
+    };
+
+    let mut edges = Fifo::new((0, 0));
+    let mut vertices = Fifo::new(0);
+
+    let mut next = 0u32;
+    let mut last = 0u32;
+    let max_cached = if version >= 1 { 13 } else { 15 };
+    let mut indices = Vec::with_capacity(count);
+    for &code in codes {
+        if code < 0xf0 {
+            // An edge from the FIFO, and a new, cached or free vertex.
+            let (a, b) = edges.get(1 + (code >> 4) as usize);
+            let fec = (code & 15) as usize;
+            let c = if fec < max_cached {
+                let c = if fec == 0 {
+                    next += 1;
+                    next - 1
+                } else {
+                    vertices.get(1 + fec)
+                };
+                vertices.push(c, fec == 0);
+                c
+            } else {
+                last = match fec {
+                    13 => last.wrapping_sub(1),
+                    14 => last.wrapping_add(1),
+                    _ => last.wrapping_add(unzigzag(cursor.vbyte()?)),
+                };
+                vertices.push(last, true);
+                last
+            };
+            indices.extend([a, b, c]);
+            edges.push((c, b), true);
+            edges.push((a, c), true);
+        } else {
+            // Three vertices without a shared edge, described by an auxiliary code from the table
+            // or from the data.
+            let explicit = code >= 0xfe;
+            let aux = if explicit {
+                cursor.byte()?
+            } else {
+                aux_table[(code & 15) as usize]
+            };
+            if explicit && aux == 0 {
+                next = 0;
+            }
+            let fea = if code == 0xff { 15 } else { 0 };
+            let feb = (aux >> 4) as usize;
+            let fec = (aux & 15) as usize;
+            // New vertices are numbered before any free index is read.
+            let mut vertex = |fe: usize| {
+                if fe == 0 {
+                    next += 1;
+                    next - 1
+                } else {
+                    vertices.get(fe)
+                }
+            };
+            let mut triangle = [vertex(fea), vertex(feb), vertex(fec)];
+            if explicit {
+                for (index, fe) in triangle.iter_mut().zip([fea, feb, fec]) {
+                    if fe == 15 {
+                        last = last.wrapping_add(unzigzag(cursor.vbyte()?));
+                        *index = last;
+                    }
+                }
+            }
+            let [a, b, c] = triangle;
+            indices.extend([a, b, c]);
+            vertices.push(a, true);
+            vertices.push(b, feb == 0 || feb == 15);
+            vertices.push(c, fec == 0 || fec == 15);
+            edges.push((b, a), true);
+            edges.push((c, b), true);
+            edges.push((a, c), true);
+        }
+    }
+    if cursor.position != cursor.data.len() {
+        return Err("Trailing compressed data".to_string());
+    }
+    Ok(indices)
+}
+
+/// Each index is a zigzag-encoded delta against one of two previous indices, selected by the
+/// lowest bit.
+fn decode_indices(data: &[u8], count: usize) -> Result<Vec<u32>, String> {
+    if data.len() < 1 + count + 4 {
+        return Err("Truncated compressed data".to_string());
+    }
+    if data[0] != 0xd1 {
+        return Err(format!("Unsupported index encoding {:#x}", data[0]));
+    }
+    let mut cursor = Cursor {
+        data: &data[..data.len() - 4],
+        position: 1,
+    };
+    let mut last = [0u32; 2];
+    let mut indices = Vec::with_capacity(count);
+    for _ in 0..count {
+        let value = cursor.vbyte()?;
+        let baseline = &mut last[(value & 1) as usize];
+        *baseline = baseline.wrapping_add(unzigzag(value >> 1));
+        indices.push(*baseline);
+    }
+    if cursor.position != cursor.data.len() {
+        return Err("Trailing compressed data".to_string());
+    }
+    Ok(indices)
+}
+
+fn write_indices(indices: &[u32], stride: usize) -> Result<Vec<u8>, String> {
+    match stride {
+        2 => Ok(indices
+            .iter()
+            .flat_map(|&index| (index as u16).to_le_bytes())
+            .collect()),
+        4 => Ok(indices
+            .iter()
+            .flat_map(|&index| index.to_le_bytes())
+            .collect()),
+        _ => Err(format!("Invalid index stride {stride}")),
+    }
+}
+
+/// Reads and writes signed little-endian components of `BYTES` bytes each.
+fn component<const BYTES: usize>(data: &[u8], index: usize) -> f32 {
+    let bytes = &data[index * BYTES..];
+    match BYTES {
+        1 => bytes[0] as i8 as f32,
+        _ => i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+    }
+}
+
+fn set_component<const BYTES: usize>(data: &mut [u8], index: usize, value: f32) {
+    // Rounds half away from zero, saturating.
+    let value = value.round() as i32;
+    data[index * BYTES..(index + 1) * BYTES].copy_from_slice(&value.to_le_bytes()[..BYTES]);
+}
+
+/// Reconstructs unit vectors from octahedral X and Y, with Z holding the scale of one. The
+/// fourth component is kept.
+fn octahedral<const BYTES: usize>(data: &mut [u8]) {
+    let max = ((1 << (8 * BYTES - 1)) - 1) as f32;
+    for vector in data.chunks_exact_mut(4 * BYTES) {
+        let mut x = component::<BYTES>(vector, 0);
+        let mut y = component::<BYTES>(vector, 1);
+        let z = component::<BYTES>(vector, 2) - x.abs() - y.abs();
+        let t = z.min(0.0);
+        x += if x >= 0.0 { t } else { -t };
+        y += if y >= 0.0 { t } else { -t };
+        let scale = max / (x * x + y * y + z * z).sqrt();
+        set_component::<BYTES>(vector, 0, x * scale);
+        set_component::<BYTES>(vector, 1, y * scale);
+        set_component::<BYTES>(vector, 2, z * scale);
+    }
+}
+
+/// Reconstructs rotations from three components, scaled by the high bits of the fourth, whose
+/// two lowest bits give the index of the largest, left-out component.
+fn quaternion(data: &mut [u8]) {
+    for rotation in data.chunks_exact_mut(8) {
+        let last = i16::from_le_bytes([rotation[6], rotation[7]]);
+        let scale = std::f32::consts::FRAC_1_SQRT_2 / f32::from(last | 3);
+        let x = component::<2>(rotation, 0) * scale;
+        let y = component::<2>(rotation, 1) * scale;
+        let z = component::<2>(rotation, 2) * scale;
+        let w = (1.0 - x * x - y * y - z * z).max(0.0).sqrt();
+        let largest = (last & 3) as usize;
+        for (offset, value) in [(1, x), (2, y), (3, z), (0, w)] {
+            set_component::<2>(rotation, (largest + offset) & 3, value * 32767.0);
+        }
+    }
+}
+
+/// Each 32-bit value holds a signed 24-bit mantissa and an 8-bit exponent.
+fn exponential(data: &mut [u8]) {
+    for value in data.chunks_exact_mut(4) {
+        let bits = i32::from_le_bytes([value[0], value[1], value[2], value[3]]);
+        let mantissa = (bits << 8) >> 8;
+        let exponent = bits >> 24;
+        let decoded = mantissa as f32 * 2f32.powi(exponent);
+        value.copy_from_slice(&decoded.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encoded by meshoptimizer's `meshopt_encodeIndexBuffer`.
+    const ENCODED_TRIANGLES: [u8; 27] = [
+        0xe0, 0xf0, 0x10, 0xfe, 0xff, 0xf0, 0x0c, 0xff, 0x02, 0x02, 0x02, 0x00, 0x76, 0x87, 0x56,
+        0x67, 0x78, 0xa9, 0x86, 0x65, 0x89, 0x68, 0x98, 0x01, 0x69, 0x00, 0x00,
+    ];
+    const TRIANGLES: [u32; 12] = [0, 1, 2, 2, 1, 3, 4, 6, 5, 7, 8, 9];
+
+    /// Encoded by meshoptimizer's `meshopt_encodeVertexBuffer`, from four vertices of four
+    /// 16-bit and four 8-bit components each.
+    const ENCODED_VERTICES: [u8; 91] = [
+        0xa0, 0x01, 0x3f, 0x00, 0x00, 0x00, 0x58, 0x57, 0x58, 0x01, 0x26, 0x00, 0x00, 0x00, 0x01,
+        0x0c, 0x00, 0x00, 0x00, 0x58, 0x01, 0x08, 0x00, 0x00, 0x00, 0x01, 0x03, 0x00, 0x00, 0x00,
+        0x2f, 0x01, 0x03, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x01, 0x0f, 0x00, 0x00, 0x00, 0xfe,
+        0xfd, 0x01, 0x03, 0x00, 0x00, 0x00, 0xfd, 0x01, 0x0c, 0x00, 0x00, 0x00, 0xfd, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7f,
+        0x00,
+    ];
+
+    fn vertices() -> Vec<u8> {
+        let vertex = |position: [u16; 4], normal: [u8; 4]| {
+            position
+                .into_iter()
+                .flat_map(u16::to_le_bytes)
+                .chain(normal)
+                .collect::<Vec<_>>()
+        };
+        [
+            vertex([0, 0, 0, 0], [0, 0, 127, 0]),
+            vertex([300, 0, 0, 0], [0, 0, 127, 0]),
+            vertex([0, 300, 0, 0], [127, 0, 0, 0]),
+            vertex([300, 300, 1000, 0], [0, 129, 0, 0]),
+        ]
+        .concat()
+    }
+
+    fn shorts(data: &[u8]) -> Vec<i16> {
+        data.chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+            .collect()
+    }
+
+    fn from_shorts(values: &[i16]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn triangles() {
+        let decoded = decode(&ENCODED_TRIANGLES, 12, 4, Mode::Triangles, Filter::None).unwrap();
+        assert_eq!(decoded, write_indices(&TRIANGLES, 4).unwrap());
+    }
+
+    #[test]
+    fn vertices_block() {
+        let decoded = decode(&ENCODED_VERTICES, 4, 12, Mode::Attributes, Filter::None).unwrap();
+        assert_eq!(decoded, vertices());
+    }
+
+    #[test]
+    fn truncated() {
+        for mode in [Mode::Triangles, Mode::Attributes] {
+            let (data, count, stride) = match mode {
+                Mode::Triangles => (&ENCODED_TRIANGLES[..], 12, 4),
+                _ => (&ENCODED_VERTICES[..], 4, 12),
+            };
+            for length in 0..data.len() - 1 {
+                assert!(decode(&data[..length], count, stride, mode, Filter::None).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn octahedral_filter() {
+        // Normals pointing away from Z fold over the octahedron's edges.
+        let mut data = vec![0, 0, 127, 7, 127, 127, 127, 7, 127, 73, 127, 7];
+        octahedral::<1>(&mut data);
+        assert_eq!(
+            data.into_iter().map(|byte| byte as i8).collect::<Vec<_>>(),
+            [0, 0, 127, 7, 0, 0, -127, 7, 76, 0, -102, 7]
+        );
+
+        let mut data = from_shorts(&[32767, 0, 32767, 1, -16384, -16383, 32767, 1]);
+        octahedral::<2>(&mut data);
+        assert_eq!(shorts(&data), [32767, 0, 0, 1, -23170, -23169, 0, 1]);
+    }
+
+    #[test]
+    fn quaternion_filter() {
+        // A quarter turn around Z, stored without its largest component W, and then without X.
+        let mut data = from_shorts(&[0, 0, 32767, 32767, 32767, 0, 0, 32764]);
+        quaternion(&mut data);
+        assert_eq!(shorts(&data), [0, 0, 23170, 23170, 23170, 23170, 0, 0]);
+    }
+
+    #[test]
+    fn exponential_filter() {
+        let mut data = [(0xff00_0003u32), (0x02ff_fffb), 0]
+            .into_iter()
+            .flat_map(u32::to_le_bytes)
+            .collect::<Vec<_>>();
+        exponential(&mut data);
+        let values: Vec<_> = data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        assert_eq!(values, [1.5, -20.0, 0.0]);
+    }
+}
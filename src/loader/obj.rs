@@ -0,0 +1,186 @@
+use std::{collections::HashMap, path::Path};
+
+use cgmath::{Matrix4, SquareMatrix, Vector2, Vector3, Vector4, Zero};
+use image::RgbaImage;
+
+use crate::{
+    material::{roughness_from_shininess, AlphaMode, Material, MaterialId},
+    mesh::{MeshData, Vertex},
+    render::Renderer,
+    scene::Object,
+    texture::TextureId,
+};
+
+use super::{vertex_cache, Model};
+
+/// A Wavefront OBJ file with its MTL materials and their decoded textures.
+#[derive(Debug)]
+pub struct Source {
+    models: Vec<tobj::Model>,
+    materials: Vec<tobj::Material>,
+    /// Keyed by file name, as referenced by the materials.
+    textures: HashMap<String, RgbaImage>,
+}
+
+pub fn read(path: &Path) -> Result<Source, String> {
+    let (models, materials) = tobj::load_obj(
+        path,
+        &tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ignore_points: true,
+            ignore_lines: true,
+        },
+    )
+    .map_err(|error| error.to_string())?;
+    let materials = materials.unwrap_or_else(|error| {
+        println!("Cannot load materials of {}: {error}", path.display());
+        Vec::new()
+    });
+
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut textures = HashMap::new();
+    for file in materials
+        .iter()
+        .filter_map(|material| material.diffuse_texture.as_ref())
+    {
+        if textures.contains_key(file) {
+            continue;
+        }
+        match image::open(directory.join(file)) {
+            Ok(image) => {
+                textures.insert(file.clone(), image.to_rgba8());
+            }
+            Err(error) => println!("Cannot load texture {file}: {error}"),
+        }
+    }
+    Ok(Source {
+        models,
+        materials,
+        textures,
+    })
+}
+
+/// Imports the models, one object per model.
+pub fn add(renderer: &mut Renderer, source: Source) -> Model {
+    let Source {
+        models,
+        materials,
+        textures: images,
+    } = source;
+    let mut textures: HashMap<String, TextureId> = HashMap::new();
+    let mut material_ids: HashMap<Option<usize>, MaterialId> = HashMap::new();
+    let mut objects = Vec::new();
+    for model in &models {
+        if model.mesh.indices.is_empty() {
+            continue;
+        }
+        let material_index = model
+            .mesh
+            .material_id
+            .filter(|&index| index < materials.len());
+        let material = match material_ids.get(&material_index) {
+            Some(&material) => material,
+            None => {
+                let material = match material_index {
+                    Some(index) => to_material(renderer, &materials[index], &images, &mut textures),
+                    None => Material::default(),
+                };
+                let id = renderer.add_material(&material);
+                material_ids.insert(material_index, id);
+                id
+            }
+        };
+        objects.push(Object {
+            name: model.name.clone(),
+            transform: Matrix4::identity(),
+            mesh: renderer.add_mesh(&mesh_data(&model.mesh)),
+            material,
+        });
+    }
+    Model {
+        objects,
+        textures: textures.into_values().collect(),
+        ..Default::default()
+    }
+}
+
+fn mesh_data(mesh: &tobj::Mesh) -> MeshData {
+    let vertices = (0..mesh.positions.len() / 3)
+        .map(|index| {
+            let vector3 = |values: &[f32]| {
+                Vector3::new(
+                    values[3 * index],
+                    values[3 * index + 1],
+                    values[3 * index + 2],
+                )
+            };
+            // OBJ texture coordinates start at the bottom left.
+            let uv = if mesh.texcoords.is_empty() {
+                Vector2::zero()
+            } else {
+                Vector2::new(
+                    mesh.texcoords[2 * index],
+                    1.0 - mesh.texcoords[2 * index + 1],
+                )
+            };
+            Vertex {
+                position: vector3(&mesh.positions),
+                normal: if mesh.normals.is_empty() {
+                    Vector3::zero()
+                } else {
+                    vector3(&mesh.normals)
+                },
+                color: if mesh.vertex_color.is_empty() {
+                    Vector4::new(1.0, 1.0, 1.0, 1.0)
+                } else {
+                    vector3(&mesh.vertex_color).extend(1.0)
+                },
+                uv,
+                lightmap_uv: uv,
+            }
+        })
+        .collect();
+
+    let mut data = MeshData {
+        vertices,
+        indices: mesh.indices.clone(),
+    };
+    if mesh.normals.is_empty() {
+        data.compute_normals();
+    }
+    vertex_cache::optimize(&mut data);
+    data
+}
+
+/// MTL colors are taken as authored, i.e. sRGB-encoded.
+fn to_material(
+    renderer: &mut Renderer,
+    material: &tobj::Material,
+    images: &HashMap<String, RgbaImage>,
+    textures: &mut HashMap<String, TextureId>,
+) -> Material {
+    let [red, green, blue] = material.diffuse.unwrap_or([1.0; 3]);
+    let alpha = material.dissolve.unwrap_or(1.0);
+    let base_color_texture = material.diffuse_texture.as_ref().and_then(|file| {
+        let image = images.get(file)?;
+        Some(
+            *textures
+                .entry(file.clone())
+                .or_insert_with(|| renderer.add_texture(image, true)),
+        )
+    });
+    Material {
+        base_color: Vector4::new(red, green, blue, alpha),
+        base_color_texture,
+        roughness: material
+            .shininess
+            .map_or(Material::default().roughness, roughness_from_shininess),
+        alpha_mode: if alpha < 1.0 {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        },
+        ..Default::default()
+    }
+}
@@ -0,0 +1,561 @@
+use std::path::Path;
+
+use cgmath::{Matrix4, SquareMatrix, Vector2, Vector3, Vector4};
+
+use super::{vertex_cache, Model};
+use crate::{
+    material::Material,
+    mesh::{MeshData, Vertex},
+    point_cloud::Point,
+    render::Renderer,
+    scene::Object,
+};
+
+/// A parsed PLY file. Files with faces become a mesh, files with only vertices a point cloud.
+#[derive(Debug)]
+pub enum Source {
+    Mesh(MeshData),
+    Points(Vec<Point>),
+}
+
+/// Parses an ASCII or binary PLY file. Vertex colors are taken as authored, i.e. sRGB-encoded.
+pub fn read(path: &Path) -> Result<Source, String> {
+    let bytes = std::fs::read(path).map_err(|error| error.to_string())?;
+    from_bytes(&bytes)
+}
+
+fn from_bytes(bytes: &[u8]) -> Result<Source, String> {
+    let (header, body) = parse_header(bytes)?;
+    let mut reader = match header.format {
+        Format::Ascii => Reader::Ascii(
+            std::str::from_utf8(body)
+                .map_err(|error| error.to_string())?
+                .split_ascii_whitespace(),
+        ),
+        Format::BinaryLittleEndian => Reader::Binary {
+            bytes: body,
+            big_endian: false,
+        },
+        Format::BinaryBigEndian => Reader::Binary {
+            bytes: body,
+            big_endian: true,
+        },
+    };
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let mut has_normals = false;
+    let mut has_faces = false;
+    for element in &header.elements {
+        match element.name.as_str() {
+            "vertex" => {
+                has_normals = element.find(&["nx"]).is_some();
+                vertices = read_vertices(&mut reader, element)?;
+            }
+            "face" => {
+                has_faces = element.count > 0;
+                indices = read_faces(&mut reader, element, vertices.len())?;
+            }
+            _ => element.skip(&mut reader)?,
+        }
+    }
+
+    if vertices.is_empty() {
+        return Err("No vertices".to_string());
+    }
+    if has_faces {
+        let mut data = MeshData { vertices, indices };
+        if !has_normals {
+            data.compute_normals();
+        }
+        vertex_cache::optimize(&mut data);
+        Ok(Source::Mesh(data))
+    } else {
+        Ok(Source::Points(
+            vertices
+                .iter()
+                .map(|vertex| Point {
+                    position: vertex.position,
+                    color: vertex.color,
+                })
+                .collect(),
+        ))
+    }
+}
+
+pub fn add(renderer: &mut Renderer, source: Source) -> Model {
+    let mut model = Model::default();
+    match source {
+        Source::Mesh(data) => model.objects.push(Object {
+            name: String::new(),
+            transform: Matrix4::identity(),
+            mesh: renderer.add_mesh(&data),
+            material: renderer.add_material(&Material::default()),
+        }),
+        Source::Points(points) => model.point_clouds.push(renderer.add_point_cloud(&points)),
+    }
+    model
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self, String> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(format!("Unknown property type {name}")),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// The factor mapping the type's range to [0, 1], for color channels.
+    fn normalization(self) -> f32 {
+        match self {
+            Scalar::U8 => 1.0 / 255.0,
+            Scalar::U16 => 1.0 / 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum PropertyType {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    ty: PropertyType,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    /// Index of the first scalar property with one of the names.
+    fn find(&self, names: &[&str]) -> Option<usize> {
+        self.properties.iter().position(|property| {
+            matches!(property.ty, PropertyType::Scalar(_))
+                && names.contains(&property.name.as_str())
+        })
+    }
+
+    /// Reads one row into the scalars or lists at the properties' indices.
+    fn read_row(&self, reader: &mut Reader, row: &mut Row) -> Result<(), String> {
+        for (index, property) in self.properties.iter().enumerate() {
+            match property.ty {
+                PropertyType::Scalar(scalar) => row.scalars[index] = reader.read(scalar)?,
+                PropertyType::List { count, item } => {
+                    let list = &mut row.lists[index];
+                    list.clear();
+                    for _ in 0..reader.read(count)? as usize {
+                        list.push(reader.read(item)?);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn row(&self) -> Row {
+        Row {
+            scalars: vec![0.0; self.properties.len()],
+            lists: vec![Vec::new(); self.properties.len()],
+        }
+    }
+
+    fn skip(&self, reader: &mut Reader) -> Result<(), String> {
+        // Rows without properties take no space, however many there are said to be.
+        if self.properties.is_empty() {
+            return Ok(());
+        }
+        let mut row = self.row();
+        for _ in 0..self.count {
+            self.read_row(reader, &mut row)?;
+        }
+        Ok(())
+    }
+}
+
+/// Values of one element, reused across rows.
+struct Row {
+    scalars: Vec<f64>,
+    lists: Vec<Vec<f64>>,
+}
+
+#[derive(Debug)]
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+/// Splits the file into its parsed header and the body following it.
+fn parse_header(bytes: &[u8]) -> Result<(Header, &[u8]), String> {
+    const END: &[u8] = b"end_header";
+    let end = bytes
+        .windows(END.len())
+        .position(|window| window == END)
+        .ok_or("Missing end of header")?;
+    // The body starts after the line break ending the header, which may be CRLF.
+    let mut body = end + END.len();
+    while body < bytes.len() && bytes[body] != b'\n' {
+        body += 1;
+    }
+    let text = std::str::from_utf8(&bytes[..end]).map_err(|error| error.to_string())?;
+
+    let mut lines = text.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err("Not a PLY file".to_string());
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words: Vec<_> = line.split_ascii_whitespace().collect();
+        match words.as_slice() {
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(format!("Unknown format {name}")),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| format!("Invalid count {count}"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or("Property outside of element")?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    ty: PropertyType::List {
+                        count: Scalar::parse(count)?,
+                        item: Scalar::parse(item)?,
+                    },
+                }),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or("Property outside of element")?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    ty: PropertyType::Scalar(Scalar::parse(ty)?),
+                }),
+            ["comment" | "obj_info", ..] | [] => {}
+            _ => return Err(format!("Invalid header line {line}")),
+        }
+    }
+
+    let header = Header {
+        format: format.ok_or("Missing format")?,
+        elements,
+    };
+    Ok((header, &bytes[(body + 1).min(bytes.len())..]))
+}
+
+enum Reader<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
+
+impl Reader<'_> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, String> {
+        match self {
+            Reader::Ascii(words) => {
+                let word = words.next().ok_or("Unexpected end of file")?;
+                word.parse().map_err(|_| format!("Invalid number {word}"))
+            }
+            Reader::Binary { bytes, big_endian } => {
+                let size = scalar.size();
+                if bytes.len() < size {
+                    return Err("Unexpected end of file".to_string());
+                }
+                let (value, rest) = bytes.split_at(size);
+                *bytes = rest;
+                let mut buffer = [0; 8];
+                buffer[..size].copy_from_slice(value);
+                if *big_endian {
+                    buffer[..size].reverse();
+                }
+                Ok(match scalar {
+                    Scalar::I8 => buffer[0] as i8 as f64,
+                    Scalar::U8 => buffer[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    Scalar::I32 => i32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+                    Scalar::U32 => u32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+                    Scalar::F32 => f32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+                    Scalar::F64 => f64::from_le_bytes(buffer),
+                })
+            }
+        }
+    }
+}
+
+fn read_vertices(reader: &mut Reader, element: &Element) -> Result<Vec<Vertex>, String> {
+    let required = |name: &str| {
+        element
+            .find(&[name])
+            .ok_or(format!("Missing vertex {name}"))
+    };
+    let position = [required("x")?, required("y")?, required("z")?];
+    let normal = [
+        element.find(&["nx"]),
+        element.find(&["ny"]),
+        element.find(&["nz"]),
+    ];
+    let color = [
+        element.find(&["red", "r"]),
+        element.find(&["green", "g"]),
+        element.find(&["blue", "b"]),
+        element.find(&["alpha", "a"]),
+    ];
+    let uv = [
+        element.find(&["s", "u", "texture_u"]),
+        element.find(&["t", "v", "texture_v"]),
+    ];
+    let normalization = color.map(|index| {
+        index.map_or(1.0, |index| match element.properties[index].ty {
+            PropertyType::Scalar(scalar) => scalar.normalization(),
+            PropertyType::List { .. } => 1.0,
+        })
+    });
+
+    let mut row = element.row();
+    (0..element.count)
+        .map(|_| {
+            element.read_row(reader, &mut row)?;
+            let scalars = &row.scalars;
+            let get = |index: Option<usize>, default: f32| {
+                index.map_or(default, |index| scalars[index] as f32)
+            };
+            let channel = |channel: usize| {
+                color[channel].map_or(1.0, |index| scalars[index] as f32 * normalization[channel])
+            };
+            // PLY texture coordinates start at the bottom left, like OBJ's.
+            let uv = Vector2::new(get(uv[0], 0.0), 1.0 - get(uv[1], 1.0));
+            Ok(Vertex {
+                position: Vector3::new(
+                    scalars[position[0]] as f32,
+                    scalars[position[1]] as f32,
+                    scalars[position[2]] as f32,
+                ),
+                normal: Vector3::new(
+                    get(normal[0], 0.0),
+                    get(normal[1], 0.0),
+                    get(normal[2], 0.0),
+                ),
+                color: Vector4::new(channel(0), channel(1), channel(2), channel(3)),
+                uv,
+                lightmap_uv: uv,
+            })
+        })
+        .collect()
+}
+
+/// Reads the faces as triangle fans.
+fn read_faces(
+    reader: &mut Reader,
+    element: &Element,
+    vertex_count: usize,
+) -> Result<Vec<u32>, String> {
+    let list = element
+        .properties
+        .iter()
+        .position(|property| {
+            matches!(property.ty, PropertyType::List { .. })
+                && matches!(property.name.as_str(), "vertex_indices" | "vertex_index")
+        })
+        .ok_or("Missing face vertex indices")?;
+    let mut row = element.row();
+    let mut indices = Vec::new();
+    for _ in 0..element.count {
+        element.read_row(reader, &mut row)?;
+        let list = &row.lists[list];
+        if list
+            .iter()
+            .any(|&index| index < 0.0 || index as usize >= vertex_count)
+        {
+            return Err("Face index out of bounds".to_string());
+        }
+        for i in 2..list.len() {
+            indices.extend([list[0], list[i - 1], list[i]].map(|index| index as u32));
+        }
+    }
+    Ok(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    /// A colored quad in the format.
+    fn quad(format: &str) -> Vec<u8> {
+        let mut bytes = format!("ply\nformat {format} 1.0\ncomment quad\n{HEADER}").into_bytes();
+        let corners = [
+            [0.0f32, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let big_endian = format == "binary_big_endian";
+        for (index, corner) in corners.iter().enumerate() {
+            let color = [255, 0, index as u8 * 50];
+            if format == "ascii" {
+                let [x, y, z] = corner;
+                let [r, g, b] = color;
+                bytes.extend(format!("{x} {y} {z} {r} {g} {b}\n").bytes());
+            } else {
+                for coordinate in corner {
+                    bytes.extend(if big_endian {
+                        coordinate.to_be_bytes()
+                    } else {
+                        coordinate.to_le_bytes()
+                    });
+                }
+                bytes.extend(color);
+            }
+        }
+        if format == "ascii" {
+            bytes.extend(b"4 0 1 2 3\n");
+        } else {
+            bytes.push(4);
+            for index in 0..4i32 {
+                bytes.extend(if big_endian {
+                    index.to_be_bytes()
+                } else {
+                    index.to_le_bytes()
+                });
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn reads_quad() {
+        for format in ["ascii", "binary_little_endian", "binary_big_endian"] {
+            let Ok(Source::Mesh(data)) = from_bytes(&quad(format)) else {
+                panic!("{format} is not a mesh");
+            };
+            assert_eq!(data.vertices.len(), 4, "{format}");
+            assert_eq!(data.indices.len(), 6, "{format}");
+            let mut positions: Vec<_> = data
+                .vertices
+                .iter()
+                .map(|vertex| [vertex.position.x, vertex.position.y, vertex.color.x])
+                .collect();
+            positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            assert_eq!(
+                positions,
+                [
+                    [0.0, 0.0, 1.0],
+                    [0.0, 1.0, 1.0],
+                    [1.0, 0.0, 1.0],
+                    [1.0, 1.0, 1.0]
+                ],
+                "{format}"
+            );
+            // Facing +z, as wound.
+            assert!(
+                data.vertices.iter().all(|vertex| vertex.normal.z > 0.99),
+                "{format}"
+            );
+        }
+    }
+
+    #[test]
+    fn reads_points() {
+        let bytes = b"ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\nproperty float z\nend_header\n1 2 3\n4 5 6\n";
+        let Ok(Source::Points(points)) = from_bytes(bytes) else {
+            panic!("Not a point cloud");
+        };
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[1].position, Vector3::new(4.0, 5.0, 6.0));
+    }
+
+    #[test]
+    fn truncated() {
+        for format in ["ascii", "binary_little_endian"] {
+            let bytes = quad(format);
+            // The ASCII file still holds every number without its final line break.
+            let complete = bytes.len() - (format == "ascii") as usize;
+            for len in 0..complete {
+                assert!(from_bytes(&bytes[..len]).is_err(), "{format} cut at {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_counts() {
+        let with = |from: &str, to: &str| {
+            let mut bytes = quad("binary_little_endian");
+            let at = bytes
+                .windows(from.len())
+                .position(|window| window == from.as_bytes())
+                .unwrap();
+            bytes.splice(at..at + from.len(), to.bytes());
+            from_bytes(&bytes)
+        };
+        assert!(with("vertex 4", "vertex -4").is_err());
+        assert!(with("vertex 4", "vertex 99999999999999").is_err());
+        assert!(with("face 1", "face 4000000000").is_err());
+        assert!(with("face 1", "face 2").is_err());
+        // Empty elements of any count take no space.
+        assert!(with("end_header", "element empty 99999999999999\nend_header").is_ok());
+    }
+}
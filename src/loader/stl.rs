@@ -0,0 +1,205 @@
+use std::path::Path;
+
+use cgmath::{Deg, EuclideanSpace, Matrix4, Vector2, Vector3, Vector4, Zero};
+
+use super::{vertex_cache, Unit};
+use crate::{
+    material::Material,
+    mesh::{MeshData, Vertex},
+    render::Renderer,
+    scene::Object,
+};
+
+/// Longest side of models imported without a unit.
+const FIT_SIZE: f32 = 2.0;
+
+/// A flat-shaded mesh with its placement.
+#[derive(Debug)]
+pub struct Source {
+    data: MeshData,
+    transform: Matrix4<f32>,
+}
+
+/// Parses a binary or ASCII STL file into a single mesh, centered at the origin. STL has no
+/// unit and is usually Z-up, so the model is turned Y-up and either scaled from the given unit
+/// to meters or to a fixed size.
+pub fn read(path: &Path, unit: Option<Unit>) -> Result<Source, String> {
+    let bytes = std::fs::read(path).map_err(|error| error.to_string())?;
+    let positions = parse(&bytes)?;
+
+    // The facet normals are often missing or wrong, so they are derived from the winding.
+    let mut data = MeshData {
+        vertices: positions
+            .into_iter()
+            .map(|position| Vertex {
+                position,
+                normal: Vector3::zero(),
+                color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+                uv: Vector2::zero(),
+                lightmap_uv: Vector2::zero(),
+            })
+            .collect(),
+        indices: Vec::new(),
+    };
+    data.indices = (0..data.vertices.len() as u32).collect();
+    data.compute_normals();
+    vertex_cache::optimize(&mut data);
+
+    let bounds = data.bounds();
+    let size = bounds.max - bounds.min;
+    let scale = match unit {
+        Some(unit) => unit.meters(),
+        None => FIT_SIZE / size.x.max(size.y).max(size.z).max(f32::EPSILON),
+    };
+    let transform = Matrix4::from_scale(scale)
+        * Matrix4::from_angle_x(Deg(-90.0))
+        * Matrix4::from_translation(-bounds.center().to_vec());
+
+    Ok(Source { data, transform })
+}
+
+pub fn add(renderer: &mut Renderer, source: Source) -> Vec<Object> {
+    vec![Object {
+        name: String::new(),
+        transform: source.transform,
+        mesh: renderer.add_mesh(&source.data),
+        material: renderer.add_material(&Material::default()),
+    }]
+}
+
+/// The corners of the triangles, three after each other.
+fn parse(bytes: &[u8]) -> Result<Vec<Vector3<f32>>, String> {
+    let positions = if is_binary(bytes) {
+        parse_binary(bytes)?
+    } else {
+        parse_ascii(bytes)?
+    };
+    if positions.is_empty() {
+        return Err("No triangles".to_string());
+    }
+    Ok(positions)
+}
+
+/// ASCII files start with `solid`, but so do some binary ones, which are recognized by their
+/// size matching the triangle count.
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() < 84 {
+        return false;
+    }
+    let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+    bytes.len() == 84 + 50 * count || !bytes.starts_with(b"solid")
+}
+
+/// 80 byte header, triangle count, then per triangle a normal, three vertices and two
+/// attribute bytes. Anything after the counted triangles is ignored.
+fn parse_binary(bytes: &[u8]) -> Result<Vec<Vector3<f32>>, String> {
+    let float = |bytes: &[u8]| f32::from_le_bytes(bytes[..4].try_into().unwrap());
+    let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+    let triangles = count
+        .checked_mul(50)
+        .and_then(|size| bytes[84..].get(..size))
+        .ok_or(format!("{count} triangles exceed the file"))?;
+    Ok(triangles
+        .chunks_exact(50)
+        .flat_map(|triangle| {
+            (0..3).map(move |vertex| {
+                let offset = 12 + 12 * vertex;
+                Vector3::new(
+                    float(&triangle[offset..]),
+                    float(&triangle[offset + 4..]),
+                    float(&triangle[offset + 8..]),
+                )
+            })
+        })
+        .collect())
+}
+
+fn parse_ascii(bytes: &[u8]) -> Result<Vec<Vector3<f32>>, String> {
+    let text = std::str::from_utf8(bytes).map_err(|error| error.to_string())?;
+    let mut positions = Vec::new();
+    for line in text.lines() {
+        let mut words = line.split_ascii_whitespace();
+        if words.next() != Some("vertex") {
+            continue;
+        }
+        let mut coordinate = || -> Result<f32, String> {
+            let word = words.next().ok_or("Missing vertex coordinate")?;
+            word.parse().map_err(|_| format!("Invalid number {word}"))
+        };
+        positions.push(Vector3::new(coordinate()?, coordinate()?, coordinate()?));
+    }
+    if positions.len() % 3 != 0 {
+        return Err("Incomplete facet".to_string());
+    }
+    Ok(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLES: [[[f32; 3]; 3]; 2] = [
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]],
+    ];
+
+    fn expected() -> Vec<Vector3<f32>> {
+        TRIANGLES
+            .iter()
+            .flatten()
+            .map(|&corner| corner.into())
+            .collect()
+    }
+
+    /// The triangles with the given count, behind a header that may start like an ASCII file.
+    fn binary(header: &[u8], count: u32) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        bytes.resize(80, 0);
+        bytes.extend(count.to_le_bytes());
+        for triangle in TRIANGLES {
+            bytes.extend([0; 12]);
+            for corner in triangle {
+                bytes.extend(
+                    corner
+                        .iter()
+                        .flat_map(|coordinate| coordinate.to_le_bytes()),
+                );
+            }
+            bytes.extend([0; 2]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn reads_ascii() {
+        let mut text = "solid quad\n".to_string();
+        for triangle in TRIANGLES {
+            text += "  facet normal 0 0 1\n    outer loop\n";
+            for [x, y, z] in triangle {
+                text += &format!("      vertex {x} {y} {z}\n");
+            }
+            text += "    endloop\n  endfacet\n";
+        }
+        text += "endsolid quad\n";
+        assert_eq!(parse(text.as_bytes()), Ok(expected()));
+    }
+
+    #[test]
+    fn reads_binary() {
+        assert_eq!(parse(&binary(b"exported", 2)), Ok(expected()));
+        // Recognized by its size despite the header.
+        assert_eq!(parse(&binary(b"solid quad", 2)), Ok(expected()));
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        assert!(parse(&binary(b"exported", 3)).is_err());
+        assert!(parse(&binary(b"exported", u32::MAX)).is_err());
+        assert!(parse(&binary(b"exported", 0)).is_err());
+        let bytes = binary(b"exported", 2);
+        assert!(parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(parse(b"solid quad\nfacet normal 0 0 1\nvertex 0 0 0\nvertex 1 0\n").is_err());
+        assert!(parse(b"solid quad\nvertex 0 0 0\nvertex 1 0 0\n").is_err());
+        assert!(parse(b"solid quad\nvertex 0 0 x\n").is_err());
+    }
+}
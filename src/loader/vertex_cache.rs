@@ -0,0 +1,265 @@
+//! Reordering of imported meshes for the post-transform vertex cache, following Tom Forsyth's
+//! linear-speed vertex cache optimisation.
+
+use std::collections::HashMap;
+
+use crate::mesh::{MeshData, Vertex};
+
+/// Vertices the simulated cache holds, a little more than most GPUs reuse.
+const CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+/// Score of the vertices of the last triangle, lower than the next ones so that the strip does
+/// not fold back onto itself.
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// Merges identical vertices, orders the triangles to reuse the vertices of recent ones, and
+/// the vertices in the order they are first used. Meshes with indices out of range or partial
+/// triangles are left as they are.
+pub fn optimize(data: &mut MeshData) {
+    if !data.indices.len().is_multiple_of(3)
+        || data
+            .indices
+            .iter()
+            .any(|&index| index as usize >= data.vertices.len())
+    {
+        return;
+    }
+    deduplicate(data);
+    reorder_triangles(&mut data.indices, data.vertices.len());
+    reorder_vertices(data);
+}
+
+/// The bits of all attributes, so that only vertices which are the same in every way are merged.
+fn key(vertex: &Vertex) -> [u32; 14] {
+    let Vertex {
+        position,
+        normal,
+        color,
+        uv,
+        lightmap_uv,
+    } = vertex;
+    [
+        position.x,
+        position.y,
+        position.z,
+        normal.x,
+        normal.y,
+        normal.z,
+        color.x,
+        color.y,
+        color.z,
+        color.w,
+        uv.x,
+        uv.y,
+        lightmap_uv.x,
+        lightmap_uv.y,
+    ]
+    .map(f32::to_bits)
+}
+
+fn deduplicate(data: &mut MeshData) {
+    let mut unique = HashMap::with_capacity(data.vertices.len());
+    let mut vertices = Vec::with_capacity(data.vertices.len());
+    let remap: Vec<u32> = data
+        .vertices
+        .iter()
+        .map(|vertex| {
+            *unique.entry(key(vertex)).or_insert_with(|| {
+                vertices.push(*vertex);
+                vertices.len() as u32 - 1
+            })
+        })
+        .collect();
+    for index in &mut data.indices {
+        *index = remap[*index as usize];
+    }
+    data.vertices = vertices;
+}
+
+/// How much drawing a triangle using the vertex next is worth, given where the vertex is in the
+/// cache and how many triangles still use it.
+fn vertex_score(cache_position: Option<usize>, remaining: u32) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache = match cache_position {
+        None => 0.0,
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            (1.0 - (position - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(CACHE_DECAY_POWER)
+        }
+    };
+    // Vertices with few triangles left are finished first, so they do not linger.
+    cache + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+/// Greedily draws the triangle with the best score next, scoring only the triangles around the
+/// cached vertices.
+fn reorder_triangles(indices: &mut [u32], vertex_count: usize) {
+    let triangle_count = indices.len() / 3;
+
+    // The triangles of each vertex, of which the first `remaining` are not drawn yet.
+    let mut remaining = vec![0u32; vertex_count];
+    for &index in indices.iter() {
+        remaining[index as usize] += 1;
+    }
+    let mut offsets = Vec::with_capacity(vertex_count + 1);
+    offsets.push(0);
+    for &count in &remaining {
+        offsets.push(offsets.last().unwrap() + count as usize);
+    }
+    let mut triangles = vec![0u32; indices.len()];
+    let mut filled = offsets.clone();
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        for &vertex in corners {
+            triangles[filled[vertex as usize]] = triangle as u32;
+            filled[vertex as usize] += 1;
+        }
+    }
+
+    let mut cache_positions = vec![None; vertex_count];
+    let mut vertex_scores: Vec<f32> = remaining
+        .iter()
+        .map(|&remaining| vertex_score(None, remaining))
+        .collect();
+    let mut drawn = vec![false; triangle_count];
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut order = Vec::with_capacity(indices.len());
+    let mut best = None;
+    // Where to look for a triangle that is not drawn yet, when none around the cache is left.
+    let mut next = 0;
+
+    loop {
+        let triangle = match best {
+            Some(triangle) => triangle,
+            None => {
+                while next < triangle_count && drawn[next] {
+                    next += 1;
+                }
+                if next == triangle_count {
+                    break;
+                }
+                next
+            }
+        };
+        drawn[triangle] = true;
+        let corners = [
+            indices[3 * triangle],
+            indices[3 * triangle + 1],
+            indices[3 * triangle + 2],
+        ];
+        order.extend_from_slice(&corners);
+
+        for vertex in corners {
+            let vertex = vertex as usize;
+            let start = offsets[vertex];
+            let end = start + remaining[vertex] as usize;
+            if let Some(position) = triangles[start..end]
+                .iter()
+                .position(|&other| other as usize == triangle)
+            {
+                triangles.swap(start + position, end - 1);
+                remaining[vertex] -= 1;
+            }
+        }
+
+        let mut updated = corners.to_vec();
+        updated.extend(cache.iter().filter(|vertex| !corners.contains(vertex)));
+        for (position, &vertex) in updated.iter().enumerate() {
+            let vertex = vertex as usize;
+            cache_positions[vertex] = (position < CACHE_SIZE).then_some(position);
+            vertex_scores[vertex] = vertex_score(cache_positions[vertex], remaining[vertex]);
+        }
+
+        best = None;
+        let mut best_score = f32::NEG_INFINITY;
+        for &vertex in &updated {
+            let start = offsets[vertex as usize];
+            let end = start + remaining[vertex as usize] as usize;
+            for &other in &triangles[start..end] {
+                let other = other as usize;
+                let score: f32 = indices[3 * other..3 * other + 3]
+                    .iter()
+                    .map(|&vertex| vertex_scores[vertex as usize])
+                    .sum();
+                if score > best_score {
+                    best = Some(other);
+                    best_score = score;
+                }
+            }
+        }
+
+        updated.truncate(CACHE_SIZE);
+        cache = updated;
+    }
+
+    indices.copy_from_slice(&order);
+}
+
+/// Renumbers the vertices in the order the triangles first use them, dropping unused ones.
+fn reorder_vertices(data: &mut MeshData) {
+    let mut remap = vec![u32::MAX; data.vertices.len()];
+    let mut vertices = Vec::with_capacity(data.vertices.len());
+    for index in &mut data.indices {
+        let remapped = &mut remap[*index as usize];
+        if *remapped == u32::MAX {
+            *remapped = vertices.len() as u32;
+            vertices.push(data.vertices[*index as usize]);
+        }
+        *index = *remapped;
+    }
+    data.vertices = vertices;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Each triangle by the attributes of its corners, rotated to start at the smallest one,
+    /// which keeps the winding, and sorted.
+    fn triangle_set(data: &MeshData) -> Vec<[[u32; 14]; 3]> {
+        let mut triangles: Vec<_> = data
+            .indices
+            .chunks_exact(3)
+            .map(|corners| {
+                let mut triangle = [0, 1, 2].map(|i| key(&data.vertices[corners[i] as usize]));
+                let first = (0..3).min_by_key(|&i| triangle[i]).unwrap();
+                triangle.rotate_left(first);
+                triangle
+            })
+            .collect();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn keeps_triangles() {
+        let indexed = MeshData::sphere(16, 8);
+        // Every corner a vertex of its own, which are merged again.
+        let unindexed = MeshData {
+            vertices: indexed
+                .indices
+                .iter()
+                .map(|&index| indexed.vertices[index as usize])
+                .collect(),
+            indices: (0..indexed.indices.len() as u32).collect(),
+        };
+        for mut data in [indexed.clone(), unindexed] {
+            let before = triangle_set(&data);
+            optimize(&mut data);
+            assert_eq!(triangle_set(&data), before);
+            assert!(data.vertices.len() <= indexed.vertices.len());
+        }
+    }
+
+    #[test]
+    fn leaves_invalid_meshes() {
+        let mut data = MeshData::cube();
+        data.indices.push(0);
+        let before = data.indices.clone();
+        optimize(&mut data);
+        assert_eq!(data.indices, before);
+    }
+}
@@ -0,0 +1,218 @@
+//! Coarser levels of detail of meshes, simplified by clustering their vertices on a grid, and
+//! their selection by the size of the mesh on screen.
+
+use std::collections::{HashMap, HashSet};
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix4, Vector3};
+
+use crate::mesh::{Bounds, MeshData};
+
+/// Cells of the clustering grid along the longest side of the bounds, from fine to coarse.
+const RESOLUTIONS: [u32; 5] = [64, 32, 16, 8, 4];
+/// Meshes with fewer triangles are drawn in full at any distance.
+const MIN_TRIANGLES: usize = 256;
+/// A level is kept only if it has at most this fraction of the triangles of the finer one.
+const MIN_REDUCTION: f32 = 0.75;
+/// Pixels on screen a cell of the clustering grid may span.
+const MAX_ERROR_PIXELS: f32 = 2.0;
+/// Fraction the screen size has to change by beyond a level's threshold before switching, so
+/// that objects at the threshold do not flicker between levels.
+const HYSTERESIS: f32 = 0.1;
+
+/// A level of detail, drawn from a range of the mesh's indices into its shared vertices.
+#[derive(Debug, Copy, Clone)]
+pub struct Lod {
+    pub first_index: u32,
+    pub index_count: u32,
+    /// Size of the cells merged into single vertices, relative to the diameter of the bounds.
+    /// Zero for the full mesh.
+    pub error: f32,
+}
+
+/// The levels of the mesh, starting with the full mesh, and the indices of all levels after
+/// each other. The coarser levels reuse vertices of the mesh, so that only indices are added.
+pub fn generate(data: &MeshData) -> (Vec<Lod>, Vec<u32>) {
+    let mut indices = data.indices.clone();
+    let mut lods = vec![Lod {
+        first_index: 0,
+        index_count: indices.len() as u32,
+        error: 0.0,
+    }];
+    if data.indices.len() < 3 * MIN_TRIANGLES
+        || !data.indices.len().is_multiple_of(3)
+        || data
+            .indices
+            .iter()
+            .any(|&index| index as usize >= data.vertices.len())
+    {
+        return (lods, indices);
+    }
+
+    let bounds = data.bounds();
+    let extent = bounds.max - bounds.min;
+    let longest = extent.x.max(extent.y).max(extent.z);
+    let diameter = extent.magnitude();
+    if longest <= 0.0 {
+        return (lods, indices);
+    }
+    for resolution in RESOLUTIONS {
+        let cell = longest / resolution as f32;
+        let level = cluster(data, &bounds, cell);
+        let finer = lods.last().unwrap().index_count as usize;
+        if level.is_empty() {
+            break;
+        }
+        if level.len() as f32 > MIN_REDUCTION * finer as f32 {
+            continue;
+        }
+        lods.push(Lod {
+            first_index: indices.len() as u32,
+            index_count: level.len() as u32,
+            error: cell / diameter,
+        });
+        indices.extend(level);
+    }
+    (lods, indices)
+}
+
+/// The triangles left after merging the vertices of each cell into the one closest to their
+/// average, without those collapsed or repeated.
+fn cluster(data: &MeshData, bounds: &Bounds, cell: f32) -> Vec<u32> {
+    let key = |position: Vector3<f32>| {
+        let cell = (position - bounds.min.to_vec()) / cell;
+        (cell.x as i32, cell.y as i32, cell.z as i32)
+    };
+    let mut sums: HashMap<_, (Vector3<f32>, u32)> = HashMap::new();
+    for vertex in &data.vertices {
+        let (sum, count) = sums
+            .entry(key(vertex.position))
+            .or_insert((Vector3::new(0.0, 0.0, 0.0), 0));
+        *sum += vertex.position;
+        *count += 1;
+    }
+    let mut representatives: HashMap<_, (u32, f32)> = HashMap::new();
+    for (index, vertex) in data.vertices.iter().enumerate() {
+        let cell = key(vertex.position);
+        let (sum, count) = sums[&cell];
+        let distance = (vertex.position - sum / count as f32).magnitude2();
+        let representative = representatives
+            .entry(cell)
+            .or_insert((index as u32, distance));
+        if distance < representative.1 {
+            *representative = (index as u32, distance);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    let mut indices = Vec::new();
+    for corners in data.indices.chunks_exact(3) {
+        let [a, b, c] = [corners[0], corners[1], corners[2]]
+            .map(|index| representatives[&key(data.vertices[index as usize].position)].0);
+        if a == b || b == c || c == a {
+            continue;
+        }
+        // Rotated to start at the lowest index, which keeps the winding.
+        let triangle = if a < b && a < c {
+            [a, b, c]
+        } else if b < c {
+            [b, c, a]
+        } else {
+            [c, a, b]
+        };
+        if seen.insert(triangle) {
+            indices.extend(triangle);
+        }
+    }
+    indices
+}
+
+/// Height of the bounds' sphere on screen, as a fraction of the screen's height. Objects level
+/// with or behind the camera count as filling the screen.
+pub fn screen_size(view_projection: Matrix4<f32>, transform: Matrix4<f32>, bounds: &Bounds) -> f32 {
+    let center = transform * bounds.center().to_homogeneous();
+    let scale = (0..3)
+        .map(|axis| transform[axis].truncate().magnitude())
+        .fold(0.0, f32::max);
+    let radius = 0.5 * scale * (bounds.max - bounds.min).magnitude();
+    let w = view_projection.row(3).dot(center);
+    if w <= f32::EPSILON {
+        return f32::INFINITY;
+    }
+    // The rows of a view-projection scale the view's y axis by the projection's, so the length
+    // of the second row is how much a length across the view grows in clip space.
+    radius * view_projection.row(1).truncate().magnitude() / w
+}
+
+/// The coarsest level whose error stays below a few pixels for an object as many pixels high.
+/// A level switched to before is kept until the height is past its threshold by some margin.
+pub fn select(lods: &[Lod], pixels: f32, previous: Option<usize>) -> usize {
+    let coarsest = |pixels: f32| {
+        lods.iter()
+            .rposition(|lod| lod.error * pixels <= MAX_ERROR_PIXELS)
+            .unwrap_or(0)
+    };
+    match previous {
+        Some(previous) => previous.clamp(
+            coarsest(pixels * (1.0 + HYSTERESIS)),
+            coarsest(pixels * (1.0 - HYSTERESIS)),
+        ),
+        None => coarsest(pixels),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_get_coarser() {
+        let data = MeshData::sphere(64, 32);
+        let (lods, indices) = generate(&data);
+        assert!(lods.len() > 1, "{lods:?}");
+        let mut end = 0;
+        for lod in &lods {
+            assert_eq!(lod.first_index, end);
+            end += lod.index_count;
+        }
+        assert_eq!(end as usize, indices.len());
+        for pair in lods.windows(2) {
+            assert!(pair[1].index_count < pair[0].index_count, "{lods:?}");
+            assert!(pair[1].error > pair[0].error, "{lods:?}");
+        }
+        assert!(indices
+            .iter()
+            .all(|&index| (index as usize) < data.vertices.len()));
+    }
+
+    #[test]
+    fn small_meshes_keep_one_level() {
+        let (lods, indices) = generate(&MeshData::cube());
+        assert_eq!(lods.len(), 1);
+        assert_eq!(indices, MeshData::cube().indices);
+    }
+
+    #[test]
+    fn hysteresis() {
+        let lod = |error| Lod {
+            first_index: 0,
+            index_count: 0,
+            error,
+        };
+        // Level 1 is fine up to 200 pixels high, level 2 up to 40.
+        let lods = [lod(0.0), lod(0.01), lod(0.05)];
+        let threshold = MAX_ERROR_PIXELS / 0.01;
+        assert_eq!(select(&lods, threshold, None), 1);
+        assert_eq!(select(&lods, threshold * 1.01, None), 0);
+
+        // Around the threshold, either level stays as it was.
+        for factor in [0.95, 0.99, 1.0, 1.01, 1.05] {
+            let pixels = threshold * factor;
+            assert_eq!(select(&lods, pixels, Some(0)), 0, "{pixels}");
+            assert_eq!(select(&lods, pixels, Some(1)), 1, "{pixels}");
+        }
+        // Only past the margin does it switch.
+        assert_eq!(select(&lods, threshold * 0.8, Some(0)), 1);
+        assert_eq!(select(&lods, threshold * 1.2, Some(1)), 0);
+        assert_eq!(select(&lods, 1.0, Some(0)), 2);
+    }
+}
@@ -0,0 +1,78 @@
+use wgpu::*;
+
+/// Must match the lookups in the main shader.
+const LTC_LUT_SIZE: u32 = 64;
+
+/// Lookup tables for shading area lights with linearly transformed cosines,
+/// indexed by roughness horizontally and `sqrt(1 - n·v)` vertically.
+#[derive(Debug)]
+pub struct LtcLuts {
+    /// Inverse transformation matrices, as the four non-trivial entries.
+    pub matrix: TextureView,
+    /// Scale and bias applied to the specular reflectance, like the split-sum BRDF lookup table.
+    pub amplitude: TextureView,
+}
+
+impl LtcLuts {
+    /// Fits a linearly transformed cosine to the GGX lobe for every entry of the tables.
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(include_str!("ltc.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: &module,
+            entry_point: Some("fit"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let create = || {
+            device
+                .create_texture(&TextureDescriptor {
+                    label: None,
+                    size: Extent3d {
+                        width: LTC_LUT_SIZE,
+                        height: LTC_LUT_SIZE,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Rgba16Float,
+                    usage: TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&Default::default())
+        };
+        let matrix = create();
+        let amplitude = create();
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&matrix),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&amplitude),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(LTC_LUT_SIZE / 8, LTC_LUT_SIZE / 8, 1);
+        drop(pass);
+        queue.submit(Some(encoder.finish()));
+
+        LtcLuts { matrix, amplitude }
+    }
+}
@@ -0,0 +1,211 @@
+const PI: f32 = 3.14159265359;
+/// Stratified samples per dimension when integrating over the hemisphere.
+const SAMPLE_COUNT: u32 = 32u;
+const FIT_ITERATIONS: u32 = 64u;
+
+@group(0) @binding(0) var ltc_matrix: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(1) var ltc_amplitude: texture_storage_2d<rgba16float, write>;
+
+/// A clamped cosine distribution transformed by `m`, scaled by the magnitude of the fitted BRDF.
+struct Ltc {
+    m: mat3x3<f32>,
+    inverse: mat3x3<f32>,
+    inverse_determinant: f32,
+    magnitude: f32,
+}
+
+/// The matrix is the frame `x`, `y`, `z` applied after `[[m11, 0, m13], [0, m22, 0], [0, 0, 1]]`.
+fn ltc_new(x: vec3<f32>, z: vec3<f32>, params: vec3<f32>, magnitude: f32) -> Ltc {
+    let m11 = max(params.x, 1e-4);
+    let m22 = max(params.y, 1e-4);
+    let m13 = params.z;
+    let frame = mat3x3<f32>(x, vec3<f32>(0.0, 1.0, 0.0), z);
+    var ltc: Ltc;
+    ltc.m = frame * mat3x3<f32>(vec3<f32>(m11, 0.0, 0.0), vec3<f32>(0.0, m22, 0.0), vec3<f32>(m13, 0.0, 1.0));
+    ltc.inverse = mat3x3<f32>(
+        vec3<f32>(1.0 / m11, 0.0, 0.0),
+        vec3<f32>(0.0, 1.0 / m22, 0.0),
+        vec3<f32>(-m13 / m11, 0.0, 1.0),
+    ) * transpose(frame);
+    ltc.inverse_determinant = 1.0 / (m11 * m22);
+    ltc.magnitude = magnitude;
+    return ltc;
+}
+
+/// Density of the distribution, without the magnitude.
+fn ltc_density(ltc: Ltc, l: vec3<f32>) -> f32 {
+    let original = ltc.inverse * l;
+    let scale = length(original);
+    return max(original.z / scale, 0.0) / PI * ltc.inverse_determinant / (scale * scale * scale);
+}
+
+fn ltc_sample(ltc: Ltc, u: vec2<f32>) -> vec3<f32> {
+    let phi = 2.0 * PI * u.y;
+    let sin_theta = sqrt(u.x);
+    return normalize(ltc.m * vec3<f32>(sin_theta * cos(phi), sin_theta * sin(phi), sqrt(1.0 - u.x)));
+}
+
+fn distribution_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+/// Matches the geometry term of the split-sum BRDF lookup table.
+fn geometry_schlick_ggx(n_dot_v: f32, alpha: f32) -> f32 {
+    let k = alpha / 2.0;
+    return n_dot_v / (n_dot_v * (1.0 - k) + k);
+}
+
+/// The GGX specular BRDF times the cosine, without Fresnel, and the density of sampling `l`
+/// through the distribution of normals.
+fn brdf(v: vec3<f32>, l: vec3<f32>, alpha: f32) -> vec2<f32> {
+    if l.z <= 0.0 {
+        return vec2<f32>(0.0);
+    }
+    let h = normalize(v + l);
+    let d = distribution_ggx(h.z, alpha);
+    let g = geometry_schlick_ggx(v.z, alpha) * geometry_schlick_ggx(l.z, alpha);
+    return vec2<f32>(d * g / (4.0 * v.z), d * h.z / (4.0 * dot(v, h)));
+}
+
+fn brdf_sample(v: vec3<f32>, alpha: f32, u: vec2<f32>) -> vec3<f32> {
+    let phi = 2.0 * PI * u.x;
+    let cos_theta = sqrt((1.0 - u.y) / (1.0 + (alpha * alpha - 1.0) * u.y));
+    let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+    let h = vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+    return normalize(2.0 * dot(v, h) * h - v);
+}
+
+fn stratified(i: u32, j: u32) -> vec2<f32> {
+    return (vec2<f32>(f32(i), f32(j)) + 0.5) / f32(SAMPLE_COUNT);
+}
+
+/// Cubed difference between the BRDF and the distribution, sampling both.
+fn fit_error(ltc: Ltc, v: vec3<f32>, alpha: f32) -> f32 {
+    var error = 0.0;
+    for (var j = 0u; j < SAMPLE_COUNT; j++) {
+        for (var i = 0u; i < SAMPLE_COUNT; i++) {
+            let u = stratified(i, j);
+            let directions = array<vec3<f32>, 2>(ltc_sample(ltc, u), brdf_sample(v, alpha, u));
+            for (var k = 0u; k < 2u; k++) {
+                let lobe = brdf(v, directions[k], alpha);
+                let density = ltc_density(ltc, directions[k]);
+                let difference = abs(lobe.x - ltc.magnitude * density);
+                if density + lobe.y > 0.0 {
+                    error += difference * difference * difference / (density + lobe.y);
+                }
+            }
+        }
+    }
+    return error / f32(SAMPLE_COUNT * SAMPLE_COUNT);
+}
+
+/// Fits a linearly transformed cosine to the GGX lobe, indexed by roughness horizontally and
+/// `sqrt(1 - n·v)` vertically. Stores the inverse matrix normalized by its middle element,
+/// and the BRDF magnitude split into scale and bias for Schlick's Fresnel.
+@compute @workgroup_size(8, 8, 1)
+fn fit(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(ltc_matrix);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+    let roughness = f32(id.x) / f32(size.x - 1u);
+    let alpha = max(roughness * roughness, 1e-3);
+    let t = f32(id.y) / f32(size.y - 1u);
+    let n_dot_v = max(1.0 - t * t, 1e-3);
+    let v = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+
+    var scale = 0.0;
+    var bias = 0.0;
+    var average = vec3<f32>(0.0);
+    for (var j = 0u; j < SAMPLE_COUNT; j++) {
+        for (var i = 0u; i < SAMPLE_COUNT; i++) {
+            let l = brdf_sample(v, alpha, stratified(i, j));
+            let lobe = brdf(v, l, alpha);
+            if lobe.y > 0.0 {
+                let weight = lobe.x / lobe.y;
+                let fc = pow(1.0 - dot(v, normalize(v + l)), 5.0);
+                scale += (1.0 - fc) * weight;
+                bias += fc * weight;
+                average += l * weight;
+            }
+        }
+    }
+    scale /= f32(SAMPLE_COUNT * SAMPLE_COUNT);
+    bias /= f32(SAMPLE_COUNT * SAMPLE_COUNT);
+    let magnitude = scale + bias;
+
+    // The lobe is symmetric about the plane of incidence, so its average direction lies within.
+    let z = normalize(vec3<f32>(average.x, 0.0, average.z));
+    let x = vec3<f32>(z.z, 0.0, -z.x);
+
+    // Nelder-Mead over m11, m22 and m13, starting from an isotropic lobe of the GGX width.
+    var simplex = array<vec3<f32>, 4>(
+        vec3<f32>(alpha, alpha, 0.0),
+        vec3<f32>(1.5 * alpha, alpha, 0.0),
+        vec3<f32>(alpha, 1.5 * alpha, 0.0),
+        vec3<f32>(alpha, alpha, 0.5 * alpha),
+    );
+    var errors: array<f32, 4>;
+    for (var i = 0u; i < 4u; i++) {
+        errors[i] = fit_error(ltc_new(x, z, simplex[i], magnitude), v, alpha);
+    }
+    for (var iteration = 0u; iteration < FIT_ITERATIONS; iteration++) {
+        // Insertion sort from best to worst.
+        for (var i = 1u; i < 4u; i++) {
+            for (var k = i; k > 0u && errors[k] < errors[k - 1u]; k--) {
+                let vertex = simplex[k];
+                simplex[k] = simplex[k - 1u];
+                simplex[k - 1u] = vertex;
+                let error = errors[k];
+                errors[k] = errors[k - 1u];
+                errors[k - 1u] = error;
+            }
+        }
+
+        let centroid = (simplex[0] + simplex[1] + simplex[2]) / 3.0;
+        let reflected = centroid + (centroid - simplex[3]);
+        let reflected_error = fit_error(ltc_new(x, z, reflected, magnitude), v, alpha);
+        if reflected_error < errors[0] {
+            let expanded = centroid + 2.0 * (centroid - simplex[3]);
+            let expanded_error = fit_error(ltc_new(x, z, expanded, magnitude), v, alpha);
+            if expanded_error < reflected_error {
+                simplex[3] = expanded;
+                errors[3] = expanded_error;
+            } else {
+                simplex[3] = reflected;
+                errors[3] = reflected_error;
+            }
+        } else if reflected_error < errors[2] {
+            simplex[3] = reflected;
+            errors[3] = reflected_error;
+        } else {
+            var contracted = centroid + 0.5 * (simplex[3] - centroid);
+            if reflected_error < errors[3] {
+                contracted = centroid + 0.5 * (reflected - centroid);
+            }
+            let contracted_error = fit_error(ltc_new(x, z, contracted, magnitude), v, alpha);
+            if contracted_error < min(errors[3], reflected_error) {
+                simplex[3] = contracted;
+                errors[3] = contracted_error;
+            } else {
+                for (var i = 1u; i < 4u; i++) {
+                    simplex[i] = simplex[0] + 0.5 * (simplex[i] - simplex[0]);
+                    errors[i] = fit_error(ltc_new(x, z, simplex[i], magnitude), v, alpha);
+                }
+            }
+        }
+    }
+    var best = 0u;
+    for (var i = 1u; i < 4u; i++) {
+        if errors[i] < errors[best] {
+            best = i;
+        }
+    }
+
+    let inverse = ltc_new(x, z, simplex[best], magnitude).inverse;
+    let normalized = inverse * (1.0 / inverse[1][1]);
+    textureStore(ltc_matrix, id.xy, vec4<f32>(normalized[0][0], normalized[2][0], normalized[0][2], normalized[2][2]));
+    textureStore(ltc_amplitude, id.xy, vec4<f32>(scale, bias, 0.0, 0.0));
+}
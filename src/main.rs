@@ -1,25 +1,735 @@
+mod animation;
+mod arena;
+mod assets;
+mod bindless;
 mod camera;
+mod cluster;
+mod console;
+mod culling;
+mod debug_draw;
+mod deferred;
+mod deformation;
+mod dynamic_resolution;
+mod environment;
+mod frame_limiter;
+mod gizmo;
+mod history;
+mod hiz;
+mod ibl;
+mod input;
+mod light;
+mod lightmap;
+mod loader;
+mod lod;
+mod ltc;
+mod material;
+mod mesh;
+#[cfg(feature = "physics")]
+mod physics;
+mod pipeline_builder;
+mod point_cloud;
+mod post;
+mod prefab;
+mod profiler;
+mod ray_shadows;
 mod render;
+mod scene;
+mod scene_file;
+mod screenshot;
+mod script;
+mod shadow;
+mod stats;
+mod texture;
+mod timeline;
+mod ui;
+mod velocity;
+mod world;
 
-use std::{cell::OnceCell, sync::Arc, time::Instant};
+use std::{
+    cell::OnceCell,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use assets::{Assets, Handle, LoadedModel};
 use camera::Camera;
+use cgmath::{Deg, Matrix4, SquareMatrix};
+use frame_limiter::FrameLimiter;
+use input::Action;
+use loader::Model;
+use prefab::{Prefab, PrefabId};
 use render::Renderer;
+use scene::Scene;
+use scene_file::SceneFile;
+use script::Script;
+use stats::GpuStats;
+use ui::{
+    show_background, show_frame_limiter, show_gpu_stats, show_gpu_timings, show_navigation,
+    show_render_settings, Ui,
+};
 use winit::{
     application::ApplicationHandler,
-    event::{MouseScrollDelta, WindowEvent},
-    event_loop::{ActiveEventLoop, EventLoop},
+    dpi::PhysicalPosition,
+    event::{ElementState, KeyEvent, Modifiers, MouseButton, MouseScrollDelta, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::PhysicalKey,
     platform::macos::WindowAttributesExtMacOS,
     window::{Window, WindowId},
 };
+use world::{Layers, Light, MaterialRef, MeshRef, Name};
+
+/// Change in the time of day after which the sky is regenerated.
+const SKY_UPDATE_HOURS: f32 = 0.1;
+
+/// Where the scene is saved without `--scene`.
+const DEFAULT_SCENE_FILE: &str = "scene.ron";
+
+/// Step of the animation time per key press.
+const ANIMATION_SCRUB_SECONDS: f32 = 0.1;
+
+/// Interval of the frame rate in the window title.
+const TITLE_UPDATE_SECONDS: f32 = 1.0;
+
+/// Seconds a single step advances while paused.
+const STEP_SECONDS: f32 = 1.0 / 60.0;
+
+/// Frames still rendered on demand after the last change, for temporal anti-aliasing to converge
+/// and for readbacks to arrive.
+const SETTLE_FRAMES: u32 = 16;
+
+/// Interval at which files are checked for changes while rendering on demand is idle.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A scene kept aside while another one is shown, together with its view and models. Scenes are
+/// only read once first shown, and their resources are unloaded from the GPU while hidden.
+#[derive(Default)]
+struct Stage {
+    scene: Scene,
+    camera: Camera,
+    camera_smoothed: Camera,
+    models: Vec<PathBuf>,
+    loaded_models: Vec<(Handle<Model>, Option<Model>)>,
+    model_prefabs: Vec<Option<PrefabId>>,
+    scene_file: Option<PathBuf>,
+    saved_scene: Option<SceneFile>,
+    script: Option<Script>,
+    #[cfg(feature = "physics")]
+    physics: physics::Physics,
+    ready: bool,
+    /// Whether the scene has been shown, which starts reading its models.
+    started: bool,
+    /// Whether models were read while the scene was hidden, to be combined once shown.
+    changed: bool,
+}
 
 #[derive(Default)]
 struct App {
     window: OnceCell<Arc<Window>>,
     renderer: OnceCell<Renderer>,
+    ui: OnceCell<Ui>,
     camera_smoothed: Camera,
     camera: Camera,
     last_render_time: Option<Instant>,
+    environment: Option<PathBuf>,
+    /// Directory to load baked lightmaps from.
+    lightmaps: Option<PathBuf>,
+    /// Directory to bake lightmaps into before exiting.
+    bake_lightmaps: Option<PathBuf>,
+    /// File to bundle the models into before exiting.
+    bundle: Option<PathBuf>,
+    /// Merges the static objects of each scene once loaded.
+    batch_static: bool,
+    /// `.cube` lookup table to grade the image with.
+    lut: Option<PathBuf>,
+    /// Models shown together in place of the demo objects.
+    models: Vec<PathBuf>,
+    /// Unit of the models whose format has none.
+    unit: Option<loader::Unit>,
+    /// The latest version of each model, once read.
+    loaded_models: Vec<(Handle<Model>, Option<Model>)>,
+    /// Prefab of each model, once read.
+    model_prefabs: Vec<Option<PrefabId>>,
+    /// Instances of each model laid out in a grid, the original included.
+    instances: usize,
+    scene: Scene,
+    assets: Assets,
+    /// Time of day the sky was last generated for.
+    sky_time: Option<f32>,
+    /// Name of the object to attach the orbit camera to once loaded.
+    follow: Option<String>,
+    /// File the arrangement is saved to and loaded from.
+    scene_file: Option<PathBuf>,
+    /// Arrangement applied once the models are loaded.
+    saved_scene: Option<SceneFile>,
+    /// Rhai script run once the models are loaded.
+    script_path: Option<PathBuf>,
+    script: Option<Script>,
+    #[cfg(feature = "physics")]
+    physics: physics::Physics,
+    /// Whether the models have been read and the scene arranged, see `scene_loaded`.
+    ready: bool,
+    /// Scenes switched to by number. The one shown is swapped out of its slot.
+    stages: Vec<Stage>,
+    /// Index of the scene shown.
+    stage: usize,
+    /// Shows the frame rate, resolution and GPU in the window title.
+    title_stats: bool,
+    /// Frames rendered since the title was last updated.
+    title_frames: u32,
+    title_updated: Option<Instant>,
+    frame_limiter: FrameLimiter,
+    /// Renders as fast as possible without vsync, showing the frame rate in the title.
+    benchmark: bool,
+    /// Renders only after input or while something moves, rather than continuously.
+    on_demand: bool,
+    /// Frames left to render on demand, see `SETTLE_FRAMES`.
+    settle_frames: u32,
+    /// Orbits independently of the main camera, whose culling and shadows it looks at.
+    debug_camera: Option<Camera>,
+    /// Freezes animations, lights, the day cycle, scripts and physics, but not the camera.
+    paused: bool,
+    /// Whether the next frame advances while paused.
+    step: bool,
+    /// Last position of the mouse cursor within the window.
+    cursor: PhysicalPosition<f64>,
+    modifiers: Modifiers,
+}
+
+impl App {
+    /// Takes in models read by the asset manager, reframing the view when new ones arrived.
+    fn models_loaded(&mut self, loaded: Vec<LoadedModel>) {
+        let mut changed = false;
+        let mut added = false;
+        let mut hidden = false;
+        let mut reloaded = false;
+        for loaded in loaded {
+            match loaded.model {
+                Ok(model) => {
+                    if let Some((_, slot)) = self
+                        .loaded_models
+                        .iter_mut()
+                        .find(|(handle, _)| *handle == loaded.handle)
+                    {
+                        *slot = Some(model);
+                        changed = true;
+                        added |= !loaded.reload;
+                        reloaded |= loaded.reload;
+                    } else if let Some(stage) = self.stages.iter_mut().find(|stage| {
+                        stage
+                            .loaded_models
+                            .iter()
+                            .any(|(handle, _)| *handle == loaded.handle)
+                    }) {
+                        for (handle, slot) in &mut stage.loaded_models {
+                            if *handle == loaded.handle {
+                                *slot = Some(model);
+                                break;
+                            }
+                        }
+                        stage.changed = true;
+                        hidden = true;
+                    }
+                }
+                Err(error) => println!(
+                    "Cannot load model {}: {error}",
+                    self.assets.path(loaded.handle).display()
+                ),
+            }
+        }
+        if changed {
+            self.combine_models();
+        }
+        if added {
+            // Look through the first authored camera, if there is one.
+            self.scene.camera = (!self.scene.cameras.is_empty()).then_some(0);
+            let radius = self.scene.bounding_radius(self.renderer.get().unwrap());
+            if radius > 0.0 {
+                self.camera.frame(radius);
+                self.camera_smoothed.radius = self.camera.radius;
+            }
+        }
+        // Hidden scenes keep their models off the GPU, and reloaded models free their previous
+        // version.
+        if hidden || reloaded {
+            self.renderer.get_mut().unwrap().unload_unused(&self.scene);
+        }
+    }
+
+    /// Replaces the demo objects with the models read so far, in the order they were given,
+    /// adopting their cameras and lights. Animations keep playing where they were, and instances
+    /// of the models' prefabs are updated in place.
+    fn combine_models(&mut self) {
+        let world = &mut self.scene.world;
+        let previous: Vec<_> = world
+            .renderables()
+            .map(|object| object.entity)
+            .filter(|&entity| world.instances.get(entity).is_none())
+            .collect();
+        for &entity in &previous {
+            world.despawn(entity);
+        }
+
+        let mut spawned = Vec::new();
+        let mut point_clouds = Vec::new();
+        let mut animations = Vec::new();
+        let mut cameras = Vec::new();
+        let mut directional_light = None;
+        let mut lights = Vec::new();
+        self.model_prefabs.resize(self.loaded_models.len(), None);
+        for ((handle, model), prefab) in self.loaded_models.iter().zip(&mut self.model_prefabs) {
+            let Some(model) = model else {
+                continue;
+            };
+            let stem = self
+                .assets
+                .path(*handle)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let model_prefab = Prefab {
+                name: stem.clone(),
+                objects: model.objects.clone(),
+            };
+            match prefab {
+                Some(prefab) => self.scene.prefabs[prefab.0] = model_prefab,
+                None => *prefab = Some(self.scene.add_prefab(model_prefab)),
+            }
+            // The first model is placed until another prefab is registered.
+            self.scene.prefab = self.scene.prefab.or(*prefab);
+            let world = &mut self.scene.world;
+            let entities: Vec<_> = model
+                .objects
+                .iter()
+                .enumerate()
+                .map(|(index, object)| {
+                    let entity = world.spawn_object(object);
+                    // Named after the file for formats without names.
+                    if object.name.is_empty() {
+                        world.names.insert(entity, Name(format!("{stem} {index}")));
+                    }
+                    entity
+                })
+                .collect();
+            point_clouds.extend_from_slice(&model.point_clouds);
+            if let Some(animation) = &model.animation {
+                let mut animation = animation.clone();
+                for (entity, _) in &mut animation.attachments {
+                    *entity = entities[entity.0];
+                }
+                animations.push(animation);
+            }
+            spawned.extend(entities);
+            cameras.extend_from_slice(&model.cameras);
+            directional_light = directional_light.or(model.directional_light);
+            lights.extend(model.point_lights.iter().copied().map(Light::Point));
+            lights.extend(model.spot_lights.iter().copied().map(Light::Spot));
+        }
+
+        let world = &mut self.scene.world;
+        let instances: Vec<_> = world
+            .instances
+            .iter()
+            .map(|(entity, instance)| (entity, *instance))
+            .collect();
+        for (entity, instance) in instances {
+            match self.scene.prefabs[instance.prefab.0]
+                .objects
+                .get(instance.object)
+            {
+                Some(object) => {
+                    world.meshes.insert(entity, MeshRef(object.mesh));
+                    world.materials.insert(entity, MaterialRef(object.material));
+                }
+                None => world.despawn(entity),
+            }
+        }
+
+        // Edits cannot be undone once their objects are replaced.
+        self.scene.history.clear();
+        // Objects are matched by their order, as they are replaced by new versions. Instances
+        // stay.
+        let replacement = |entity| {
+            if world.instances.get(entity).is_some() {
+                return Some(entity);
+            }
+            let index = previous.iter().position(|&previous| previous == entity)?;
+            spawned.get(index).copied()
+        };
+        self.scene.selected = self.scene.selected.and_then(replacement);
+        self.scene.camera_parent = self.scene.camera_parent.and_then(replacement);
+        // Authored lights replace the demo's.
+        if !lights.is_empty() {
+            let previous: Vec<_> = world.lights.iter().map(|(entity, _)| entity).collect();
+            for entity in previous {
+                world.despawn(entity);
+            }
+            for light in lights {
+                world.spawn_light(light);
+            }
+        }
+        self.scene.point_clouds = point_clouds;
+        let previous = std::mem::replace(&mut self.scene.animations, animations);
+        for (animation, previous) in self.scene.animations.iter_mut().zip(previous) {
+            if previous.clip < animation.clips.len() {
+                animation.clip = previous.clip;
+                animation.time = previous.time;
+                animation.playing = previous.playing;
+            }
+        }
+        self.scene.cameras = cameras;
+        if self
+            .scene
+            .camera
+            .is_some_and(|camera| camera >= self.scene.cameras.len())
+        {
+            self.scene.camera = None;
+        }
+        if let Some(light) = directional_light {
+            self.scene.light = light;
+        }
+        self.scene.update_animation(0.0);
+    }
+
+    /// Reads the arrangement to apply once the models are loaded, and the models it lists.
+    fn read_scene_file(&mut self) {
+        // A scene file which does not exist yet is only saved to.
+        if let Some(path) = self.scene_file.as_ref().filter(|path| path.exists()) {
+            match SceneFile::read(path) {
+                Ok(saved) => {
+                    self.models.extend_from_slice(&saved.models);
+                    self.saved_scene = Some(saved);
+                }
+                Err(error) => println!("Cannot load scene {}: {error}", path.display()),
+            }
+        }
+    }
+
+    /// Shows the demo scene and starts reading the models, which replace its objects once read.
+    fn start_scene(&mut self) {
+        self.scene = Scene::demo(self.renderer.get_mut().unwrap());
+        for path in &self.models {
+            let handle = self.assets.load_model(path, self.unit);
+            self.loaded_models.push((handle, None));
+        }
+    }
+
+    /// Bakes or loads the lightmaps, which need the final scene, or writes the bundle. Runs once
+    /// per scene, and the options given for the first scene's models only apply to it.
+    fn scene_loaded(&mut self, event_loop: &ActiveEventLoop) {
+        if self.ready {
+            return;
+        }
+        self.ready = true;
+        let renderer = self.renderer.get_mut().unwrap();
+        for prefab in self.model_prefabs.iter().flatten() {
+            let spacing = 2.0 * self.scene.prefabs[prefab.0].radius(renderer);
+            for offset in prefab::grid(self.instances, spacing).skip(1) {
+                self.scene
+                    .instantiate(*prefab, Matrix4::from_translation(offset));
+            }
+        }
+        // Laid out as if loaded with the models.
+        self.scene.history.clear();
+        if let Some(saved) = self.saved_scene.take() {
+            saved.apply(&mut self.scene, renderer, &mut self.camera);
+        }
+        if self.batch_static {
+            let merged = self.scene.batch_static(renderer);
+            println!("Batched {merged} static objects");
+        }
+        if self.stage > 0 {
+            return;
+        }
+        if let Some(path) = &self.script_path {
+            self.script = Some(Script::new(path, &mut self.scene));
+        }
+        if let Some(name) = &self.follow {
+            match self.scene.find(name) {
+                Some(entity) => self.scene.camera_parent = Some(entity),
+                None => println!("Cannot find object {name} to follow"),
+            }
+        }
+        if let Some(path) = &self.bundle {
+            let models: Vec<_> = self
+                .loaded_models
+                .iter()
+                .filter_map(|(_, model)| model.as_ref())
+                .collect();
+            match loader::bundle::write(path, renderer, &models) {
+                Ok(()) => println!("Bundled into {}", path.display()),
+                Err(error) => println!("Cannot bundle into {}: {error}", path.display()),
+            }
+            event_loop.exit();
+            return;
+        }
+        if let Some(path) = &self.bake_lightmaps {
+            if let Err(error) = lightmap::bake(renderer, &self.scene, path) {
+                println!("Cannot bake lightmaps into {}: {error}", path.display());
+            }
+            event_loop.exit();
+        }
+        self.load_lightmaps();
+    }
+
+    /// Loads the lightmaps, which are baked for the first scene.
+    fn load_lightmaps(&mut self) {
+        let renderer = self.renderer.get_mut().unwrap();
+        if let Some(path) = self.lightmaps.as_ref().filter(|_| self.stage == 0) {
+            if let Err(error) = renderer.load_lightmaps(path, &self.scene) {
+                println!("Cannot load lightmaps {}: {error}", path.display());
+            }
+        }
+    }
+
+    /// Exchanges the scene shown with the stage's.
+    fn swap_stage(&mut self, stage: &mut Stage) {
+        std::mem::swap(&mut self.scene, &mut stage.scene);
+        std::mem::swap(&mut self.camera, &mut stage.camera);
+        std::mem::swap(&mut self.camera_smoothed, &mut stage.camera_smoothed);
+        std::mem::swap(&mut self.models, &mut stage.models);
+        std::mem::swap(&mut self.loaded_models, &mut stage.loaded_models);
+        std::mem::swap(&mut self.model_prefabs, &mut stage.model_prefabs);
+        std::mem::swap(&mut self.scene_file, &mut stage.scene_file);
+        std::mem::swap(&mut self.saved_scene, &mut stage.saved_scene);
+        std::mem::swap(&mut self.script, &mut stage.script);
+        #[cfg(feature = "physics")]
+        std::mem::swap(&mut self.physics, &mut stage.physics);
+        std::mem::swap(&mut self.ready, &mut stage.ready);
+    }
+
+    /// Shows another scene, which is read the first time. The resources only the previous scene
+    /// uses are unloaded from the GPU, and the new scene's uploaded again as it is drawn.
+    fn switch_scene(&mut self, index: usize, event_loop: &ActiveEventLoop) {
+        if index == self.stage {
+            return;
+        }
+        if index >= self.stages.len() {
+            println!("No scene {}", index + 1);
+            return;
+        }
+        self.scene.release_gizmo();
+        let mut stages = std::mem::take(&mut self.stages);
+        self.swap_stage(&mut stages[self.stage]);
+        stages[self.stage].started = true;
+        self.swap_stage(&mut stages[index]);
+        let started = stages[index].started;
+        let changed = std::mem::take(&mut stages[index].changed);
+        self.stages = stages;
+        self.stage = index;
+
+        // The sky follows the scene's time of day.
+        self.sky_time = None;
+        self.renderer.get_mut().unwrap().clear_lightmaps();
+        if !started {
+            self.read_scene_file();
+            self.start_scene();
+        } else if changed {
+            self.combine_models();
+        }
+        if self.ready {
+            self.load_lightmaps();
+        } else if !self.assets.is_loading() {
+            self.scene_loaded(event_loop);
+        }
+        self.renderer.get_mut().unwrap().unload_unused(&self.scene);
+        println!("Scene {}: {}", index + 1, self.scene_path().display());
+    }
+
+    fn scene_path(&self) -> PathBuf {
+        self.scene_file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_SCENE_FILE))
+    }
+
+    fn save_scene(&self) {
+        let path = self.scene_path();
+        let saved = SceneFile::capture(
+            &self.scene,
+            self.renderer.get().unwrap(),
+            &self.camera,
+            &self.models,
+        );
+        match saved.write(&path) {
+            Ok(()) => println!("Saved scene to {}", path.display()),
+            Err(error) => println!("Cannot save scene to {}: {error}", path.display()),
+        }
+    }
+
+    /// Rearranges the scene as saved. Its models are only loaded on startup.
+    fn load_scene(&mut self) {
+        let path = self.scene_path();
+        let saved = match SceneFile::read(&path) {
+            Ok(saved) => saved,
+            Err(error) => {
+                println!("Cannot load scene {}: {error}", path.display());
+                return;
+            }
+        };
+        if saved.models != self.models {
+            println!(
+                "Restart with --scene to load the models of {}",
+                path.display()
+            );
+        }
+        saved.apply(
+            &mut self.scene,
+            self.renderer.get_mut().unwrap(),
+            &mut self.camera,
+        );
+        println!("Loaded scene {}", path.display());
+    }
+
+    /// Updates the window title with the average frame rate once in a while.
+    fn update_title(&mut self) {
+        self.title_frames += 1;
+        let now = Instant::now();
+        let updated = *self.title_updated.get_or_insert(now);
+        let elapsed = (now - updated).as_secs_f32();
+        if elapsed < TITLE_UPDATE_SECONDS {
+            return;
+        }
+        let renderer = self.renderer.get().unwrap();
+        let (width, height) = renderer.render_size();
+        let adapter = renderer.adapter_info();
+        self.window.get().unwrap().set_title(&format!(
+            "{} - {:.0} fps, {width}x{height}, {:?}, {}",
+            env!("CARGO_PKG_NAME"),
+            self.title_frames as f32 / elapsed,
+            adapter.backend,
+            adapter.name
+        ));
+        self.title_frames = 0;
+        self.title_updated = Some(now);
+    }
+
+    /// Renders another frame, unless rendering on demand and nothing changed for a while.
+    fn schedule_redraw(&mut self, changed: bool) {
+        self.settle_frames = if changed {
+            SETTLE_FRAMES
+        } else {
+            self.settle_frames.saturating_sub(1)
+        };
+        if !self.on_demand || self.settle_frames > 0 {
+            if let Some(window) = self.window.get() {
+                window.request_redraw();
+            }
+        } else {
+            // The next frame starts from rest rather than catching up on the idle time.
+            self.last_render_time = None;
+        }
+    }
+
+    /// The view through the active scene camera, or else the orbit camera.
+    fn view(&self) -> Matrix4<f32> {
+        self.scene.camera_view().unwrap_or_else(|| {
+            let parent = self.scene.camera_parent_transform();
+            self.camera_smoothed.matrix() * parent.invert().unwrap_or(Matrix4::identity())
+        })
+    }
+
+    fn perform(&mut self, action: Action, event_loop: &ActiveEventLoop) {
+        let renderer = self.renderer.get_mut().unwrap();
+        match action {
+            Action::CycleAntiAliasing => renderer.cycle_anti_aliasing(),
+            Action::ToggleToon => renderer.toggle_toon(),
+            Action::RotateLight => self.scene.light.rotate(Deg(30.0).into()),
+            Action::ToggleCascadeDebug => renderer.toggle_cascade_debug(),
+            Action::CycleShadowFilter => renderer.cycle_shadow_filter(),
+            Action::ToggleLightGizmos => renderer.toggle_light_gizmos(),
+            Action::ToggleBounds => renderer.toggle_bounds_gizmos(),
+            Action::ToggleDebugCamera => {
+                // Starts out behind the main camera, to see its frustum.
+                self.debug_camera = match self.debug_camera {
+                    Some(_) => None,
+                    None => Some(Camera {
+                        radius: 2.0 * self.camera.radius,
+                        ..self.camera
+                    }),
+                };
+            }
+            Action::TogglePause => {
+                self.paused = !self.paused;
+                println!("{}", if self.paused { "Paused" } else { "Resumed" });
+            }
+            Action::StepFrame => {
+                if self.paused {
+                    self.step = true;
+                } else {
+                    self.paused = true;
+                    println!("Paused");
+                }
+            }
+            Action::ToggleDeferred => renderer.toggle_deferred(),
+            Action::CycleRenderMode => renderer.cycle_render_mode(),
+            Action::Screenshot => renderer.take_screenshot(),
+            Action::ToggleHemisphere => self.scene.toggle_hemisphere(),
+            Action::ToggleDayCycle => self.scene.toggle_day_cycle(),
+            Action::ToggleLightOrbit => self.scene.toggle_light_orbit(),
+            Action::TimeOfDayBackward => self.scene.scrub_time_of_day(-0.5),
+            Action::TimeOfDayForward => self.scene.scrub_time_of_day(0.5),
+            Action::DecreaseShadowBias => renderer.adjust_shadow_bias(-1),
+            Action::IncreaseShadowBias => renderer.adjust_shadow_bias(1),
+            Action::DecreaseShadowNormalOffset => renderer.adjust_shadow_normal_offset(-1),
+            Action::IncreaseShadowNormalOffset => renderer.adjust_shadow_normal_offset(1),
+            Action::ToggleMotionBlur => renderer.toggle_motion_blur(),
+            Action::ToggleBloom => renderer.toggle_bloom(),
+            Action::CycleTonemapper => renderer.cycle_tonemapper(),
+            Action::ToggleAutoExposure => renderer.toggle_auto_exposure(),
+            Action::DecreaseExposure => renderer.adjust_exposure(-1),
+            Action::IncreaseExposure => renderer.adjust_exposure(1),
+            Action::ToggleStylize => renderer.toggle_stylize(),
+            Action::ToggleColorGrading => renderer.toggle_color_grading(),
+            Action::ToggleChromaticAberration => renderer.toggle_chromatic_aberration(),
+            Action::ToggleGammaDebug => renderer.toggle_gamma_debug(),
+            Action::CycleFog => renderer.cycle_fog(),
+            Action::CyclePixelation => renderer.cycle_pixelation(),
+            Action::ToggleSharpening => renderer.toggle_sharpening(),
+            Action::ToggleDithering => renderer.toggle_dithering(),
+            Action::TogglePainterly => renderer.toggle_painterly(),
+            Action::CycleBufferView => renderer.cycle_buffer_view(),
+            Action::ToggleDynamicResolution => renderer.toggle_dynamic_resolution(),
+            Action::ToggleAnimation => self.scene.toggle_animation(),
+            Action::AnimationBackward => self.scene.scrub_animation(-ANIMATION_SCRUB_SECONDS),
+            Action::AnimationForward => self.scene.scrub_animation(ANIMATION_SCRUB_SECONDS),
+            Action::CycleCamera => self.scene.cycle_camera(),
+            Action::ToggleCameraParent => self.scene.toggle_camera_parent(),
+            Action::CycleGizmo => self.scene.gizmo.cycle_mode(),
+            Action::DeleteSelected => self.scene.delete_selected(),
+            Action::DuplicateSelected => self.scene.duplicate_selected(),
+            Action::Undo => self.scene.undo(),
+            Action::Redo => self.scene.redo(),
+            Action::RegisterPrefab => self.scene.register_selected(),
+            Action::PlacePrefab => self.scene.place_prefab(),
+            Action::KeySelected => self.scene.key_selected(),
+            Action::PrintStats => {
+                println!("Scene: {}", renderer.stats());
+                println!("GPU: {}", renderer.gpu_stats());
+            }
+            Action::ToggleUi => {
+                let ui = self.ui.get_mut().unwrap();
+                ui.debug = !ui.debug;
+            }
+            Action::ToggleHelp => {
+                let ui = self.ui.get_mut().unwrap();
+                ui.help = !ui.help;
+            }
+            Action::ToggleConsole => {
+                let ui = self.ui.get_mut().unwrap();
+                ui.console = !ui.console;
+            }
+            Action::SwitchScene(index) => self.switch_scene(index, event_loop),
+            Action::BookmarkView => self.scene.bookmark_view(&self.camera),
+            Action::NextBookmark => self.scene.next_bookmark(&mut self.camera),
+            Action::Spawn(primitive) => {
+                self.scene.spawn_primitive(renderer, primitive);
+            }
+            #[cfg(feature = "physics")]
+            Action::DropCubes => self.physics.drop_cubes(&mut self.scene, renderer),
+            Action::SaveScene => self.save_scene(),
+            Action::LoadScene => self.load_scene(),
+        }
+    }
 }
 
 impl ApplicationHandler for App {
@@ -36,30 +746,152 @@ impl ApplicationHandler for App {
                 .unwrap(),
         );
         self.window.set(window.clone()).unwrap();
+        self.ui.set(Ui::new(&window)).unwrap();
 
-        let renderer = Renderer::new(window);
-        self.renderer
-            .set(futures::executor::block_on(renderer))
-            .unwrap();
+        let mut renderer = futures::executor::block_on(Renderer::new(window));
+        if self.benchmark {
+            let mut settings = renderer.settings();
+            settings.vsync = false;
+            renderer.apply_settings(settings);
+        }
+        if let Some(path) = &self.environment {
+            if let Err(error) = renderer.load_environment(path) {
+                println!("Cannot load environment {}: {error}", path.display());
+            }
+        }
+        if let Some(path) = &self.lut {
+            if let Err(error) = renderer.load_color_lut(path) {
+                println!("Cannot load lookup table {}: {error}", path.display());
+            }
+        }
+        self.renderer.set(renderer).unwrap();
+        self.start_scene();
+        if !self.assets.is_loading() {
+            self.scene_loaded(event_loop);
+        }
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        if !matches!(event, WindowEvent::RedrawRequested) {
+            self.schedule_redraw(true);
+        }
+        // Events taken by the debug UI do not reach the viewer.
+        let window = self.window.get().unwrap();
+        if self.ui.get_mut().unwrap().on_window_event(window, &event) {
+            return;
+        }
         match event {
             WindowEvent::Resized(size) => {
                 self.renderer.get_mut().unwrap().resize(size);
                 self.window.get().unwrap().request_redraw();
             }
             WindowEvent::RedrawRequested => {
+                self.frame_limiter.wait();
+                let _frame = tracing::info_span!("frame").entered();
                 let dt = match self.last_render_time {
                     None => 0.0,
                     Some(t) => (Instant::now() - t).as_secs_f32(),
                 };
                 self.last_render_time = Some(Instant::now());
-                self.camera_smoothed.lerp_exp(&self.camera, 0.9, dt);
 
+                let loaded = self.assets.update(self.renderer.get_mut().unwrap());
+                let mut changed = !loaded.is_empty();
+                let finished =
+                    loaded.iter().any(|loaded| !loaded.reload) && !self.assets.is_loading();
+                self.models_loaded(loaded);
+                if finished {
+                    self.scene_loaded(event_loop);
+                }
+
+                changed |= self.camera_smoothed.lerp_exp(&self.camera, 0.9, dt);
+                let scene_dt = if !self.paused {
+                    dt
+                } else if std::mem::take(&mut self.step) {
+                    STEP_SECONDS
+                } else {
+                    0.0
+                };
+                self.scene.animate_point_lights(scene_dt);
+                self.scene.update_day_cycle(scene_dt);
+                self.scene.update_animation(scene_dt);
+                if let Some(script) = &mut self.script {
+                    script.update(&mut self.scene, scene_dt);
+                }
+                #[cfg(feature = "physics")]
+                self.physics.update(&mut self.scene.world, scene_dt);
+
+                let view = self.view();
                 let renderer = self.renderer.get_mut().unwrap();
-                renderer.render(self.camera_smoothed.matrix());
-                self.window.get().unwrap().request_redraw();
+                let stats = renderer.stats();
+                let stage = self.stage;
+                let paused = self.paused;
+                let view_projection = renderer.view_projection(view, &self.scene);
+                let (shown_view, _) = renderer.shown_view(view, &self.scene);
+                let background = &mut self.scene.background;
+                let frame_limiter = &mut self.frame_limiter;
+                let on_demand = &mut self.on_demand;
+                let mut settings = renderer.settings();
+                let ui = self.ui.get_mut().unwrap();
+                let mut debug = ui.debug;
+                // Reading the reports locks wgpu's registries, so only while shown.
+                let gpu_stats = if debug {
+                    renderer.gpu_stats()
+                } else {
+                    GpuStats::default()
+                };
+                let mut look_from = None;
+                let ui_frame = ui.run(self.window.get().unwrap(), view_projection, |context| {
+                    look_from = show_navigation(context, shown_view);
+                    egui::Window::new("Debug")
+                        .open(&mut debug)
+                        .show(context, |ui| {
+                            ui.label(format!("Scene {}", stage + 1));
+                            ui.label(format!("Frame time: {:.1} ms", 1000.0 * dt));
+                            show_frame_limiter(ui, frame_limiter);
+                            ui.checkbox(on_demand, "Render on demand");
+                            if paused {
+                                ui.label("Paused");
+                            }
+                            ui.label(stats.to_string());
+                            show_render_settings(ui, &mut settings);
+                            show_background(ui, background);
+                            show_gpu_stats(ui, &gpu_stats);
+                            show_gpu_timings(ui, renderer.gpu_timings());
+                        });
+                });
+                ui.debug = debug;
+                renderer.apply_settings(settings);
+                if let Some(direction) = look_from {
+                    let camera = self.debug_camera.as_mut().unwrap_or(&mut self.camera);
+                    camera.look_from(direction);
+                }
+                // Regenerating the sky refilters the image-based lighting, so skip small steps.
+                if let Some(cycle) = self.scene.day_cycle {
+                    if self.environment.is_none()
+                        && self
+                            .sky_time
+                            .is_none_or(|time| (time - cycle.time).abs() >= SKY_UPDATE_HOURS)
+                    {
+                        renderer.set_sky(&self.scene.sky());
+                        self.sky_time = Some(cycle.time);
+                    }
+                }
+                renderer.set_debug_view(self.debug_camera.map(|camera| camera.matrix()));
+                renderer.render(view, &self.scene, &ui_frame, dt);
+                let building = renderer.is_building();
+                if self.title_stats {
+                    self.update_title();
+                }
+                #[cfg(feature = "physics")]
+                let simulating = self.physics.is_active();
+                #[cfg(not(feature = "physics"))]
+                let simulating = false;
+                changed |= self.assets.is_busy()
+                    || building
+                    || self.ui.get().unwrap().wants_repaint()
+                    || !self.paused
+                        && (self.scene.is_animating() || self.script.is_some() || simulating);
+                self.schedule_redraw(changed);
             }
             WindowEvent::CloseRequested => {
                 event_loop.exit();
@@ -68,19 +900,185 @@ impl ApplicationHandler for App {
                 delta: MouseScrollDelta::PixelDelta(delta),
                 ..
             } => {
-                self.camera.yaw += 0.01 * delta.x as f32;
-                self.camera.pitch += 0.01 * delta.y as f32;
+                let camera = self.debug_camera.as_mut().unwrap_or(&mut self.camera);
+                camera.yaw += 0.01 * delta.x as f32;
+                camera.pitch += 0.01 * delta.y as f32;
             }
             WindowEvent::PinchGesture { delta, .. } => {
-                self.camera.radius /= 1.0 + delta as f32;
+                let camera = self.debug_camera.as_mut().unwrap_or(&mut self.camera);
+                camera.radius /= 1.0 + delta as f32;
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = position;
+                if self.scene.gizmo.is_dragging() {
+                    let renderer = self.renderer.get().unwrap();
+                    if let Some(ray) = renderer.ray(
+                        self.view(),
+                        &self.scene,
+                        position.x as f32,
+                        position.y as f32,
+                    ) {
+                        self.scene.gizmo.drag(&mut self.scene.world, ray);
+                    }
+                }
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                let renderer = self.renderer.get().unwrap();
+                let (x, y) = (self.cursor.x as f32, self.cursor.y as f32);
+                let view = self.view();
+                // The gizmo's handles take precedence over the objects behind them, where drawn.
+                let editor = self.scene.layers().intersects(Layers::EDITOR);
+                if let (Some(selected), Some(ray)) = (
+                    self.scene.selected.filter(|_| editor),
+                    renderer.ray(view, &self.scene, x, y),
+                ) {
+                    let (view, projection) = renderer.shown_view(view, &self.scene);
+                    if self
+                        .scene
+                        .gizmo
+                        .grab(&self.scene.world, selected, ray, view, &projection)
+                    {
+                        return;
+                    }
+                }
+                self.scene.selected = renderer.pick(view, &self.scene, x, y);
+                match self.scene.selected {
+                    Some(selected) => {
+                        let world = &self.scene.world;
+                        match world.tags.get(selected) {
+                            Some(tags) => println!(
+                                "Selected: {} ({})",
+                                world.name(selected),
+                                Vec::from_iter(tags.0.iter().map(String::as_str)).join(", ")
+                            ),
+                            None => println!("Selected: {}", world.name(selected)),
+                        }
+                    }
+                    None => println!("Selected: nothing"),
+                }
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } => self.scene.release_gizmo(),
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers;
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                if let Some(action) = input::action(key, self.modifiers.state()) {
+                    self.perform(action, event_loop);
+                }
             }
             _ => {}
         }
     }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        // Idle rendering on demand still notices models changing on disk.
+        if self.on_demand {
+            self.assets.poll_changes();
+            if self.assets.is_busy() {
+                self.schedule_redraw(true);
+            }
+            event_loop
+                .set_control_flow(ControlFlow::WaitUntil(Instant::now() + IDLE_POLL_INTERVAL));
+        } else {
+            event_loop.set_control_flow(ControlFlow::Wait);
+        }
+    }
 }
 
 fn main() {
+    console::init();
+    for binding in input::KEY_BINDINGS {
+        println!("{}: {}", binding.shortcut(), binding.description);
+    }
+    for (input, description) in input::MOUSE_BINDINGS {
+        println!("{input}: {description}");
+    }
+
     let event_loop = EventLoop::new().unwrap();
     let mut app = App::default();
+
+    let mut args = std::env::args().skip(1).peekable();
+    // `bundle <file> <model>...` preprocesses the models for faster loading.
+    if args.next_if_eq("bundle").is_some() {
+        app.bundle = args.next().map(PathBuf::from);
+    }
+    while let Some(arg) = args.next() {
+        if arg == "--environment" {
+            app.environment = args.next().map(PathBuf::from);
+        } else if arg == "--lightmaps" {
+            app.lightmaps = args.next().map(PathBuf::from);
+        } else if arg == "--bake-lightmaps" {
+            app.bake_lightmaps = args.next().map(PathBuf::from);
+        } else if arg == "--lut" {
+            app.lut = args.next().map(PathBuf::from);
+        } else if arg == "--model" {
+            app.models.extend(args.next().map(PathBuf::from));
+        } else if arg == "--follow" {
+            app.follow = args.next();
+        } else if arg == "--scene" {
+            // Further scenes are read once switched to.
+            let path = args.next().map(PathBuf::from);
+            if app.scene_file.is_none() {
+                app.scene_file = path;
+            } else {
+                app.stages.push(Stage {
+                    scene_file: path,
+                    ..Default::default()
+                });
+            }
+        } else if arg == "--script" {
+            app.script_path = args.next().map(PathBuf::from);
+        } else if arg == "--instances" {
+            app.instances = args
+                .next()
+                .and_then(|count| count.parse().ok())
+                .unwrap_or(1);
+        } else if arg == "--batch-static" {
+            app.batch_static = true;
+        } else if arg == "--title-stats" {
+            app.title_stats = true;
+        } else if arg == "--fps-limit" {
+            if let Some(fps) = args.next().and_then(|fps| fps.parse().ok()) {
+                app.frame_limiter.enabled = true;
+                app.frame_limiter.target_fps = fps;
+            }
+        } else if arg == "--on-demand" {
+            app.on_demand = true;
+        } else if arg == "--benchmark" {
+            app.benchmark = true;
+            app.title_stats = true;
+        } else if arg == "--unit" {
+            app.unit = args.next().as_deref().and_then(loader::Unit::parse);
+        } else if !arg.starts_with("--") {
+            app.models.push(PathBuf::from(arg));
+        }
+    }
+
+    if app.benchmark {
+        app.frame_limiter.enabled = false;
+        app.on_demand = false;
+    }
+
+    // The first scene is shown from the start, so its slot stays empty.
+    app.stages.insert(0, Stage::default());
+    app.read_scene_file();
+
     event_loop.run_app(&mut app).unwrap();
 }
@@ -0,0 +1,375 @@
+use cgmath::Vector4;
+use serde::{Deserialize, Serialize};
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::{render::as_byte_slice, texture::TextureId};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AlphaMode {
+    #[default]
+    Opaque,
+    /// Cut out below the alpha cutoff, using alpha-to-coverage when MSAA is enabled.
+    #[allow(dead_code)]
+    Mask,
+    /// Blended over the opaque scene, sorted back to front and without depth writes.
+    Blend,
+}
+
+/// How the base color texture is projected onto surfaces.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum TextureMapping {
+    #[default]
+    Uv,
+    /// World-space projections along the three axes, blended by the surface normal.
+    /// Useful for terrain and meshes without UVs.
+    Triplanar {
+        /// Texture repetitions per world unit.
+        scale: f32,
+        /// Exponent applied to the blend weights; higher values give narrower transitions.
+        sharpness: f32,
+    },
+}
+
+/// How vertex colors combine with the sampled base color texture.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub enum VertexColorBlend {
+    #[default]
+    Multiply,
+    /// Adds the vertex color to the texture color, while alpha is still multiplied.
+    Add,
+    /// Uses the vertex color and ignores the texture.
+    Replace,
+}
+
+/// The lighting model used for a material.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum Shading {
+    #[default]
+    Pbr,
+    /// Cel shading with quantized diffuse bands and a rim highlight.
+    Toon {
+        /// Number of diffuse bands.
+        bands: u32,
+        /// Strength of the rim highlight.
+        rim: f32,
+    },
+    /// Cheap Lambertian diffuse with normalized Blinn-Phong highlights.
+    /// The material's roughness is ignored in favor of the shininess.
+    BlinnPhong { shininess: f32 },
+}
+
+/// The Blinn-Phong exponent whose highlight best matches a GGX lobe of the given roughness.
+pub fn shininess_from_roughness(roughness: f32) -> f32 {
+    let alpha = (roughness * roughness).max(1e-3);
+    2.0 / (alpha * alpha) - 2.0
+}
+
+/// Inverse of [`shininess_from_roughness`].
+pub fn roughness_from_shininess(shininess: f32) -> f32 {
+    (2.0 / (shininess.max(0.0) + 2.0)).sqrt().sqrt()
+}
+
+/// An inverted-hull outline drawn around the silhouette.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Outline {
+    /// Width in pixels.
+    pub width: f32,
+    /// sRGB-encoded, with linear alpha.
+    pub color: Vector4<f32>,
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        Outline {
+            width: 2.0,
+            color: Vector4::new(0.0, 0.0, 0.0, 1.0),
+        }
+    }
+}
+
+/// A pattern generated in the shader in place of the base color texture, for checking UVs and
+/// scale without texture assets.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(dead_code)]
+pub enum Pattern {
+    /// Two-tone checkerboard over the UVs.
+    Checker { cells: f32 },
+    /// U and V as red and green gradients with grid lines.
+    UvGrid { cells: f32 },
+    /// Stripes along the world axes, one color channel per axis.
+    Stripes { scale: f32 },
+    /// Grayscale value noise over world positions.
+    Noise { scale: f32 },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Material {
+    /// Multiplied with the vertex color and the base color texture. sRGB-encoded like a color
+    /// picked in an image editor, with linear alpha.
+    pub base_color: Vector4<f32>,
+    /// Not saved with scenes, which keep the texture the object was loaded with.
+    #[serde(skip)]
+    pub base_color_texture: Option<TextureId>,
+    /// Takes precedence over the base color texture.
+    pub pattern: Option<Pattern>,
+    pub mapping: TextureMapping,
+    pub vertex_color_blend: VertexColorBlend,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub alpha_mode: AlphaMode,
+    /// Alpha below which masked materials are cut out.
+    pub alpha_cutoff: f32,
+    pub shading: Shading,
+    /// Only drawn for opaque and masked materials.
+    pub outline: Option<Outline>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            base_color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            base_color_texture: None,
+            pattern: None,
+            mapping: TextureMapping::Uv,
+            vertex_color_blend: VertexColorBlend::Multiply,
+            metallic: 0.0,
+            roughness: 0.5,
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            shading: Shading::Pbr,
+            outline: None,
+        }
+    }
+}
+
+/// Encodes a linear color in sRGB, for colors from formats which store them linearly.
+pub fn linear_to_srgb(color: Vector4<f32>) -> Vector4<f32> {
+    let encode = |channel: f32| {
+        if channel <= 0.0031308 {
+            channel * 12.92
+        } else {
+            1.055 * channel.powf(1.0 / 2.4) - 0.055
+        }
+    };
+    Vector4::new(encode(color.x), encode(color.y), encode(color.z), color.w)
+}
+
+/// Decodes an sRGB-encoded color for shading, leaving alpha as is.
+pub fn srgb_to_linear(color: Vector4<f32>) -> Vector4<f32> {
+    let decode = |channel: f32| {
+        if channel <= 0.04045 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    Vector4::new(decode(color.x), decode(color.y), decode(color.z), color.w)
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct MaterialUniforms {
+    #[allow(dead_code)]
+    base_color: Vector4<f32>,
+    #[allow(dead_code)]
+    metallic: f32,
+    #[allow(dead_code)]
+    roughness: f32,
+    /// Zero unless the material is masked.
+    #[allow(dead_code)]
+    alpha_cutoff: f32,
+    #[allow(dead_code)]
+    mapping: u32,
+    #[allow(dead_code)]
+    triplanar_scale: f32,
+    #[allow(dead_code)]
+    triplanar_sharpness: f32,
+    #[allow(dead_code)]
+    vertex_color_blend: u32,
+    #[allow(dead_code)]
+    shading: u32,
+    #[allow(dead_code)]
+    toon_bands: u32,
+    #[allow(dead_code)]
+    rim: f32,
+    /// Zero without an outline, in which case the global toon mode uses its default width.
+    #[allow(dead_code)]
+    outline_width: f32,
+    #[allow(dead_code)]
+    pattern: u32,
+    #[allow(dead_code)]
+    outline_color: Vector4<f32>,
+    #[allow(dead_code)]
+    pattern_scale: f32,
+    /// Zero unless the material uses Blinn-Phong shading.
+    #[allow(dead_code)]
+    shininess: f32,
+    /// Index into the array of textures, for bindless materials.
+    #[allow(dead_code)]
+    texture: u32,
+    #[allow(dead_code)]
+    padding: f32,
+}
+
+impl MaterialUniforms {
+    /// For the bindless materials, whose base color texture is the one at the index.
+    pub fn with_texture(material: &Material, texture: u32) -> Self {
+        MaterialUniforms {
+            texture,
+            ..material.into()
+        }
+    }
+}
+
+impl From<&Material> for MaterialUniforms {
+    fn from(material: &Material) -> Self {
+        MaterialUniforms {
+            base_color: srgb_to_linear(material.base_color),
+            metallic: material.metallic,
+            roughness: match material.shading {
+                Shading::BlinnPhong { shininess } => roughness_from_shininess(shininess),
+                _ => material.roughness,
+            },
+            alpha_cutoff: match material.alpha_mode {
+                AlphaMode::Mask => material.alpha_cutoff,
+                _ => 0.0,
+            },
+            mapping: match material.mapping {
+                TextureMapping::Uv => 0,
+                TextureMapping::Triplanar { .. } => 1,
+            },
+            triplanar_scale: match material.mapping {
+                TextureMapping::Triplanar { scale, .. } => scale,
+                _ => 1.0,
+            },
+            triplanar_sharpness: match material.mapping {
+                TextureMapping::Triplanar { sharpness, .. } => sharpness,
+                _ => 1.0,
+            },
+            vertex_color_blend: material.vertex_color_blend as u32,
+            shading: match material.shading {
+                Shading::Pbr => 0,
+                Shading::Toon { .. } => 1,
+                Shading::BlinnPhong { .. } => 2,
+            },
+            toon_bands: match material.shading {
+                Shading::Toon { bands, .. } => bands,
+                _ => 0,
+            },
+            rim: match material.shading {
+                Shading::Toon { rim, .. } => rim,
+                _ => 0.0,
+            },
+            outline_width: material.outline.map_or(0.0, |outline| outline.width),
+            pattern: match material.pattern {
+                None => 0,
+                Some(Pattern::Checker { .. }) => 1,
+                Some(Pattern::UvGrid { .. }) => 2,
+                Some(Pattern::Stripes { .. }) => 3,
+                Some(Pattern::Noise { .. }) => 4,
+            },
+            outline_color: srgb_to_linear(material.outline.unwrap_or_default().color),
+            pattern_scale: match material.pattern {
+                Some(
+                    Pattern::Checker { cells: scale }
+                    | Pattern::UvGrid { cells: scale }
+                    | Pattern::Stripes { scale }
+                    | Pattern::Noise { scale },
+                ) => scale,
+                None => 1.0,
+            },
+            shininess: match material.shading {
+                Shading::BlinnPhong { shininess } => shininess,
+                _ => 0.0,
+            },
+            texture: 0,
+            padding: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MaterialId(pub usize);
+
+/// A material together with its GPU uniforms.
+#[derive(Debug)]
+pub struct MaterialBinding {
+    pub material: Material,
+    /// None while unloaded, so that it does not keep its texture alive.
+    pub bind_group: Option<BindGroup>,
+}
+
+impl MaterialBinding {
+    pub fn bind_group_layout(device: &Device) -> BindGroupLayout {
+        device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        })
+    }
+
+    pub fn new(
+        device: &Device,
+        layout: &BindGroupLayout,
+        material: &Material,
+        base_color_texture: &TextureView,
+        sampler: &Sampler,
+    ) -> Self {
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[MaterialUniforms::from(material)]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(base_color_texture),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(sampler),
+                },
+            ],
+        });
+
+        MaterialBinding {
+            material: *material,
+            bind_group: Some(bind_group),
+        }
+    }
+}
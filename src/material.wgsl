@@ -0,0 +1,12 @@
+/// Bound per draw, on adapters without large arrays of textures.
+@group(2) @binding(0) var<uniform> material_uniforms: Material;
+@group(2) @binding(1) var base_color_texture: texture_2d<f32>;
+@group(2) @binding(2) var material_sampler: sampler;
+
+fn material() -> Material {
+    return material_uniforms;
+}
+
+fn sample_material_texture(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(base_color_texture, material_sampler, uv);
+}
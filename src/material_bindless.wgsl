@@ -0,0 +1,12 @@
+/// All materials and their textures, bound once and indexed by the object drawn.
+@group(2) @binding(0) var<storage, read> materials: array<Material>;
+@group(2) @binding(1) var textures: binding_array<texture_2d<f32>>;
+@group(2) @binding(2) var material_sampler: sampler;
+
+fn material() -> Material {
+    return materials[object.material];
+}
+
+fn sample_material_texture(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(textures[materials[object.material].texture], material_sampler, uv);
+}
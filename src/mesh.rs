@@ -0,0 +1,449 @@
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix, Matrix3, Matrix4, Point3, SquareMatrix, Vector2, Vector3,
+    Vector4, Zero,
+};
+use wgpu::*;
+
+use crate::{
+    arena::{Allocation, MeshArena},
+    lod::{self, Lod},
+};
+
+#[derive(Debug, Copy, Clone)]
+pub struct Vertex {
+    pub position: Vector3<f32>,
+    #[allow(dead_code)]
+    pub normal: Vector3<f32>,
+    /// sRGB-encoded, with linear alpha.
+    #[allow(dead_code)]
+    pub color: Vector4<f32>,
+    #[allow(dead_code)]
+    pub uv: Vector2<f32>,
+    /// Second UV set, which must not overlap, for addressing the baked lightmap.
+    pub lightmap_uv: Vector2<f32>,
+}
+
+impl Vertex {
+    pub const LAYOUT: VertexBufferLayout<'static> = VertexBufferLayout {
+        array_stride: std::mem::size_of::<Vertex>() as BufferAddress,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &vertex_attr_array![
+            0 => Float32x3,
+            1 => Float32x3,
+            2 => Float32x4,
+            3 => Float32x2,
+            4 => Float32x2,
+        ],
+    };
+}
+
+/// Möller-Trumbore intersection, returning the distance and whether the front face was hit.
+pub fn intersect_triangle(
+    a: Vector3<f32>,
+    b: Vector3<f32>,
+    c: Vector3<f32>,
+    origin: Vector3<f32>,
+    direction: Vector3<f32>,
+) -> Option<(f32, bool)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < 1e-9 {
+        return None;
+    }
+    let offset = origin - a;
+    let u = offset.dot(p) / determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = offset.cross(edge1);
+    let v = direction.dot(q) / determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(q) / determinant;
+    (t > 0.0).then_some((t, determinant > 0.0))
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Bounds {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Bounds {
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        std::array::from_fn(|corner| {
+            Point3::new(
+                if corner & 1 == 0 {
+                    self.min.x
+                } else {
+                    self.max.x
+                },
+                if corner & 2 == 0 {
+                    self.min.y
+                } else {
+                    self.max.y
+                },
+                if corner & 4 == 0 {
+                    self.min.z
+                } else {
+                    self.max.z
+                },
+            )
+        })
+    }
+}
+
+/// CPU-side triangle mesh.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn bounds(&self) -> Bounds {
+        let mut min = Point3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut max = Point3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for vertex in &self.vertices {
+            min = Point3::new(
+                min.x.min(vertex.position.x),
+                min.y.min(vertex.position.y),
+                min.z.min(vertex.position.z),
+            );
+            max = Point3::new(
+                max.x.max(vertex.position.x),
+                max.y.max(vertex.position.y),
+                max.z.max(vertex.position.z),
+            );
+        }
+        Bounds { min, max }
+    }
+
+    /// Distance along the ray to the closest triangle it hits from either side, in units of the
+    /// direction's length.
+    pub fn raycast(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        self.indices
+            .chunks_exact(3)
+            .filter_map(|indices| {
+                let [a, b, c] = <[u32; 3]>::try_from(indices)
+                    .unwrap()
+                    .map(|index| self.vertices[index as usize].position);
+                intersect_triangle(a, b, c, origin, direction).map(|(distance, _)| distance)
+            })
+            .min_by(f32::total_cmp)
+    }
+
+    /// Adds the other mesh's triangles, moved by the transform. Normals are transformed by its
+    /// inverse transpose, and mirroring transforms flip the winding to keep the front faces.
+    pub fn append(&mut self, other: &MeshData, transform: Matrix4<f32>) {
+        let linear = Matrix3::from_cols(
+            transform.x.truncate(),
+            transform.y.truncate(),
+            transform.z.truncate(),
+        );
+        let normal_matrix = linear.invert().unwrap_or(Matrix3::identity()).transpose();
+        let base = self.vertices.len() as u32;
+        self.vertices
+            .extend(other.vertices.iter().map(|vertex| Vertex {
+                position: (transform * vertex.position.extend(1.0)).truncate(),
+                normal: (normal_matrix * vertex.normal).normalize(),
+                ..*vertex
+            }));
+        let mirrored = linear.determinant() < 0.0;
+        for triangle in other.indices.chunks_exact(3) {
+            if mirrored {
+                self.indices
+                    .extend([triangle[0], triangle[2], triangle[1]].map(|index| base + index));
+            } else {
+                self.indices
+                    .extend(triangle.iter().map(|index| base + index));
+            }
+        }
+    }
+
+    /// Replaces the normals by the area-weighted average of the adjacent triangles' normals, for
+    /// meshes imported without them.
+    pub fn compute_normals(&mut self) {
+        for vertex in &mut self.vertices {
+            vertex.normal = Vector3::zero();
+        }
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| self.vertices[triangle[i] as usize].position);
+            // The cross product's length is twice the area.
+            let normal = (b - a).cross(c - a);
+            for &index in triangle {
+                self.vertices[index as usize].normal += normal;
+            }
+        }
+        for vertex in &mut self.vertices {
+            if vertex.normal.magnitude2() > 0.0 {
+                vertex.normal = vertex.normal.normalize();
+            }
+        }
+    }
+
+    /// A cube spanning [-1, 1]³ with one color per face, whose faces are laid out in a 3×2 grid
+    /// in the lightmap.
+    pub fn cube() -> Self {
+        let faces = [
+            // Bottom
+            (
+                Vector3::new(0.0, 0.0, -1.0),
+                Vector4::new(1.0, 0.0, 0.0, 1.0),
+                [
+                    Vector3::new(-1.0, -1.0, -1.0),
+                    Vector3::new(-1.0, 1.0, -1.0),
+                    Vector3::new(1.0, -1.0, -1.0),
+                    Vector3::new(1.0, 1.0, -1.0),
+                ],
+            ),
+            // Top
+            (
+                Vector3::new(0.0, 0.0, 1.0),
+                Vector4::new(0.0, 1.0, 0.0, 1.0),
+                [
+                    Vector3::new(-1.0, -1.0, 1.0),
+                    Vector3::new(1.0, -1.0, 1.0),
+                    Vector3::new(-1.0, 1.0, 1.0),
+                    Vector3::new(1.0, 1.0, 1.0),
+                ],
+            ),
+            // Front
+            (
+                Vector3::new(0.0, -1.0, 0.0),
+                Vector4::new(0.0, 0.0, 1.0, 1.0),
+                [
+                    Vector3::new(-1.0, -1.0, -1.0),
+                    Vector3::new(1.0, -1.0, -1.0),
+                    Vector3::new(-1.0, -1.0, 1.0),
+                    Vector3::new(1.0, -1.0, 1.0),
+                ],
+            ),
+            // Back
+            (
+                Vector3::new(0.0, 1.0, 0.0),
+                Vector4::new(1.0, 1.0, 0.0, 1.0),
+                [
+                    Vector3::new(-1.0, 1.0, -1.0),
+                    Vector3::new(-1.0, 1.0, 1.0),
+                    Vector3::new(1.0, 1.0, -1.0),
+                    Vector3::new(1.0, 1.0, 1.0),
+                ],
+            ),
+            // Left
+            (
+                Vector3::new(-1.0, 0.0, 0.0),
+                Vector4::new(1.0, 0.0, 1.0, 1.0),
+                [
+                    Vector3::new(-1.0, -1.0, -1.0),
+                    Vector3::new(-1.0, -1.0, 1.0),
+                    Vector3::new(-1.0, 1.0, -1.0),
+                    Vector3::new(-1.0, 1.0, 1.0),
+                ],
+            ),
+            // Right
+            (
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector4::new(0.0, 1.0, 1.0, 1.0),
+                [
+                    Vector3::new(1.0, -1.0, -1.0),
+                    Vector3::new(1.0, 1.0, -1.0),
+                    Vector3::new(1.0, -1.0, 1.0),
+                    Vector3::new(1.0, 1.0, 1.0),
+                ],
+            ),
+        ];
+
+        let mut mesh = MeshData::default();
+        for (face, (normal, color, corners)) in faces.into_iter().enumerate() {
+            let cell = Vector2::new((face % 3) as f32, (face / 3) as f32);
+            let base = mesh.vertices.len() as u32;
+            let uvs = [
+                Vector2::new(0.0, 1.0),
+                Vector2::new(1.0, 1.0),
+                Vector2::new(0.0, 0.0),
+                Vector2::new(1.0, 0.0),
+            ];
+            mesh.vertices
+                .extend(corners.iter().zip(uvs).map(|(&position, uv)| {
+                    Vertex {
+                        position,
+                        normal,
+                        color,
+                        uv,
+                        // Margins keep the bilinear footprints of neighboring faces apart.
+                        lightmap_uv: (cell + Vector2::new(0.05, 0.05) + 0.9 * uv)
+                            .zip(Vector2::new(3.0, 2.0), |a, b| a / b),
+                    }
+                }));
+            mesh.indices
+                .extend([0, 1, 2, 3, 2, 1].map(|index| base + index));
+        }
+        mesh
+    }
+
+    /// A white UV sphere of radius 1.
+    pub fn sphere(segments: u32, rings: u32) -> Self {
+        let mut mesh = MeshData::default();
+        for ring in 0..=rings {
+            let theta = std::f32::consts::PI * ring as f32 / rings as f32;
+            for segment in 0..=segments {
+                let phi = 2.0 * std::f32::consts::PI * segment as f32 / segments as f32;
+                let normal = Vector3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                let uv = Vector2::new(segment as f32 / segments as f32, ring as f32 / rings as f32);
+                mesh.vertices.push(Vertex {
+                    position: normal,
+                    normal: normal.normalize(),
+                    color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+                    uv,
+                    lightmap_uv: uv,
+                });
+            }
+        }
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let a = ring * (segments + 1) + segment;
+                let b = a + segments + 1;
+                mesh.indices.extend([a, a + 1, b, b, a + 1, b + 1]);
+            }
+        }
+        mesh
+    }
+
+    /// A white square in the XZ plane facing +Y, spanning [-1, 1] with UVs covering [0, 1].
+    pub fn plane() -> Self {
+        let corners = [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)];
+        MeshData {
+            vertices: corners
+                .iter()
+                .map(|&(x, z)| {
+                    let uv = Vector2::new(0.5 * x + 0.5, 0.5 * z + 0.5);
+                    Vertex {
+                        position: Vector3::new(x, 0.0, z),
+                        normal: Vector3::new(0.0, 1.0, 0.0),
+                        color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+                        uv,
+                        lightmap_uv: uv,
+                    }
+                })
+                .collect(),
+            indices: vec![0, 1, 2, 3, 2, 1],
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MeshId(pub usize);
+
+/// A mesh uploaded to the GPU.
+#[derive(Debug)]
+pub struct Mesh {
+    /// Where the vertices and indices are in the arena, or none while unloaded.
+    pub allocation: Option<Allocation>,
+    /// Whether the mesh has a page of its own.
+    dedicated: bool,
+    /// Indices of the full mesh.
+    pub index_count: u32,
+    /// The levels of detail, starting with the full mesh.
+    pub lods: Vec<Lod>,
+    /// The indices of all levels, uploaded after each other.
+    indices: Vec<u32>,
+    pub bounds: Bounds,
+    /// Kept for baking, and to upload the mesh again once unloaded.
+    pub data: MeshData,
+}
+
+impl Mesh {
+    pub fn new(arena: &mut MeshArena, device: &Device, queue: &Queue, data: &MeshData) -> Self {
+        let (lods, indices) = lod::generate(data);
+        Mesh {
+            allocation: Some(arena.allocate(device, queue, &data.vertices, &indices, false)),
+            dedicated: false,
+            index_count: data.indices.len() as u32,
+            lods,
+            indices,
+            bounds: data.bounds(),
+            data: data.clone(),
+        }
+    }
+
+    /// Uploads the mesh again if it has been unloaded.
+    pub fn upload(&mut self, arena: &mut MeshArena, device: &Device, queue: &Queue) {
+        if self.allocation.is_none() {
+            self.allocation = Some(arena.allocate(
+                device,
+                queue,
+                &self.data.vertices,
+                &self.indices,
+                self.dedicated,
+            ));
+        }
+    }
+
+    /// Frees the mesh's place in the arena, returning whether it had one.
+    pub fn unload(&mut self, arena: &mut MeshArena) -> bool {
+        let allocation = self.allocation.take();
+        if let Some(allocation) = &allocation {
+            arena.free(allocation);
+        }
+        allocation.is_some()
+    }
+
+    /// Moves the mesh into a page of its own, so that its vertices start the buffer.
+    pub fn make_dedicated(&mut self, arena: &mut MeshArena, device: &Device, queue: &Queue) {
+        if !self.dedicated {
+            self.dedicated = true;
+            if self.unload(arena) {
+                self.upload(arena, device, queue);
+            }
+        }
+    }
+
+    /// Draws the level of detail into a render pass or bundle with the mesh's page bound, or
+    /// nothing while unloaded.
+    pub fn draw<'a>(
+        &'a self,
+        pass: &mut impl util::RenderEncoder<'a>,
+        level: usize,
+        instances: std::ops::Range<u32>,
+    ) {
+        let Some(allocation) = &self.allocation else {
+            return;
+        };
+        let lod = self.lods[level];
+        let first_index = allocation.first_index + lod.first_index;
+        pass.draw_indexed(
+            first_index..first_index + lod.index_count,
+            allocation.first_vertex as i32,
+            instances,
+        );
+    }
+
+    /// Arguments drawing the level of detail once.
+    pub fn indirect_args(&self, level: usize) -> util::DrawIndexedIndirectArgs {
+        let lod = self.lods[level];
+        util::DrawIndexedIndirectArgs {
+            index_count: lod.index_count,
+            instance_count: 1,
+            first_index: self
+                .allocation
+                .map_or(0, |allocation| allocation.first_index)
+                + lod.first_index,
+            base_vertex: self
+                .allocation
+                .map_or(0, |allocation| allocation.first_vertex as i32),
+            first_instance: 0,
+        }
+    }
+}
@@ -0,0 +1,5 @@
+/// Bound per draw at the object's dynamic offset.
+@group(3) @binding(0) var<uniform> object: Object;
+
+/// Nothing to load, as the drawn object's uniforms are bound.
+fn load_object(instance: u32) {}
@@ -0,0 +1,14 @@
+/// The object uniforms, at the stride of their dynamic offsets.
+struct ObjectSlot {
+    @size(256) object: Object,
+}
+
+/// Bound once and indexed by the instance, on devices which compact the culled draws into
+/// multi-draws of many objects.
+@group(3) @binding(0) var<storage, read> objects: array<ObjectSlot>;
+var<private> object: Object;
+
+/// Loads the uniforms of the object drawn as the instance.
+fn load_object(instance: u32) {
+    object = objects[instance].object;
+}
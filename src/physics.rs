@@ -0,0 +1,291 @@
+use cgmath::{
+    ElementWise, EuclideanSpace, InnerSpace, Matrix4, Quaternion, Rad, Rotation3, Vector3,
+};
+use rapier3d::{
+    na::{self, Translation3, UnitQuaternion},
+    prelude::*,
+};
+
+use crate::{
+    history::Edit,
+    material::{Material, MaterialId},
+    mesh::{MeshData, MeshId},
+    render::Renderer,
+    scene::{Object, Scene},
+    world::{Entity, Transform, World},
+};
+
+/// Seconds simulated per step.
+const STEP: f32 = 1.0 / 60.0;
+/// Steps per frame at most, dropping the rest when frames take too long rather than falling
+/// further behind.
+const MAX_STEPS: usize = 5;
+/// Half the thickness of boxes around flat meshes such as planes.
+const MIN_HALF_EXTENT: f32 = 0.01;
+
+const CUBE_COUNT: usize = 100;
+const CUBE_SIZE: f32 = 0.15;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Shape {
+    /// The mesh's bounding box.
+    Aabb,
+    /// The convex hull of the mesh's vertices, or its bounding box if they span no volume.
+    ConvexHull,
+}
+
+/// An entity simulated as a rigid body.
+#[derive(Debug, Clone)]
+struct Body {
+    entity: Entity,
+    handle: RigidBodyHandle,
+    /// Applied to the collider, as bodies cannot scale.
+    scale: Vector3<f32>,
+    /// The entity's transform as last synchronized, to notice when it is moved by hand.
+    transform: Matrix4<f32>,
+}
+
+/// Rigid bodies stepped at a fixed rate, moving the entities they belong to.
+#[derive(Default)]
+pub struct Physics {
+    pipeline: PhysicsPipeline,
+    parameters: IntegrationParameters,
+    islands: IslandManager,
+    broad_phase: DefaultBroadPhase,
+    narrow_phase: NarrowPhase,
+    bodies: RigidBodySet,
+    colliders: ColliderSet,
+    impulse_joints: ImpulseJointSet,
+    multibody_joints: MultibodyJointSet,
+    ccd: CCDSolver,
+    entities: Vec<Body>,
+    /// Seconds not simulated yet.
+    accumulator: f32,
+    /// Dropped by the demo, added on first use.
+    cube: Option<(MeshId, MaterialId)>,
+    dropped: usize,
+}
+
+impl Physics {
+    /// Simulates the entity from its current transform. Fixed bodies do not move.
+    pub fn add(
+        &mut self,
+        world: &World,
+        renderer: &Renderer,
+        entity: Entity,
+        dynamic: bool,
+        shape: Shape,
+    ) {
+        let (Some(transform), Some(mesh)) =
+            (world.transforms.get(entity), world.meshes.get(entity))
+        else {
+            return;
+        };
+        let (_, _, scale) = transform.decompose();
+        let mesh = renderer.mesh(mesh.0);
+        let aabb = || {
+            let bounds = mesh.bounds;
+            let half = (bounds.max - bounds.min).mul_element_wise(scale) * 0.5;
+            let center = bounds.center().to_vec().mul_element_wise(scale);
+            ColliderBuilder::cuboid(
+                half.x.abs().max(MIN_HALF_EXTENT),
+                half.y.abs().max(MIN_HALF_EXTENT),
+                half.z.abs().max(MIN_HALF_EXTENT),
+            )
+            .translation(vector![center.x, center.y, center.z])
+        };
+        let collider = match shape {
+            Shape::Aabb => aabb(),
+            Shape::ConvexHull => {
+                let points: Vec<_> = mesh
+                    .data
+                    .vertices
+                    .iter()
+                    .map(|vertex| {
+                        let position = vertex.position.mul_element_wise(scale);
+                        point![position.x, position.y, position.z]
+                    })
+                    .collect();
+                ColliderBuilder::convex_hull(&points).unwrap_or_else(aabb)
+            }
+        };
+        let body = if dynamic {
+            RigidBodyBuilder::dynamic()
+        } else {
+            RigidBodyBuilder::fixed()
+        }
+        .position(isometry(transform.0))
+        .build();
+        let handle = self.bodies.insert(body);
+        self.colliders
+            .insert_with_parent(collider.build(), handle, &mut self.bodies);
+        self.entities.push(Body {
+            entity,
+            handle,
+            scale,
+            transform: transform.0,
+        });
+    }
+
+    fn remove(&mut self, handle: RigidBodyHandle) {
+        self.bodies.remove(
+            handle,
+            &mut self.islands,
+            &mut self.colliders,
+            &mut self.impulse_joints,
+            &mut self.multibody_joints,
+            true,
+        );
+    }
+
+    /// Whether any dynamic body is still moving, rather than asleep.
+    pub fn is_active(&self) -> bool {
+        !self.islands.active_dynamic_bodies().is_empty()
+    }
+
+    /// Steps the simulation to the current time and moves the entities of dynamic bodies.
+    /// Bodies of despawned entities are removed, and those of entities moved by hand are placed
+    /// where they are now.
+    pub fn update(&mut self, world: &mut World, dt: f32) {
+        if self.entities.is_empty() {
+            return;
+        }
+        let removed: Vec<_> = self
+            .entities
+            .iter()
+            .filter(|body| !world.is_alive(body.entity))
+            .map(|body| body.handle)
+            .collect();
+        for handle in removed {
+            self.remove(handle);
+        }
+        self.entities.retain(|body| world.is_alive(body.entity));
+
+        for body in &mut self.entities {
+            let Some(transform) = world.transforms.get(body.entity) else {
+                continue;
+            };
+            if transform.0 != body.transform {
+                body.transform = transform.0;
+                let rigid_body = &mut self.bodies[body.handle];
+                rigid_body.set_position(isometry(transform.0), true);
+                rigid_body.set_linvel(Vector::zeros(), true);
+                rigid_body.set_angvel(Vector::zeros(), true);
+            }
+        }
+
+        self.accumulator += dt;
+        let mut steps = 0;
+        while self.accumulator >= STEP {
+            if steps == MAX_STEPS {
+                self.accumulator = 0.0;
+                break;
+            }
+            self.parameters.dt = STEP;
+            self.pipeline.step(
+                &vector![0.0, -9.81, 0.0],
+                &self.parameters,
+                &mut self.islands,
+                &mut self.broad_phase,
+                &mut self.narrow_phase,
+                &mut self.bodies,
+                &mut self.colliders,
+                &mut self.impulse_joints,
+                &mut self.multibody_joints,
+                &mut self.ccd,
+                None,
+                &(),
+                &(),
+            );
+            self.accumulator -= STEP;
+            steps += 1;
+        }
+
+        for body in &mut self.entities {
+            let rigid_body = &self.bodies[body.handle];
+            if !rigid_body.is_dynamic() {
+                continue;
+            }
+            let position = rigid_body.position();
+            let translation = position.translation.vector;
+            let rotation = position.rotation;
+            body.transform = Transform::compose(
+                Vector3::new(translation.x, translation.y, translation.z),
+                Quaternion::new(rotation.w, rotation.i, rotation.j, rotation.k),
+                body.scale,
+            )
+            .0;
+            world
+                .transforms
+                .insert(body.entity, Transform(body.transform));
+        }
+    }
+
+    /// Drops cubes from above onto the objects, which are fixed in place.
+    pub fn drop_cubes(&mut self, scene: &mut Scene, renderer: &mut Renderer) {
+        let (mesh, material) = *self.cube.get_or_insert_with(|| {
+            (
+                renderer.add_mesh(&MeshData::cube()),
+                renderer.add_material(&Material::default()),
+            )
+        });
+        let world = &mut scene.world;
+        let fixed: Vec<_> = world
+            .renderables()
+            .map(|object| object.entity)
+            .filter(|&entity| self.entities.iter().all(|body| body.entity != entity))
+            .collect();
+        for entity in fixed {
+            self.add(world, renderer, entity, false, Shape::Aabb);
+        }
+
+        let height = scene.bounding_radius(renderer) + 1.0;
+        let world = &mut scene.world;
+        let mut edit = Edit::begin(format!("drop {CUBE_COUNT} cubes"), world, []);
+        let columns = (CUBE_COUNT as f32).sqrt().ceil() as usize;
+        let spacing = 3.0 * CUBE_SIZE;
+        for index in 0..CUBE_COUNT {
+            let (column, row) = (index % columns, index / columns);
+            let offset = 0.5 * (columns - 1) as f32;
+            let position = Vector3::new(
+                (column as f32 - offset) * spacing,
+                height + index as f32 * 0.5 * CUBE_SIZE,
+                (row as f32 - offset) * spacing,
+            );
+            // Tumbling, so that the cubes do not stack up neatly.
+            let axis = Vector3::new(1.0, index as f32, 2.0).normalize();
+            let rotation = Quaternion::from_axis_angle(axis, Rad(index as f32));
+            self.dropped += 1;
+            let entity = world.spawn_object(&Object {
+                name: format!("falling cube {}", self.dropped),
+                transform: Transform::compose(
+                    position,
+                    rotation,
+                    Vector3::new(CUBE_SIZE, CUBE_SIZE, CUBE_SIZE),
+                )
+                .0,
+                mesh,
+                material,
+            });
+            world.tag(entity, "physics");
+            self.add(world, renderer, entity, true, Shape::ConvexHull);
+            edit.spawned(entity);
+        }
+        scene.history.push(edit.finish(world));
+        println!("Dropped {CUBE_COUNT} cubes");
+    }
+}
+
+/// The rotation and translation of a transform.
+fn isometry(transform: Matrix4<f32>) -> Isometry<Real> {
+    let (translation, rotation, _) = Transform(transform).decompose();
+    Isometry::from_parts(
+        Translation3::new(translation.x, translation.y, translation.z),
+        UnitQuaternion::from_quaternion(na::Quaternion::new(
+            rotation.s,
+            rotation.v.x,
+            rotation.v.y,
+            rotation.v.z,
+        )),
+    )
+}
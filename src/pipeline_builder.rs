@@ -0,0 +1,53 @@
+use std::thread::JoinHandle;
+
+use tracing::error;
+
+/// Builds pipelines on a background thread, as compiling them can take long enough to drop
+/// frames. The pipelines in use keep drawing until the new ones are taken.
+#[derive(Debug)]
+pub struct PipelineBuilder<K, T> {
+    /// What is being built for, and the thread building it.
+    pending: Option<(K, JoinHandle<T>)>,
+}
+
+impl<K, T> Default for PipelineBuilder<K, T> {
+    fn default() -> Self {
+        PipelineBuilder { pending: None }
+    }
+}
+
+impl<K: Copy + PartialEq, T: Send + 'static> PipelineBuilder<K, T> {
+    /// Starts building for the key, unless already doing so. A build for another key is
+    /// abandoned, its thread detached and its result dropped once done.
+    pub fn build(&mut self, key: K, build: impl FnOnce() -> T + Send + 'static) {
+        if self.building() == Some(key) {
+            return;
+        }
+        self.pending = Some((key, std::thread::spawn(build)));
+    }
+
+    /// Abandons the build, if any.
+    pub fn cancel(&mut self) {
+        self.pending = None;
+    }
+
+    /// The key being built for.
+    pub fn building(&self) -> Option<K> {
+        self.pending.as_ref().map(|(key, _)| *key)
+    }
+
+    /// Takes the result once built.
+    pub fn poll(&mut self) -> Option<(K, T)> {
+        if !self.pending.as_ref()?.1.is_finished() {
+            return None;
+        }
+        let (key, thread) = self.pending.take()?;
+        match thread.join() {
+            Ok(built) => Some((key, built)),
+            Err(_) => {
+                error!("Building pipelines failed");
+                None
+            }
+        }
+    }
+}
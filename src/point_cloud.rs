@@ -0,0 +1,141 @@
+use cgmath::{Vector3, Vector4};
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::render::as_byte_slice;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Point {
+    #[allow(dead_code)]
+    pub position: Vector3<f32>,
+    /// sRGB-encoded, with linear alpha.
+    #[allow(dead_code)]
+    pub color: Vector4<f32>,
+}
+
+impl Point {
+    /// One instance per point, expanded into a screen-facing splat.
+    const LAYOUT: VertexBufferLayout<'static> = VertexBufferLayout {
+        array_stride: std::mem::size_of::<Point>() as BufferAddress,
+        step_mode: VertexStepMode::Instance,
+        attributes: &vertex_attr_array![0 => Float32x3, 1 => Float32x4],
+    };
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PointCloudId(pub usize);
+
+/// Unlit points, drawn as round splats of a fixed size in pixels.
+#[derive(Debug)]
+pub struct PointCloud {
+    /// None while unloaded.
+    buffer: Option<Buffer>,
+    /// Kept to upload the points again once unloaded.
+    points: Vec<Point>,
+}
+
+impl PointCloud {
+    pub fn new(device: &Device, points: &[Point]) -> Self {
+        let mut point_cloud = PointCloud {
+            buffer: None,
+            points: points.to_vec(),
+        };
+        point_cloud.upload(device);
+        point_cloud
+    }
+
+    /// Uploads the points again if they have been unloaded.
+    pub fn upload(&mut self, device: &Device) {
+        if self.buffer.is_none() {
+            self.buffer = Some(device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                contents: as_byte_slice(&self.points),
+                usage: BufferUsages::VERTEX,
+            }));
+        }
+    }
+
+    pub fn unload(&mut self) {
+        self.buffer = None;
+    }
+
+    /// Bytes taken on the GPU.
+    pub fn memory(&self) -> u64 {
+        self.buffer.as_ref().map_or(0, Buffer::size)
+    }
+}
+
+#[derive(Debug)]
+pub struct PointCloudPipeline {
+    pipeline: RenderPipeline,
+}
+
+impl PointCloudPipeline {
+    pub fn new(
+        device: &Device,
+        color_format: TextureFormat,
+        sample_count: u32,
+        bind_group_layouts: &[&BindGroupLayout],
+    ) -> Self {
+        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(include_str!("point_cloud.wgsl").into()),
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            cache: None,
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                bind_group_layouts,
+                ..Default::default()
+            })),
+            vertex: VertexState {
+                module: &shader_module,
+                entry_point: None,
+                buffers: &[Point::LAYOUT],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader_module,
+                entry_point: None,
+                targets: &[Some(ColorTargetState {
+                    format: color_format,
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            multisample: MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth24Plus,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Less,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multiview: None,
+        });
+
+        PointCloudPipeline { pipeline }
+    }
+
+    /// Expects the frame uniforms to be bound. Unloaded point clouds are skipped.
+    pub fn draw<'a>(
+        &self,
+        pass: &mut RenderPass,
+        point_clouds: impl IntoIterator<Item = &'a PointCloud>,
+    ) {
+        pass.set_pipeline(&self.pipeline);
+        for point_cloud in point_clouds {
+            let Some(buffer) = &point_cloud.buffer else {
+                continue;
+            };
+            pass.set_vertex_buffer(0, buffer.slice(..));
+            pass.draw(0..6, 0..point_cloud.points.len() as u32);
+        }
+    }
+}
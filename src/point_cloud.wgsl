@@ -0,0 +1,57 @@
+struct Uniforms {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    environment: mat4x4<f32>,
+    camera_position: vec4<f32>,
+    /// Width and height in pixels, followed by their reciprocals.
+    viewport: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+/// Radius of the splats in pixels.
+const POINT_RADIUS: f32 = 2.0;
+
+struct FragmentInput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    /// Position within the splat, in [-1, 1].
+    @location(1) offset: vec2<f32>,
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    return select(pow((color + 0.055) / 1.055, vec3<f32>(2.4)), color / 12.92, color <= vec3<f32>(0.04045));
+}
+
+/// Expands each point instance into a quad of two triangles facing the screen.
+@vertex
+fn vertex(
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+) -> FragmentInput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let offset = corners[vertex_index];
+
+    var out: FragmentInput;
+    let clip = uniforms.projection * uniforms.view * vec4<f32>(position, 1.0);
+    out.position = clip + vec4<f32>(2.0 * POINT_RADIUS * offset * uniforms.viewport.zw * clip.w, 0.0, 0.0);
+    out.color = vec4<f32>(srgb_to_linear(color.rgb), color.a);
+    out.offset = offset;
+    return out;
+}
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    if dot(in.offset, in.offset) > 1.0 {
+        discard;
+    }
+    return in.color;
+}
@@ -0,0 +1,554 @@
+use cgmath::{Matrix4, Vector3};
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use crate::render::as_byte_slice;
+
+mod auto_exposure;
+mod bloom;
+mod buffer_debug;
+mod chromatic_aberration;
+mod color_grading;
+mod fog;
+mod fxaa;
+mod motion_blur;
+mod painterly;
+mod pixelate;
+mod selection_outline;
+mod sharpen;
+mod stylize;
+mod taa;
+mod tonemap;
+mod upscale;
+
+pub use bloom::Bloom;
+pub use buffer_debug::{BufferDebug, BufferView};
+pub use chromatic_aberration::ChromaticAberration;
+pub use color_grading::ColorGrading;
+pub use fog::{Fog, FogFalloff};
+pub use fxaa::Fxaa;
+pub use motion_blur::MotionBlur;
+pub use painterly::Painterly;
+pub use pixelate::Pixelate;
+pub use selection_outline::{SelectionOutline, SELECTION_MASK_FORMAT};
+pub use sharpen::Sharpen;
+pub use stylize::Stylize;
+pub use taa::Taa;
+pub use tonemap::{Tonemap, Tonemapper};
+pub use upscale::Upscale;
+
+/// Format of the scene color and the intermediate post-processing targets.
+pub const HDR_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+#[derive(Debug, Copy, Clone)]
+struct BlitUniforms {
+    #[allow(dead_code)]
+    encode_srgb: u32,
+    #[allow(dead_code)]
+    gamma_debug: u32,
+    #[allow(dead_code)]
+    dither: u32,
+    #[allow(dead_code)]
+    frame_index: u32,
+}
+
+/// Resources shared by the post-processing passes of a frame.
+pub struct PostContext<'a> {
+    pub device: &'a Device,
+    pub queue: &'a Queue,
+    /// Linear sampler clamping to the edge.
+    pub sampler: &'a Sampler,
+    /// Size of the targets in pixels.
+    pub width: u32,
+    pub height: u32,
+    pub frame: FrameInputs<'a>,
+}
+
+/// What the scene pass leaves for the effects besides its color.
+#[derive(Debug, Copy, Clone)]
+pub struct FrameInputs<'a> {
+    /// Single-sampled unless MSAA is enabled.
+    pub depth: &'a TextureView,
+    pub depth_multisampled: bool,
+    /// The directional light's cascades, as layers of a depth texture.
+    pub shadow_map: &'a TextureView,
+    /// Maps the depth buffer's normalized device coordinates back to world space.
+    pub inverse_view_projection: Matrix4<f32>,
+    /// The previous frame's view-projection, without jitter.
+    pub previous_view_projection: Matrix4<f32>,
+    pub camera_position: Vector3<f32>,
+    /// Screen-space motion since the previous frame, if rendered.
+    pub velocity: Option<&'a TextureView>,
+    /// Increases every frame.
+    pub frame_index: u32,
+    /// Seconds since the previous frame.
+    pub delta_time: f32,
+}
+
+/// A screen-space effect, reading the previous pass's output and writing its own.
+pub trait PostEffect {
+    fn enabled(&self) -> bool {
+        true
+    }
+
+    /// Renders the effect of the input into the output, both HDR targets of the current size.
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    );
+
+    /// Recreates resources depending on the target size.
+    fn resize(&mut self, _device: &Device, _width: u32, _height: u32) {}
+}
+
+/// A full-screen triangle shaded by a fragment entry point, sampling the input at group 0.
+#[derive(Debug)]
+pub struct FullscreenPass {
+    pipeline: RenderPipeline,
+    input_layout: BindGroupLayout,
+    /// Whether the output is blended onto instead of cleared.
+    blended: bool,
+}
+
+impl FullscreenPass {
+    /// The source is appended to the shared post-processing shader. Additional bind groups
+    /// follow the input from group 1 on.
+    pub fn new(
+        device: &Device,
+        source: &str,
+        entry_point: &str,
+        format: TextureFormat,
+        bind_group_layouts: &[&BindGroupLayout],
+    ) -> Self {
+        Self::with_blend(
+            device,
+            source,
+            entry_point,
+            format,
+            bind_group_layouts,
+            None,
+        )
+    }
+
+    /// Like [`FullscreenPass::new`], but blends onto the output's previous contents.
+    pub fn with_blend(
+        device: &Device,
+        source: &str,
+        entry_point: &str,
+        format: TextureFormat,
+        bind_group_layouts: &[&BindGroupLayout],
+        blend: Option<BlendState>,
+    ) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl([include_str!("post.wgsl"), source].concat().into()),
+        });
+        let input_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let layouts: Vec<_> = std::iter::once(&input_layout)
+            .chain(bind_group_layouts.iter().copied())
+            .collect();
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            cache: None,
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                bind_group_layouts: &layouts,
+                ..Default::default()
+            })),
+            vertex: VertexState {
+                module: &module,
+                entry_point: Some("fullscreen_vertex"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: Some(entry_point),
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend,
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            multisample: Default::default(),
+            depth_stencil: None,
+            multiview: None,
+        });
+
+        FullscreenPass {
+            pipeline,
+            input_layout,
+            blended: blend.is_some(),
+        }
+    }
+
+    pub fn draw(
+        &self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+        bind_groups: &[&BindGroup],
+    ) {
+        let input_bind_group = context.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.input_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(input),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(context.sampler),
+                },
+            ],
+        });
+
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: Operations {
+                    load: if self.blended {
+                        LoadOp::Load
+                    } else {
+                        LoadOp::Clear(Color::BLACK)
+                    },
+                    store: StoreOp::Store,
+                },
+            })],
+            ..Default::default()
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &input_bind_group, &[]);
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            pass.set_bind_group(index as u32 + 1, *bind_group, &[]);
+        }
+        pass.draw(0..3, 0..1);
+    }
+}
+
+/// The scene is rendered into an HDR target, which the enabled effects process in turn
+/// before the result is copied to the surface. The effects up to anti-aliasing run at the render
+/// resolution, which may be lower than the display resolution the rest runs at after upscaling.
+#[derive(Debug)]
+pub struct PostProcessing {
+    /// Render target of the scene.
+    pub scene: TextureView,
+    /// Alternating outputs of the effects, which compute passes may write as storage textures,
+    /// at the render resolution.
+    scene_targets: [TextureView; 2],
+    /// Likewise at the display resolution.
+    targets: [TextureView; 2],
+    width: u32,
+    height: u32,
+    render_width: u32,
+    render_height: u32,
+    sampler: Sampler,
+    blit: FullscreenPass,
+    blit_buffer: Buffer,
+    blit_bind_group: BindGroup,
+    /// Whether the surface stores the linear values as they are, needing them encoded first.
+    encode_srgb: bool,
+    /// Shows the right half of the screen without gamma encoding.
+    pub gamma_debug: bool,
+    /// Adds noise below the surface's precision, trading banding for less noticeable grain.
+    pub dither: bool,
+    /// Skips the effects at the render resolution, showing the scene as drawn.
+    pub raw: bool,
+    /// Reads the depth of the unprocessed scene, so it comes first.
+    pub fog: Fog,
+    pub taa: Taa,
+    pub motion_blur: MotionBlur,
+    pub bloom: Bloom,
+    pub tonemap: Tonemap,
+    pub color_grading: ColorGrading,
+    pub painterly: Painterly,
+    /// Runs on the tone mapped image, where contrast matches what is displayed.
+    pub fxaa: Fxaa,
+    pub upscale: Upscale,
+    /// Restores detail lost to TAA and upscaling.
+    pub sharpen: Sharpen,
+    pub pixelate: Pixelate,
+    pub chromatic_aberration: ChromaticAberration,
+    pub stylize: Stylize,
+    pub selection_outline: SelectionOutline,
+    /// Replaces the image, so it comes last.
+    pub buffer_debug: BufferDebug,
+}
+
+impl PostProcessing {
+    pub fn new(device: &Device, surface_format: TextureFormat, width: u32, height: u32) -> Self {
+        let blit_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let blit_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[BlitUniforms {
+                encode_srgb: 0,
+                gamma_debug: 0,
+                dither: 0,
+                frame_index: 0,
+            }]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let blit_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &blit_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: blit_buffer.as_entire_binding(),
+            }],
+        });
+
+        PostProcessing {
+            scene: create_target(device, width, height),
+            scene_targets: [
+                create_target(device, width, height),
+                create_target(device, width, height),
+            ],
+            targets: [
+                create_target(device, width, height),
+                create_target(device, width, height),
+            ],
+            width,
+            height,
+            render_width: width,
+            render_height: height,
+            sampler: device.create_sampler(&SamplerDescriptor {
+                mag_filter: FilterMode::Linear,
+                min_filter: FilterMode::Linear,
+                ..Default::default()
+            }),
+            blit: FullscreenPass::new(
+                device,
+                include_str!("post/blit.wgsl"),
+                "blit_fragment",
+                surface_format,
+                &[&blit_layout],
+            ),
+            blit_buffer,
+            blit_bind_group,
+            encode_srgb: !surface_format.is_srgb(),
+            gamma_debug: false,
+            dither: true,
+            raw: false,
+            fog: Fog::new(device),
+            taa: Taa::new(device, width, height),
+            motion_blur: MotionBlur::new(device),
+            bloom: Bloom::new(device, width, height),
+            tonemap: Tonemap::new(device),
+            color_grading: ColorGrading::new(device),
+            painterly: Painterly::new(device),
+            fxaa: Fxaa::new(device),
+            upscale: Upscale::new(device),
+            sharpen: Sharpen::new(device),
+            pixelate: Pixelate::new(device),
+            chromatic_aberration: ChromaticAberration::new(device),
+            stylize: Stylize::new(device),
+            selection_outline: SelectionOutline::new(device, width, height),
+            buffer_debug: BufferDebug::new(device),
+        }
+    }
+
+    /// Recreates the targets for the display resolution and the resolution the scene is rendered
+    /// at, which must not be larger.
+    pub fn resize(
+        &mut self,
+        device: &Device,
+        width: u32,
+        height: u32,
+        render_width: u32,
+        render_height: u32,
+    ) {
+        self.scene = create_target(device, render_width, render_height);
+        self.scene_targets = [
+            create_target(device, render_width, render_height),
+            create_target(device, render_width, render_height),
+        ];
+        self.targets = [
+            create_target(device, width, height),
+            create_target(device, width, height),
+        ];
+        self.width = width;
+        self.height = height;
+        self.render_width = render_width;
+        self.render_height = render_height;
+        for effect in self.scene_effects() {
+            effect.resize(device, render_width, render_height);
+        }
+        for effect in self.display_effects() {
+            effect.resize(device, width, height);
+        }
+    }
+
+    /// The effects at the render resolution in the order they are applied.
+    fn scene_effects(&mut self) -> Vec<&mut dyn PostEffect> {
+        vec![
+            &mut self.fog,
+            &mut self.taa,
+            &mut self.motion_blur,
+            &mut self.bloom,
+            &mut self.tonemap,
+            &mut self.color_grading,
+            &mut self.painterly,
+            &mut self.fxaa,
+        ]
+    }
+
+    /// The effects at the display resolution in the order they are applied.
+    fn display_effects(&mut self) -> Vec<&mut dyn PostEffect> {
+        vec![
+            &mut self.sharpen,
+            &mut self.pixelate,
+            &mut self.chromatic_aberration,
+            &mut self.stylize,
+            &mut self.selection_outline,
+            &mut self.buffer_debug,
+        ]
+    }
+
+    /// Applies the enabled effects to the scene and writes the result to the surface, and to the
+    /// capture target if given.
+    pub fn run(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        frame: FrameInputs,
+        surface: &TextureView,
+        capture: Option<&TextureView>,
+    ) {
+        let sampler = self.sampler.clone();
+        let mut context = PostContext {
+            device,
+            queue,
+            sampler: &sampler,
+            width: self.render_width,
+            height: self.render_height,
+            frame,
+        };
+        let scene = self.scene.clone();
+        let scene_targets = self.scene_targets.clone();
+        let targets = self.targets.clone();
+
+        let scene_effects = if self.raw {
+            Vec::new()
+        } else {
+            self.scene_effects()
+        };
+        let mut input = apply_effects(scene_effects, &context, encoder, scene, &scene_targets);
+        context.width = self.width;
+        context.height = self.height;
+        if (self.render_width, self.render_height) != (self.width, self.height) {
+            // Into the second target, as the display effects start with the first.
+            self.upscale.apply(&context, encoder, &input, &targets[1]);
+            input = targets[1].clone();
+        }
+        let input = apply_effects(self.display_effects(), &context, encoder, input, &targets);
+
+        queue.write_buffer(
+            &self.blit_buffer,
+            0,
+            as_byte_slice(&[BlitUniforms {
+                encode_srgb: self.encode_srgb as u32,
+                gamma_debug: self.gamma_debug as u32,
+                dither: self.dither as u32,
+                frame_index: context.frame.frame_index,
+            }]),
+        );
+        for target in std::iter::once(surface).chain(capture) {
+            self.blit
+                .draw(&context, encoder, &input, target, &[&self.blit_bind_group]);
+        }
+    }
+}
+
+/// Applies the enabled effects in turn, alternating between the targets, and returns the last
+/// output.
+fn apply_effects(
+    effects: Vec<&mut dyn PostEffect>,
+    context: &PostContext,
+    encoder: &mut CommandEncoder,
+    mut input: TextureView,
+    targets: &[TextureView; 2],
+) -> TextureView {
+    for (index, effect) in effects
+        .into_iter()
+        .filter(|effect| effect.enabled())
+        .enumerate()
+    {
+        let output = &targets[index % 2];
+        effect.apply(context, encoder, &input, output);
+        input = output.clone();
+    }
+    input
+}
+
+fn create_target(device: &Device, width: u32, height: u32) -> TextureView {
+    device
+        .create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: HDR_FORMAT,
+            view_formats: &[],
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::STORAGE_BINDING,
+        })
+        .create_view(&Default::default())
+}
+
+/// Declares the scene depth as `depth_texture` at a binding of group 1, for shaders which are
+/// built for both the single-sampled and the multisampled depth buffer.
+fn depth_texture_declaration(binding: u32, multisampled: bool) -> String {
+    let ty = if multisampled {
+        "texture_depth_multisampled_2d"
+    } else {
+        "texture_depth_2d"
+    };
+    format!("@group(1) @binding({binding}) var depth_texture: {ty};\n")
+}
@@ -0,0 +1,182 @@
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::PostContext;
+use crate::render::as_byte_slice;
+
+/// Must match the shader.
+const HISTOGRAM_BINS: u64 = 256;
+
+#[derive(Debug, Copy, Clone)]
+struct AutoExposureUniforms {
+    #[allow(dead_code)]
+    min_log_luminance: f32,
+    #[allow(dead_code)]
+    log_luminance_range: f32,
+    #[allow(dead_code)]
+    min_exposure: f32,
+    #[allow(dead_code)]
+    max_exposure: f32,
+    #[allow(dead_code)]
+    adaptation: f32,
+    #[allow(dead_code)]
+    pixel_count: u32,
+    #[allow(dead_code)]
+    padding: [u32; 2],
+}
+
+/// Eye adaptation: a histogram of the log luminance, whose average the exposure gradually follows.
+#[derive(Debug)]
+pub struct AutoExposure {
+    pub enabled: bool,
+    /// Clamps of the adapted exposure, in stops.
+    pub min_exposure: f32,
+    pub max_exposure: f32,
+    /// Rate of adaptation per second.
+    pub speed: f32,
+    /// The adapted exposure in stops, written on the GPU.
+    pub buffer: Buffer,
+    histogram: Buffer,
+    uniform_buffer: Buffer,
+    layout: BindGroupLayout,
+    build_histogram: ComputePipeline,
+    average_histogram: ComputePipeline,
+}
+
+impl AutoExposure {
+    pub fn new(device: &Device) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(include_str!("auto_exposure.wgsl").into()),
+        });
+        let storage = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                storage(1),
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(3),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            bind_group_layouts: &[&layout],
+            ..Default::default()
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: None,
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point: Some(entry_point),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+
+        AutoExposure {
+            enabled: true,
+            min_exposure: -4.0,
+            max_exposure: 4.0,
+            speed: 1.5,
+            buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                contents: as_byte_slice(&[0.0f32]),
+                usage: BufferUsages::STORAGE,
+            }),
+            histogram: device.create_buffer(&BufferDescriptor {
+                label: None,
+                size: HISTOGRAM_BINS * 4,
+                usage: BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            }),
+            uniform_buffer: device.create_buffer(&BufferDescriptor {
+                label: None,
+                size: std::mem::size_of::<AutoExposureUniforms>() as u64,
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            layout,
+            build_histogram: pipeline("build_histogram"),
+            average_histogram: pipeline("average_histogram"),
+        }
+    }
+
+    /// Measures the input and adapts the exposure towards it.
+    pub fn measure(
+        &self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+    ) {
+        context.queue.write_buffer(
+            &self.uniform_buffer,
+            0,
+            as_byte_slice(&[AutoExposureUniforms {
+                min_log_luminance: -10.0,
+                log_luminance_range: 20.0,
+                min_exposure: self.min_exposure,
+                max_exposure: self.max_exposure,
+                adaptation: 1.0 - (-context.frame.delta_time * self.speed).exp(),
+                pixel_count: context.width * context.height,
+                padding: [0; 2],
+            }]),
+        );
+        let bind_group = context.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(input),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: self.histogram.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: self.buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.set_pipeline(&self.build_histogram);
+        pass.dispatch_workgroups(context.width.div_ceil(16), context.height.div_ceil(16), 1);
+        pass.set_pipeline(&self.average_histogram);
+        pass.dispatch_workgroups(1, 1, 1);
+    }
+}
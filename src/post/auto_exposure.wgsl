@@ -0,0 +1,75 @@
+struct AutoExposureParams {
+    /// Range of the histogram, as log2 luminance.
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    /// Clamps of the adapted exposure, in stops.
+    min_exposure: f32,
+    max_exposure: f32,
+    /// Fraction of the way to the target exposure covered this frame.
+    adaptation: f32,
+    pixel_count: u32,
+}
+
+const HISTOGRAM_BINS: u32 = 256u;
+/// Middle gray, which the average luminance is exposed to.
+const EXPOSURE_KEY: f32 = 0.18;
+
+@group(0) @binding(0) var input_texture: texture_2d<f32>;
+@group(0) @binding(1) var<storage, read_write> histogram: array<atomic<u32>, HISTOGRAM_BINS>;
+@group(0) @binding(2) var<uniform> params: AutoExposureParams;
+/// The adapted exposure in stops, persisting across frames.
+@group(0) @binding(3) var<storage, read_write> exposure: f32;
+
+var<workgroup> local_histogram: array<atomic<u32>, HISTOGRAM_BINS>;
+var<workgroup> weighted_counts: array<u32, HISTOGRAM_BINS>;
+
+/// Bin 0 holds black pixels, the others log luminance in equal steps.
+fn histogram_bin(color: vec3<f32>) -> u32 {
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    if luminance < 1e-5 {
+        return 0u;
+    }
+    let position = saturate((log2(luminance) - params.min_log_luminance) / params.log_luminance_range);
+    return u32(position * f32(HISTOGRAM_BINS - 2u)) + 1u;
+}
+
+@compute @workgroup_size(16, 16)
+fn build_histogram(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    atomicStore(&local_histogram[index], 0u);
+    workgroupBarrier();
+
+    if all(id.xy < textureDimensions(input_texture)) {
+        let color = textureLoad(input_texture, id.xy, 0).rgb;
+        atomicAdd(&local_histogram[histogram_bin(color)], 1u);
+    }
+    workgroupBarrier();
+
+    atomicAdd(&histogram[index], atomicLoad(&local_histogram[index]));
+}
+
+/// Averages the non-black bins, moves the exposure towards the one mapping the average
+/// to middle gray, and clears the histogram for the next frame.
+@compute @workgroup_size(256)
+fn average_histogram(@builtin(local_invocation_index) index: u32) {
+    let count = atomicExchange(&histogram[index], 0u);
+    weighted_counts[index] = count * index;
+    workgroupBarrier();
+
+    for (var stride = HISTOGRAM_BINS / 2u; stride > 0u; stride >>= 1u) {
+        if index < stride {
+            weighted_counts[index] += weighted_counts[index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if index == 0u {
+        let lit_pixels = max(f32(params.pixel_count) - f32(count), 1.0);
+        let average_bin = f32(weighted_counts[0]) / lit_pixels - 1.0;
+        let log_average = average_bin / f32(HISTOGRAM_BINS - 2u) * params.log_luminance_range + params.min_log_luminance;
+        let target_exposure = clamp(log2(EXPOSURE_KEY) - log_average, params.min_exposure, params.max_exposure);
+        exposure = mix(exposure, target_exposure, params.adaptation);
+    }
+}
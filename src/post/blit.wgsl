@@ -0,0 +1,45 @@
+struct BlitParams {
+    /// Nonzero if the surface format does not encode to sRGB by itself.
+    encode_srgb: u32,
+    /// Nonzero to show the right half without gamma encoding, as if it was skipped.
+    gamma_debug: u32,
+    /// Nonzero to dither the encoded color before it is quantized by the surface.
+    dither: u32,
+    /// Animates the dither pattern.
+    frame_index: u32,
+}
+
+@group(1) @binding(0) var<uniform> blit: BlitParams;
+
+/// Interleaved gradient noise, whose neighboring values differ a lot, like blue noise.
+fn interleaved_gradient_noise(pixel: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(pixel, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+/// Triangularly distributed noise in (-1, 1), which unlike uniform noise makes the error
+/// independent of the signal.
+fn dither_noise(pixel: vec2<f32>) -> f32 {
+    let offset = 5.588238 * f32(blit.frame_index % 64u);
+    return interleaved_gradient_noise(pixel + offset) + interleaved_gradient_noise(pixel + offset + vec2<f32>(47.0, 17.0)) - 1.0;
+}
+
+/// Copies the linear image to the surface, gamma encoding it unless the surface does.
+@fragment
+fn blit_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(input_texture, input_sampler, in.uv, 0.0);
+    if blit.gamma_debug != 0u && abs(in.position.x - 0.5 * f32(textureDimensions(input_texture).x)) < 1.0 {
+        return vec4<f32>(1.0);
+    }
+
+    let skip_encoding = blit.gamma_debug != 0u && in.uv.x > 0.5;
+    var encoded = select(linear_to_srgb(color.rgb), color.rgb, skip_encoding);
+    if blit.dither != 0u {
+        // Spread the rounding to 8 bits over neighboring pixels, hiding banding in gradients.
+        encoded = saturate(encoded + dither_noise(in.position.xy) / 255.0);
+    }
+    if blit.encode_srgb != 0u {
+        return vec4<f32>(encoded, color.a);
+    }
+    // Undo the encoding the surface applies when writing.
+    return vec4<f32>(srgb_to_linear(encoded), color.a);
+}
@@ -0,0 +1,232 @@
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::{FullscreenPass, PostContext, PostEffect, HDR_FORMAT};
+use crate::render::as_byte_slice;
+
+/// Upper bound on the levels of the bloom chain, the smallest spanning the widest halo.
+const MAX_BLOOM_LEVELS: u32 = 6;
+
+#[derive(Debug, Copy, Clone)]
+struct BloomUniforms {
+    #[allow(dead_code)]
+    threshold: f32,
+    #[allow(dead_code)]
+    intensity: f32,
+}
+
+/// Glow around bright areas, from progressively downsampling the parts above a threshold
+/// and blurring them back up.
+#[derive(Debug)]
+pub struct Bloom {
+    pub enabled: bool,
+    /// Brightness in linear HDR units above which pixels bloom.
+    pub threshold: f32,
+    /// Weight of the blurred highlights added to the scene.
+    pub intensity: f32,
+    prefilter: FullscreenPass,
+    downsample: FullscreenPass,
+    upsample: FullscreenPass,
+    composite: FullscreenPass,
+    buffer: Buffer,
+    params_bind_group: BindGroup,
+    texture_layout: BindGroupLayout,
+    /// One view per level, each half the size of the previous, starting at half resolution.
+    levels: Vec<TextureView>,
+    texture_bind_group: BindGroup,
+}
+
+impl Bloom {
+    pub fn new(device: &Device, width: u32, height: u32) -> Self {
+        let params_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let texture_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+
+        let uniforms = BloomUniforms {
+            threshold: 1.0,
+            intensity: 0.05,
+        };
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[uniforms]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let params_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &params_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let source = include_str!("bloom.wgsl");
+        let pass = |entry_point, blend| {
+            FullscreenPass::with_blend(
+                device,
+                source,
+                entry_point,
+                HDR_FORMAT,
+                &[&params_layout],
+                blend,
+            )
+        };
+        let additive = BlendState {
+            color: BlendComponent {
+                src_factor: BlendFactor::One,
+                dst_factor: BlendFactor::One,
+                operation: BlendOperation::Add,
+            },
+            alpha: BlendComponent::REPLACE,
+        };
+        let prefilter = pass("bloom_prefilter_fragment", None);
+        let downsample = pass("bloom_downsample_fragment", None);
+        let upsample = pass("bloom_upsample_fragment", Some(additive));
+        let composite = FullscreenPass::new(
+            device,
+            source,
+            "bloom_composite_fragment",
+            HDR_FORMAT,
+            &[&params_layout, &texture_layout],
+        );
+
+        let levels = create_levels(device, width, height);
+        let texture_bind_group = create_texture_bind_group(device, &texture_layout, &levels[0]);
+
+        Bloom {
+            enabled: true,
+            threshold: uniforms.threshold,
+            intensity: uniforms.intensity,
+            prefilter,
+            downsample,
+            upsample,
+            composite,
+            buffer,
+            params_bind_group,
+            texture_layout,
+            levels,
+            texture_bind_group,
+        }
+    }
+}
+
+impl PostEffect for Bloom {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            as_byte_slice(&[BloomUniforms {
+                threshold: self.threshold,
+                intensity: self.intensity,
+            }]),
+        );
+        let params = &[&self.params_bind_group];
+
+        self.prefilter
+            .draw(context, encoder, input, &self.levels[0], params);
+        for pair in self.levels.windows(2) {
+            self.downsample
+                .draw(context, encoder, &pair[0], &pair[1], params);
+        }
+        for pair in self.levels.windows(2).rev() {
+            self.upsample
+                .draw(context, encoder, &pair[1], &pair[0], params);
+        }
+        self.composite.draw(
+            context,
+            encoder,
+            input,
+            output,
+            &[&self.params_bind_group, &self.texture_bind_group],
+        );
+    }
+
+    fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        self.levels = create_levels(device, width, height);
+        self.texture_bind_group =
+            create_texture_bind_group(device, &self.texture_layout, &self.levels[0]);
+    }
+}
+
+fn create_levels(device: &Device, width: u32, height: u32) -> Vec<TextureView> {
+    let width = (width / 2).max(1);
+    let height = (height / 2).max(1);
+    // Stop before the smallest level gets narrower than a few texels.
+    let level_count = width
+        .min(height)
+        .ilog2()
+        .saturating_sub(2)
+        .clamp(1, MAX_BLOOM_LEVELS);
+
+    let texture = device.create_texture(&TextureDescriptor {
+        label: None,
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: level_count,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: HDR_FORMAT,
+        view_formats: &[],
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    });
+    (0..level_count)
+        .map(|level| {
+            texture.create_view(&TextureViewDescriptor {
+                base_mip_level: level,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+fn create_texture_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    view: &TextureView,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: BindingResource::TextureView(view),
+        }],
+    })
+}
@@ -0,0 +1,74 @@
+struct BloomParams {
+    /// Brightness above which pixels bloom, with a soft knee of half the threshold below it.
+    threshold: f32,
+    intensity: f32,
+}
+
+@group(1) @binding(0) var<uniform> bloom: BloomParams;
+/// Largest level of the bloom chain, only bound for compositing.
+@group(2) @binding(0) var bloom_texture: texture_2d<f32>;
+
+fn sample_input(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(input_texture, input_sampler, uv, 0.0).rgb;
+}
+
+/// 13 bilinear taps of the input at twice the output's resolution, weighted as overlapping boxes,
+/// which avoids the pulsating aliasing of a plain 2×2 box.
+fn downsample(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input_texture));
+
+    let a = sample_input(uv + texel * vec2<f32>(-2.0, -2.0));
+    let b = sample_input(uv + texel * vec2<f32>(0.0, -2.0));
+    let c = sample_input(uv + texel * vec2<f32>(2.0, -2.0));
+    let d = sample_input(uv + texel * vec2<f32>(-2.0, 0.0));
+    let e = sample_input(uv);
+    let f = sample_input(uv + texel * vec2<f32>(2.0, 0.0));
+    let g = sample_input(uv + texel * vec2<f32>(-2.0, 2.0));
+    let h = sample_input(uv + texel * vec2<f32>(0.0, 2.0));
+    let i = sample_input(uv + texel * vec2<f32>(2.0, 2.0));
+    let j = sample_input(uv + texel * vec2<f32>(-1.0, -1.0));
+    let k = sample_input(uv + texel * vec2<f32>(1.0, -1.0));
+    let l = sample_input(uv + texel * vec2<f32>(-1.0, 1.0));
+    let m = sample_input(uv + texel * vec2<f32>(1.0, 1.0));
+
+    return e * 0.125 + (a + c + g + i) * 0.03125 + (b + d + f + h) * 0.0625 + (j + k + l + m) * 0.125;
+}
+
+/// Downsamples the scene into the first level, keeping only what exceeds the threshold.
+@fragment
+fn bloom_prefilter_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let color = downsample(in.uv);
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = 0.5 * bloom.threshold;
+    let soft = clamp(brightness - bloom.threshold + knee, 0.0, 2.0 * knee);
+    let contribution = max(soft * soft / (4.0 * knee + 1e-4), brightness - bloom.threshold);
+    return vec4<f32>(color * contribution / max(brightness, 1e-4), 1.0);
+}
+
+@fragment
+fn bloom_downsample_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample(in.uv), 1.0);
+}
+
+/// 3×3 tent filter of the smaller level, added onto the larger one.
+@fragment
+fn bloom_upsample_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input_texture));
+
+    var color = sample_input(in.uv) * 4.0;
+    color += (sample_input(in.uv + texel * vec2<f32>(-1.0, 0.0))
+        + sample_input(in.uv + texel * vec2<f32>(1.0, 0.0))
+        + sample_input(in.uv + texel * vec2<f32>(0.0, -1.0))
+        + sample_input(in.uv + texel * vec2<f32>(0.0, 1.0))) * 2.0;
+    color += sample_input(in.uv + texel * vec2<f32>(-1.0, -1.0))
+        + sample_input(in.uv + texel * vec2<f32>(1.0, -1.0))
+        + sample_input(in.uv + texel * vec2<f32>(-1.0, 1.0))
+        + sample_input(in.uv + texel * vec2<f32>(1.0, 1.0));
+    return vec4<f32>(color / 16.0, 1.0);
+}
+
+@fragment
+fn bloom_composite_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let bloom_color = textureSampleLevel(bloom_texture, input_sampler, in.uv, 0.0).rgb;
+    return vec4<f32>(sample_input(in.uv) + bloom.intensity * bloom_color, 1.0);
+}
@@ -0,0 +1,207 @@
+use cgmath::{Matrix4, SquareMatrix, Vector3};
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::{
+    create_target, depth_texture_declaration, FullscreenPass, PostContext, PostEffect, HDR_FORMAT,
+};
+use crate::render::as_byte_slice;
+
+/// An intermediate buffer to show in place of the image, in the order of the shader's constants.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BufferView {
+    /// Distance from the camera, linear up to the depth range.
+    Depth,
+    /// World-space normals, reconstructed from the depth.
+    Normals,
+    /// Screen-space motion, gray where nothing moves.
+    Velocity,
+    /// The shadow cascades side by side.
+    ShadowMap,
+}
+
+impl BufferView {
+    /// The next view, or none after the last one.
+    pub fn next(view: Option<Self>) -> Option<Self> {
+        match view {
+            None => Some(BufferView::Depth),
+            Some(BufferView::Depth) => Some(BufferView::Normals),
+            Some(BufferView::Normals) => Some(BufferView::Velocity),
+            Some(BufferView::Velocity) => Some(BufferView::ShadowMap),
+            Some(BufferView::ShadowMap) => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct BufferDebugUniforms {
+    #[allow(dead_code)]
+    inverse_view_projection: Matrix4<f32>,
+    #[allow(dead_code)]
+    camera_position: Vector3<f32>,
+    #[allow(dead_code)]
+    view: u32,
+    #[allow(dead_code)]
+    depth_range: f32,
+    #[allow(dead_code)]
+    velocity_range: f32,
+    #[allow(dead_code)]
+    padding: [f32; 2],
+}
+
+/// Shows an intermediate buffer full-screen, for diagnosing the passes producing it.
+#[derive(Debug)]
+pub struct BufferDebug {
+    pub view: Option<BufferView>,
+    /// Distance shown as white in the depth view.
+    pub depth_range: f32,
+    /// Motion in UV units per frame shown at full saturation in the velocity view.
+    pub velocity_range: f32,
+    /// Reading the depth buffer without and with MSAA.
+    passes: [FullscreenPass; 2],
+    layouts: [BindGroupLayout; 2],
+    buffer: Buffer,
+    /// Bound in place of the velocity buffer while it is not rendered.
+    empty_velocity: TextureView,
+}
+
+impl BufferDebug {
+    pub fn new(device: &Device) -> Self {
+        let texture = |binding, sample_type, view_dimension, multisampled| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type,
+                view_dimension,
+                multisampled,
+            },
+            count: None,
+        };
+        let create_layout = |multisampled: bool| {
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    texture(
+                        1,
+                        TextureSampleType::Depth,
+                        TextureViewDimension::D2,
+                        multisampled,
+                    ),
+                    texture(
+                        2,
+                        TextureSampleType::Float { filterable: true },
+                        TextureViewDimension::D2,
+                        false,
+                    ),
+                    texture(
+                        3,
+                        TextureSampleType::Depth,
+                        TextureViewDimension::D2Array,
+                        false,
+                    ),
+                ],
+            })
+        };
+        let layouts = [create_layout(false), create_layout(true)];
+        let create_pass = |multisampled: bool| {
+            let source =
+                depth_texture_declaration(1, multisampled) + include_str!("buffer_debug.wgsl");
+            FullscreenPass::new(
+                device,
+                &source,
+                "buffer_debug_fragment",
+                HDR_FORMAT,
+                &[&layouts[multisampled as usize]],
+            )
+        };
+        let passes = [create_pass(false), create_pass(true)];
+
+        let uniforms = BufferDebugUniforms {
+            inverse_view_projection: Matrix4::identity(),
+            camera_position: Vector3::new(0.0, 0.0, 0.0),
+            view: 0,
+            depth_range: 30.0,
+            velocity_range: 0.01,
+            padding: [0.0; 2],
+        };
+
+        BufferDebug {
+            view: None,
+            depth_range: uniforms.depth_range,
+            velocity_range: uniforms.velocity_range,
+            passes,
+            layouts,
+            buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                contents: as_byte_slice(&[uniforms]),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            }),
+            empty_velocity: create_target(device, 1, 1),
+        }
+    }
+}
+
+impl PostEffect for BufferDebug {
+    fn enabled(&self) -> bool {
+        self.view.is_some()
+    }
+
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        let frame = &context.frame;
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            as_byte_slice(&[BufferDebugUniforms {
+                inverse_view_projection: frame.inverse_view_projection,
+                camera_position: frame.camera_position,
+                view: self.view.map_or(0, |view| view as u32),
+                depth_range: self.depth_range,
+                velocity_range: self.velocity_range,
+                padding: [0.0; 2],
+            }]),
+        );
+
+        let index = frame.depth_multisampled as usize;
+        let bind_group = context.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.layouts[index],
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: self.buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(frame.depth),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(
+                        frame.velocity.unwrap_or(&self.empty_velocity),
+                    ),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(frame.shadow_map),
+                },
+            ],
+        });
+        self.passes[index].draw(context, encoder, input, output, &[&bind_group]);
+    }
+}
@@ -0,0 +1,64 @@
+const VIEW_DEPTH: u32 = 0u;
+const VIEW_NORMALS: u32 = 1u;
+const VIEW_VELOCITY: u32 = 2u;
+const VIEW_SHADOW_MAP: u32 = 3u;
+
+struct BufferDebugParams {
+    inverse_view_projection: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    view: u32,
+    /// Distance shown as white.
+    depth_range: f32,
+    /// Motion in UV units shown at full saturation.
+    velocity_range: f32,
+}
+
+@group(1) @binding(0) var<uniform> buffer_debug: BufferDebugParams;
+@group(1) @binding(2) var velocity_texture: texture_2d<f32>;
+@group(1) @binding(3) var shadow_map: texture_depth_2d_array;
+
+fn world_position(pixel: vec2<i32>, size: vec2<i32>) -> vec3<f32> {
+    let clamped = clamp(pixel, vec2<i32>(0), size - 1);
+    let uv = (vec2<f32>(clamped) + 0.5) / vec2<f32>(size);
+    let ndc = vec2<f32>(2.0, -2.0) * uv + vec2<f32>(-1.0, 1.0);
+    let world = buffer_debug.inverse_view_projection * vec4<f32>(ndc, textureLoad(depth_texture, clamped, 0), 1.0);
+    return world.xyz / world.w;
+}
+
+/// Replaces the image with one of the intermediate buffers.
+@fragment
+fn buffer_debug_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    // The buffers are at the render resolution, which may be below the display resolution.
+    let size = vec2<i32>(textureDimensions(depth_texture));
+    let pixel = vec2<i32>(in.uv * vec2<f32>(size));
+    switch buffer_debug.view {
+        case VIEW_DEPTH: {
+            let distance = length(world_position(pixel, size) - buffer_debug.camera_position);
+            return vec4<f32>(vec3<f32>(saturate(distance / buffer_debug.depth_range)), 1.0);
+        }
+        case VIEW_NORMALS: {
+            // Reconstructed from the depth, so that it works without the G-buffer.
+            if textureLoad(depth_texture, pixel, 0) >= 1.0 {
+                return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+            }
+            let center = world_position(pixel, size);
+            let dx = world_position(pixel + vec2<i32>(1, 0), size) - center;
+            let dy = world_position(pixel + vec2<i32>(0, 1), size) - center;
+            let normal = normalize(cross(dy, dx));
+            return vec4<f32>(0.5 * normal + 0.5, 1.0);
+        }
+        case VIEW_VELOCITY: {
+            let velocity = textureLoad(velocity_texture, pixel, 0).xy;
+            return vec4<f32>(0.5 + 0.5 * clamp(velocity / buffer_debug.velocity_range, vec2<f32>(-1.0), vec2<f32>(1.0)), 0.5, 1.0);
+        }
+        default: {
+            // The cascades side by side.
+            let cascades = f32(textureNumLayers(shadow_map));
+            let layer = min(i32(in.uv.x * cascades), i32(cascades) - 1);
+            let uv = vec2<f32>(fract(in.uv.x * cascades), in.uv.y);
+            let shadow_size = vec2<f32>(textureDimensions(shadow_map));
+            let depth = textureLoad(shadow_map, vec2<i32>(uv * shadow_size), layer, 0);
+            return vec4<f32>(vec3<f32>(depth), 1.0);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::{FullscreenPass, PostContext, PostEffect, HDR_FORMAT};
+use crate::render::as_byte_slice;
+
+#[derive(Debug, Copy, Clone)]
+struct ChromaticAberrationUniforms {
+    #[allow(dead_code)]
+    intensity: f32,
+    #[allow(dead_code)]
+    padding: [f32; 3],
+}
+
+/// Color fringes towards the edges of the image, splitting the channels like a cheap lens.
+#[derive(Debug)]
+pub struct ChromaticAberration {
+    pub enabled: bool,
+    /// Offset of the red and blue channels in the corners, in UV units.
+    pub intensity: f32,
+    pass: FullscreenPass,
+    buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl ChromaticAberration {
+    pub fn new(device: &Device) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let uniforms = ChromaticAberrationUniforms {
+            intensity: 0.004,
+            padding: [0.0; 3],
+        };
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[uniforms]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        ChromaticAberration {
+            enabled: false,
+            intensity: uniforms.intensity,
+            pass: FullscreenPass::new(
+                device,
+                include_str!("chromatic_aberration.wgsl"),
+                "chromatic_aberration_fragment",
+                HDR_FORMAT,
+                &[&layout],
+            ),
+            buffer,
+            bind_group,
+        }
+    }
+}
+
+impl PostEffect for ChromaticAberration {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            as_byte_slice(&[ChromaticAberrationUniforms {
+                intensity: self.intensity,
+                padding: [0.0; 3],
+            }]),
+        );
+        self.pass
+            .draw(context, encoder, input, output, &[&self.bind_group]);
+    }
+}
@@ -0,0 +1,21 @@
+struct ChromaticAberrationParams {
+    /// Offset of the red and blue channels in the corners, in UV units.
+    intensity: f32,
+}
+
+@group(1) @binding(0) var<uniform> chromatic_aberration: ChromaticAberrationParams;
+
+/// Scales red outwards and blue inwards from the center, growing with the squared radius
+/// like the lateral aberration of a lens.
+@fragment
+fn chromatic_aberration_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let offset = in.uv - 0.5;
+    // 1 in the corners.
+    let radius_squared = 2.0 * dot(offset, offset);
+    let shift = chromatic_aberration.intensity * radius_squared * normalize(offset + 1e-6);
+
+    let red = textureSampleLevel(input_texture, input_sampler, in.uv + shift, 0.0).r;
+    let green = textureSampleLevel(input_texture, input_sampler, in.uv, 0.0).g;
+    let blue = textureSampleLevel(input_texture, input_sampler, in.uv - shift, 0.0).b;
+    return vec4<f32>(red, green, blue, 1.0);
+}
@@ -0,0 +1,218 @@
+use std::path::Path;
+
+use cgmath::Vector3;
+use half::f16;
+use util::{BufferInitDescriptor, DeviceExt, TextureDataOrder};
+use wgpu::*;
+
+use super::{FullscreenPass, PostContext, PostEffect, HDR_FORMAT};
+use crate::render::as_byte_slice;
+
+/// A 3D color lookup table in the Resolve/Adobe `.cube` format.
+#[derive(Debug)]
+struct CubeLut {
+    size: u32,
+    domain_min: Vector3<f32>,
+    domain_max: Vector3<f32>,
+    /// Red varies fastest, then green, then blue.
+    entries: Vec<[f32; 3]>,
+}
+
+impl CubeLut {
+    fn parse(source: &str) -> Result<Self, String> {
+        let mut size = None;
+        let mut domain_min = Vector3::new(0.0, 0.0, 0.0);
+        let mut domain_max = Vector3::new(1.0, 1.0, 1.0);
+        let mut entries = Vec::new();
+
+        let vector = |values: &[&str]| -> Result<Vector3<f32>, String> {
+            let parsed: Vec<f32> = values
+                .iter()
+                .map(|value| value.parse().map_err(|_| format!("Invalid number {value}")))
+                .collect::<Result<_, _>>()?;
+            match parsed[..] {
+                [r, g, b] => Ok(Vector3::new(r, g, b)),
+                _ => Err(format!("Expected three numbers, found {}", values.len())),
+            }
+        };
+
+        for line in source.lines() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                [comment, ..] if comment.starts_with('#') => {}
+                ["TITLE", ..] => {}
+                ["LUT_3D_SIZE", value] => {
+                    size = Some(value.parse().map_err(|_| format!("Invalid size {value}"))?);
+                }
+                ["LUT_1D_SIZE", ..] => return Err("1D lookup tables are not supported".into()),
+                ["DOMAIN_MIN", values @ ..] => domain_min = vector(values)?,
+                ["DOMAIN_MAX", values @ ..] => domain_max = vector(values)?,
+                values => entries.push(vector(values)?.into()),
+            }
+        }
+
+        let size: u32 = size.ok_or("Missing LUT_3D_SIZE")?;
+        if entries.len() != size.pow(3) as usize {
+            return Err(format!(
+                "Expected {} entries for size {size}, found {}",
+                size.pow(3),
+                entries.len()
+            ));
+        }
+        Ok(CubeLut {
+            size,
+            domain_min,
+            domain_max,
+            entries,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct ColorGradingUniforms {
+    #[allow(dead_code)]
+    domain_min: Vector3<f32>,
+    #[allow(dead_code)]
+    padding_min: f32,
+    #[allow(dead_code)]
+    domain_max: Vector3<f32>,
+    #[allow(dead_code)]
+    padding_max: f32,
+}
+
+/// Remaps the tone mapped colors through a 3D lookup table.
+#[derive(Debug)]
+pub struct ColorGrading {
+    /// Has no effect until a lookup table is loaded.
+    pub enabled: bool,
+    pass: FullscreenPass,
+    layout: BindGroupLayout,
+    /// Binds the lookup table, if loaded.
+    bind_group: Option<BindGroup>,
+}
+
+impl ColorGrading {
+    pub fn new(device: &Device) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        ColorGrading {
+            enabled: false,
+            pass: FullscreenPass::new(
+                device,
+                include_str!("color_grading.wgsl"),
+                "color_grading_fragment",
+                HDR_FORMAT,
+                &[&layout],
+            ),
+            layout,
+            bind_group: None,
+        }
+    }
+
+    /// Loads a `.cube` lookup table and enables grading with it.
+    pub fn load(&mut self, device: &Device, queue: &Queue, path: &Path) -> Result<(), String> {
+        let source = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let lut = CubeLut::parse(&source)?;
+
+        let data: Vec<f16> = lut
+            .entries
+            .iter()
+            .flat_map(|&[r, g, b]| [r, g, b, 1.0].map(f16::from_f32))
+            .collect();
+        let texture = device.create_texture_with_data(
+            queue,
+            &TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width: lut.size,
+                    height: lut.size,
+                    depth_or_array_layers: lut.size,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D3,
+                format: TextureFormat::Rgba16Float,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            TextureDataOrder::LayerMajor,
+            as_byte_slice(&data),
+        );
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[ColorGradingUniforms {
+                domain_min: lut.domain_min,
+                padding_min: 0.0,
+                domain_max: lut.domain_max,
+                padding_max: 0.0,
+            }]),
+            usage: BufferUsages::UNIFORM,
+        });
+
+        self.bind_group = Some(device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(
+                        &texture.create_view(&Default::default()),
+                    ),
+                },
+            ],
+        }));
+        self.enabled = true;
+        Ok(())
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.bind_group.is_some()
+    }
+}
+
+impl PostEffect for ColorGrading {
+    fn enabled(&self) -> bool {
+        self.enabled && self.bind_group.is_some()
+    }
+
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        let bind_group = self.bind_group.as_ref().unwrap();
+        self.pass
+            .draw(context, encoder, input, output, &[bind_group]);
+    }
+}
@@ -0,0 +1,20 @@
+struct ColorGradingParams {
+    /// Input range of the lookup table.
+    domain_min: vec3<f32>,
+    domain_max: vec3<f32>,
+}
+
+@group(1) @binding(0) var<uniform> color_grading: ColorGradingParams;
+@group(1) @binding(1) var lut: texture_3d<f32>;
+
+/// Looks up the gamma-encoded color, as lookup tables are authored for display values.
+@fragment
+fn color_grading_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let color = linear_to_srgb(saturate(textureSampleLevel(input_texture, input_sampler, in.uv, 0.0).rgb));
+    let size = f32(textureDimensions(lut).x);
+    let normalized = saturate((color - color_grading.domain_min) / (color_grading.domain_max - color_grading.domain_min));
+    // Map onto texel centers, so that the ends of the domain hit the first and last entries.
+    let coordinates = (normalized * (size - 1.0) + 0.5) / size;
+    let graded = textureSampleLevel(lut, input_sampler, coordinates, 0.0).rgb;
+    return vec4<f32>(srgb_to_linear(saturate(graded)), 1.0);
+}
@@ -0,0 +1,174 @@
+use cgmath::{Matrix4, SquareMatrix, Vector3};
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::{depth_texture_declaration, FullscreenPass, PostContext, PostEffect, HDR_FORMAT};
+use crate::render::as_byte_slice;
+
+/// How the fog thickens with distance, in the order of the shader's constants.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum FogFalloff {
+    /// Ramps from none at the start distance to opaque at the end distance.
+    Linear,
+    /// Decays the visibility by the density per unit beyond the start distance.
+    #[default]
+    Exponential,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct FogUniforms {
+    #[allow(dead_code)]
+    inverse_view_projection: Matrix4<f32>,
+    #[allow(dead_code)]
+    camera_position: Vector3<f32>,
+    #[allow(dead_code)]
+    falloff: u32,
+    #[allow(dead_code)]
+    color: Vector3<f32>,
+    #[allow(dead_code)]
+    density: f32,
+    #[allow(dead_code)]
+    start: f32,
+    #[allow(dead_code)]
+    end: f32,
+    #[allow(dead_code)]
+    padding: [f32; 2],
+}
+
+/// Fades geometry into a uniform color by its distance, reconstructed from the depth buffer.
+#[derive(Debug)]
+pub struct Fog {
+    pub enabled: bool,
+    pub falloff: FogFalloff,
+    /// Linear color the distance fades into.
+    pub color: Vector3<f32>,
+    /// Per world unit, for exponential fog.
+    pub density: f32,
+    /// Distance from the camera at which the fog begins.
+    pub start: f32,
+    /// Distance from the camera at which linear fog becomes opaque.
+    pub end: f32,
+    /// Reading the depth buffer without and with MSAA.
+    passes: [FullscreenPass; 2],
+    layouts: [BindGroupLayout; 2],
+    buffer: Buffer,
+}
+
+impl Fog {
+    pub fn new(device: &Device) -> Self {
+        let create_layout = |multisampled: bool| {
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Depth,
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled,
+                        },
+                        count: None,
+                    },
+                ],
+            })
+        };
+        let layouts = [create_layout(false), create_layout(true)];
+        let create_pass = |multisampled: bool| {
+            let source = depth_texture_declaration(1, multisampled) + include_str!("fog.wgsl");
+            FullscreenPass::new(
+                device,
+                &source,
+                "fog_fragment",
+                HDR_FORMAT,
+                &[&layouts[multisampled as usize]],
+            )
+        };
+        let passes = [create_pass(false), create_pass(true)];
+
+        let uniforms = FogUniforms {
+            inverse_view_projection: Matrix4::identity(),
+            camera_position: Vector3::new(0.0, 0.0, 0.0),
+            falloff: FogFalloff::default() as u32,
+            color: Vector3::new(0.5, 0.6, 0.7),
+            density: 0.05,
+            start: 5.0,
+            end: 40.0,
+            padding: [0.0; 2],
+        };
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[uniforms]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        Fog {
+            enabled: false,
+            falloff: FogFalloff::default(),
+            color: uniforms.color,
+            density: uniforms.density,
+            start: uniforms.start,
+            end: uniforms.end,
+            passes,
+            layouts,
+            buffer,
+        }
+    }
+}
+
+impl PostEffect for Fog {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        let frame = &context.frame;
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            as_byte_slice(&[FogUniforms {
+                inverse_view_projection: frame.inverse_view_projection,
+                camera_position: frame.camera_position,
+                falloff: self.falloff as u32,
+                color: self.color,
+                density: self.density,
+                start: self.start,
+                end: self.end,
+                padding: [0.0; 2],
+            }]),
+        );
+
+        let index = frame.depth_multisampled as usize;
+        let bind_group = context.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.layouts[index],
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: self.buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(frame.depth),
+                },
+            ],
+        });
+        self.passes[index].draw(context, encoder, input, output, &[&bind_group]);
+    }
+}
@@ -0,0 +1,41 @@
+const FOG_LINEAR: u32 = 0u;
+const FOG_EXPONENTIAL: u32 = 1u;
+
+struct FogParams {
+    inverse_view_projection: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    falloff: u32,
+    color: vec3<f32>,
+    density: f32,
+    start: f32,
+    end: f32,
+}
+
+@group(1) @binding(0) var<uniform> fog: FogParams;
+
+/// Fraction of the surface's color hidden by fog at a distance from the camera.
+fn fog_amount(distance: f32) -> f32 {
+    switch fog.falloff {
+        case FOG_LINEAR: {
+            return saturate((distance - fog.start) / (fog.end - fog.start));
+        }
+        default: {
+            return 1.0 - exp(-fog.density * max(distance - fog.start, 0.0));
+        }
+    }
+}
+
+/// Blends geometry towards the fog color by its distance, leaving the sky as it is.
+@fragment
+fn fog_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(input_texture, input_sampler, in.uv, 0.0).rgb;
+    let depth = textureLoad(depth_texture, vec2<i32>(in.position.xy), 0);
+    if depth >= 1.0 {
+        return vec4<f32>(color, 1.0);
+    }
+
+    let ndc = vec2<f32>(2.0, -2.0) * in.uv + vec2<f32>(-1.0, 1.0);
+    let world = fog.inverse_view_projection * vec4<f32>(ndc, depth, 1.0);
+    let distance = length(world.xyz / world.w - fog.camera_position);
+    return vec4<f32>(mix(color, fog.color, fog_amount(distance)), 1.0);
+}
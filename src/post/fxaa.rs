@@ -0,0 +1,41 @@
+use wgpu::*;
+
+use super::{FullscreenPass, PostContext, PostEffect, HDR_FORMAT};
+
+/// Fast approximate anti-aliasing, smoothing edges found by their contrast in the final image.
+#[derive(Debug)]
+pub struct Fxaa {
+    pub enabled: bool,
+    pass: FullscreenPass,
+}
+
+impl Fxaa {
+    pub fn new(device: &Device) -> Self {
+        Fxaa {
+            enabled: false,
+            pass: FullscreenPass::new(
+                device,
+                include_str!("fxaa.wgsl"),
+                "fxaa_fragment",
+                HDR_FORMAT,
+                &[],
+            ),
+        }
+    }
+}
+
+impl PostEffect for Fxaa {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        self.pass.draw(context, encoder, input, output, &[]);
+    }
+}
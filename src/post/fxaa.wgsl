@@ -0,0 +1,104 @@
+/// Smallest contrast to smooth, absolute and relative to the brightest neighbor.
+const FXAA_EDGE_THRESHOLD_MIN: f32 = 0.0312;
+const FXAA_EDGE_THRESHOLD_MAX: f32 = 0.125;
+const FXAA_SEARCH_STEPS: i32 = 12;
+/// How much single-pixel features are blurred.
+const FXAA_SUBPIXEL_QUALITY: f32 = 0.75;
+
+/// Perceptual brightness, approximating gamma with a square root.
+fn fxaa_luma(uv: vec2<f32>) -> f32 {
+    let color = textureSampleLevel(input_texture, input_sampler, uv, 0.0).rgb;
+    return sqrt(dot(saturate(color), vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+/// Finds the edge through the pixel and its ends, then resamples across it in proportion
+/// to how close the pixel is to the nearer end.
+@fragment
+fn fxaa_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(input_texture));
+    let uv = in.uv;
+    let color = textureSampleLevel(input_texture, input_sampler, uv, 0.0);
+
+    let luma_center = fxaa_luma(uv);
+    let luma_up = fxaa_luma(uv + texel * vec2<f32>(0.0, -1.0));
+    let luma_down = fxaa_luma(uv + texel * vec2<f32>(0.0, 1.0));
+    let luma_left = fxaa_luma(uv + texel * vec2<f32>(-1.0, 0.0));
+    let luma_right = fxaa_luma(uv + texel * vec2<f32>(1.0, 0.0));
+
+    let luma_min = min(luma_center, min(min(luma_up, luma_down), min(luma_left, luma_right)));
+    let luma_max = max(luma_center, max(max(luma_up, luma_down), max(luma_left, luma_right)));
+    let luma_range = luma_max - luma_min;
+    if luma_range < max(FXAA_EDGE_THRESHOLD_MIN, luma_max * FXAA_EDGE_THRESHOLD_MAX) {
+        return color;
+    }
+
+    let luma_up_left = fxaa_luma(uv + texel * vec2<f32>(-1.0, -1.0));
+    let luma_up_right = fxaa_luma(uv + texel * vec2<f32>(1.0, -1.0));
+    let luma_down_left = fxaa_luma(uv + texel * vec2<f32>(-1.0, 1.0));
+    let luma_down_right = fxaa_luma(uv + texel * vec2<f32>(1.0, 1.0));
+
+    let luma_up_down = luma_up + luma_down;
+    let luma_left_right = luma_left + luma_right;
+    let luma_left_corners = luma_up_left + luma_down_left;
+    let luma_right_corners = luma_up_right + luma_down_right;
+    let luma_up_corners = luma_up_left + luma_up_right;
+    let luma_down_corners = luma_down_left + luma_down_right;
+
+    let edge_horizontal = abs(luma_left_corners - 2.0 * luma_left)
+        + 2.0 * abs(luma_up_down - 2.0 * luma_center)
+        + abs(luma_right_corners - 2.0 * luma_right);
+    let edge_vertical = abs(luma_up_corners - 2.0 * luma_up)
+        + 2.0 * abs(luma_left_right - 2.0 * luma_center)
+        + abs(luma_down_corners - 2.0 * luma_down);
+    let is_horizontal = edge_horizontal >= edge_vertical;
+
+    // Step across the edge towards the side with the steeper gradient.
+    let luma_negative = select(luma_left, luma_up, is_horizontal);
+    let luma_positive = select(luma_right, luma_down, is_horizontal);
+    let gradient_negative = abs(luma_negative - luma_center);
+    let gradient_positive = abs(luma_positive - luma_center);
+    let negative_steeper = gradient_negative >= gradient_positive;
+    let gradient_scaled = 0.25 * max(gradient_negative, gradient_positive);
+    let across = select(vec2<f32>(texel.x, 0.0), vec2<f32>(0.0, texel.y), is_horizontal)
+        * select(1.0, -1.0, negative_steeper);
+    let luma_local_average = 0.5 * (select(luma_positive, luma_negative, negative_steeper) + luma_center);
+
+    // Walk along the edge in both directions until the contrast to the edge's average changes.
+    let along = select(vec2<f32>(0.0, texel.y), vec2<f32>(texel.x, 0.0), is_horizontal);
+    let edge_uv = uv + 0.5 * across;
+    var steps = array<f32, 12>(1.0, 1.0, 1.0, 1.0, 1.0, 1.5, 2.0, 2.0, 2.0, 2.0, 4.0, 8.0);
+    var uv_negative = edge_uv - along;
+    var uv_positive = edge_uv + along;
+    var luma_end_negative = fxaa_luma(uv_negative) - luma_local_average;
+    var luma_end_positive = fxaa_luma(uv_positive) - luma_local_average;
+    var reached_negative = abs(luma_end_negative) >= gradient_scaled;
+    var reached_positive = abs(luma_end_positive) >= gradient_scaled;
+    for (var i = 1; i < FXAA_SEARCH_STEPS && !(reached_negative && reached_positive); i++) {
+        if !reached_negative {
+            uv_negative -= along * steps[i];
+            luma_end_negative = fxaa_luma(uv_negative) - luma_local_average;
+            reached_negative = abs(luma_end_negative) >= gradient_scaled;
+        }
+        if !reached_positive {
+            uv_positive += along * steps[i];
+            luma_end_positive = fxaa_luma(uv_positive) - luma_local_average;
+            reached_positive = abs(luma_end_positive) >= gradient_scaled;
+        }
+    }
+
+    let distance_negative = select(uv.y - uv_negative.y, uv.x - uv_negative.x, is_horizontal);
+    let distance_positive = select(uv_positive.y - uv.y, uv_positive.x - uv.x, is_horizontal);
+    let negative_nearer = distance_negative < distance_positive;
+    let pixel_offset = 0.5 - min(distance_negative, distance_positive) / (distance_negative + distance_positive);
+    // Only blend if the nearer end varies in the direction opposite to the center.
+    let luma_end = select(luma_end_positive, luma_end_negative, negative_nearer);
+    let correct_variation = (luma_end < 0.0) != (luma_center < luma_local_average);
+    var offset = select(0.0, pixel_offset, correct_variation);
+
+    let luma_average = (2.0 * (luma_up_down + luma_left_right) + luma_left_corners + luma_right_corners) / 12.0;
+    let subpixel = saturate(abs(luma_average - luma_center) / luma_range);
+    let subpixel_smooth = (3.0 - 2.0 * subpixel) * subpixel * subpixel;
+    offset = max(offset, subpixel_smooth * subpixel_smooth * FXAA_SUBPIXEL_QUALITY);
+
+    return textureSampleLevel(input_texture, input_sampler, uv + offset * across, 0.0);
+}
@@ -0,0 +1,121 @@
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::{FullscreenPass, PostContext, PostEffect, HDR_FORMAT};
+use crate::render::as_byte_slice;
+
+#[derive(Debug, Copy, Clone)]
+struct MotionBlurUniforms {
+    #[allow(dead_code)]
+    shutter: f32,
+    #[allow(dead_code)]
+    padding: [f32; 3],
+}
+
+/// Smears pixels along their motion since the previous frame, read from the velocity buffer.
+#[derive(Debug)]
+pub struct MotionBlur {
+    /// Requires the velocity buffer to be rendered.
+    pub enabled: bool,
+    /// Fraction of the frame's motion the shutter is open for, 0.5 like a 180° film shutter.
+    pub shutter: f32,
+    pass: FullscreenPass,
+    buffer: Buffer,
+    layout: BindGroupLayout,
+}
+
+impl MotionBlur {
+    pub fn new(device: &Device) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let uniforms = MotionBlurUniforms {
+            shutter: 0.5,
+            padding: [0.0; 3],
+        };
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[uniforms]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        MotionBlur {
+            enabled: false,
+            shutter: uniforms.shutter,
+            pass: FullscreenPass::new(
+                device,
+                include_str!("motion_blur.wgsl"),
+                "motion_blur_fragment",
+                HDR_FORMAT,
+                &[&layout],
+            ),
+            buffer,
+            layout,
+        }
+    }
+}
+
+impl PostEffect for MotionBlur {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        let velocity = context
+            .frame
+            .velocity
+            .expect("Motion blur requires the velocity buffer");
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            as_byte_slice(&[MotionBlurUniforms {
+                shutter: self.shutter,
+                padding: [0.0; 3],
+            }]),
+        );
+        let bind_group = context.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: self.buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(velocity),
+                },
+            ],
+        });
+        self.pass
+            .draw(context, encoder, input, output, &[&bind_group]);
+    }
+}
@@ -0,0 +1,28 @@
+struct MotionBlurParams {
+    /// Fraction of the frame's motion the shutter is open for.
+    shutter: f32,
+}
+
+@group(1) @binding(0) var<uniform> motion_blur: MotionBlurParams;
+@group(1) @binding(1) var velocity_texture: texture_2d<f32>;
+
+const MOTION_BLUR_SAMPLES: i32 = 12;
+/// Longest smear in UV units, limiting the blur of very fast motion.
+const MOTION_BLUR_MAX_LENGTH: f32 = 0.05;
+
+/// Averages the input along the pixel's motion, centered on the pixel.
+@fragment
+fn motion_blur_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    var velocity = textureLoad(velocity_texture, vec2<i32>(in.position.xy), 0).xy * motion_blur.shutter;
+    let speed = length(velocity);
+    if speed > MOTION_BLUR_MAX_LENGTH {
+        velocity *= MOTION_BLUR_MAX_LENGTH / speed;
+    }
+
+    var color = vec3<f32>(0.0);
+    for (var i = 0; i < MOTION_BLUR_SAMPLES; i++) {
+        let t = (f32(i) + 0.5) / f32(MOTION_BLUR_SAMPLES) - 0.5;
+        color += textureSampleLevel(input_texture, input_sampler, in.uv - t * velocity, 0.0).rgb;
+    }
+    return vec4<f32>(color / f32(MOTION_BLUR_SAMPLES), 1.0);
+}
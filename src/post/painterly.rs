@@ -0,0 +1,142 @@
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::{PostContext, PostEffect, HDR_FORMAT};
+use crate::render::as_byte_slice;
+
+#[derive(Debug, Copy, Clone)]
+struct PainterlyUniforms {
+    #[allow(dead_code)]
+    radius: i32,
+    #[allow(dead_code)]
+    padding: [i32; 3],
+}
+
+/// A non-photorealistic look like brush strokes, from a Kuwahara filter in a compute pass writing
+/// the output directly.
+#[derive(Debug)]
+pub struct Painterly {
+    pub enabled: bool,
+    /// In pixels, larger for coarser strokes.
+    pub radius: u32,
+    pipeline: ComputePipeline,
+    layout: BindGroupLayout,
+    buffer: Buffer,
+}
+
+impl Painterly {
+    pub fn new(device: &Device) -> Self {
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(include_str!("painterly.wgsl").into()),
+        });
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: HDR_FORMAT,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: None,
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                bind_group_layouts: &[&layout],
+                ..Default::default()
+            })),
+            module: &module,
+            entry_point: Some("kuwahara"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let uniforms = PainterlyUniforms {
+            radius: 4,
+            padding: [0; 3],
+        };
+
+        Painterly {
+            enabled: false,
+            radius: uniforms.radius as u32,
+            pipeline,
+            layout,
+            buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                contents: as_byte_slice(&[uniforms]),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            }),
+        }
+    }
+}
+
+impl PostEffect for Painterly {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            as_byte_slice(&[PainterlyUniforms {
+                radius: self.radius as i32,
+                padding: [0; 3],
+            }]),
+        );
+        let bind_group = context.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(input),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(output),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(context.width.div_ceil(8), context.height.div_ceil(8), 1);
+    }
+}
@@ -0,0 +1,45 @@
+struct PainterlyParams {
+    /// Size of each of the four sectors beyond the center pixel.
+    radius: i32,
+}
+
+@group(0) @binding(0) var input_texture: texture_2d<f32>;
+@group(0) @binding(1) var output_texture: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(2) var<uniform> painterly: PainterlyParams;
+
+/// Kuwahara filter: averages whichever of the four square sectors meeting at the pixel is the
+/// most uniform, flattening areas into strokes of color while keeping their edges.
+@compute @workgroup_size(8, 8)
+fn kuwahara(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<i32>(textureDimensions(input_texture));
+    let pixel = vec2<i32>(id.xy);
+    if any(pixel >= size) {
+        return;
+    }
+
+    let radius = painterly.radius;
+    let count = f32((radius + 1) * (radius + 1));
+    var best_mean = vec3<f32>(0.0);
+    var best_variance = 1e30;
+    for (var sector = 0; sector < 4; sector++) {
+        let direction = vec2<i32>(select(-1, 1, (sector & 1) != 0), select(-1, 1, (sector & 2) != 0));
+        var sum = vec3<f32>(0.0);
+        var square_sum = vec3<f32>(0.0);
+        for (var y = 0; y <= radius; y++) {
+            for (var x = 0; x <= radius; x++) {
+                let neighbor = clamp(pixel + direction * vec2<i32>(x, y), vec2<i32>(0), size - 1);
+                let color = textureLoad(input_texture, neighbor, 0).rgb;
+                sum += color;
+                square_sum += color * color;
+            }
+        }
+        let mean = sum / count;
+        let variance = square_sum / count - mean * mean;
+        let total_variance = variance.r + variance.g + variance.b;
+        if total_variance < best_variance {
+            best_variance = total_variance;
+            best_mean = mean;
+        }
+    }
+    textureStore(output_texture, pixel, vec4<f32>(best_mean, 1.0));
+}
@@ -0,0 +1,124 @@
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::{create_target, FullscreenPass, PostContext, PostEffect, HDR_FORMAT};
+use crate::render::as_byte_slice;
+
+#[derive(Debug, Copy, Clone)]
+struct PixelateUniforms {
+    #[allow(dead_code)]
+    levels: u32,
+    #[allow(dead_code)]
+    padding: [u32; 3],
+}
+
+/// A retro look, downsampling the tone mapped image to a low resolution and enlarging it with
+/// hard pixel edges.
+#[derive(Debug)]
+pub struct Pixelate {
+    pub enabled: bool,
+    /// Height of the low resolution image, whose width follows from the aspect ratio.
+    pub height: u32,
+    /// Levels per color channel to quantize to, if any.
+    pub levels: Option<u32>,
+    downsample: FullscreenPass,
+    upscale: FullscreenPass,
+    buffer: Buffer,
+    bind_group: BindGroup,
+    /// The low resolution image, sized for the output it was last created for.
+    target: TextureView,
+    target_size: (u32, u32),
+}
+
+impl Pixelate {
+    pub fn new(device: &Device) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[PixelateUniforms {
+                levels: 0,
+                padding: [0; 3],
+            }]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Pixelate {
+            enabled: false,
+            height: 180,
+            levels: None,
+            downsample: FullscreenPass::new(
+                device,
+                include_str!("pixelate.wgsl"),
+                "pixelate_downsample_fragment",
+                HDR_FORMAT,
+                &[&layout],
+            ),
+            upscale: FullscreenPass::new(
+                device,
+                include_str!("pixelate.wgsl"),
+                "pixelate_upscale_fragment",
+                HDR_FORMAT,
+                &[],
+            ),
+            buffer,
+            bind_group,
+            target: create_target(device, 1, 1),
+            target_size: (1, 1),
+        }
+    }
+}
+
+impl PostEffect for Pixelate {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        let height = self.height.clamp(1, context.height);
+        let width = (height as f32 * context.width as f32 / context.height as f32).round() as u32;
+        let size = (width.max(1), height);
+        if size != self.target_size {
+            self.target = create_target(context.device, size.0, size.1);
+            self.target_size = size;
+        }
+
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            as_byte_slice(&[PixelateUniforms {
+                levels: self.levels.map_or(0, |levels| levels.max(2)),
+                padding: [0; 3],
+            }]),
+        );
+        self.downsample
+            .draw(context, encoder, input, &self.target, &[&self.bind_group]);
+        self.upscale
+            .draw(context, encoder, &self.target, output, &[]);
+    }
+}
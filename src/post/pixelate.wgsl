@@ -0,0 +1,27 @@
+struct PixelateParams {
+    /// Levels per color channel after quantization, zero to keep the colors.
+    levels: u32,
+}
+
+@group(1) @binding(0) var<uniform> pixelate: PixelateParams;
+
+/// Point samples the input at the center of each low resolution pixel, quantizing the
+/// gamma-encoded color to a few levels per channel.
+@fragment
+fn pixelate_downsample_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(input_texture));
+    let color = textureLoad(input_texture, vec2<i32>(in.uv * size), 0).rgb;
+    if pixelate.levels == 0u {
+        return vec4<f32>(color, 1.0);
+    }
+    let steps = f32(pixelate.levels - 1u);
+    let quantized = round(linear_to_srgb(saturate(color)) * steps) / steps;
+    return vec4<f32>(srgb_to_linear(quantized), 1.0);
+}
+
+/// Enlarges the low resolution image without filtering.
+@fragment
+fn pixelate_upscale_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(input_texture));
+    return textureLoad(input_texture, vec2<i32>(in.uv * size), 0);
+}
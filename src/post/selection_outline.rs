@@ -0,0 +1,166 @@
+use cgmath::Vector4;
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::{FullscreenPass, PostContext, PostEffect, HDR_FORMAT};
+use crate::render::as_byte_slice;
+
+pub const SELECTION_MASK_FORMAT: TextureFormat = TextureFormat::R8Unorm;
+
+#[derive(Debug, Copy, Clone)]
+struct SelectionOutlineUniforms {
+    #[allow(dead_code)]
+    color: Vector4<f32>,
+    #[allow(dead_code)]
+    width: f32,
+    #[allow(dead_code)]
+    padding: [f32; 3],
+}
+
+/// Outlines the selected objects, which the renderer draws into the mask beforehand.
+#[derive(Debug)]
+pub struct SelectionOutline {
+    /// Whether anything is selected.
+    pub enabled: bool,
+    /// Linear color, blended by its alpha.
+    pub color: Vector4<f32>,
+    /// In pixels.
+    pub width: f32,
+    /// Nonzero where a selected object is, even behind others.
+    pub mask: TextureView,
+    pass: FullscreenPass,
+    buffer: Buffer,
+    layout: BindGroupLayout,
+    bind_group: BindGroup,
+}
+
+impl SelectionOutline {
+    pub fn new(device: &Device, width: u32, height: u32) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let uniforms = SelectionOutlineUniforms {
+            color: Vector4::new(1.0, 0.45, 0.05, 1.0),
+            width: 3.0,
+            padding: [0.0; 3],
+        };
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[uniforms]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let mask = create_mask(device, width, height);
+        let bind_group = create_bind_group(device, &layout, &buffer, &mask);
+
+        SelectionOutline {
+            enabled: false,
+            color: uniforms.color,
+            width: uniforms.width,
+            mask,
+            pass: FullscreenPass::new(
+                device,
+                include_str!("selection_outline.wgsl"),
+                "selection_outline_fragment",
+                HDR_FORMAT,
+                &[&layout],
+            ),
+            buffer,
+            layout,
+            bind_group,
+        }
+    }
+}
+
+impl PostEffect for SelectionOutline {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            as_byte_slice(&[SelectionOutlineUniforms {
+                color: self.color,
+                width: self.width,
+                padding: [0.0; 3],
+            }]),
+        );
+        self.pass
+            .draw(context, encoder, input, output, &[&self.bind_group]);
+    }
+
+    fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        self.mask = create_mask(device, width, height);
+        self.bind_group = create_bind_group(device, &self.layout, &self.buffer, &self.mask);
+    }
+}
+
+fn create_mask(device: &Device, width: u32, height: u32) -> TextureView {
+    device
+        .create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: SELECTION_MASK_FORMAT,
+            view_formats: &[],
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        })
+        .create_view(&Default::default())
+}
+
+fn create_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    buffer: &Buffer,
+    mask: &TextureView,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::TextureView(mask),
+            },
+        ],
+    })
+}
@@ -0,0 +1,34 @@
+struct SelectionOutlineParams {
+    color: vec4<f32>,
+    /// In pixels.
+    width: f32,
+}
+
+@group(1) @binding(0) var<uniform> selection_outline: SelectionOutlineParams;
+/// Covered by the selected objects, regardless of occlusion.
+@group(1) @binding(1) var selection_mask: texture_2d<f32>;
+
+/// Dilates the mask by the outline width and colors the dilated pixels outside of it.
+@fragment
+fn selection_outline_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(input_texture, input_sampler, in.uv, 0.0);
+    let pixel = vec2<i32>(in.position.xy);
+    if textureLoad(selection_mask, pixel, 0).r > 0.0 {
+        return color;
+    }
+
+    let size = vec2<i32>(textureDimensions(selection_mask));
+    let radius = i32(ceil(selection_outline.width));
+    var coverage = 0.0;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let distance = length(vec2<f32>(f32(x), f32(y)));
+            let neighbor = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let mask = textureLoad(selection_mask, neighbor, 0).r;
+            // Antialias the outer edge of the outline.
+            coverage = max(coverage, mask * saturate(selection_outline.width + 0.5 - distance));
+        }
+    }
+    let outline = selection_outline.color;
+    return vec4<f32>(mix(color.rgb, outline.rgb, coverage * outline.a), 1.0);
+}
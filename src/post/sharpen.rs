@@ -0,0 +1,98 @@
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::{FullscreenPass, PostContext, PostEffect, HDR_FORMAT};
+use crate::render::as_byte_slice;
+
+#[derive(Debug, Copy, Clone)]
+struct SharpenUniforms {
+    #[allow(dead_code)]
+    sharpness: f32,
+    #[allow(dead_code)]
+    padding: [f32; 3],
+}
+
+/// Contrast-adaptive sharpening of the tone mapped image, restoring detail softened by TAA.
+#[derive(Debug)]
+pub struct Sharpen {
+    pub enabled: bool,
+    /// From 0 to 1.
+    pub sharpness: f32,
+    pass: FullscreenPass,
+    buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl Sharpen {
+    pub fn new(device: &Device) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let uniforms = SharpenUniforms {
+            sharpness: 0.5,
+            padding: [0.0; 3],
+        };
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[uniforms]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Sharpen {
+            enabled: false,
+            sharpness: uniforms.sharpness,
+            pass: FullscreenPass::new(
+                device,
+                include_str!("sharpen.wgsl"),
+                "sharpen_fragment",
+                HDR_FORMAT,
+                &[&layout],
+            ),
+            buffer,
+            bind_group,
+        }
+    }
+}
+
+impl PostEffect for Sharpen {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            as_byte_slice(&[SharpenUniforms {
+                sharpness: self.sharpness.clamp(0.0, 1.0),
+                padding: [0.0; 3],
+            }]),
+        );
+        self.pass
+            .draw(context, encoder, input, output, &[&self.bind_group]);
+    }
+}
@@ -0,0 +1,27 @@
+struct SharpenParams {
+    /// From 0 to 1.
+    sharpness: f32,
+}
+
+@group(1) @binding(0) var<uniform> sharpen: SharpenParams;
+
+/// Contrast-adaptive sharpening, after AMD's FidelityFX CAS. Subtracts the neighbors weighted by
+/// how much headroom the local contrast leaves, so that edges are enhanced without ringing.
+@fragment
+fn sharpen_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.position.xy);
+    let size = vec2<i32>(textureDimensions(input_texture));
+    let n = textureLoad(input_texture, clamp(pixel + vec2<i32>(0, -1), vec2<i32>(0), size - 1), 0).rgb;
+    let w = textureLoad(input_texture, clamp(pixel + vec2<i32>(-1, 0), vec2<i32>(0), size - 1), 0).rgb;
+    let c = textureLoad(input_texture, pixel, 0).rgb;
+    let e = textureLoad(input_texture, clamp(pixel + vec2<i32>(1, 0), vec2<i32>(0), size - 1), 0).rgb;
+    let s = textureLoad(input_texture, clamp(pixel + vec2<i32>(0, 1), vec2<i32>(0), size - 1), 0).rgb;
+
+    let minimum = min(c, min(min(n, s), min(w, e)));
+    let maximum = max(c, max(max(n, s), max(w, e)));
+    // Soft falloff of the sharpening where the neighborhood approaches black or white.
+    let amount = sqrt(saturate(min(minimum, 1.0 - maximum) / max(maximum, vec3<f32>(1e-5))));
+    let weight = -amount / mix(8.0, 5.0, sharpen.sharpness);
+    let color = ((n + w + e + s) * weight + c) / (1.0 + 4.0 * weight);
+    return vec4<f32>(saturate(color), 1.0);
+}
@@ -0,0 +1,112 @@
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::{FullscreenPass, PostContext, PostEffect, HDR_FORMAT};
+use crate::render::as_byte_slice;
+
+#[derive(Debug, Copy, Clone)]
+struct StylizeUniforms {
+    #[allow(dead_code)]
+    vignette_strength: f32,
+    #[allow(dead_code)]
+    vignette_falloff: f32,
+    #[allow(dead_code)]
+    grain_strength: f32,
+    #[allow(dead_code)]
+    seed: u32,
+}
+
+/// A vignette darkening the corners and animated film grain, on the tone mapped image.
+#[derive(Debug)]
+pub struct Stylize {
+    pub enabled: bool,
+    /// Darkening in the corners, from 0 to 1.
+    pub vignette_strength: f32,
+    /// Fraction of the distance from the corners to the center over which the vignette fades in.
+    pub vignette_falloff: f32,
+    /// Amplitude of the grain in the midtones.
+    pub grain_strength: f32,
+    pass: FullscreenPass,
+    buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl Stylize {
+    pub fn new(device: &Device) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let uniforms = StylizeUniforms {
+            vignette_strength: 0.4,
+            vignette_falloff: 0.8,
+            grain_strength: 0.08,
+            seed: 0,
+        };
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[uniforms]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Stylize {
+            enabled: false,
+            vignette_strength: uniforms.vignette_strength,
+            vignette_falloff: uniforms.vignette_falloff,
+            grain_strength: uniforms.grain_strength,
+            pass: FullscreenPass::new(
+                device,
+                include_str!("stylize.wgsl"),
+                "stylize_fragment",
+                HDR_FORMAT,
+                &[&layout],
+            ),
+            buffer,
+            bind_group,
+        }
+    }
+}
+
+impl PostEffect for Stylize {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            as_byte_slice(&[StylizeUniforms {
+                vignette_strength: self.vignette_strength,
+                vignette_falloff: self.vignette_falloff,
+                grain_strength: self.grain_strength,
+                seed: context.frame.frame_index,
+            }]),
+        );
+        self.pass
+            .draw(context, encoder, input, output, &[&self.bind_group]);
+    }
+}
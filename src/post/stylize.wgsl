@@ -0,0 +1,35 @@
+struct StylizeParams {
+    /// Darkening in the corners.
+    vignette_strength: f32,
+    /// Fraction of the distance to the corners over which the vignette fades in.
+    vignette_falloff: f32,
+    grain_strength: f32,
+    /// Changes every frame, animating the grain.
+    seed: u32,
+}
+
+@group(1) @binding(0) var<uniform> stylize: StylizeParams;
+
+/// PCG hash, uniformly distributed in [0, 1).
+fn stylize_noise(pixel: vec2<u32>, seed: u32) -> f32 {
+    var state = pixel.x * 747796405u + pixel.y * 2891336453u + seed * 277803737u;
+    state = state * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return f32((word >> 22u) ^ word) / 4294967296.0;
+}
+
+@fragment
+fn stylize_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    var color = textureSampleLevel(input_texture, input_sampler, in.uv, 0.0).rgb;
+
+    // 0 in the center and 1 in the corners.
+    let distance = length(in.uv - 0.5) * sqrt(2.0);
+    color *= 1.0 - stylize.vignette_strength * smoothstep(1.0 - stylize.vignette_falloff, 1.0, distance);
+
+    // Grain is most visible in the midtones, like on film.
+    let luminance = saturate(dot(color, vec3<f32>(0.2126, 0.7152, 0.0722)));
+    let noise = stylize_noise(vec2<u32>(in.position.xy), stylize.seed) - 0.5;
+    color += stylize.grain_strength * noise * 4.0 * luminance * (1.0 - luminance);
+
+    return vec4<f32>(max(color, vec3<f32>(0.0)), 1.0);
+}
@@ -0,0 +1,168 @@
+use cgmath::{Matrix4, SquareMatrix};
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::{create_target, FullscreenPass, PostContext, PostEffect, HDR_FORMAT};
+use crate::render::as_byte_slice;
+
+/// Weight of each new frame in the accumulated history.
+const TAA_BLEND: f32 = 0.1;
+
+#[derive(Debug, Copy, Clone)]
+struct TaaUniforms {
+    #[allow(dead_code)]
+    reprojection: Matrix4<f32>,
+    #[allow(dead_code)]
+    blend: f32,
+    #[allow(dead_code)]
+    padding: [f32; 3],
+}
+
+/// Temporal anti-aliasing, accumulating frames rendered with sub-pixel jitter.
+#[derive(Debug)]
+pub struct Taa {
+    pub enabled: bool,
+    resolve: FullscreenPass,
+    copy: FullscreenPass,
+    buffer: Buffer,
+    layout: BindGroupLayout,
+    /// Accumulated frames, alternately read and written.
+    history: [TextureView; 2],
+    /// Index of the history written last.
+    current: usize,
+    /// Whether the history holds an earlier frame.
+    history_valid: bool,
+}
+
+impl Taa {
+    pub fn new(device: &Device, width: u32, height: u32) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Depth,
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[TaaUniforms {
+                reprojection: Matrix4::identity(),
+                blend: 1.0,
+                padding: [0.0; 3],
+            }]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        Taa {
+            enabled: false,
+            resolve: FullscreenPass::new(
+                device,
+                include_str!("taa.wgsl"),
+                "taa_fragment",
+                HDR_FORMAT,
+                &[&layout],
+            ),
+            copy: FullscreenPass::new(device, "", "copy_fragment", HDR_FORMAT, &[]),
+            buffer,
+            layout,
+            history: [
+                create_target(device, width, height),
+                create_target(device, width, height),
+            ],
+            current: 0,
+            history_valid: false,
+        }
+    }
+
+    /// Discards the history, for example after the effect was disabled for a while.
+    pub fn reset(&mut self) {
+        self.history_valid = false;
+    }
+}
+
+impl PostEffect for Taa {
+    fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        let frame = &context.frame;
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            as_byte_slice(&[TaaUniforms {
+                reprojection: frame.previous_view_projection * frame.inverse_view_projection,
+                blend: if self.history_valid { TAA_BLEND } else { 1.0 },
+                padding: [0.0; 3],
+            }]),
+        );
+
+        let previous = &self.history[self.current];
+        self.current = 1 - self.current;
+        let next = &self.history[self.current];
+        let bind_group = context.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: self.buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(previous),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(frame.depth),
+                },
+            ],
+        });
+        self.resolve
+            .draw(context, encoder, input, next, &[&bind_group]);
+        self.copy.draw(context, encoder, next, output, &[]);
+        self.history_valid = true;
+    }
+
+    fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        self.history = [
+            create_target(device, width, height),
+            create_target(device, width, height),
+        ];
+        self.history_valid = false;
+    }
+}
@@ -0,0 +1,61 @@
+struct TaaParams {
+    /// Maps the current frame's normalized device coordinates to the previous frame's clip space.
+    reprojection: mat4x4<f32>,
+    /// Weight of the current frame, or 1 while there is no history.
+    blend: f32,
+}
+
+@group(1) @binding(0) var<uniform> taa: TaaParams;
+@group(1) @binding(1) var history_texture: texture_2d<f32>;
+@group(1) @binding(2) var depth_texture: texture_depth_2d;
+
+fn rgb_to_ycocg(color: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        dot(color, vec3<f32>(0.25, 0.5, 0.25)),
+        dot(color, vec3<f32>(0.5, 0.0, -0.5)),
+        dot(color, vec3<f32>(-0.25, 0.5, -0.25)),
+    );
+}
+
+fn ycocg_to_rgb(color: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(color.x + color.y - color.z, color.x + color.z, color.x - color.y - color.z);
+}
+
+/// Weighs HDR colors inversely to their brightness, so that single bright samples do not flicker.
+fn taa_weight(color: vec3<f32>) -> f32 {
+    return 1.0 / (1.0 + max(color.r, max(color.g, color.b)));
+}
+
+/// Blends the jittered frame into the history, reprojected by the camera motion
+/// and clamped to the current neighborhood to reject disoccluded and changed pixels.
+@fragment
+fn taa_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let size = vec2<i32>(textureDimensions(input_texture));
+    let pixel = vec2<i32>(in.position.xy);
+    let current = textureLoad(input_texture, pixel, 0).rgb;
+
+    var minimum = rgb_to_ycocg(current);
+    var maximum = minimum;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbor = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let color = rgb_to_ycocg(textureLoad(input_texture, neighbor, 0).rgb);
+            minimum = min(minimum, color);
+            maximum = max(maximum, color);
+        }
+    }
+
+    let depth = textureLoad(depth_texture, pixel, 0);
+    let ndc = vec2<f32>(2.0, -2.0) * in.uv + vec2<f32>(-1.0, 1.0);
+    let previous = taa.reprojection * vec4<f32>(ndc, depth, 1.0);
+    let previous_uv = previous.xy / previous.w * vec2<f32>(0.5, -0.5) + 0.5;
+    if any(previous_uv != saturate(previous_uv)) {
+        return vec4<f32>(current, 1.0);
+    }
+
+    let history = textureSampleLevel(history_texture, input_sampler, previous_uv, 0.0).rgb;
+    let clamped = ycocg_to_rgb(clamp(rgb_to_ycocg(history), minimum, maximum));
+    let current_weight = taa.blend * taa_weight(current);
+    let history_weight = (1.0 - taa.blend) * taa_weight(clamped);
+    return vec4<f32>((current * current_weight + clamped * history_weight) / (current_weight + history_weight), 1.0);
+}
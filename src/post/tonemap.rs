@@ -0,0 +1,151 @@
+use util::{BufferInitDescriptor, DeviceExt};
+use wgpu::*;
+
+use super::{auto_exposure::AutoExposure, FullscreenPass, PostContext, PostEffect, HDR_FORMAT};
+use crate::render::as_byte_slice;
+
+/// Curve compressing HDR colors into the displayable range, in the order of the shader's constants.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Tonemapper {
+    /// Filmic curve with a toe and a desaturating shoulder.
+    #[default]
+    Aces,
+    /// `x / (1 + x)` on the luminance.
+    Reinhard,
+    /// Khronos PBR Neutral, keeping base colors intact up to a high threshold.
+    Neutral,
+    /// Clips at white.
+    Clamp,
+}
+
+impl Tonemapper {
+    pub fn next(self) -> Self {
+        match self {
+            Tonemapper::Aces => Tonemapper::Reinhard,
+            Tonemapper::Reinhard => Tonemapper::Neutral,
+            Tonemapper::Neutral => Tonemapper::Clamp,
+            Tonemapper::Clamp => Tonemapper::Aces,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct TonemapUniforms {
+    #[allow(dead_code)]
+    exposure: f32,
+    #[allow(dead_code)]
+    mapping: u32,
+    #[allow(dead_code)]
+    auto_exposure: u32,
+    #[allow(dead_code)]
+    padding: u32,
+}
+
+/// Scales the scene by the exposure and maps it to the displayable range.
+#[derive(Debug)]
+pub struct Tonemap {
+    pub tonemapper: Tonemapper,
+    /// In stops, so each step doubles or halves the brightness. Compensates the adapted exposure
+    /// while automatic exposure is enabled.
+    pub exposure: f32,
+    pub auto_exposure: AutoExposure,
+    pass: FullscreenPass,
+    buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+impl Tonemap {
+    pub fn new(device: &Device) -> Self {
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: as_byte_slice(&[TonemapUniforms {
+                exposure: 0.0,
+                mapping: 0,
+                auto_exposure: 0,
+                padding: 0,
+            }]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let auto_exposure = AutoExposure::new(device);
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: auto_exposure.buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        Tonemap {
+            tonemapper: Tonemapper::default(),
+            exposure: 0.0,
+            auto_exposure,
+            pass: FullscreenPass::new(
+                device,
+                include_str!("tonemap.wgsl"),
+                "tonemap_fragment",
+                HDR_FORMAT,
+                &[&layout],
+            ),
+            buffer,
+            bind_group,
+        }
+    }
+}
+
+impl PostEffect for Tonemap {
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        if self.auto_exposure.enabled {
+            self.auto_exposure.measure(context, encoder, input);
+        }
+        context.queue.write_buffer(
+            &self.buffer,
+            0,
+            as_byte_slice(&[TonemapUniforms {
+                exposure: self.exposure,
+                mapping: self.tonemapper as u32,
+                auto_exposure: self.auto_exposure.enabled as u32,
+                padding: 0,
+            }]),
+        );
+        self.pass
+            .draw(context, encoder, input, output, &[&self.bind_group]);
+    }
+}
@@ -0,0 +1,65 @@
+struct TonemapParams {
+    /// In stops, added to the adapted exposure if enabled.
+    exposure: f32,
+    mapping: u32,
+    auto_exposure: u32,
+}
+
+@group(1) @binding(0) var<uniform> tonemap: TonemapParams;
+/// In stops, adapted to the scene's average luminance.
+@group(1) @binding(1) var<storage, read> adapted_exposure: f32;
+
+const TONEMAP_ACES: u32 = 0u;
+const TONEMAP_REINHARD: u32 = 1u;
+const TONEMAP_NEUTRAL: u32 = 2u;
+
+/// Narkowicz's fit of the ACES filmic curve.
+fn tonemap_aces(color: vec3<f32>) -> vec3<f32> {
+    return saturate(color * (2.51 * color + 0.03) / (color * (2.43 * color + 0.59) + 0.14));
+}
+
+/// Reinhard on the luminance, which preserves hues.
+fn tonemap_reinhard(color: vec3<f32>) -> vec3<f32> {
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    return color / (1.0 + luminance);
+}
+
+/// Khronos PBR Neutral, which keeps colors below the compression threshold unchanged.
+fn tonemap_neutral(input: vec3<f32>) -> vec3<f32> {
+    let start_compression = 0.8 - 0.04;
+    let desaturation = 0.15;
+
+    let x = min(input.r, min(input.g, input.b));
+    let offset = select(0.04, x - 6.25 * x * x, x < 0.08);
+    var color = input - offset;
+
+    let peak = max(color.r, max(color.g, color.b));
+    if peak < start_compression {
+        return color;
+    }
+    let d = 1.0 - start_compression;
+    let new_peak = 1.0 - d * d / (peak + d - start_compression);
+    color *= new_peak / peak;
+    let g = 1.0 - 1.0 / (desaturation * (peak - new_peak) + 1.0);
+    return mix(color, vec3<f32>(new_peak), g);
+}
+
+@fragment
+fn tonemap_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let exposure = tonemap.exposure + select(0.0, adapted_exposure, tonemap.auto_exposure != 0u);
+    let color = textureSampleLevel(input_texture, input_sampler, in.uv, 0.0).rgb * exp2(exposure);
+    switch tonemap.mapping {
+        case TONEMAP_ACES: {
+            return vec4<f32>(tonemap_aces(color), 1.0);
+        }
+        case TONEMAP_REINHARD: {
+            return vec4<f32>(tonemap_reinhard(color), 1.0);
+        }
+        case TONEMAP_NEUTRAL: {
+            return vec4<f32>(tonemap_neutral(color), 1.0);
+        }
+        default: {
+            return vec4<f32>(saturate(color), 1.0);
+        }
+    }
+}
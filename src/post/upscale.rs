@@ -0,0 +1,36 @@
+use wgpu::*;
+
+use super::{FullscreenPass, PostContext, PostEffect, HDR_FORMAT};
+
+/// Edge-adaptive spatial upscaling from the render resolution to the display resolution, in the
+/// manner of FSR 1. Only runs while the scene is rendered at a lower resolution.
+#[derive(Debug)]
+pub struct Upscale {
+    pass: FullscreenPass,
+}
+
+impl Upscale {
+    pub fn new(device: &Device) -> Self {
+        Upscale {
+            pass: FullscreenPass::new(
+                device,
+                include_str!("upscale.wgsl"),
+                "upscale_fragment",
+                HDR_FORMAT,
+                &[],
+            ),
+        }
+    }
+}
+
+impl PostEffect for Upscale {
+    fn apply(
+        &mut self,
+        context: &PostContext,
+        encoder: &mut CommandEncoder,
+        input: &TextureView,
+        output: &TextureView,
+    ) {
+        self.pass.draw(context, encoder, input, output, &[]);
+    }
+}
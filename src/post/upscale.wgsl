@@ -0,0 +1,71 @@
+fn upscale_load(pixel: vec2<i32>) -> vec3<f32> {
+    let size = vec2<i32>(textureDimensions(input_texture));
+    return textureLoad(input_texture, clamp(pixel, vec2<i32>(0), size - 1), 0).rgb;
+}
+
+fn upscale_luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+/// Edge-adaptive spatial upscaling after the EASU pass of AMD's FidelityFX Super Resolution 1.
+/// Finds the direction and strength of the local edge from the luma gradient, then filters the
+/// twelve nearest texels with a Lanczos-like kernel, stretched along the edge and sharpened
+/// across it. The result is clamped to the four nearest texels to avoid ringing.
+@fragment
+fn upscale_fragment(in: PostInput) -> @location(0) vec4<f32> {
+    let position = in.uv * vec2<f32>(textureDimensions(input_texture)) - 0.5;
+    let base = vec2<i32>(floor(position));
+    let f = position - floor(position);
+
+    // Luma gradients at the four nearest texels from central differences, bilinearly weighted.
+    var gradient = vec2<f32>(0.0);
+    var minimum = vec3<f32>(1e30);
+    var maximum = vec3<f32>(-1e30);
+    for (var i = 0; i < 4; i++) {
+        let offset = vec2<i32>(i & 1, i >> 1u);
+        let pixel = base + offset;
+        let weight = select(1.0 - f.x, f.x, offset.x == 1) * select(1.0 - f.y, f.y, offset.y == 1);
+        gradient += weight * vec2<f32>(
+            upscale_luma(upscale_load(pixel + vec2<i32>(1, 0))) - upscale_luma(upscale_load(pixel - vec2<i32>(1, 0))),
+            upscale_luma(upscale_load(pixel + vec2<i32>(0, 1))) - upscale_luma(upscale_load(pixel - vec2<i32>(0, 1))),
+        );
+        let color = upscale_load(pixel);
+        minimum = min(minimum, color);
+        maximum = max(maximum, color);
+    }
+    let luma_range = upscale_luma(maximum) - upscale_luma(minimum);
+    let gradient_length = length(gradient);
+    // Zero in flat areas, one at a clean edge spanning the local range. Squared like in FSR to
+    // leave noise mostly unsharpened.
+    let edge = pow(saturate(gradient_length / (luma_range + 1e-4)), 2.0);
+    let direction = select(vec2<f32>(1.0, 0.0), gradient / gradient_length, gradient_length > 1e-5);
+
+    // Stretch the kernel along diagonal edges, where the square footprint is shortest.
+    let stretch = dot(direction, direction) / max(abs(direction.x), abs(direction.y));
+    let scale = vec2<f32>(1.0 + (stretch - 1.0) * edge, 1.0 - 0.5 * edge);
+    // Negative lobe of the kernel, stronger at edges.
+    let lobe = 0.5 + (0.25 - 0.04 - 0.5) * edge;
+    let clip = 1.0 / lobe;
+
+    var sum = vec3<f32>(0.0);
+    var weight_sum = 0.0;
+    for (var y = -1; y <= 2; y++) {
+        for (var x = -1; x <= 2; x++) {
+            // The twelve taps without the corners of the 4×4 block.
+            if (x == -1 || x == 2) && (y == -1 || y == 2) {
+                continue;
+            }
+            let offset = vec2<f32>(f32(x), f32(y)) - f;
+            // Across the edge along the gradient, along the edge perpendicular to it.
+            let rotated = vec2<f32>(dot(offset, direction), dot(offset, vec2<f32>(-direction.y, direction.x))) * scale;
+            let distance2 = min(dot(rotated, rotated), clip);
+            // Polynomial approximation of a windowed Lanczos-2 kernel.
+            let window = 25.0 / 16.0 * (2.0 / 5.0 * distance2 - 1.0) * (2.0 / 5.0 * distance2 - 1.0) - (25.0 / 16.0 - 1.0);
+            let base_kernel = (lobe * distance2 - 1.0) * (lobe * distance2 - 1.0);
+            let weight = window * base_kernel;
+            sum += weight * upscale_load(base + vec2<i32>(x, y));
+            weight_sum += weight;
+        }
+    }
+    return vec4<f32>(clamp(sum / weight_sum, minimum, maximum), 1.0);
+}
@@ -0,0 +1,107 @@
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3};
+
+use crate::{
+    render::Renderer,
+    scene::Object,
+    world::{Entity, Name, PrefabInstance, World},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PrefabId(pub usize);
+
+/// Objects placed together any number of times. Instances share the prefab's meshes and
+/// materials, so that each one costs no more than its entities.
+#[derive(Debug, Clone, Default)]
+pub struct Prefab {
+    pub name: String,
+    /// Placed relative to the instance.
+    pub objects: Vec<Object>,
+}
+
+impl Prefab {
+    /// The entities' objects, relative to the position of the first one.
+    pub fn from_entities(name: impl Into<String>, world: &World, entities: &[Entity]) -> Self {
+        let renderables: Vec<_> = world
+            .renderables()
+            .filter(|object| entities.contains(&object.entity))
+            .collect();
+        let origin = renderables.first().map_or(Matrix4::identity(), |object| {
+            Matrix4::from_translation(object.transform.w.truncate())
+        });
+        let inverse_origin = origin.invert().unwrap_or(Matrix4::identity());
+        Prefab {
+            name: name.into(),
+            objects: renderables
+                .iter()
+                .map(|object| Object {
+                    name: world.name(object.entity).to_string(),
+                    transform: inverse_origin * object.transform,
+                    mesh: object.mesh,
+                    material: object.material,
+                })
+                .collect(),
+        }
+    }
+
+    /// Radius of the smallest sphere around the instance's origin enclosing the objects' bounds.
+    pub fn radius(&self, renderer: &Renderer) -> f32 {
+        self.objects
+            .iter()
+            .flat_map(|object| {
+                let bounds = renderer.mesh(object.mesh).bounds;
+                bounds.corners().map(move |corner| {
+                    (object.transform * corner.to_homogeneous())
+                        .truncate()
+                        .magnitude()
+                })
+            })
+            .fold(0.0, f32::max)
+    }
+
+    /// Spawns the objects placed by the transform, named after the prefab and numbered by the
+    /// instance.
+    pub fn instantiate(
+        &self,
+        world: &mut World,
+        id: PrefabId,
+        transform: Matrix4<f32>,
+    ) -> Vec<Entity> {
+        let number = 1 + world
+            .instances
+            .iter()
+            .filter(|(_, instance)| instance.prefab == id && instance.object == 0)
+            .count();
+        self.objects
+            .iter()
+            .enumerate()
+            .map(|(index, object)| {
+                let entity = world.spawn_object(&Object {
+                    transform: transform * object.transform,
+                    ..object.clone()
+                });
+                let name = if object.name.is_empty() {
+                    format!("{} {number}", self.name)
+                } else {
+                    format!("{} {number} {}", self.name, object.name)
+                };
+                world.names.insert(entity, Name(name));
+                world.instances.insert(
+                    entity,
+                    PrefabInstance {
+                        prefab: id,
+                        object: index,
+                    },
+                );
+                entity
+            })
+            .collect()
+    }
+}
+
+/// Offsets of instances laid out in a square grid on the ground, starting with the origin.
+pub fn grid(count: usize, spacing: f32) -> impl Iterator<Item = Vector3<f32>> {
+    let columns = (count as f32).sqrt().ceil() as usize;
+    (0..count).map(move |index| {
+        spacing * Vector3::new((index % columns) as f32, 0.0, (index / columns) as f32)
+    })
+}
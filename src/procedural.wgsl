@@ -0,0 +1,79 @@
+const PATTERN_CHECKER: u32 = 1u;
+const PATTERN_UV_GRID: u32 = 2u;
+const PATTERN_STRIPES: u32 = 3u;
+const PATTERN_NOISE: u32 = 4u;
+
+/// Alternating light and dark cells, `cells` per unit of UV.
+fn checker_pattern(uv: vec2<f32>, cells: f32) -> vec3<f32> {
+    let cell = vec2<i32>(floor(uv * cells));
+    let even = ((cell.x + cell.y) & 1) == 0;
+    return select(vec3<f32>(0.2), vec3<f32>(0.8), even);
+}
+
+/// Red and green gradients along U and V, overlaid with thin grid lines.
+fn uv_grid_pattern(uv: vec2<f32>, cells: f32) -> vec3<f32> {
+    let grid = abs(fract(uv * cells + 0.5) - 0.5) / fwidth(uv * cells);
+    let line = 1.0 - min(min(grid.x, grid.y), 1.0);
+    let gradient = vec3<f32>(fract(uv), 0.5);
+    return mix(gradient, vec3<f32>(1.0), line);
+}
+
+/// Each color channel alternates along one world axis, `scale` stripes per world unit.
+fn stripes_pattern(position: vec3<f32>, scale: f32) -> vec3<f32> {
+    return mix(vec3<f32>(0.25), vec3<f32>(1.0), step(vec3<f32>(0.5), fract(position * scale)));
+}
+
+fn hash(p: vec3<f32>) -> f32 {
+    let q = fract(p * 0.3183099 + 0.1) * 17.0;
+    return fract(q.x * q.y * q.z * (q.x + q.y + q.z));
+}
+
+/// Trilinearly interpolated value noise in [0, 1].
+fn value_noise(p: vec3<f32>) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    return mix(
+        mix(
+            mix(hash(i), hash(i + vec3<f32>(1.0, 0.0, 0.0)), u.x),
+            mix(hash(i + vec3<f32>(0.0, 1.0, 0.0)), hash(i + vec3<f32>(1.0, 1.0, 0.0)), u.x),
+            u.y,
+        ),
+        mix(
+            mix(hash(i + vec3<f32>(0.0, 0.0, 1.0)), hash(i + vec3<f32>(1.0, 0.0, 1.0)), u.x),
+            mix(hash(i + vec3<f32>(0.0, 1.0, 1.0)), hash(i + vec3<f32>(1.0, 1.0, 1.0)), u.x),
+            u.y,
+        ),
+        u.z,
+    );
+}
+
+/// Four octaves of value noise over world space, with features of roughly `1 / scale` units.
+fn noise_pattern(position: vec3<f32>, scale: f32) -> vec3<f32> {
+    var p = position * scale;
+    var amplitude = 0.5;
+    var sum = 0.0;
+    for (var octave = 0; octave < 4; octave++) {
+        sum += amplitude * value_noise(p);
+        p *= 2.0;
+        amplitude *= 0.5;
+    }
+    return vec3<f32>(sum / 0.9375);
+}
+
+fn procedural_pattern(pattern: u32, scale: f32, uv: vec2<f32>, position: vec3<f32>) -> vec3<f32> {
+    switch pattern {
+        case PATTERN_CHECKER: {
+            return checker_pattern(uv, scale);
+        }
+        case PATTERN_UV_GRID: {
+            return uv_grid_pattern(uv, scale);
+        }
+        case PATTERN_STRIPES: {
+            return stripes_pattern(position, scale);
+        }
+        default: {
+            return noise_pattern(position, scale);
+        }
+    }
+}
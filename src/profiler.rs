@@ -0,0 +1,233 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use tracing::error;
+use wgpu::*;
+
+/// Frames kept for the chart and the percentiles.
+pub const FRAMES: usize = 240;
+/// Frames read back at once, beyond which frames go unmeasured rather than waiting for the GPU.
+const READBACKS: usize = 3;
+
+/// The sections of a frame timed on the GPU, in the order they are recorded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pass {
+    /// The shadow maps, or building the acceleration structures for ray traced shadows.
+    Shadow,
+    /// The depth prepass, the scene, the outlines and the velocity buffer.
+    Geometry,
+    Post,
+    Ui,
+}
+
+impl Pass {
+    pub const ALL: [Pass; 4] = [Pass::Shadow, Pass::Geometry, Pass::Post, Pass::Ui];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::Shadow => "Shadow",
+            Pass::Geometry => "Geometry",
+            Pass::Post => "Post",
+            Pass::Ui => "UI",
+        }
+    }
+}
+
+/// Milliseconds each pass took on the GPU over the recent frames.
+#[derive(Debug, Clone, Default)]
+pub struct GpuTimings {
+    frames: VecDeque<[f32; Pass::ALL.len()]>,
+}
+
+impl GpuTimings {
+    /// The passes' times of each frame, oldest first.
+    pub fn frames(&self) -> impl ExactSizeIterator<Item = &[f32; Pass::ALL.len()]> {
+        self.frames.iter()
+    }
+
+    /// The time the pass stays below in the given fraction of frames, or the total of all
+    /// passes if none is given.
+    pub fn percentile(&self, pass: Option<Pass>, fraction: f32) -> f32 {
+        let mut times: Vec<f32> = self
+            .frames
+            .iter()
+            .map(|times| match pass {
+                Some(pass) => times[pass as usize],
+                None => times.iter().sum(),
+            })
+            .collect();
+        if times.is_empty() {
+            return 0.0;
+        }
+        times.sort_by(f32::total_cmp);
+        times[((times.len() - 1) as f32 * fraction).round() as usize]
+    }
+
+    fn push(&mut self, times: [f32; Pass::ALL.len()]) {
+        if self.frames.len() == FRAMES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(times);
+    }
+}
+
+#[derive(Debug)]
+struct Readback {
+    buffer: Buffer,
+    mapped: Arc<AtomicBool>,
+    /// Whether the buffer is being copied to or mapped.
+    busy: bool,
+    frame: u64,
+}
+
+/// Times the passes with timestamp queries, read back a few frames late without stalling.
+/// The timestamps are written by empty compute passes, as writing them between passes needs a
+/// feature fewer adapters have.
+#[derive(Debug)]
+pub struct GpuProfiler {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readbacks: Vec<Readback>,
+    /// Copied to this frame, to be mapped once submitted.
+    submitted: Option<usize>,
+    frame: u64,
+    /// Nanoseconds per tick.
+    period: f32,
+    timings: GpuTimings,
+}
+
+impl GpuProfiler {
+    /// Returns `None` unless the device has `Features::TIMESTAMP_QUERY`.
+    pub fn new(device: &Device, queue: &Queue) -> Option<Self> {
+        if !device.features().contains(Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+        // One at the start of each pass and one at the end of the last.
+        let count = Pass::ALL.len() as u32 + 1;
+        let size = count as u64 * QUERY_SIZE as u64;
+        let query_set = device.create_query_set(&QuerySetDescriptor {
+            label: Some("Pass timestamps"),
+            ty: QueryType::Timestamp,
+            count,
+        });
+        let resolve_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readbacks = (0..READBACKS)
+            .map(|_| Readback {
+                buffer: device.create_buffer(&BufferDescriptor {
+                    label: None,
+                    size,
+                    usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+                mapped: Arc::new(AtomicBool::new(false)),
+                busy: false,
+                frame: 0,
+            })
+            .collect();
+        Some(GpuProfiler {
+            query_set,
+            resolve_buffer,
+            readbacks,
+            submitted: None,
+            frame: 0,
+            period: queue.get_timestamp_period(),
+            timings: GpuTimings::default(),
+        })
+    }
+
+    pub fn timings(&self) -> &GpuTimings {
+        &self.timings
+    }
+
+    /// Takes the times of the frames read back since, once the device has been polled.
+    pub fn collect(&mut self) {
+        let mut ready: Vec<_> = self
+            .readbacks
+            .iter_mut()
+            .filter(|readback| readback.mapped.load(Ordering::Acquire))
+            .collect();
+        ready.sort_by_key(|readback| readback.frame);
+        for readback in ready {
+            let ticks: Vec<u64> = readback
+                .buffer
+                .slice(..)
+                .get_mapped_range()
+                .chunks_exact(QUERY_SIZE as usize)
+                .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+                .collect();
+            readback.buffer.unmap();
+            readback.mapped.store(false, Ordering::Release);
+            readback.busy = false;
+            let mut times = [0.0; Pass::ALL.len()];
+            for (time, ticks) in times.iter_mut().zip(ticks.windows(2)) {
+                // Ticks may go backwards when the GPU changes clocks.
+                *time = ticks[1].saturating_sub(ticks[0]) as f32 * self.period / 1e6;
+            }
+            self.timings.push(times);
+        }
+    }
+
+    /// Records the start of the pass, which ends where the next one starts.
+    pub fn begin(&self, encoder: &mut CommandEncoder, pass: Pass) {
+        self.write_timestamp(encoder, pass as u32);
+    }
+
+    /// Records the end of the last pass and copies the timestamps out, if a readback buffer is
+    /// free.
+    pub fn end_frame(&mut self, encoder: &mut CommandEncoder) {
+        let count = Pass::ALL.len() as u32 + 1;
+        self.write_timestamp(encoder, count - 1);
+        self.frame += 1;
+        let Some(index) = self.readbacks.iter().position(|readback| !readback.busy) else {
+            return;
+        };
+        let readback = &mut self.readbacks[index];
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &readback.buffer,
+            0,
+            self.resolve_buffer.size(),
+        );
+        readback.busy = true;
+        readback.frame = self.frame;
+        self.submitted = Some(index);
+    }
+
+    /// Starts mapping this frame's timestamps, once submitted.
+    pub fn map(&mut self) {
+        let Some(index) = self.submitted.take() else {
+            return;
+        };
+        let mapped = self.readbacks[index].mapped.clone();
+        self.readbacks[index].buffer.slice(..).map_async(
+            MapMode::Read,
+            move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(error) => error!("Cannot read back the pass timestamps: {error}"),
+            },
+        );
+    }
+
+    fn write_timestamp(&self, encoder: &mut CommandEncoder, index: u32) {
+        encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: None,
+            timestamp_writes: Some(ComputePassTimestampWrites {
+                query_set: &self.query_set,
+                beginning_of_pass_write_index: Some(index),
+                end_of_pass_write_index: None,
+            }),
+        });
+    }
+}
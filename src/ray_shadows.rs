@@ -0,0 +1,135 @@
+use cgmath::Matrix4;
+use wgpu::*;
+
+use crate::{
+    arena::MeshArena,
+    mesh::{Mesh, MeshId, Vertex},
+};
+
+/// Features required for tracing shadow rays, which fall back to shadow maps without them.
+pub const RAY_TRACING_FEATURES: Features = Features::EXPERIMENTAL_RAY_QUERY
+    .union(Features::EXPERIMENTAL_RAY_TRACING_ACCELERATION_STRUCTURE);
+
+/// Acceleration structures over the shadow casters, queried by the fragment shader
+/// in place of the shadow maps.
+pub struct RayTracedShadows {
+    /// One per mesh, in the order the meshes were added, or none while the mesh is unloaded.
+    blases: Vec<Option<Blas>>,
+    tlas_package: TlasPackage,
+}
+
+impl std::fmt::Debug for RayTracedShadows {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RayTracedShadows")
+            .field("blases", &self.blases)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RayTracedShadows {
+    pub fn new(device: &Device) -> Self {
+        RayTracedShadows {
+            blases: Vec::new(),
+            tlas_package: create_tlas_package(device, 1),
+        }
+    }
+
+    pub fn binding(&self) -> BindingResource<'_> {
+        self.tlas_package.as_binding()
+    }
+
+    /// Builds the bottom level structures of meshes added or uploaded again since the last
+    /// update and rebuilds the top level one from the casters' current transforms.
+    pub fn update(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        meshes: &[Mesh],
+        arena: &MeshArena,
+        casters: &[(MeshId, Matrix4<f32>)],
+    ) {
+        self.blases.resize_with(meshes.len(), || None);
+        let missing: Vec<_> = (0..meshes.len())
+            .filter(|&index| self.blases[index].is_none() && meshes[index].allocation.is_some())
+            .collect();
+        let sizes: Vec<_> = missing
+            .iter()
+            .map(|&index| BlasTriangleGeometrySizeDescriptor {
+                vertex_format: VertexFormat::Float32x3,
+                vertex_count: meshes[index].data.vertices.len() as u32,
+                index_format: Some(IndexFormat::Uint32),
+                index_count: Some(meshes[index].index_count),
+                flags: AccelerationStructureGeometryFlags::OPAQUE,
+            })
+            .collect();
+        for (&index, size) in missing.iter().zip(&sizes) {
+            self.blases[index] = Some(device.create_blas(
+                &CreateBlasDescriptor {
+                    label: None,
+                    flags: AccelerationStructureFlags::PREFER_FAST_TRACE,
+                    update_mode: AccelerationStructureUpdateMode::Build,
+                },
+                BlasGeometrySizeDescriptors::Triangles {
+                    descriptors: vec![size.clone()],
+                },
+            ));
+        }
+
+        if casters.len() > self.tlas_package.get().len() {
+            self.tlas_package = create_tlas_package(device, casters.len().next_power_of_two());
+        }
+        let capacity = self.tlas_package.get().len();
+        for (index, instance) in self
+            .tlas_package
+            .get_mut_slice(0..capacity)
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+        {
+            *instance = casters.get(index).and_then(|&(mesh, transform)| {
+                // Row-major 3x4 from the column-major model matrix.
+                let transform = std::array::from_fn(|i| transform[i % 4][i / 4]);
+                let blas = self.blases[mesh.0].as_ref()?;
+                Some(TlasInstance::new(blas, transform, 0, 0xff))
+            });
+        }
+
+        let entries: Vec<_> = missing
+            .iter()
+            .zip(&sizes)
+            .filter_map(|(&index, size)| {
+                let allocation = meshes[index].allocation?;
+                Some(BlasBuildEntry {
+                    blas: self.blases[index].as_ref()?,
+                    geometry: BlasGeometries::TriangleGeometries(vec![BlasTriangleGeometry {
+                        size,
+                        vertex_buffer: arena.vertices(allocation.page),
+                        first_vertex: allocation.first_vertex,
+                        vertex_stride: std::mem::size_of::<Vertex>() as BufferAddress,
+                        index_buffer: Some(arena.indices(allocation.page)),
+                        first_index: Some(allocation.first_index),
+                        transform_buffer: None,
+                        transform_buffer_offset: None,
+                    }]),
+                })
+            })
+            .collect();
+        encoder.build_acceleration_structures(&entries, Some(&self.tlas_package));
+    }
+
+    /// Drops the mesh's bottom level structure, built again once the mesh is uploaded again.
+    pub fn unload(&mut self, mesh: MeshId) {
+        if let Some(blas) = self.blases.get_mut(mesh.0) {
+            *blas = None;
+        }
+    }
+}
+
+fn create_tlas_package(device: &Device, max_instances: usize) -> TlasPackage {
+    TlasPackage::new(device.create_tlas(&CreateTlasDescriptor {
+        label: None,
+        max_instances: max_instances as u32,
+        flags: AccelerationStructureFlags::PREFER_FAST_BUILD,
+        update_mode: AccelerationStructureUpdateMode::Build,
+    }))
+}
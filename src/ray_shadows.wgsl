@@ -0,0 +1,15 @@
+const RAY_TRACED_SHADOWS: bool = true;
+/// Distance by which shadow rays start off the surface, avoiding self-intersections.
+const SHADOW_RAY_NORMAL_OFFSET: f32 = 0.01;
+
+@group(0) @binding(12) var shadow_casters: acceleration_structure;
+
+/// Whether anything blocks the ray leaving the surface in the given direction up to the distance.
+fn traced_visibility(position: vec3<f32>, normal: vec3<f32>, direction: vec3<f32>, distance: f32) -> f32 {
+    var query: ray_query;
+    let origin = position + normal * SHADOW_RAY_NORMAL_OFFSET;
+    rayQueryInitialize(&query, shadow_casters, RayDesc(RAY_FLAG_TERMINATE_ON_FIRST_HIT, 0xffu, 0.0, distance, origin, direction));
+    rayQueryProceed(&query);
+    let intersection = rayQueryGetCommittedIntersection(&query);
+    return select(1.0, 0.0, intersection.kind != RAY_QUERY_INTERSECTION_NONE);
+}
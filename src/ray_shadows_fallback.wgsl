@@ -0,0 +1,6 @@
+/// Used on adapters without ray queries, which sample the shadow maps instead.
+const RAY_TRACED_SHADOWS: bool = false;
+
+fn traced_visibility(position: vec3<f32>, normal: vec3<f32>, direction: vec3<f32>, distance: f32) -> f32 {
+    return 1.0;
+}
@@ -1,42 +1,906 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    path::Path,
+    sync::Arc,
+};
 
-use cgmath::{Matrix4, SquareMatrix, Vector4};
-use util::{BufferInitDescriptor, DeviceExt};
+use cgmath::{Deg, InnerSpace, Matrix, Matrix4, SquareMatrix, Vector2, Vector3, Vector4};
+use tracing::{error, info, info_span};
 use wgpu::*;
 use winit::window::Window;
 
+use crate::{
+    arena::MeshArena,
+    bindless::{BindlessMaterials, BINDLESS_FEATURES},
+    camera::Projection,
+    cluster::LightClusters,
+    culling::{CullBounds, GpuCulling, Rejection, COMPACTION_FEATURES},
+    debug_draw::{self, DebugDraw, DebugDrawPipeline},
+    deferred::{DeferredPipelines, GBuffer},
+    deformation::{Deformation, DeformationId, MorphTarget, SkinVertex},
+    dynamic_resolution::DynamicResolution,
+    environment::{Cubemap, SkyGradient, Skybox},
+    ibl::{create_brdf_lut, Ibl},
+    light::{LightUniforms, PointLightUniforms, RectLightUniforms, SpotLightUniforms},
+    lightmap::Lightmaps,
+    lod,
+    ltc::LtcLuts,
+    material::{AlphaMode, Material, MaterialBinding, MaterialId},
+    mesh::{Bounds, Mesh, MeshData, MeshId, Vertex},
+    pipeline_builder::PipelineBuilder,
+    point_cloud::{Point, PointCloud, PointCloudId, PointCloudPipeline},
+    post::{
+        BufferView, FogFalloff, FrameInputs, PostProcessing, Tonemapper, HDR_FORMAT,
+        SELECTION_MASK_FORMAT,
+    },
+    profiler::{GpuProfiler, GpuTimings, Pass},
+    ray_shadows::{RayTracedShadows, RAY_TRACING_FEATURES},
+    scene::Scene,
+    screenshot::{self, Screenshot},
+    shadow::{
+        self, PointShadowMaps, ShadowFilter, ShadowMap, ShadowQuality, ShadowSettings,
+        CASCADE_COUNT, MAX_SHADOWED_POINT_LIGHTS,
+    },
+    stats::{GpuStats, SceneStats},
+    texture::{create_texture, texture_memory, MipChain, TextureCache, TextureId},
+    ui::{UiFrame, UiPass},
+    velocity::{VelocityBuffer, VelocityPipelines},
+    world::{Components, Entity, Layers},
+};
+
+pub const FOVY: Deg<f32> = Deg(60.0);
+pub const NEAR: f32 = 0.1;
+pub const FAR: f32 = 100.0;
+
+/// How geometric edges are smoothed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AntiAliasing {
+    Off,
+    /// 4× multisampling, unless the deferred path is active.
+    Msaa,
+    /// A post-processing pass, which also covers deferred rendering.
+    Fxaa,
+    /// Accumulates frames rendered with sub-pixel jitter, also covering shading aliasing.
+    Taa,
+}
+
+impl AntiAliasing {
+    pub fn next(self) -> Self {
+        match self {
+            AntiAliasing::Off => AntiAliasing::Msaa,
+            AntiAliasing::Msaa => AntiAliasing::Fxaa,
+            AntiAliasing::Fxaa => AntiAliasing::Taa,
+            AntiAliasing::Taa => AntiAliasing::Off,
+        }
+    }
+}
+
+/// What the forward pass shows, in the order of the shader's constants. Modes other than the
+/// shaded image skip the deferred path and the scene's post effects, to show values unaltered.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    #[default]
+    Shaded,
+    /// Base color of the materials, unlit.
+    Albedo,
+    /// World-space normals of the surfaces.
+    Normals,
+    /// Distance from the camera, logarithmic up to the far plane.
+    Depth,
+    /// Texture coordinates, repeating.
+    Uv,
+    /// Triangle edges over the shaded image, if the adapter can draw polygons as lines.
+    Wireframe,
+    /// Counts the fragments drawn per pixel, from red over yellow to white.
+    Overdraw,
+}
+
+impl RenderMode {
+    pub fn next(self) -> Self {
+        match self {
+            RenderMode::Shaded => RenderMode::Albedo,
+            RenderMode::Albedo => RenderMode::Normals,
+            RenderMode::Normals => RenderMode::Depth,
+            RenderMode::Depth => RenderMode::Uv,
+            RenderMode::Uv => RenderMode::Wireframe,
+            RenderMode::Wireframe => RenderMode::Overdraw,
+            RenderMode::Overdraw => RenderMode::Shaded,
+        }
+    }
+}
+
+/// The runtime options of the renderer, edited together in the debug UI. Applying them rebuilds
+/// only what changed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RenderSettings {
+    pub anti_aliasing: AntiAliasing,
+    /// Waits for the display's refresh, or else presents right away, possibly tearing.
+    pub vsync: bool,
+    /// Fraction of the surface resolution the scene is rendered at, the most with dynamic
+    /// resolution.
+    pub render_scale: f32,
+    pub dynamic_resolution: bool,
+    pub shadow_quality: ShadowQuality,
+    pub shadow_filter: ShadowFilter,
+    pub tonemapper: Tonemapper,
+    pub render_mode: RenderMode,
+    pub deferred: bool,
+    /// Draws with the arguments read from a buffer, one entry per object.
+    pub indirect: bool,
+    /// Culls the objects against the frustum in a compute pass writing their indirect arguments,
+    /// in place of testing them on the CPU. Shadow casters are drawn indirectly but unculled.
+    /// Where supported, the opaque and masked objects are drawn by a multi-draw per page of the
+    /// arena of the arguments the pass compacts.
+    pub gpu_culling: bool,
+    /// Also culls objects on the GPU which are hidden behind the depth of the previous frame.
+    pub occlusion_culling: bool,
+    /// Records long lists of forward drawn items on several threads.
+    pub parallel_encoding: bool,
+    /// Draws coarser levels of detail of meshes small on screen.
+    pub lod: bool,
+}
+
+/// Length of the jitter sequence of temporal anti-aliasing.
+const TAA_JITTER_PHASES: u32 = 8;
+
+/// Distance between per-object uniforms, satisfying the minimum dynamic offset alignment.
+const OBJECT_UNIFORMS_STRIDE: u64 = 256;
+
+/// Distance between the indirect arguments of the objects.
+pub const INDIRECT_ARGS_STRIDE: u64 = std::mem::size_of::<util::DrawIndexedIndirectArgs>() as u64;
+
+/// Opaque and masked items from which the forward pass is recorded on several threads.
+const PARALLEL_ENCODING_THRESHOLD: usize = 1024;
+
+/// Size of the chunks uniforms are staged in, which grow to fit larger writes.
+const STAGING_CHUNK_SIZE: u64 = 64 * 1024;
+
 #[derive(Debug)]
 pub struct Renderer {
+    /// Kept for its reports of the live resources.
+    instance: Instance,
     surface: Surface<'static>,
     config: SurfaceConfiguration,
+    adapter_info: AdapterInfo,
     device: Device,
     queue: Queue,
-    pipeline: RenderPipeline,
+    /// Shared with the threads building pipelines.
+    shader_module: Arc<ShaderModule>,
+    pipeline_layout: PipelineLayout,
+    pipelines: Pipelines,
+    /// What the pipelines and scene targets are built for, which lags behind the settings while
+    /// the pipelines for them are being built.
+    built: PipelineKey,
+    /// Builds the pipelines for changed settings without stalling the frames.
+    pipeline_builder: PipelineBuilder<PipelineKey, ScenePipelines>,
+    deferred_pipelines: DeferredPipelines,
+    gbuffer_bind_group_layout: BindGroupLayout,
+    /// Only allocated while deferred rendering is enabled.
+    gbuffer: Option<GBuffer>,
+    velocity_pipelines: VelocityPipelines,
+    selection_pipeline: RenderPipeline,
+    /// Only allocated while motion blur or its debug view is enabled.
+    velocity_buffer: Option<VelocityBuffer>,
+    stats: SceneStats,
+    /// Model matrices of the previous frame, by entity, as slots change when objects are added
+    /// or removed.
+    previous_transforms: Components<Matrix4<f32>>,
+    /// Shades opaque and masked objects from a G-buffer instead of while rasterizing them.
+    /// Disables MSAA.
+    deferred: bool,
+    anti_aliasing: AntiAliasing,
+    render_mode: RenderMode,
+    /// Counts rendered frames, selecting the projection jitter.
+    frame_index: u32,
+    previous_view_projection: Matrix4<f32>,
+    /// 1 if the adapter cannot multisample the HDR format.
+    max_sample_count: u32,
     uniform_buffer: Buffer,
-    vertex_position_buffer: Buffer,
-    vertex_color_buffer: Buffer,
+    light_buffer: Buffer,
+    /// Storage buffers holding the point, spot and rectangular lights, grown to the next power of
+    /// two as needed.
+    point_light_buffer: Buffer,
+    spot_light_buffer: Buffer,
+    rect_light_buffer: Buffer,
+    light_clusters: LightClusters,
+    uniform_bind_group_layout: BindGroupLayout,
     depth_texture: Texture,
+    /// Multisampled color target resolved into the scene target, if MSAA is enabled.
+    msaa_texture: Option<Texture>,
+    /// Owns the scene target and processes it into the surface.
+    post: PostProcessing,
+    /// Draws the debug UI over the processed image.
+    ui_pass: UiPass,
+    meshes: Vec<Mesh>,
+    /// Holds the vertices and indices of the meshes.
+    arena: MeshArena,
+    materials: Vec<MaterialBinding>,
+    material_bind_group_layout: BindGroupLayout,
+    textures: TextureCache,
+    /// Replaces the per-material bind groups if the adapter supports large arrays of textures.
+    bindless: Option<BindlessMaterials>,
+    /// Bound in place of missing material textures.
+    white_texture: TextureView,
+    material_sampler: Sampler,
+    object_buffer: Buffer,
+    /// Mapped chunks the frame's uniforms are copied from, each reused once the GPU is done with
+    /// it, so that writing them never waits for frames in flight.
+    staging_belt: util::StagingBelt,
+    object_bind_group_layout: BindGroupLayout,
+    object_bind_group: BindGroup,
+    /// The whole object buffer, which the scene shader indexes by the instance on devices that
+    /// compact the culled draws. The shadow passes keep binding the objects one by one.
+    object_array_bind_group_layout: Option<BindGroupLayout>,
+    object_array_bind_group: Option<BindGroup>,
+    environment: Cubemap,
+    ibl: Ibl,
+    brdf_lut: TextureView,
+    ltc_luts: LtcLuts,
+    /// Baked static lighting, replacing the irradiance from the environment where present.
+    lightmaps: Lightmaps,
+    environment_sampler: Sampler,
+    environment_bind_group_layout: BindGroupLayout,
+    environment_bind_group: BindGroup,
+    skybox: Skybox,
+    shadow_map: ShadowMap,
+    point_shadow_maps: PointShadowMaps,
+    shadow_settings: ShadowSettings,
+    /// Replaces the shadow maps if the adapter supports ray queries.
+    ray_traced_shadows: Option<RayTracedShadows>,
+    light_gizmos: bool,
+    /// Outlines the bounds of objects on the viewed layers, red where culled.
+    bounds_gizmos: bool,
+    /// Whether the next frame is captured.
+    screenshot_requested: bool,
+    /// Captured frames waiting to be read back.
+    screenshots: Vec<Screenshot>,
+    /// Times the passes if the adapter supports timestamp queries.
+    profiler: Option<GpuProfiler>,
+    /// Seen through instead of the given view, which still culls and fits the shadow cascades.
+    debug_view: Option<Matrix4<f32>>,
+    /// Scales the resolution the scene is rendered at to the frame time.
+    dynamic_resolution: DynamicResolution,
+    /// Lines collected for the current frame.
+    debug_draw: DebugDraw,
+    /// Lines drawn over everything, for the transform gizmo.
+    gizmo_draw: DebugDraw,
+    debug_draw_pipeline: DebugDrawPipeline,
+    point_clouds: Vec<PointCloud>,
+    point_cloud_pipeline: PointCloudPipeline,
+    deformation: Deformation,
+    /// Meshes deformed beyond their bounds, which are never culled.
+    deformed_meshes: HashSet<MeshId>,
+    /// Forces toon shading and outlines on every material.
+    toon: bool,
+    indirect: bool,
+    /// The indirect arguments of the objects, in the order of their uniforms.
+    indirect_buffer: Buffer,
+    gpu_culling: bool,
+    culling: GpuCulling,
+    occlusion_culling: bool,
+    parallel_encoding: bool,
+    lod: bool,
+    /// Levels of detail of the previous frame, by entity, which objects stay at until their
+    /// size on screen is clearly past the thresholds.
+    lod_levels: Components<usize>,
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct Uniforms {
-    #[allow(dead_code)]
-    model: Matrix4<f32>,
     #[allow(dead_code)]
     view: Matrix4<f32>,
     #[allow(dead_code)]
     projection: Matrix4<f32>,
+    /// Maps clip-space positions on the far plane to world-space view directions.
+    #[allow(dead_code)]
+    environment: Matrix4<f32>,
+    #[allow(dead_code)]
+    camera_position: Vector4<f32>,
+    /// Width and height in pixels, followed by their reciprocals.
+    #[allow(dead_code)]
+    viewport: Vector4<f32>,
+    #[allow(dead_code)]
+    toon: u32,
+    #[allow(dead_code)]
+    point_light_count: u32,
+    #[allow(dead_code)]
+    spot_light_count: u32,
+    #[allow(dead_code)]
+    rect_light_count: u32,
+    /// Maps normalized device coordinates back to world space.
+    #[allow(dead_code)]
+    inverse_view_projection: Matrix4<f32>,
+    #[allow(dead_code)]
+    near: f32,
+    #[allow(dead_code)]
+    far: f32,
+    /// Sub-pixel offset of the projection in normalized device coordinates.
+    #[allow(dead_code)]
+    jitter: Vector2<f32>,
+    /// The previous frame's view-projection, without jitter.
+    #[allow(dead_code)]
+    previous_view_projection: Matrix4<f32>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ObjectUniforms {
+    #[allow(dead_code)]
+    model: Matrix4<f32>,
+    /// Inverse transpose of the model matrix, for transforming normals.
+    #[allow(dead_code)]
+    normal: Matrix4<f32>,
+    /// Layer in the lightmap array, or -1 if the object has none.
+    #[allow(dead_code)]
+    lightmap: i32,
+    /// Index into the array of materials, for bindless materials.
+    #[allow(dead_code)]
+    material: u32,
+    #[allow(dead_code)]
+    padding: [i32; 2],
+    /// The model matrix of the previous frame.
+    #[allow(dead_code)]
+    previous_model: Matrix4<f32>,
+}
+
+#[derive(Debug)]
+struct Pipelines {
+    opaque: RenderPipeline,
+    mask: RenderPipeline,
+    blend: RenderPipeline,
+    /// Draws back faces extruded along their normals.
+    outline: RenderPipeline,
+    /// Only built for the wireframe render mode.
+    wireframe: Option<RenderPipeline>,
+}
+
+/// What the pipelines drawing into the scene targets are built for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct PipelineKey {
+    sample_count: u32,
+    render_mode: RenderMode,
+}
+
+/// The pipelines drawing into the scene targets, which are rebuilt together when the sample
+/// count or render mode changes.
+#[derive(Debug)]
+struct ScenePipelines {
+    pipelines: Pipelines,
+    skybox: Skybox,
+    debug_draw: DebugDrawPipeline,
+    point_cloud: PointCloudPipeline,
+}
+
+/// Objects prepared for drawing in the current frame, grouped by pipeline.
+#[derive(Debug, Default)]
+struct DrawList {
+    opaque: Vec<DrawItem>,
+    masked: Vec<DrawItem>,
+    /// Opaque and masked items drawn again as inverted hulls.
+    outlined: Vec<DrawItem>,
+    /// Sorted back to front.
+    transparent: Vec<DrawItem>,
+    /// Opaque and masked items on the shadow layers, whether visible in the view or not.
+    shadow_casters: Vec<DrawItem>,
+    /// The opaque and masked items again, if the culling pass compacts their arguments.
+    groups: Vec<DrawGroup>,
+}
+
+/// Opaque or masked items sharing a page of the arena, drawn by a single multi-draw of the
+/// arguments the culling pass compacts for the visible ones.
+#[derive(Debug, Copy, Clone)]
+struct DrawGroup {
+    masked: bool,
+    page: usize,
+    /// Where the group's arguments start in the compacted arguments.
+    first: u32,
+    /// Items in the group, of which as many may be visible.
+    count: u32,
+}
+
+/// Layers drawn into the shadow maps.
+const SHADOW_LAYERS: Layers = Layers::ALL.without(Layers::EDITOR).without(Layers::DEBUG);
+
+/// Whether any part of the bounds may be visible through the model-view-projection. Bounds are
+/// culled once all their corners lie outside the same plane of the frustum.
+fn in_frustum(model_view_projection: Matrix4<f32>, bounds: &Bounds) -> bool {
+    let corners = bounds
+        .corners()
+        .map(|corner| model_view_projection * corner.to_homogeneous());
+    let outside = |plane: fn(Vector4<f32>) -> bool| corners.iter().all(|&corner| plane(corner));
+    !(outside(|c| c.x < -c.w)
+        || outside(|c| c.x > c.w)
+        || outside(|c| c.y < -c.w)
+        || outside(|c| c.y > c.w)
+        || outside(|c| c.z < 0.0)
+        || outside(|c| c.z > c.w))
+}
+
+/// An object prepared for drawing in the current frame.
+#[derive(Debug, Copy, Clone)]
+struct DrawItem {
+    entity: Entity,
+    mesh: MeshId,
+    material: MaterialId,
+    /// Index of the object's uniforms in the object buffer.
+    slot: u32,
+    /// Level of detail of the mesh.
+    level: usize,
+    /// View-space depth of the mesh's bounding sphere center.
+    depth: f32,
 }
 
-fn as_byte_slice<T>(slice: &[T]) -> &[u8] {
-    unsafe {
-        std::slice::from_raw_parts(
-            slice.as_ptr() as *const u8,
-            slice.len() * std::mem::size_of::<T>(),
+/// Element of the Halton low-discrepancy sequence in [0, 1).
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+pub fn as_byte_slice<T>(slice: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(slice.as_ptr() as *const u8, std::mem::size_of_val(slice)) }
+}
+
+fn create_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader_module: &ShaderModule,
+    format: TextureFormat,
+    alpha_mode: AlphaMode,
+    sample_count: u32,
+    render_mode: RenderMode,
+) -> RenderPipeline {
+    let (blend, depth_write_enabled) = match alpha_mode {
+        _ if render_mode == RenderMode::Overdraw => (OVERDRAW_BLEND, false),
+        AlphaMode::Opaque | AlphaMode::Mask => (BlendState::REPLACE, true),
+        AlphaMode::Blend => (BlendState::ALPHA_BLENDING, false),
+    };
+    let alpha_to_coverage_enabled = alpha_mode == AlphaMode::Mask && sample_count > 1;
+    // Overdraw counts the hidden fragments too.
+    let depth_compare = if render_mode == RenderMode::Overdraw {
+        CompareFunction::Always
+    } else {
+        CompareFunction::LessEqual
+    };
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: None,
+        cache: None,
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader_module,
+            entry_point: Some("vertex"),
+            buffers: &[Vertex::LAYOUT],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(FragmentState {
+            module: shader_module,
+            entry_point: Some("fragment"),
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: Some(blend),
+                write_mask: ColorWrites::ALL,
+            })],
+            compilation_options: PipelineCompilationOptions {
+                constants: &[
+                    (
+                        "alpha_to_coverage".into(),
+                        alpha_to_coverage_enabled as u32 as f64,
+                    ),
+                    ("render_mode".into(), render_mode as u32 as f64),
+                ]
+                .into(),
+                ..Default::default()
+            },
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: Some(Face::Back),
+            polygon_mode: PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        multisample: MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled,
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: TextureFormat::Depth24Plus,
+            depth_write_enabled,
+            depth_compare,
+            stencil: Default::default(),
+            bias: Default::default(),
+        }),
+        multiview: None,
+    })
+}
+
+/// Adds up the fragments of the overdraw heatmap.
+const OVERDRAW_BLEND: BlendState = BlendState {
+    color: BlendComponent {
+        src_factor: BlendFactor::One,
+        dst_factor: BlendFactor::One,
+        operation: BlendOperation::Add,
+    },
+    alpha: BlendComponent::REPLACE,
+};
+
+/// Draws the triangles' edges over the depth of the shaded image, which needs
+/// `Features::POLYGON_MODE_LINE`.
+fn create_wireframe_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader_module: &ShaderModule,
+    format: TextureFormat,
+    sample_count: u32,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: None,
+        cache: None,
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader_module,
+            entry_point: Some("vertex"),
+            buffers: &[Vertex::LAYOUT],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(FragmentState {
+            module: shader_module,
+            entry_point: Some("wireframe_fragment"),
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: Some(BlendState::REPLACE),
+                write_mask: ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: Some(Face::Back),
+            polygon_mode: PolygonMode::Line,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        multisample: MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: TextureFormat::Depth24Plus,
+            depth_write_enabled: false,
+            depth_compare: CompareFunction::LessEqual,
+            stencil: Default::default(),
+            // Pulls the edges in front of the faces they lie on.
+            bias: DepthBiasState {
+                constant: -2,
+                slope_scale: -1.0,
+                clamp: 0.0,
+            },
+        }),
+        multiview: None,
+    })
+}
+
+fn create_outline_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader_module: &ShaderModule,
+    format: TextureFormat,
+    sample_count: u32,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: None,
+        cache: None,
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader_module,
+            entry_point: Some("outline_vertex"),
+            buffers: &[Vertex::LAYOUT],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(FragmentState {
+            module: shader_module,
+            entry_point: Some("outline_fragment"),
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: Some(BlendState::REPLACE),
+                write_mask: ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Ccw,
+            cull_mode: Some(Face::Front),
+            polygon_mode: PolygonMode::Fill,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        multisample: MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: TextureFormat::Depth24Plus,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: Default::default(),
+        }),
+        multiview: None,
+    })
+}
+
+fn create_pipelines(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader_module: &ShaderModule,
+    format: TextureFormat,
+    sample_count: u32,
+    render_mode: RenderMode,
+) -> Pipelines {
+    let create = |alpha_mode| {
+        create_pipeline(
+            device,
+            layout,
+            shader_module,
+            format,
+            alpha_mode,
+            sample_count,
+            render_mode,
         )
+    };
+    Pipelines {
+        opaque: create(AlphaMode::Opaque),
+        mask: create(AlphaMode::Mask),
+        blend: create(AlphaMode::Blend),
+        outline: create_outline_pipeline(device, layout, shader_module, format, sample_count),
+        wireframe: (render_mode == RenderMode::Wireframe).then(|| {
+            create_wireframe_pipeline(device, layout, shader_module, format, sample_count)
+        }),
+    }
+}
+
+fn create_scene_pipelines(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader_module: &ShaderModule,
+    uniform_layout: &BindGroupLayout,
+    environment_layout: &BindGroupLayout,
+    key: PipelineKey,
+) -> ScenePipelines {
+    let PipelineKey {
+        sample_count,
+        render_mode,
+    } = key;
+    ScenePipelines {
+        pipelines: create_pipelines(
+            device,
+            layout,
+            shader_module,
+            HDR_FORMAT,
+            sample_count,
+            render_mode,
+        ),
+        skybox: Skybox::new(
+            device,
+            HDR_FORMAT,
+            sample_count,
+            &[uniform_layout, environment_layout],
+        ),
+        debug_draw: DebugDrawPipeline::new(device, HDR_FORMAT, sample_count, &[uniform_layout]),
+        point_cloud: PointCloudPipeline::new(device, HDR_FORMAT, sample_count, &[uniform_layout]),
+    }
+}
+
+fn create_render_target(
+    device: &Device,
+    (width, height): (u32, u32),
+    format: TextureFormat,
+    sample_count: u32,
+) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: None,
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count,
+        dimension: TextureDimension::D2,
+        format,
+        view_formats: &[],
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+    })
+}
+
+/// Draws the selected objects into the selection mask, ignoring depth.
+fn create_selection_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader_module: &ShaderModule,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: None,
+        cache: None,
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader_module,
+            entry_point: Some("vertex"),
+            buffers: &[Vertex::LAYOUT],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(FragmentState {
+            module: shader_module,
+            entry_point: Some("selection_fragment"),
+            targets: &[Some(ColorTargetState {
+                format: SELECTION_MASK_FORMAT,
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: PrimitiveState::default(),
+        multisample: Default::default(),
+        depth_stencil: None,
+        multiview: None,
+    })
+}
+
+fn create_msaa_texture(device: &Device, size: (u32, u32), sample_count: u32) -> Option<Texture> {
+    (sample_count > 1).then(|| create_render_target(device, size, HDR_FORMAT, sample_count))
+}
+
+fn create_object_buffer(device: &Device, capacity: u64) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: None,
+        // Read as storage by the culling pass.
+        usage: BufferUsages::UNIFORM | BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        size: capacity.max(1) * OBJECT_UNIFORMS_STRIDE,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_indirect_buffer(device: &Device, capacity: u64) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: None,
+        usage: BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+        size: capacity.max(1) * INDIRECT_ARGS_STRIDE,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_storage_buffer<T>(device: &Device, capacity: u64) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: None,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        size: capacity.max(1) * std::mem::size_of::<T>() as u64,
+        mapped_at_creation: false,
+    })
+}
+
+/// Writes the data to the start of the storage buffer, replacing the buffer if it is too small.
+fn write_storage_buffer<T>(
+    device: &Device,
+    staging_belt: &mut util::StagingBelt,
+    encoder: &mut CommandEncoder,
+    buffer: &mut Buffer,
+    data: &[T],
+) {
+    if std::mem::size_of_val(data) as u64 > buffer.size() {
+        *buffer = create_storage_buffer::<T>(device, (data.len() as u64).next_power_of_two());
+    }
+    stage(device, staging_belt, encoder, buffer, as_byte_slice(data));
+}
+
+/// Records copying the data to the start of the buffer out of the staging belt.
+pub fn stage(
+    device: &Device,
+    staging_belt: &mut util::StagingBelt,
+    encoder: &mut CommandEncoder,
+    buffer: &Buffer,
+    data: &[u8],
+) {
+    if let Some(size) = BufferSize::new(data.len() as u64) {
+        staging_belt
+            .write_buffer(encoder, buffer, 0, size, device)
+            .copy_from_slice(data);
     }
 }
 
+fn create_object_array_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    buffer: &Buffer,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: buffer.as_entire_binding(),
+        }],
+    })
+}
+
+fn create_object_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    buffer: &Buffer,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: BindingResource::Buffer(BufferBinding {
+                buffer,
+                offset: 0,
+                size: BufferSize::new(std::mem::size_of::<ObjectUniforms>() as u64),
+            }),
+        }],
+    })
+}
+
+fn create_environment_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    environment: &Cubemap,
+    ibl: &Ibl,
+    brdf_lut: &TextureView,
+    ltc_luts: &LtcLuts,
+    sampler: &Sampler,
+) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&environment.view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Sampler(sampler),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::TextureView(&ibl.irradiance.view),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::TextureView(&ibl.specular.view),
+            },
+            BindGroupEntry {
+                binding: 4,
+                resource: BindingResource::TextureView(brdf_lut),
+            },
+            BindGroupEntry {
+                binding: 5,
+                resource: BindingResource::TextureView(&ltc_luts.matrix),
+            },
+            BindGroupEntry {
+                binding: 6,
+                resource: BindingResource::TextureView(&ltc_luts.amplitude),
+            },
+        ],
+    })
+}
+
 impl Renderer {
     pub async fn new(window: Arc<Window>) -> Self {
         let instance = Instance::new(&InstanceDescriptor::default());
@@ -51,15 +915,81 @@ impl Renderer {
             .await
             .expect("No GPU available");
 
-        println!("GPU: {}", adapter.get_info().name);
-        println!("Render Backend: {:?}", adapter.get_info().backend);
+        let adapter_info = adapter.get_info();
+        info!(
+            name = %adapter_info.name,
+            backend = ?adapter_info.backend,
+            "GPU"
+        );
+
+        let ray_traced_shadows = adapter.features().contains(RAY_TRACING_FEATURES);
+        info!(
+            "Shadows: {}",
+            if ray_traced_shadows {
+                "ray traced"
+            } else {
+                "shadow maps"
+            }
+        );
+
+        let bindless_capacity = BindlessMaterials::capacity(&adapter);
+        info!(
+            "Materials: {}",
+            if bindless_capacity.is_some() {
+                "bindless"
+            } else {
+                "bound per draw"
+            }
+        );
+
+        // The compacted draws of a page share a material bind group, so they need bindless
+        // materials.
+        let compaction =
+            bindless_capacity.is_some() && adapter.features().contains(COMPACTION_FEATURES);
+        info!(
+            "Draws: {}",
+            if compaction {
+                "compacted"
+            } else {
+                "per object"
+            }
+        );
 
         let (device, queue) = adapter
-            .request_device(&DeviceDescriptor::default(), None)
+            .request_device(
+                &DeviceDescriptor {
+                    required_features: if ray_traced_shadows {
+                        RAY_TRACING_FEATURES
+                    } else {
+                        Features::empty()
+                    } | if bindless_capacity.is_some() {
+                        BINDLESS_FEATURES
+                    } else {
+                        Features::empty()
+                    } | if compaction {
+                        COMPACTION_FEATURES
+                    } else {
+                        Features::empty()
+                    } | (adapter.features()
+                        & (Features::POLYGON_MODE_LINE | Features::TIMESTAMP_QUERY)),
+                    // The array of textures counts against the limit of sampled textures.
+                    required_limits: match bindless_capacity {
+                        Some(_) => Limits {
+                            max_sampled_textures_per_shader_stage: adapter
+                                .limits()
+                                .max_sampled_textures_per_shader_stage,
+                            ..Default::default()
+                        },
+                        None => Default::default(),
+                    },
+                    ..Default::default()
+                },
+                None,
+            )
             .await
             .unwrap();
 
-        let config = surface
+        let mut config = surface
             .get_default_config(
                 &adapter,
                 window.inner_size().width,
@@ -67,340 +997,2343 @@ impl Renderer {
             )
             .expect("Adapter does not support creation of surface");
 
-        println!("Surface format: {:?}", config.format);
+        // Prefer a surface which encodes to sRGB on write. Otherwise, the final blit encodes.
+        let capabilities = surface.get_capabilities(&adapter);
+        if let Some(&format) = capabilities.formats.iter().find(|format| format.is_srgb()) {
+            config.format = format;
+        }
+        info!(format = ?config.format, "Surface");
 
         surface.configure(&device, &config);
 
-        let positions: [Vector4<f32>; 36] = [
-            // Bottom
-            Vector4::new(-1.0, -1.0, -1.0, 1.0),
-            Vector4::new(-1.0, 1.0, -1.0, 1.0),
-            Vector4::new(1.0, -1.0, -1.0, 1.0),
-            Vector4::new(1.0, 1.0, -1.0, 1.0),
-            Vector4::new(1.0, -1.0, -1.0, 1.0),
-            Vector4::new(-1.0, 1.0, -1.0, 1.0),
-            // Top
-            Vector4::new(-1.0, -1.0, 1.0, 1.0),
-            Vector4::new(1.0, -1.0, 1.0, 1.0),
-            Vector4::new(-1.0, 1.0, 1.0, 1.0),
-            Vector4::new(1.0, 1.0, 1.0, 1.0),
-            Vector4::new(-1.0, 1.0, 1.0, 1.0),
-            Vector4::new(1.0, -1.0, 1.0, 1.0),
-            // Front
-            Vector4::new(-1.0, -1.0, -1.0, 1.0),
-            Vector4::new(1.0, -1.0, -1.0, 1.0),
-            Vector4::new(-1.0, -1.0, 1.0, 1.0),
-            Vector4::new(1.0, -1.0, 1.0, 1.0),
-            Vector4::new(-1.0, -1.0, 1.0, 1.0),
-            Vector4::new(1.0, -1.0, -1.0, 1.0),
-            // Back
-            Vector4::new(-1.0, 1.0, -1.0, 1.0),
-            Vector4::new(-1.0, 1.0, 1.0, 1.0),
-            Vector4::new(1.0, 1.0, -1.0, 1.0),
-            Vector4::new(1.0, 1.0, 1.0, 1.0),
-            Vector4::new(1.0, 1.0, -1.0, 1.0),
-            Vector4::new(-1.0, 1.0, 1.0, 1.0),
-            // Left
-            Vector4::new(-1.0, -1.0, -1.0, 1.0),
-            Vector4::new(-1.0, -1.0, 1.0, 1.0),
-            Vector4::new(-1.0, 1.0, -1.0, 1.0),
-            Vector4::new(-1.0, 1.0, 1.0, 1.0),
-            Vector4::new(-1.0, 1.0, -1.0, 1.0),
-            Vector4::new(-1.0, -1.0, 1.0, 1.0),
-            // Right
-            Vector4::new(1.0, -1.0, -1.0, 1.0),
-            Vector4::new(1.0, 1.0, -1.0, 1.0),
-            Vector4::new(1.0, -1.0, 1.0, 1.0),
-            Vector4::new(1.0, 1.0, 1.0, 1.0),
-            Vector4::new(1.0, -1.0, 1.0, 1.0),
-            Vector4::new(1.0, 1.0, -1.0, 1.0),
-        ];
-
-        let colors: [Vector4<f32>; 36] = [
-            // Bottom
-            Vector4::new(1.0, 0.0, 0.0, 1.0),
-            Vector4::new(1.0, 0.0, 0.0, 1.0),
-            Vector4::new(1.0, 0.0, 0.0, 1.0),
-            Vector4::new(1.0, 0.0, 0.0, 1.0),
-            Vector4::new(1.0, 0.0, 0.0, 1.0),
-            Vector4::new(1.0, 0.0, 0.0, 1.0),
-            // Top
-            Vector4::new(0.0, 1.0, 0.0, 1.0),
-            Vector4::new(0.0, 1.0, 0.0, 1.0),
-            Vector4::new(0.0, 1.0, 0.0, 1.0),
-            Vector4::new(0.0, 1.0, 0.0, 1.0),
-            Vector4::new(0.0, 1.0, 0.0, 1.0),
-            Vector4::new(0.0, 1.0, 0.0, 1.0),
-            // Front
-            Vector4::new(0.0, 0.0, 1.0, 1.0),
-            Vector4::new(0.0, 0.0, 1.0, 1.0),
-            Vector4::new(0.0, 0.0, 1.0, 1.0),
-            Vector4::new(0.0, 0.0, 1.0, 1.0),
-            Vector4::new(0.0, 0.0, 1.0, 1.0),
-            Vector4::new(0.0, 0.0, 1.0, 1.0),
-            // Back
-            Vector4::new(1.0, 1.0, 0.0, 1.0),
-            Vector4::new(1.0, 1.0, 0.0, 1.0),
-            Vector4::new(1.0, 1.0, 0.0, 1.0),
-            Vector4::new(1.0, 1.0, 0.0, 1.0),
-            Vector4::new(1.0, 1.0, 0.0, 1.0),
-            Vector4::new(1.0, 1.0, 0.0, 1.0),
-            // Left
-            Vector4::new(1.0, 0.0, 1.0, 1.0),
-            Vector4::new(1.0, 0.0, 1.0, 1.0),
-            Vector4::new(1.0, 0.0, 1.0, 1.0),
-            Vector4::new(1.0, 0.0, 1.0, 1.0),
-            Vector4::new(1.0, 0.0, 1.0, 1.0),
-            Vector4::new(1.0, 0.0, 1.0, 1.0),
-            // Right
-            Vector4::new(0.0, 1.0, 1.0, 1.0),
-            Vector4::new(0.0, 1.0, 1.0, 1.0),
-            Vector4::new(0.0, 1.0, 1.0, 1.0),
-            Vector4::new(0.0, 1.0, 1.0, 1.0),
-            Vector4::new(0.0, 1.0, 1.0, 1.0),
-            Vector4::new(0.0, 1.0, 1.0, 1.0),
-        ];
-
-        let vertex_position_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: as_byte_slice(&positions),
-            usage: BufferUsages::VERTEX,
-        });
-
-        let vertex_color_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        let uniform_buffer = device.create_buffer(&BufferDescriptor {
             label: None,
-            contents: as_byte_slice(&colors),
-            usage: BufferUsages::VERTEX,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            size: std::mem::size_of::<Uniforms>() as u64,
+            mapped_at_creation: false,
         });
 
-        let uniform_buffer = device.create_buffer(&BufferDescriptor {
+        let light_buffer = device.create_buffer(&BufferDescriptor {
             label: None,
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-            size: std::mem::size_of::<Uniforms>() as u64,
+            size: std::mem::size_of::<LightUniforms>() as u64,
             mapped_at_creation: false,
         });
 
+        let point_light_buffer = create_storage_buffer::<PointLightUniforms>(&device, 1);
+        let spot_light_buffer = create_storage_buffer::<SpotLightUniforms>(&device, 1);
+        let rect_light_buffer = create_storage_buffer::<RectLightUniforms>(&device, 1);
+        let light_clusters = LightClusters::new(&device);
+
         let shader_module = device.create_shader_module(ShaderModuleDescriptor {
             label: None,
-            source: ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
+            source: ShaderSource::Wgsl(
+                [
+                    concat!(
+                        include_str!("procedural.wgsl"),
+                        include_str!("shader.wgsl"),
+                        include_str!("deferred.wgsl"),
+                        include_str!("velocity.wgsl")
+                    ),
+                    if compaction {
+                        include_str!("object_indexed.wgsl")
+                    } else {
+                        include_str!("object.wgsl")
+                    },
+                    if bindless_capacity.is_some() {
+                        include_str!("material_bindless.wgsl")
+                    } else {
+                        include_str!("material.wgsl")
+                    },
+                    if ray_traced_shadows {
+                        include_str!("ray_shadows.wgsl")
+                    } else {
+                        include_str!("ray_shadows_fallback.wgsl")
+                    },
+                ]
+                .concat()
+                .into(),
+            ),
         });
 
-        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: None,
-            cache: None,
-            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
-                bind_group_layouts: &[&device.create_bind_group_layout(
-                    &BindGroupLayoutDescriptor {
-                        label: None,
-                        entries: &[BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: ShaderStages::VERTEX,
-                            ty: BindingType::Buffer {
-                                ty: BufferBindingType::Uniform,
-                                has_dynamic_offset: false,
-                                min_binding_size: None,
-                            },
-                            count: None,
-                        }],
+        let mut uniform_bind_group_layout_entries = vec![
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX_FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 1,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 3,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 4,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 5,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Depth,
+                    view_dimension: TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 6,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Comparison),
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 7,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Depth,
+                    view_dimension: TextureViewDimension::CubeArray,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 8,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Sampler(SamplerBindingType::Comparison),
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 9,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 10,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            BindGroupLayoutEntry {
+                binding: 11,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: true },
+                    view_dimension: TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+        ];
+        if ray_traced_shadows {
+            uniform_bind_group_layout_entries.push(BindGroupLayoutEntry {
+                binding: 12,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::AccelerationStructure,
+                count: None,
+            });
+        }
+        let uniform_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &uniform_bind_group_layout_entries,
+            });
+
+        let environment_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::Cube,
+                            multisampled: false,
+                        },
+                        count: None,
                     },
-                )],
-                ..Default::default()
-            })),
-            vertex: VertexState {
-                module: &shader_module,
-                entry_point: None,
-                buffers: &[
-                    VertexBufferLayout {
-                        array_stride: std::mem::size_of::<Vector4<f32>>() as BufferAddress,
-                        step_mode: VertexStepMode::Vertex,
-                        attributes: &[VertexAttribute {
-                            offset: 0,
-                            shader_location: 0,
-                            format: VertexFormat::Float32x4,
-                        }],
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::Cube,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::Cube,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
                     },
-                    VertexBufferLayout {
-                        array_stride: std::mem::size_of::<Vector4<f32>>() as BufferAddress,
-                        step_mode: VertexStepMode::Vertex,
-                        attributes: &[VertexAttribute {
-                            offset: 0,
-                            shader_location: 1,
-                            format: VertexFormat::Float32x4,
-                        }],
+                    BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
                     },
                 ],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(FragmentState {
-                module: &shader_module,
-                entry_point: None,
-                targets: &[Some(ColorTargetState {
-                    format: config.format,
-                    blend: Some(BlendState::REPLACE),
-                    write_mask: ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
-                polygon_mode: PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            multisample: MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            depth_stencil: Some(DepthStencilState {
-                format: TextureFormat::Depth24Plus,
-                depth_write_enabled: true,
-                depth_compare: CompareFunction::LessEqual,
-                stencil: Default::default(),
-                bias: Default::default(),
-            }),
-            multiview: None,
+            });
+
+        let material_bind_group_layout = MaterialBinding::bind_group_layout(&device);
+        let bindless = bindless_capacity.map(|capacity| BindlessMaterials::new(&device, capacity));
+        let white_texture = create_texture(
+            &device,
+            &queue,
+            &image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4])),
+            true,
+        )
+        .create_view(&Default::default());
+        let material_sampler = device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            anisotropy_clamp: 16,
+            ..Default::default()
         });
 
-        let depth_texture = device.create_texture(
-            &(TextureDescriptor {
+        let object_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
                 label: None,
-                size: Extent3d {
-                    width: config.width,
-                    height: config.height,
-                    depth_or_array_layers: 1,
-                },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: TextureDimension::D2,
-                format: TextureFormat::Depth24Plus,
-                view_formats: &[],
-                usage: TextureUsages::RENDER_ATTACHMENT,
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let object_array_bind_group_layout = compaction.then(|| {
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            })
+        });
+        let object_buffer = create_object_buffer(&device, 1);
+        let indirect_buffer = create_indirect_buffer(&device, 1);
+        let culling = GpuCulling::new(&device);
+        let shadow_settings = ShadowSettings::default();
+        let shadow_map = ShadowMap::new(
+            &device,
+            &object_bind_group_layout,
+            shadow_settings.quality.size(),
+            shadow_settings.depth_bias(),
+        );
+        let point_shadow_maps = PointShadowMaps::new(&device, &object_bind_group_layout);
+        let object_bind_group =
+            create_object_bind_group(&device, &object_bind_group_layout, &object_buffer);
+        let object_array_bind_group = object_array_bind_group_layout
+            .as_ref()
+            .map(|layout| create_object_array_bind_group(&device, layout, &object_buffer));
+
+        let environment = Cubemap::sky(&device, &queue, &SkyGradient::default());
+        let ibl = Ibl::new(&device, &queue, &environment);
+        let brdf_lut = create_brdf_lut(&device, &queue);
+        let ltc_luts = LtcLuts::new(&device, &queue);
+        let lightmaps = Lightmaps::empty(&device, &queue);
+        let ray_traced_shadows = ray_traced_shadows.then(|| RayTracedShadows::new(&device));
+        let environment_sampler = device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+        let environment_bind_group = create_environment_bind_group(
+            &device,
+            &environment_bind_group_layout,
+            &environment,
+            &ibl,
+            &brdf_lut,
+            &ltc_luts,
+            &environment_sampler,
+        );
+
+        let max_sample_count = if adapter
+            .get_texture_format_features(HDR_FORMAT)
+            .flags
+            .sample_count_supported(4)
+        {
+            4
+        } else {
+            1
+        };
+        let anti_aliasing = if max_sample_count > 1 {
+            AntiAliasing::Msaa
+        } else {
+            AntiAliasing::Fxaa
+        };
+        let sample_count = max_sample_count;
+
+        let deformation = Deformation::new(&device);
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            bind_group_layouts: &[
+                &uniform_bind_group_layout,
+                &environment_bind_group_layout,
+                bindless
+                    .as_ref()
+                    .map_or(&material_bind_group_layout, |bindless| &bindless.layout),
+                object_array_bind_group_layout
+                    .as_ref()
+                    .unwrap_or(&object_bind_group_layout),
+            ],
+            ..Default::default()
+        });
+        let built = PipelineKey {
+            sample_count,
+            render_mode: RenderMode::default(),
+        };
+        let ScenePipelines {
+            pipelines,
+            skybox,
+            debug_draw: debug_draw_pipeline,
+            point_cloud: point_cloud_pipeline,
+        } = create_scene_pipelines(
+            &device,
+            &pipeline_layout,
+            &shader_module,
+            &uniform_bind_group_layout,
+            &environment_bind_group_layout,
+            built,
+        );
+
+        let gbuffer_bind_group_layout = GBuffer::bind_group_layout(&device);
+        let velocity_pipelines = VelocityPipelines::new(&device, &shader_module, &pipeline_layout);
+        let selection_pipeline =
+            create_selection_pipeline(&device, &pipeline_layout, &shader_module);
+        let deferred_pipelines = DeferredPipelines::new(
+            &device,
+            &shader_module,
+            &pipeline_layout,
+            &device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                bind_group_layouts: &[
+                    &uniform_bind_group_layout,
+                    &environment_bind_group_layout,
+                    &gbuffer_bind_group_layout,
+                ],
+                ..Default::default()
             }),
+            HDR_FORMAT,
+        );
+
+        let size = (config.width, config.height);
+        let depth_texture =
+            create_render_target(&device, size, TextureFormat::Depth24Plus, sample_count);
+        let msaa_texture = create_msaa_texture(&device, size, sample_count);
+        let mut post = PostProcessing::new(&device, config.format, config.width, config.height);
+        post.fxaa.enabled = anti_aliasing == AntiAliasing::Fxaa;
+        let ui_pass = UiPass::new(&device, config.format);
+        let profiler = GpuProfiler::new(&device, &queue);
+        info!(
+            "Pass timing: {}",
+            if profiler.is_some() {
+                "timestamp queries"
+            } else {
+                "unsupported"
+            }
         );
 
         Renderer {
+            instance,
             surface,
             config,
+            adapter_info,
             device,
             queue,
-            pipeline,
+            shader_module: Arc::new(shader_module),
+            pipeline_layout,
+            pipelines,
+            built,
+            pipeline_builder: PipelineBuilder::default(),
+            deferred_pipelines,
+            velocity_pipelines,
+            selection_pipeline,
+            velocity_buffer: None,
+            stats: SceneStats::default(),
+            previous_transforms: Components::default(),
+            gbuffer_bind_group_layout,
+            gbuffer: None,
+            deferred: false,
+            anti_aliasing,
+            render_mode: RenderMode::default(),
+            frame_index: 0,
+            previous_view_projection: Matrix4::identity(),
+            max_sample_count,
             uniform_buffer,
-            vertex_position_buffer,
-            vertex_color_buffer,
+            light_buffer,
+            point_light_buffer,
+            spot_light_buffer,
+            rect_light_buffer,
+            light_clusters,
+            uniform_bind_group_layout,
             depth_texture,
+            msaa_texture,
+            post,
+            ui_pass,
+            meshes: Vec::new(),
+            arena: MeshArena::default(),
+            materials: Vec::new(),
+            material_bind_group_layout,
+            textures: TextureCache::default(),
+            bindless,
+            white_texture,
+            material_sampler,
+            object_buffer,
+            staging_belt: util::StagingBelt::new(STAGING_CHUNK_SIZE),
+            object_bind_group_layout,
+            object_bind_group,
+            object_array_bind_group_layout,
+            object_array_bind_group,
+            environment,
+            ibl,
+            brdf_lut,
+            ltc_luts,
+            lightmaps,
+            environment_sampler,
+            environment_bind_group_layout,
+            environment_bind_group,
+            skybox,
+            shadow_map,
+            point_shadow_maps,
+            shadow_settings,
+            ray_traced_shadows,
+            light_gizmos: true,
+            bounds_gizmos: false,
+            screenshot_requested: false,
+            screenshots: Vec::new(),
+            profiler,
+            debug_view: None,
+            dynamic_resolution: DynamicResolution::default(),
+            debug_draw: DebugDraw::default(),
+            gizmo_draw: DebugDraw::default(),
+            debug_draw_pipeline,
+            point_clouds: Vec::new(),
+            point_cloud_pipeline,
+            deformation,
+            deformed_meshes: HashSet::new(),
+            toon: false,
+            indirect: false,
+            indirect_buffer,
+            gpu_culling: false,
+            culling,
+            occlusion_culling: false,
+            parallel_encoding: true,
+            lod: true,
+            lod_levels: Components::default(),
         }
     }
 
-    pub fn render(&mut self, view: Matrix4<f32>) {
-        let surface_texture = self
-            .surface
-            .get_current_texture()
-            .expect("Cannot get next texture");
-        let surface_texture_view = surface_texture
-            .texture
-            .create_view(&TextureViewDescriptor::default());
-        let depth_texture_view = self
-            .depth_texture
-            .create_view(&TextureViewDescriptor::default());
+    /// Toggles tinting each shadow cascade in a different color.
+    pub fn toggle_cascade_debug(&mut self) {
+        self.shadow_settings.cascade_debug = !self.shadow_settings.cascade_debug;
+    }
 
-        self.queue.write_buffer(
-            &self.uniform_buffer,
-            0,
-            as_byte_slice(&[Uniforms {
-                model: Matrix4::identity(),
-                view,
-                projection: {
-                    let fovy = 60.0_f32.to_radians();
-                    let near = 0.1;
-                    let far = 100.0;
-
-                    let aspect = self.config.width as f32 / self.config.height as f32;
-                    let tan_half_fovy = (0.5 * fovy).tan();
-                    Matrix4::from_cols(
-                        Vector4::new(1.0 / (aspect * tan_half_fovy), 0.0, 0.0, 0.0),
-                        Vector4::new(0.0, 1.0 / tan_half_fovy, 0.0, 0.0),
-                        Vector4::new(0.0, 0.0, -(far + near) / (far - near), -1.0),
-                        Vector4::new(0.0, 0.0, -2.0 * far * near / (far - near), 0.0),
-                    )
-                },
-            }]),
+    /// Switches to the next shadow filtering mode.
+    pub fn cycle_shadow_filter(&mut self) {
+        self.shadow_settings.filter = self.shadow_settings.filter.next();
+        println!("Shadow filter: {:?}", self.shadow_settings.filter);
+    }
+
+    /// Scales the constant and slope-scaled depth bias of the shadow cascades by the given number
+    /// of steps, trading shadow acne for peter-panning.
+    pub fn adjust_shadow_bias(&mut self, steps: i32) {
+        let settings = &mut self.shadow_settings;
+        settings.constant_bias = (settings.constant_bias + steps).max(0);
+        settings.slope_bias = (settings.slope_bias + 0.5 * steps as f32).max(0.0);
+        self.shadow_map
+            .set_depth_bias(&self.device, settings.depth_bias());
+        println!(
+            "Shadow depth bias: {} constant, {} slope-scaled",
+            settings.constant_bias, settings.slope_bias
         );
+    }
 
-        let mut encoder = self.device.create_command_encoder(&Default::default());
+    /// Moves the shadow receivers along their normals by the given number of quarter texels.
+    pub fn adjust_shadow_normal_offset(&mut self, steps: i32) {
+        let settings = &mut self.shadow_settings;
+        settings.normal_offset = (settings.normal_offset + 0.25 * steps as f32).max(0.0);
+        println!("Shadow normal offset: {} texels", settings.normal_offset);
+    }
 
-        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
-            color_attachments: &[Some(RenderPassColorAttachment {
-                view: &surface_texture_view,
-                resolve_target: None,
-                ops: Operations {
-                    load: LoadOp::Clear(wgpu::Color {
-                        r: 0.01,
-                        g: 0.01,
-                        b: 0.01,
-                        a: 1.0,
-                    }),
-                    store: StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                view: &depth_texture_view,
-                depth_ops: Some(Operations {
-                    load: LoadOp::Clear(1.0),
-                    store: StoreOp::Store,
-                }),
-                stencil_ops: None,
-            }),
-            ..Default::default()
-        });
-        pass.set_bind_group(
-            0,
-            &self.device.create_bind_group(&BindGroupDescriptor {
-                label: None,
-                layout: &self.pipeline.get_bind_group_layout(0),
-                entries: &[BindGroupEntry {
-                    binding: 0,
-                    resource: self.uniform_buffer.as_entire_binding(),
-                }],
-            }),
-            &[],
+    /// Toggles motion blur together with the velocity buffer it reads.
+    pub fn toggle_motion_blur(&mut self) {
+        self.post.motion_blur.enabled = !self.post.motion_blur.enabled;
+        self.update_velocity_buffer();
+        println!(
+            "Motion blur: {}",
+            if self.post.motion_blur.enabled {
+                "on"
+            } else {
+                "off"
+            }
         );
-        pass.set_vertex_buffer(0, self.vertex_position_buffer.slice(..));
-        pass.set_vertex_buffer(1, self.vertex_color_buffer.slice(..));
-        pass.set_pipeline(&self.pipeline);
-        pass.draw(0..36, 0..1);
-        drop(pass);
-
-        self.queue.submit(Some(encoder.finish()));
-        surface_texture.present();
     }
 
-    pub fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
-        if size.width == 0 || size.height == 0 {
-            return;
+    /// Allocates the velocity buffer while something reads it and frees it otherwise.
+    fn update_velocity_buffer(&mut self) {
+        let needed = self.post.motion_blur.enabled
+            || self.post.buffer_debug.view == Some(BufferView::Velocity);
+        if needed != self.velocity_buffer.is_some() {
+            self.velocity_buffer = needed.then(|| {
+                VelocityBuffer::new(&self.device, self.render_size().0, self.render_size().1)
+            });
         }
-        self.config.width = size.width;
-        self.config.height = size.height;
-        self.surface.configure(&self.device, &self.config);
+    }
 
-        self.depth_texture = self.device.create_texture(
-            &(wgpu::TextureDescriptor {
-                label: None,
-                size: wgpu::Extent3d {
-                    width: self.config.width,
-                    height: self.config.height,
-                    depth_or_array_layers: 1,
+    /// Toggles adapting the render resolution to the frame time, upscaling to the surface.
+    pub fn toggle_dynamic_resolution(&mut self) {
+        let dynamic_resolution = &mut self.dynamic_resolution;
+        dynamic_resolution.enabled = !dynamic_resolution.enabled;
+        println!(
+            "Dynamic resolution: {}",
+            if dynamic_resolution.enabled {
+                "on"
+            } else {
+                "off"
+            }
+        );
+    }
+
+    /// Cycles through showing the intermediate buffers in place of the image.
+    pub fn cycle_buffer_view(&mut self) {
+        let buffer_debug = &mut self.post.buffer_debug;
+        buffer_debug.view = BufferView::next(buffer_debug.view);
+        match buffer_debug.view {
+            Some(view) => println!("Buffer view: {view:?}"),
+            None => println!("Buffer view: off"),
+        }
+        self.update_velocity_buffer();
+    }
+
+    /// Toggles the glow around highlights.
+    pub fn toggle_bloom(&mut self) {
+        let bloom = &mut self.post.bloom;
+        bloom.enabled = !bloom.enabled;
+        println!("Bloom: {}", if bloom.enabled { "on" } else { "off" });
+    }
+
+    /// Toggles grading with the loaded lookup table.
+    pub fn toggle_color_grading(&mut self) {
+        let color_grading = &mut self.post.color_grading;
+        if !color_grading.is_loaded() {
+            println!("Color grading: no lookup table loaded, pass --lut <file.cube>");
+            return;
+        }
+        color_grading.enabled = !color_grading.enabled;
+        println!(
+            "Color grading: {}",
+            if color_grading.enabled { "on" } else { "off" }
+        );
+    }
+
+    /// Cycles between no fog and each falloff.
+    pub fn cycle_fog(&mut self) {
+        let fog = &mut self.post.fog;
+        match (fog.enabled, fog.falloff) {
+            (false, _) => {
+                fog.enabled = true;
+                fog.falloff = FogFalloff::Linear;
+            }
+            (true, FogFalloff::Linear) => fog.falloff = FogFalloff::Exponential,
+            (true, FogFalloff::Exponential) => fog.enabled = false,
+        }
+        if fog.enabled {
+            println!("Fog: {:?}", fog.falloff);
+        } else {
+            println!("Fog: off");
+        }
+    }
+
+    pub fn toggle_painterly(&mut self) {
+        let painterly = &mut self.post.painterly;
+        painterly.enabled = !painterly.enabled;
+        println!(
+            "Painterly filter: {}",
+            if painterly.enabled { "on" } else { "off" }
+        );
+    }
+
+    pub fn toggle_sharpening(&mut self) {
+        let sharpen = &mut self.post.sharpen;
+        sharpen.enabled = !sharpen.enabled;
+        if sharpen.enabled {
+            println!("Sharpening: {}", sharpen.sharpness);
+        } else {
+            println!("Sharpening: off");
+        }
+    }
+
+    /// Cycles between full resolution, pixelation, and pixelation with a reduced palette.
+    pub fn cycle_pixelation(&mut self) {
+        let pixelate = &mut self.post.pixelate;
+        match (pixelate.enabled, pixelate.levels) {
+            (false, _) => {
+                pixelate.enabled = true;
+                pixelate.levels = None;
+            }
+            (true, None) => pixelate.levels = Some(4),
+            (true, Some(_)) => pixelate.enabled = false,
+        }
+        match (pixelate.enabled, pixelate.levels) {
+            (false, _) => println!("Pixelation: off"),
+            (true, None) => println!("Pixelation: {} pixels high", pixelate.height),
+            (true, Some(levels)) => println!(
+                "Pixelation: {} pixels high, {levels} levels per channel",
+                pixelate.height
+            ),
+        }
+    }
+
+    pub fn toggle_dithering(&mut self) {
+        self.post.dither = !self.post.dither;
+        println!("Dithering: {}", if self.post.dither { "on" } else { "off" });
+    }
+
+    pub fn toggle_gamma_debug(&mut self) {
+        self.post.gamma_debug = !self.post.gamma_debug;
+        println!(
+            "Gamma debug: {}",
+            if self.post.gamma_debug { "on" } else { "off" }
+        );
+    }
+
+    /// Toggles the color fringes towards the edges.
+    pub fn toggle_chromatic_aberration(&mut self) {
+        let chromatic_aberration = &mut self.post.chromatic_aberration;
+        chromatic_aberration.enabled = !chromatic_aberration.enabled;
+        println!(
+            "Chromatic aberration: {}",
+            if chromatic_aberration.enabled {
+                "on"
+            } else {
+                "off"
+            }
+        );
+    }
+
+    /// Toggles the vignette and film grain.
+    pub fn toggle_stylize(&mut self) {
+        let stylize = &mut self.post.stylize;
+        stylize.enabled = !stylize.enabled;
+        println!(
+            "Vignette and film grain: {}",
+            if stylize.enabled { "on" } else { "off" }
+        );
+    }
+
+    /// Switches to the next tone mapping curve.
+    pub fn cycle_tonemapper(&mut self) {
+        let tonemap = &mut self.post.tonemap;
+        tonemap.tonemapper = tonemap.tonemapper.next();
+        println!("Tone mapping: {:?}", tonemap.tonemapper);
+    }
+
+    /// Toggles between adapting the exposure to the scene and the manual exposure alone.
+    pub fn toggle_auto_exposure(&mut self) {
+        let auto_exposure = &mut self.post.tonemap.auto_exposure;
+        auto_exposure.enabled = !auto_exposure.enabled;
+        println!(
+            "Exposure: {}",
+            if auto_exposure.enabled {
+                "automatic"
+            } else {
+                "manual"
+            }
+        );
+    }
+
+    /// Brightens or darkens the image by the given number of half stops.
+    pub fn adjust_exposure(&mut self, steps: i32) {
+        let tonemap = &mut self.post.tonemap;
+        tonemap.exposure += 0.5 * steps as f32;
+        println!("Exposure: {:+} EV", tonemap.exposure);
+    }
+
+    /// Toggles the wireframes outlining every light.
+    pub fn toggle_light_gizmos(&mut self) {
+        self.light_gizmos = !self.light_gizmos;
+    }
+
+    /// Saves the next frame to a PNG, without the UI, once it has been read back.
+    pub fn take_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    /// Saves the screenshots and takes the pass times whose frames have arrived, without waiting
+    /// for the others.
+    fn read_back(&mut self) {
+        self.device.poll(Maintain::Poll);
+        let (ready, pending) = std::mem::take(&mut self.screenshots)
+            .into_iter()
+            .partition(Screenshot::is_mapped);
+        self.screenshots = pending;
+        for screenshot in ready {
+            screenshot.save();
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.collect();
+        }
+        self.culling.collect();
+    }
+
+    /// Sees the frame through the debug camera's view, through the default projection, until
+    /// unset. Its frustum is drawn together with the cascade splits.
+    pub fn set_debug_view(&mut self, view: Option<Matrix4<f32>>) {
+        self.debug_view = view;
+    }
+
+    /// The view and projection the frame is seen through, which are the given view and the
+    /// scene's unless a debug view is set.
+    pub fn shown_view(&self, view: Matrix4<f32>, scene: &Scene) -> (Matrix4<f32>, Projection) {
+        match self.debug_view {
+            Some(debug_view) => (debug_view, Projection::default()),
+            None => (view, scene.projection()),
+        }
+    }
+
+    /// Toggles the bounds, axes and names of objects, for debugging the culling. Objects culled
+    /// on the GPU are shown as last read back, occluded ones in magenta.
+    pub fn toggle_bounds_gizmos(&mut self) {
+        self.bounds_gizmos = !self.bounds_gizmos;
+    }
+
+    /// Toggles toon shading and outlines for all materials.
+    pub fn toggle_toon(&mut self) {
+        self.toon = !self.toon;
+        println!("Toon shading: {}", if self.toon { "on" } else { "off" });
+    }
+
+    /// Switches to the next anti-aliasing mode, skipping MSAA if unsupported.
+    pub fn cycle_anti_aliasing(&mut self) {
+        let mut anti_aliasing = self.anti_aliasing.next();
+        if anti_aliasing == AntiAliasing::Msaa && self.max_sample_count == 1 {
+            anti_aliasing = anti_aliasing.next();
+        }
+        println!("Anti-aliasing: {anti_aliasing:?}");
+        self.apply_settings(RenderSettings {
+            anti_aliasing,
+            ..self.settings()
+        });
+    }
+
+    /// Cycles through visualizing the surfaces' values in place of the shaded image, skipping the
+    /// wireframe where the adapter cannot draw it.
+    pub fn cycle_render_mode(&mut self) {
+        let mut render_mode = self.render_mode.next();
+        if render_mode == RenderMode::Wireframe
+            && !self.device.features().contains(Features::POLYGON_MODE_LINE)
+        {
+            render_mode = render_mode.next();
+        }
+        println!("Render mode: {render_mode:?}");
+        self.apply_settings(RenderSettings {
+            render_mode,
+            ..self.settings()
+        });
+    }
+
+    /// Toggles between forward and deferred shading of opaque and masked objects.
+    pub fn toggle_deferred(&mut self) {
+        let deferred = !self.deferred;
+        println!(
+            "Rendering: {}",
+            if deferred { "deferred" } else { "forward" }
+        );
+        self.apply_settings(RenderSettings {
+            deferred,
+            ..self.settings()
+        });
+    }
+
+    pub fn settings(&self) -> RenderSettings {
+        RenderSettings {
+            anti_aliasing: self.anti_aliasing,
+            vsync: self.config.present_mode != PresentMode::AutoNoVsync,
+            render_scale: self.dynamic_resolution.max_scale,
+            dynamic_resolution: self.dynamic_resolution.enabled,
+            shadow_quality: self.shadow_settings.quality,
+            shadow_filter: self.shadow_settings.filter,
+            tonemapper: self.post.tonemap.tonemapper,
+            render_mode: self.render_mode,
+            deferred: self.deferred,
+            indirect: self.indirect,
+            gpu_culling: self.gpu_culling,
+            occlusion_culling: self.occlusion_culling,
+            parallel_encoding: self.parallel_encoding,
+            lod: self.lod,
+        }
+    }
+
+    /// Switches to the settings, rebuilding only the pipelines, render targets, surface or shadow
+    /// map they affect. New pipelines are built in the background, and the render targets
+    /// rebuilt once they are ready. MSAA without multisampling and the wireframe without line
+    /// polygons keep the current choice.
+    pub fn apply_settings(&mut self, mut settings: RenderSettings) {
+        let current = self.settings();
+        if settings.anti_aliasing == AntiAliasing::Msaa && self.max_sample_count == 1 {
+            settings.anti_aliasing = current.anti_aliasing;
+        }
+        if settings.render_mode == RenderMode::Wireframe
+            && !self.device.features().contains(Features::POLYGON_MODE_LINE)
+        {
+            settings.render_mode = current.render_mode;
+        }
+        if settings == current {
+            return;
+        }
+        self.anti_aliasing = settings.anti_aliasing;
+        self.post.fxaa.enabled = settings.anti_aliasing == AntiAliasing::Fxaa;
+        self.post.taa.enabled = settings.anti_aliasing == AntiAliasing::Taa;
+        if settings.anti_aliasing != current.anti_aliasing {
+            self.post.taa.reset();
+        }
+        if settings.vsync != current.vsync {
+            self.config.present_mode = if settings.vsync {
+                PresentMode::AutoVsync
+            } else {
+                PresentMode::AutoNoVsync
+            };
+            self.surface.configure(&self.device, &self.config);
+        }
+        // Picked up by the next frame, which resizes the targets if the scale changes.
+        self.dynamic_resolution.max_scale = settings.render_scale;
+        self.dynamic_resolution.enabled = settings.dynamic_resolution;
+        if settings.shadow_quality != current.shadow_quality {
+            self.shadow_map
+                .set_size(&self.device, settings.shadow_quality.size());
+        }
+        self.shadow_settings.quality = settings.shadow_quality;
+        self.shadow_settings.filter = settings.shadow_filter;
+        self.post.tonemap.tonemapper = settings.tonemapper;
+        self.render_mode = settings.render_mode;
+        self.deferred = settings.deferred;
+        self.indirect = settings.indirect;
+        self.gpu_culling = settings.gpu_culling;
+        self.occlusion_culling = settings.occlusion_culling;
+        self.parallel_encoding = settings.parallel_encoding;
+        self.lod = settings.lod;
+        if !(settings.gpu_culling && settings.occlusion_culling) {
+            self.culling.depth_pyramid.invalidate();
+        }
+
+        // The sample count is shared by the pipelines and targets, so the targets wait for the
+        // pipelines, and the frames keep drawing the previous settings until then.
+        let key = self.pipeline_key();
+        if key != self.built {
+            self.request_pipelines(key);
+        } else {
+            self.pipeline_builder.cancel();
+            if settings.deferred != current.deferred {
+                self.rebuild_targets();
+            }
+        }
+    }
+
+    /// Deferred rendering draws without multisampling, since the G-buffer is lit per pixel.
+    fn active_sample_count(&self) -> u32 {
+        if self.deferred || self.anti_aliasing != AntiAliasing::Msaa {
+            1
+        } else {
+            self.max_sample_count
+        }
+    }
+
+    fn pipeline_key(&self) -> PipelineKey {
+        PipelineKey {
+            sample_count: self.active_sample_count(),
+            render_mode: self.render_mode,
+        }
+    }
+
+    fn request_pipelines(&mut self, key: PipelineKey) {
+        let device = self.device.clone();
+        let layout = self.pipeline_layout.clone();
+        let shader_module = self.shader_module.clone();
+        let uniform_layout = self.uniform_bind_group_layout.clone();
+        let environment_layout = self.environment_bind_group_layout.clone();
+        self.pipeline_builder.build(key, move || {
+            create_scene_pipelines(
+                &device,
+                &layout,
+                &shader_module,
+                &uniform_layout,
+                &environment_layout,
+                key,
+            )
+        });
+    }
+
+    /// Swaps in the pipelines built since, with render targets to match.
+    fn poll_pipelines(&mut self) {
+        let Some((key, built)) = self.pipeline_builder.poll() else {
+            return;
+        };
+        self.pipelines = built.pipelines;
+        self.skybox = built.skybox;
+        self.debug_draw_pipeline = built.debug_draw;
+        self.point_cloud_pipeline = built.point_cloud;
+        self.built = key;
+        self.post.raw = key.render_mode != RenderMode::Shaded;
+        self.rebuild_targets();
+    }
+
+    /// Whether pipelines for changed settings are still being built.
+    pub fn is_building(&self) -> bool {
+        self.pipeline_builder.building().is_some()
+    }
+
+    /// The resolution the scene is rendered at, before upscaling to the surface.
+    pub fn render_size(&self) -> (u32, u32) {
+        let scale =
+            |size: u32| ((size as f32 * self.dynamic_resolution.scale).round() as u32).max(1);
+        (scale(self.config.width), scale(self.config.height))
+    }
+
+    fn rebuild_targets(&mut self) {
+        let sample_count = self.built.sample_count;
+        let (width, height) = self.render_size();
+        self.depth_texture = create_render_target(
+            &self.device,
+            (width, height),
+            TextureFormat::Depth24Plus,
+            sample_count,
+        );
+        self.msaa_texture = create_msaa_texture(&self.device, (width, height), sample_count);
+        if self.velocity_buffer.is_some() {
+            self.velocity_buffer = Some(VelocityBuffer::new(&self.device, width, height));
+        }
+        self.gbuffer = self.deferred.then(|| {
+            GBuffer::new(
+                &self.device,
+                &self.gbuffer_bind_group_layout,
+                width,
+                height,
+                &self.depth_texture.create_view(&Default::default()),
+            )
+        });
+    }
+
+    pub fn add_mesh(&mut self, data: &MeshData) -> MeshId {
+        self.meshes
+            .push(Mesh::new(&mut self.arena, &self.device, &self.queue, data));
+        MeshId(self.meshes.len() - 1)
+    }
+
+    pub fn add_point_cloud(&mut self, points: &[Point]) -> PointCloudId {
+        self.point_clouds
+            .push(PointCloud::new(&self.device, points));
+        PointCloudId(self.point_clouds.len() - 1)
+    }
+
+    /// Deforms the mesh by morph targets and the joints of a skin, which the animation drives.
+    pub fn add_deformation(
+        &mut self,
+        mesh: MeshId,
+        skin: Option<(&[SkinVertex], usize)>,
+        targets: &[MorphTarget],
+    ) -> DeformationId {
+        self.deformed_meshes.insert(mesh);
+        self.meshes[mesh.0].make_dedicated(&mut self.arena, &self.device, &self.queue);
+        self.deformation.add(
+            &self.device,
+            &self.meshes[mesh.0],
+            &self.arena,
+            skin,
+            targets,
+        )
+    }
+
+    /// Whether the mesh is skinned or morphed, and so drawn differently from its data.
+    pub fn is_deformed(&self, id: MeshId) -> bool {
+        self.deformed_meshes.contains(&id)
+    }
+
+    pub fn mesh(&self, id: MeshId) -> &Mesh {
+        &self.meshes[id.0]
+    }
+
+    pub fn material(&self, id: MaterialId) -> &Material {
+        &self.materials[id.0].material
+    }
+
+    /// Uploads an 8-bit texture, which is interpreted as sRGB when it holds colors. Identical
+    /// images share one texture, which stays uploaded until each of them is released.
+    pub fn add_texture(&mut self, image: &image::RgbaImage, srgb: bool) -> TextureId {
+        self.textures.add(&self.device, &self.queue, image, srgb)
+    }
+
+    /// Like `add_texture`, for images which come with their mip chain.
+    pub fn add_mipped_texture(&mut self, mips: &MipChain) -> TextureId {
+        self.textures.add_mipped(&self.device, &self.queue, mips)
+    }
+
+    /// The texture's levels, copied back from the GPU.
+    pub fn read_texture(&self, id: TextureId) -> MipChain<'static> {
+        self.textures.read_back(&self.device, &self.queue, id)
+    }
+
+    /// Releases a texture returned by `add_texture`. Materials already using it keep it alive.
+    pub fn release_texture(&mut self, id: TextureId) {
+        self.textures.release(id);
+    }
+
+    pub fn add_material(&mut self, material: &Material) -> MaterialId {
+        let binding = self.bind_material(material);
+        self.materials.push(binding);
+        if let Some(bindless) = &mut self.bindless {
+            bindless.invalidate();
+        }
+        MaterialId(self.materials.len() - 1)
+    }
+
+    /// Uploads the material's texture again if it has been unloaded.
+    fn bind_material(&mut self, material: &Material) -> MaterialBinding {
+        if let Some(texture) = material.base_color_texture {
+            self.textures.upload(&self.device, &self.queue, texture);
+        }
+        let base_color_texture = material
+            .base_color_texture
+            .map_or(&self.white_texture, |id| self.textures.view(id));
+        MaterialBinding::new(
+            &self.device,
+            &self.material_bind_group_layout,
+            material,
+            base_color_texture,
+            &self.material_sampler,
+        )
+    }
+
+    /// Uploads the meshes, materials, textures and point clouds the scene draws which have been
+    /// unloaded.
+    fn upload_scene(&mut self, scene: &Scene) {
+        for object in scene.world.renderables() {
+            self.meshes[object.mesh.0].upload(&mut self.arena, &self.device, &self.queue);
+            if self.materials[object.material.0].bind_group.is_none() {
+                let material = self.materials[object.material.0].material;
+                self.materials[object.material.0] = self.bind_material(&material);
+                if let Some(bindless) = &mut self.bindless {
+                    bindless.invalidate();
+                }
+            }
+        }
+        for point_cloud in &scene.point_clouds {
+            self.point_clouds[point_cloud.0].upload(&self.device);
+        }
+    }
+
+    /// Unloads the meshes, materials, textures and point clouds the scene does not draw from the
+    /// GPU, keeping what is needed to upload them again once a scene draws them. Deformed meshes
+    /// stay, as their deformation writes into their buffers.
+    pub fn unload_unused(&mut self, scene: &Scene) {
+        let objects: Vec<_> = scene.world.renderables().collect();
+        let meshes: HashSet<_> = objects
+            .iter()
+            .map(|object| object.mesh)
+            .chain(self.deformed_meshes.iter().copied())
+            .collect();
+        for (index, mesh) in self.meshes.iter_mut().enumerate() {
+            if !meshes.contains(&MeshId(index)) && mesh.unload(&mut self.arena) {
+                if let Some(ray_traced_shadows) = &mut self.ray_traced_shadows {
+                    ray_traced_shadows.unload(MeshId(index));
+                }
+            }
+        }
+
+        let materials: HashSet<_> = objects.iter().map(|object| object.material).collect();
+        for (index, binding) in self.materials.iter_mut().enumerate() {
+            if !materials.contains(&MaterialId(index)) && binding.bind_group.take().is_some() {
+                if let Some(bindless) = &mut self.bindless {
+                    bindless.invalidate();
+                }
+            }
+        }
+        // Textures stay while a material still binds them.
+        let textures: HashSet<_> = self
+            .materials
+            .iter()
+            .filter(|binding| binding.bind_group.is_some())
+            .filter_map(|binding| binding.material.base_color_texture)
+            .collect();
+        let unused: Vec<_> = self
+            .textures
+            .uploaded()
+            .filter(|texture| !textures.contains(texture))
+            .collect();
+        for texture in unused {
+            self.textures.unload(&self.device, &self.queue, texture);
+        }
+
+        for (index, point_cloud) in self.point_clouds.iter_mut().enumerate() {
+            if !scene.point_clouds.contains(&PointCloudId(index)) {
+                point_cloud.unload();
+            }
+        }
+    }
+
+    /// Replaces the environment and prefilters its image-based lighting.
+    pub fn set_environment(&mut self, environment: Cubemap) {
+        self.ibl = Ibl::new(&self.device, &self.queue, &environment);
+        self.environment_bind_group = create_environment_bind_group(
+            &self.device,
+            &self.environment_bind_group_layout,
+            &environment,
+            &self.ibl,
+            &self.brdf_lut,
+            &self.ltc_luts,
+            &self.environment_sampler,
+        );
+        self.environment = environment;
+    }
+
+    /// Replaces the environment with a procedural sky gradient.
+    pub fn set_sky(&mut self, sky: &SkyGradient) {
+        let environment = Cubemap::sky(&self.device, &self.queue, sky);
+        self.set_environment(environment);
+    }
+
+    /// Loads a cubemap from a directory of six faces, a cross image, or an equirectangular panorama
+    /// and uses it as the environment.
+    pub fn load_environment(&mut self, path: &Path) -> Result<(), String> {
+        let environment = Cubemap::load(&self.device, &self.queue, path)?;
+        self.set_environment(environment);
+        Ok(())
+    }
+
+    /// Loads the lightmaps baked for the scene's objects from a directory.
+    pub fn load_lightmaps(&mut self, directory: &Path, scene: &Scene) -> Result<(), String> {
+        self.lightmaps = Lightmaps::load(
+            &self.device,
+            &self.queue,
+            directory,
+            scene.world.renderables().count(),
+        )?;
+        Ok(())
+    }
+
+    /// Stops lighting the objects by baked lightmaps.
+    pub fn clear_lightmaps(&mut self) {
+        self.lightmaps = Lightmaps::empty(&self.device, &self.queue);
+    }
+
+    /// Loads a `.cube` lookup table to grade the tone mapped image with.
+    pub fn load_color_lut(&mut self, path: &Path) -> Result<(), String> {
+        self.post
+            .color_grading
+            .load(&self.device, &self.queue, path)
+    }
+
+    /// Writes all light uniforms and assigns shadow maps to the first shadow-casting point lights.
+    /// Returns the number of point lights with shadows.
+    fn write_lights(&mut self, encoder: &mut CommandEncoder, scene: &Scene) -> usize {
+        stage(
+            &self.device,
+            &mut self.staging_belt,
+            encoder,
+            &self.light_buffer,
+            as_byte_slice(&[LightUniforms::new(&scene.light, scene.hemisphere.as_ref())]),
+        );
+        let mut shadowed = Vec::new();
+        let point_lights: Vec<_> = scene
+            .world
+            .point_lights()
+            .map(|light| {
+                let shadow_index =
+                    (light.cast_shadows && shadowed.len() < MAX_SHADOWED_POINT_LIGHTS).then(|| {
+                        shadowed.push((light.position, light.radius));
+                        shadowed.len() - 1
+                    });
+                PointLightUniforms::new(light, shadow_index)
+            })
+            .collect();
+        self.point_shadow_maps.update(&self.queue, &shadowed);
+        write_storage_buffer(
+            &self.device,
+            &mut self.staging_belt,
+            encoder,
+            &mut self.point_light_buffer,
+            &point_lights,
+        );
+        let spot_lights: Vec<_> = scene
+            .world
+            .spot_lights()
+            .map(SpotLightUniforms::from)
+            .collect();
+        write_storage_buffer(
+            &self.device,
+            &mut self.staging_belt,
+            encoder,
+            &mut self.spot_light_buffer,
+            &spot_lights,
+        );
+        let rect_lights: Vec<_> = scene
+            .world
+            .rect_lights()
+            .map(RectLightUniforms::from)
+            .collect();
+        write_storage_buffer(
+            &self.device,
+            &mut self.staging_belt,
+            encoder,
+            &mut self.rect_light_buffer,
+            &rect_lights,
+        );
+        shadowed.len()
+    }
+
+    /// Writes the per-object uniforms and groups the renderable entities into draw items.
+    /// Objects outside the culling view-projection's frustum are left out of the view's lists,
+    /// but not the shadow casters. With culling on the GPU, every object is listed and the
+    /// culling pass is recorded instead.
+    fn prepare_draw_list(
+        &mut self,
+        encoder: &mut CommandEncoder,
+        view: Matrix4<f32>,
+        culling: Matrix4<f32>,
+        scene: &Scene,
+    ) -> DrawList {
+        let renderables: Vec<_> = scene.world.renderables().collect();
+        let count = renderables.len() as u64;
+        if count * INDIRECT_ARGS_STRIDE > self.indirect_buffer.size() {
+            self.indirect_buffer = create_indirect_buffer(&self.device, count.next_power_of_two());
+        }
+        if count * OBJECT_UNIFORMS_STRIDE > self.object_buffer.size() {
+            self.object_buffer = create_object_buffer(&self.device, count.next_power_of_two());
+            self.object_bind_group = create_object_bind_group(
+                &self.device,
+                &self.object_bind_group_layout,
+                &self.object_buffer,
+            );
+            self.object_array_bind_group =
+                self.object_array_bind_group_layout.as_ref().map(|layout| {
+                    create_object_array_bind_group(&self.device, layout, &self.object_buffer)
+                });
+        }
+
+        let mut data = vec![0; (count * OBJECT_UNIFORMS_STRIDE) as usize];
+        let mut indirect_args = Vec::with_capacity((count * INDIRECT_ARGS_STRIDE) as usize);
+        let mut cull_bounds = Vec::with_capacity(if self.gpu_culling { count as usize } else { 0 });
+        let mut draw_list = DrawList::default();
+        let compacting = self.compacting();
+        // Groups by whether masked and by page, and the slots of the objects in each.
+        let mut groups = HashMap::new();
+        let mut grouped = Vec::new();
+        let layers = scene.layers();
+        let mut stats = SceneStats {
+            objects: renderables.len(),
+            lights: 1 + scene.world.lights.iter().count(),
+            gpu_memory: self.gpu_memory(),
+            ..Default::default()
+        };
+        let mut bounds_draw = self.bounds_gizmos.then(debug_draw::frame);
+        let mut lod_levels = Components::default();
+        for (slot, object) in renderables.iter().enumerate() {
+            let uniforms = ObjectUniforms {
+                model: object.transform,
+                normal: object
+                    .transform
+                    .invert()
+                    .unwrap_or(Matrix4::identity())
+                    .transpose(),
+                lightmap: self.lightmaps.layer(slot),
+                material: object.material.0 as u32,
+                padding: [0; 2],
+                previous_model: self
+                    .previous_transforms
+                    .get(object.entity)
+                    .copied()
+                    .unwrap_or(object.transform),
+            };
+            let offset = slot * OBJECT_UNIFORMS_STRIDE as usize;
+            let bytes = as_byte_slice(std::slice::from_ref(&uniforms));
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            let mesh = &self.meshes[object.mesh.0];
+            // Deformed meshes may not be where their bounds are, so they are drawn in full.
+            let deformed = self.deformed_meshes.contains(&object.mesh);
+            let level = if self.lod && !deformed && mesh.lods.len() > 1 {
+                let pixels = lod::screen_size(culling, object.transform, &mesh.bounds)
+                    * self.config.height as f32;
+                let level = lod::select(
+                    &mesh.lods,
+                    pixels,
+                    self.lod_levels.get(object.entity).copied(),
+                );
+                lod_levels.insert(object.entity, level);
+                level
+            } else {
+                0
+            };
+            let first_instance = self.first_instance(slot as u32);
+            let arguments = util::DrawIndexedIndirectArgs {
+                first_instance,
+                ..mesh.indirect_args(level)
+            };
+            indirect_args.extend_from_slice(arguments.as_bytes());
+            if self.gpu_culling {
+                cull_bounds.push(CullBounds::new(mesh, level, deformed, first_instance));
+            }
+
+            let center = self.meshes[object.mesh.0].bounds.center();
+            let item = DrawItem {
+                entity: object.entity,
+                mesh: object.mesh,
+                material: object.material,
+                slot: slot as u32,
+                level,
+                depth: (view * object.transform * center.to_homogeneous()).z,
+            };
+            let material = &self.materials[object.material.0].material;
+            if material.alpha_mode != AlphaMode::Blend && object.layers.intersects(SHADOW_LAYERS) {
+                draw_list.shadow_casters.push(item);
+            }
+            if !object.layers.intersects(layers) {
+                continue;
+            }
+            let triangles = mesh.lods[level].index_count as u64 / 3;
+            stats.triangles_submitted += triangles;
+            let bounds = &self.meshes[object.mesh.0].bounds;
+            let visible =
+                self.gpu_culling || deformed || in_frustum(culling * object.transform, bounds);
+            if let Some(bounds_draw) = &mut bounds_draw {
+                let rejection = if self.gpu_culling {
+                    self.culling.rejection(slot)
+                } else {
+                    (!visible).then_some(Rejection::Frustum)
+                };
+                if rejection.is_none() {
+                    bounds_draw.aabb(bounds, object.transform, Vector4::new(0.0, 1.0, 0.0, 1.0));
+                    bounds_draw.axis(
+                        object.transform,
+                        0.5 * (bounds.max - bounds.min).magnitude(),
+                    );
+                    bounds_draw.text(
+                        (object.transform * center.to_homogeneous()).truncate(),
+                        scene.world.name(object.entity),
+                        Vector4::new(1.0, 1.0, 1.0, 1.0),
+                    );
+                } else {
+                    let color = match rejection {
+                        Some(Rejection::Occlusion) => Vector4::new(1.0, 0.0, 1.0, 1.0),
+                        _ => Vector4::new(1.0, 0.0, 0.0, 1.0),
+                    };
+                    bounds_draw.aabb(bounds, object.transform, color);
+                }
+            }
+            if !visible {
+                continue;
+            }
+            stats.triangles_drawn += triangles;
+            match material.alpha_mode {
+                AlphaMode::Opaque => draw_list.opaque.push(item),
+                AlphaMode::Mask => draw_list.masked.push(item),
+                AlphaMode::Blend => draw_list.transparent.push(item),
+            }
+            // Items without buffers or a material are not drawn, as in `draw_items`.
+            let drawn = self.materials[object.material.0].bind_group.is_some();
+            if compacting && drawn && material.alpha_mode != AlphaMode::Blend {
+                if let Some(allocation) = mesh.allocation {
+                    let masked = material.alpha_mode == AlphaMode::Mask;
+                    let group = *groups.entry((masked, allocation.page)).or_insert_with(|| {
+                        draw_list.groups.push(DrawGroup {
+                            masked,
+                            page: allocation.page,
+                            first: 0,
+                            count: 0,
+                        });
+                        draw_list.groups.len() - 1
+                    });
+                    draw_list.groups[group].count += 1;
+                    grouped.push((slot, group));
+                }
+            }
+            if material.alpha_mode != AlphaMode::Blend && (self.toon || material.outline.is_some())
+            {
+                draw_list.outlined.push(item);
+            }
+        }
+        stage(
+            &self.device,
+            &mut self.staging_belt,
+            encoder,
+            &self.object_buffer,
+            &data,
+        );
+        stage(
+            &self.device,
+            &mut self.staging_belt,
+            encoder,
+            &self.indirect_buffer,
+            &indirect_args,
+        );
+        let mut first = 0;
+        for group in &mut draw_list.groups {
+            group.first = first;
+            first += group.count;
+        }
+        for (slot, group) in grouped {
+            cull_bounds[slot].compact(group as u32, draw_list.groups[group].first);
+        }
+        if self.gpu_culling {
+            self.culling.read_back = self.bounds_gizmos;
+            self.culling.cull(
+                &self.device,
+                &mut self.staging_belt,
+                encoder,
+                culling,
+                &self.object_buffer,
+                &cull_bounds,
+            );
+        }
+        self.stats = stats;
+        self.lod_levels = lod_levels;
+        self.previous_transforms = Components::default();
+        for object in &renderables {
+            self.previous_transforms
+                .insert(object.entity, object.transform);
+        }
+
+        // View space looks down -z, so the farthest items have the smallest depth.
+        draw_list
+            .transparent
+            .sort_by(|a, b| a.depth.total_cmp(&b.depth));
+
+        draw_list
+    }
+
+    /// Whether the culling pass compacts the arguments of the opaque and masked items.
+    fn compacting(&self) -> bool {
+        self.gpu_culling && self.object_array_bind_group.is_some()
+    }
+
+    /// The instance drawing the object in the slot, which is its index into the object buffer
+    /// where the scene shader reads the objects by the instance.
+    fn first_instance(&self, slot: u32) -> u32 {
+        if self.object_array_bind_group.is_some() {
+            slot
+        } else {
+            0
+        }
+    }
+
+    /// Draws into a render pass or bundle.
+    fn draw_items<'a>(&'a self, pass: &mut impl util::RenderEncoder<'a>, items: &[DrawItem]) {
+        // Bindless materials are bound once, and picked by the object uniforms.
+        let bindless = self
+            .bindless
+            .as_ref()
+            .and_then(BindlessMaterials::bind_group);
+        if bindless.is_some() {
+            pass.set_bind_group(2, bindless, &[]);
+        }
+        let object_array = self.object_array_bind_group.as_ref();
+        if object_array.is_some() {
+            pass.set_bind_group(3, object_array, &[]);
+        }
+        let mut bound_page = None;
+        for item in items {
+            let Some(material) = &self.materials[item.material.0].bind_group else {
+                continue;
+            };
+            if bindless.is_none() {
+                pass.set_bind_group(2, Some(material), &[]);
+            }
+            if object_array.is_none() {
+                pass.set_bind_group(
+                    3,
+                    Some(&self.object_bind_group),
+                    &[item.slot * OBJECT_UNIFORMS_STRIDE as u32],
+                );
+            }
+            let arguments = if self.gpu_culling {
+                Some(&self.culling.draws)
+            } else {
+                self.indirect.then_some(&self.indirect_buffer)
+            };
+            self.draw_mesh(pass, item, arguments, &mut bound_page);
+        }
+    }
+
+    /// Draws the groups of opaque or masked items, each by a multi-draw of as many of the
+    /// compacted arguments as the culling pass counted.
+    fn draw_groups<'a>(&'a self, pass: &mut RenderPass<'a>, groups: &[DrawGroup], masked: bool) {
+        if let Some(bindless) = self
+            .bindless
+            .as_ref()
+            .and_then(BindlessMaterials::bind_group)
+        {
+            pass.set_bind_group(2, bindless, &[]);
+        }
+        pass.set_bind_group(3, self.object_array_bind_group.as_ref(), &[]);
+        for (index, group) in groups.iter().enumerate() {
+            if group.masked != masked {
+                continue;
+            }
+            self.arena.bind(pass, group.page);
+            pass.multi_draw_indexed_indirect_count(
+                &self.culling.compacted,
+                group.first as u64 * INDIRECT_ARGS_STRIDE,
+                &self.culling.counts,
+                (index * std::mem::size_of::<u32>()) as u64,
+                group.count,
+            );
+        }
+    }
+
+    /// Records the opaque and masked items of the forward pass into render bundles, split across
+    /// a thread per core.
+    fn record_bundles(
+        &self,
+        uniform_bind_group: &BindGroup,
+        draw_list: &DrawList,
+    ) -> Vec<RenderBundle> {
+        let threads = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        let chunk_size = |items: &[DrawItem]| items.len().div_ceil(threads).max(1);
+        let sample_count = self.built.sample_count;
+        std::thread::scope(|scope| {
+            let opaque = draw_list.opaque.chunks(chunk_size(&draw_list.opaque));
+            let masked = draw_list.masked.chunks(chunk_size(&draw_list.masked));
+            let recorders: Vec<_> = opaque
+                .map(|items| (&self.pipelines.opaque, items))
+                .chain(masked.map(|items| (&self.pipelines.mask, items)))
+                .map(|(pipeline, items)| {
+                    scope.spawn(move || {
+                        let mut bundle = self.device.create_render_bundle_encoder(
+                            &RenderBundleEncoderDescriptor {
+                                label: None,
+                                color_formats: &[Some(HDR_FORMAT)],
+                                depth_stencil: Some(RenderBundleDepthStencil {
+                                    format: TextureFormat::Depth24Plus,
+                                    depth_read_only: false,
+                                    stencil_read_only: true,
+                                }),
+                                sample_count,
+                                multiview: None,
+                            },
+                        );
+                        bundle.set_bind_group(0, uniform_bind_group, &[]);
+                        bundle.set_bind_group(1, &self.environment_bind_group, &[]);
+                        bundle.set_pipeline(pipeline);
+                        self.draw_items(&mut bundle, items);
+                        bundle.finish(&RenderBundleDescriptor { label: None })
+                    })
+                })
+                .collect();
+            recorders
+                .into_iter()
+                .map(|recorder| recorder.join().expect("Cannot record a render bundle"))
+                .collect()
+        })
+    }
+
+    /// Draws the item's mesh directly, or with its entry of the indirect arguments.
+    fn draw_mesh<'a>(
+        &'a self,
+        pass: &mut impl util::RenderEncoder<'a>,
+        item: &DrawItem,
+        arguments: Option<&'a Buffer>,
+        bound_page: &mut Option<usize>,
+    ) {
+        let mesh = &self.meshes[item.mesh.0];
+        let Some(allocation) = mesh.allocation else {
+            return;
+        };
+        // Consecutive meshes from the same page of the arena share its buffers.
+        if *bound_page != Some(allocation.page) {
+            self.arena.bind(pass, allocation.page);
+            *bound_page = Some(allocation.page);
+        }
+        match arguments {
+            Some(arguments) => {
+                pass.draw_indexed_indirect(arguments, item.slot as u64 * INDIRECT_ARGS_STRIDE)
+            }
+            None => {
+                let instance = self.first_instance(item.slot);
+                mesh.draw(pass, item.level, instance..instance + 1)
+            }
+        }
+    }
+
+    /// Outlines every light: an arrow for the sun, the sphere of influence of point lights,
+    /// the outer cone of spot lights, and the rectangle of rectangular lights.
+    fn draw_light_gizmos(&mut self, scene: &Scene) {
+        let sun = scene.light.direction.normalize();
+        self.debug_draw
+            .arrow(-6.0 * sun, -4.0 * sun, scene.light.color.extend(1.0));
+        for point_light in scene.world.point_lights() {
+            self.debug_draw.sphere(
+                point_light.position,
+                point_light.radius,
+                point_light.color.extend(0.3),
+            );
+            self.debug_draw
+                .sphere(point_light.position, 0.1, point_light.color.extend(1.0));
+        }
+        for spot_light in scene.world.spot_lights() {
+            self.debug_draw.cone(
+                spot_light.position,
+                spot_light.direction,
+                spot_light.outer_angle,
+                spot_light.radius,
+                spot_light.color.extend(1.0),
+            );
+        }
+        for rect_light in scene.world.rect_lights() {
+            let (right, up) = rect_light.axes();
+            let corners = [
+                rect_light.position - right - up,
+                rect_light.position - right + up,
+                rect_light.position + right + up,
+                rect_light.position + right - up,
+            ];
+            let color = rect_light.color.extend(1.0);
+            for i in 0..4 {
+                self.debug_draw
+                    .line(corners[i], corners[(i + 1) % 4], color);
+            }
+            self.debug_draw.arrow(
+                rect_light.position,
+                rect_light.position + 0.5 * rect_light.direction.normalize(),
+                color,
+            );
+        }
+    }
+
+    /// Draws the opaque and masked items with only their object uniforms bound at group 1.
+    fn draw_shadow_casters<'a>(&'a self, pass: &mut RenderPass<'a>, draw_list: &DrawList) {
+        let mut bound_page = None;
+        for item in &draw_list.shadow_casters {
+            pass.set_bind_group(
+                1,
+                &self.object_bind_group,
+                &[item.slot * OBJECT_UNIFORMS_STRIDE as u32],
+            );
+            // Not the culled arguments, which are of the view rather than the light.
+            let arguments = (self.indirect || self.gpu_culling).then_some(&self.indirect_buffer);
+            self.draw_mesh(pass, item, arguments, &mut bound_page);
+        }
+    }
+
+    /// The GPU rendered with and its backend.
+    pub fn adapter_info(&self) -> &AdapterInfo {
+        &self.adapter_info
+    }
+
+    /// Counts of the last frame.
+    pub fn stats(&self) -> SceneStats {
+        self.stats
+    }
+
+    /// The passes' recent times on the GPU, if the adapter supports timestamp queries.
+    pub fn gpu_timings(&self) -> Option<&GpuTimings> {
+        self.profiler.as_ref().map(GpuProfiler::timings)
+    }
+
+    /// Counts of the live GPU resources and the memory allocated for them.
+    pub fn gpu_stats(&self) -> GpuStats {
+        let allocator = self.device.generate_allocator_report();
+        GpuStats {
+            allocated: allocator
+                .as_ref()
+                .map(|report| report.total_allocated_bytes),
+            reserved: allocator.as_ref().map(|report| report.total_reserved_bytes),
+            ..GpuStats::new(self.instance.generate_report())
+        }
+    }
+
+    fn gpu_memory(&self) -> u64 {
+        let point_clouds: u64 = self.point_clouds.iter().map(PointCloud::memory).sum();
+        self.arena.memory()
+            + point_clouds
+            + self.object_buffer.size()
+            + self.indirect_buffer.size()
+            + self.culling.depth_pyramid.memory()
+            + self.textures.memory()
+            + texture_memory(&self.depth_texture)
+            + self.msaa_texture.as_ref().map_or(0, texture_memory)
+    }
+
+    fn projection(&self, projection: &Projection) -> Matrix4<f32> {
+        projection.matrix(self.config.width as f32 / self.config.height as f32)
+    }
+
+    /// The view-projection the frame is seen through, without the jitter.
+    pub fn view_projection(&self, view: Matrix4<f32>, scene: &Scene) -> Matrix4<f32> {
+        let (view, projection) = self.shown_view(view, scene);
+        self.projection(&projection) * view
+    }
+
+    /// The origin and direction of the ray through a position in physical pixels.
+    pub fn ray(
+        &self,
+        view: Matrix4<f32>,
+        scene: &Scene,
+        x: f32,
+        y: f32,
+    ) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let ndc_x = 2.0 * x / self.config.width as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / self.config.height as f32;
+        let inverse_view_projection = self.view_projection(view, scene).invert()?;
+        let unproject = |depth: f32| {
+            let point = inverse_view_projection * Vector4::new(ndc_x, ndc_y, depth, 1.0);
+            point.truncate() / point.w
+        };
+        let origin = unproject(0.0);
+        Some((origin, unproject(0.5) - origin))
+    }
+
+    /// Finds the closest renderable entity visible in the view under a position in physical
+    /// pixels.
+    pub fn pick(&self, view: Matrix4<f32>, scene: &Scene, x: f32, y: f32) -> Option<Entity> {
+        let (origin, direction) = self.ray(view, scene, x, y)?;
+        let layers = scene.layers();
+        scene
+            .world
+            .renderables()
+            .filter(|object| object.layers.intersects(layers))
+            .filter_map(|object| {
+                let inverse_model = object.transform.invert()?;
+                let distance = self.meshes[object.mesh.0].data.raycast(
+                    (inverse_model * origin.extend(1.0)).truncate(),
+                    (inverse_model * direction.extend(0.0)).truncate(),
+                )?;
+                Some((object.entity, distance))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(entity, _)| entity)
+    }
+
+    pub fn render(&mut self, view: Matrix4<f32>, scene: &Scene, ui: &UiFrame, delta_time: f32) {
+        let _span = info_span!("render").entered();
+        self.read_back();
+        self.poll_pipelines();
+        self.upload_scene(scene);
+        if let Some(bindless) = &mut self.bindless {
+            bindless.update(
+                &self.device,
+                &self.materials,
+                &self.textures,
+                &self.white_texture,
+                &self.material_sampler,
+            );
+        }
+        if self.dynamic_resolution.update(delta_time) {
+            println!("Render scale: {:.1}", self.dynamic_resolution.scale);
+            self.resize_targets();
+        }
+
+        let surface_texture = self
+            .surface
+            .get_current_texture()
+            .expect("Cannot get next texture");
+        let surface_texture_view = surface_texture
+            .texture
+            .create_view(&TextureViewDescriptor::default());
+        let scene_view = self.post.scene.clone();
+        let depth_texture_view = self
+            .depth_texture
+            .create_view(&TextureViewDescriptor::default());
+        let msaa_texture_view = self
+            .msaa_texture
+            .as_ref()
+            .map(|texture| texture.create_view(&TextureViewDescriptor::default()));
+
+        let main_view = view;
+        let main_projection_settings = scene.projection();
+        let main_projection = self.projection(&main_projection_settings);
+        let (view, projection_settings) = self.shown_view(main_view, scene);
+        let projection = self.projection(&projection_settings);
+
+        // Only the shading uses the jittered projection; the shadows stay put.
+        let unjittered_projection = projection;
+        self.frame_index = self.frame_index.wrapping_add(1);
+        let (width, height) = self.render_size();
+        let jitter = if self.anti_aliasing == AntiAliasing::Taa && !self.post.raw {
+            let index = self.frame_index % TAA_JITTER_PHASES + 1;
+            Vector2::new(
+                (2.0 * halton(index, 2) - 1.0) / width as f32,
+                (2.0 * halton(index, 3) - 1.0) / height as f32,
+            )
+        } else {
+            Vector2::new(0.0, 0.0)
+        };
+        let projection = Matrix4::from_translation(jitter.extend(0.0)) * projection;
+
+        let mut view_rotation = view;
+        view_rotation.w = Vector4::new(0.0, 0.0, 0.0, 1.0);
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        stage(
+            &self.device,
+            &mut self.staging_belt,
+            &mut encoder,
+            &self.uniform_buffer,
+            as_byte_slice(&[Uniforms {
+                view,
+                projection,
+                environment: (projection * view_rotation).invert().unwrap(),
+                camera_position: view.invert().unwrap().w,
+                viewport: Vector4::new(
+                    width as f32,
+                    height as f32,
+                    1.0 / width as f32,
+                    1.0 / height as f32,
+                ),
+                toon: self.toon as u32,
+                point_light_count: scene.world.point_lights().count() as u32,
+                spot_light_count: scene.world.spot_lights().count() as u32,
+                rect_light_count: scene.world.rect_lights().count() as u32,
+                inverse_view_projection: (projection * view).invert().unwrap(),
+                near: projection_settings.near,
+                far: projection_settings.far,
+                jitter,
+                previous_view_projection: self.previous_view_projection,
+            }]),
+        );
+
+        let shadowed_point_lights = self.write_lights(&mut encoder, scene);
+        self.shadow_map.update(
+            &self.queue,
+            main_view,
+            main_projection,
+            main_projection_settings.near,
+            scene.light.direction,
+            self.shadow_settings,
+        );
+        if self.debug_view.is_some() {
+            shadow::draw_frustum(
+                &mut self.debug_draw,
+                main_view,
+                main_projection,
+                main_projection_settings.near,
+                main_projection_settings.far,
+            );
+        }
+        let editor = scene.layers().intersects(Layers::EDITOR);
+        if self.light_gizmos && editor {
+            self.draw_light_gizmos(scene);
+        }
+        if let Some(transform) = scene
+            .selected
+            .filter(|_| editor)
+            .and_then(|selected| scene.world.transforms.get(selected))
+        {
+            scene.gizmo.draw(
+                &mut self.gizmo_draw,
+                transform.0,
+                view,
+                &projection_settings,
+            );
+        }
+
+        let draw_list =
+            self.prepare_draw_list(&mut encoder, view, main_projection * main_view, scene);
+
+        for animation in &scene.animations {
+            for skin in &animation.skins {
+                self.deformation
+                    .write_joints(&self.queue, skin.deformation, &skin.joint_matrices);
+            }
+            for morph in &animation.morphs {
+                self.deformation
+                    .write_weights(&self.queue, morph.deformation, &morph.weights);
+            }
+        }
+        self.deformation.run(&mut encoder);
+        self.light_clusters.cull(
+            &self.device,
+            &mut encoder,
+            &self.uniform_buffer,
+            &self.point_light_buffer,
+            &self.spot_light_buffer,
+        );
+
+        if let Some(profiler) = &self.profiler {
+            profiler.begin(&mut encoder, Pass::Shadow);
+        }
+        if let Some(ray_traced_shadows) = &mut self.ray_traced_shadows {
+            let casters: Vec<_> = draw_list
+                .shadow_casters
+                .iter()
+                .filter_map(|item| Some((item.mesh, scene.world.transforms.get(item.entity)?.0)))
+                .collect();
+            ray_traced_shadows.update(
+                &self.device,
+                &mut encoder,
+                &self.meshes,
+                &self.arena,
+                &casters,
+            );
+        } else {
+            for cascade in 0..CASCADE_COUNT {
+                let mut pass = self.shadow_map.begin_pass(&mut encoder, cascade);
+                self.draw_shadow_casters(&mut pass, &draw_list);
+            }
+            for light in 0..shadowed_point_lights {
+                for face in 0..6 {
+                    let mut pass = self.point_shadow_maps.begin_pass(&mut encoder, light, face);
+                    self.draw_shadow_casters(&mut pass, &draw_list);
+                }
+            }
+        }
+        if let Some(profiler) = &self.profiler {
+            profiler.begin(&mut encoder, Pass::Geometry);
+        }
+
+        let mut uniform_entries = vec![
+            BindGroupEntry {
+                binding: 0,
+                resource: self.uniform_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: self.light_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: self.point_light_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: self.spot_light_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 4,
+                resource: self.shadow_map.uniform_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 5,
+                resource: BindingResource::TextureView(&self.shadow_map.view),
+            },
+            BindGroupEntry {
+                binding: 6,
+                resource: BindingResource::Sampler(&self.shadow_map.sampler),
+            },
+            BindGroupEntry {
+                binding: 7,
+                resource: BindingResource::TextureView(&self.point_shadow_maps.view),
+            },
+            BindGroupEntry {
+                binding: 8,
+                resource: BindingResource::Sampler(&self.shadow_map.hard_sampler),
+            },
+            BindGroupEntry {
+                binding: 9,
+                resource: self.light_clusters.buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 10,
+                resource: self.rect_light_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 11,
+                resource: BindingResource::TextureView(&self.lightmaps.view),
+            },
+        ];
+        if let Some(ray_traced_shadows) = &self.ray_traced_shadows {
+            uniform_entries.push(BindGroupEntry {
+                binding: 12,
+                resource: ray_traced_shadows.binding(),
+            });
+        }
+        let uniform_bind_group = self.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.uniform_bind_group_layout,
+            entries: &uniform_entries,
+        });
+
+        let clear_color = scene.background.clear_color;
+        let clear_color = LoadOp::Clear(wgpu::Color {
+            r: clear_color.x as f64,
+            g: clear_color.y as f64,
+            b: clear_color.z as f64,
+            a: 1.0,
+        });
+        let gbuffer = self
+            .gbuffer
+            .as_ref()
+            .filter(|_| self.built.render_mode == RenderMode::Shaded);
+        // Taken before the pass, which borrows the renderer until it ends.
+        self.debug_draw.append(&mut debug_draw::frame());
+        let mut pass = if let Some(gbuffer) = gbuffer {
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                color_attachments: &gbuffer.color_attachments(),
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &depth_texture_view,
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(1.0),
+                        store: StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                ..Default::default()
+            });
+            pass.set_bind_group(0, &uniform_bind_group, &[]);
+            pass.set_bind_group(1, &self.environment_bind_group, &[]);
+            pass.set_pipeline(&self.deferred_pipelines.geometry);
+            if self.compacting() {
+                self.draw_groups(&mut pass, &draw_list.groups, false);
+                self.draw_groups(&mut pass, &draw_list.groups, true);
+            } else {
+                self.draw_items(&mut pass, &draw_list.opaque);
+                self.draw_items(&mut pass, &draw_list.masked);
+            }
+            drop(pass);
+
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &scene_view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: clear_color,
+                        store: StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            pass.set_bind_group(0, &uniform_bind_group, &[]);
+            pass.set_bind_group(1, &self.environment_bind_group, &[]);
+            pass.set_bind_group(2, &gbuffer.bind_group, &[]);
+            pass.set_pipeline(&self.deferred_pipelines.lighting);
+            pass.draw(0..3, 0..1);
+            drop(pass);
+
+            // Everything the G-buffer cannot hold is drawn forward on top.
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &scene_view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Load,
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &depth_texture_view,
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Load,
+                        store: StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                ..Default::default()
+            });
+            pass.set_bind_group(0, &uniform_bind_group, &[]);
+            pass.set_bind_group(1, &self.environment_bind_group, &[]);
+            pass
+        } else {
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: msaa_texture_view.as_ref().unwrap_or(&scene_view),
+                    resolve_target: msaa_texture_view.as_ref().map(|_| &scene_view),
+                    ops: Operations {
+                        load: clear_color,
+                        store: if msaa_texture_view.is_some() {
+                            StoreOp::Discard
+                        } else {
+                            StoreOp::Store
+                        },
+                    },
+                })],
+                depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                    view: &depth_texture_view,
+                    depth_ops: Some(Operations {
+                        load: LoadOp::Clear(1.0),
+                        store: StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                ..Default::default()
+            });
+            pass.set_bind_group(0, &uniform_bind_group, &[]);
+            pass.set_bind_group(1, &self.environment_bind_group, &[]);
+            if self.compacting() {
+                pass.set_pipeline(&self.pipelines.opaque);
+                self.draw_groups(&mut pass, &draw_list.groups, false);
+                pass.set_pipeline(&self.pipelines.mask);
+                self.draw_groups(&mut pass, &draw_list.groups, true);
+            } else if self.parallel_encoding
+                && draw_list.opaque.len() + draw_list.masked.len() >= PARALLEL_ENCODING_THRESHOLD
+            {
+                pass.execute_bundles(&self.record_bundles(&uniform_bind_group, &draw_list));
+                // Executing bundles clears the pass's bindings.
+                pass.set_bind_group(0, &uniform_bind_group, &[]);
+                pass.set_bind_group(1, &self.environment_bind_group, &[]);
+            } else {
+                pass.set_pipeline(&self.pipelines.opaque);
+                self.draw_items(&mut pass, &draw_list.opaque);
+                pass.set_pipeline(&self.pipelines.mask);
+                self.draw_items(&mut pass, &draw_list.masked);
+            }
+            pass
+        };
+        self.point_cloud_pipeline.draw(
+            &mut pass,
+            scene
+                .point_clouds
+                .iter()
+                .map(|point_cloud| &self.point_clouds[point_cloud.0]),
+        );
+        // Nothing is drawn over the heatmap, which leaves the depth empty.
+        if self.built.render_mode != RenderMode::Overdraw {
+            pass.set_pipeline(&self.pipelines.outline);
+            self.draw_items(&mut pass, &draw_list.outlined);
+            self.skybox.draw(&self.queue, &mut pass, &scene.background);
+        }
+        pass.set_pipeline(&self.pipelines.blend);
+        self.draw_items(&mut pass, &draw_list.transparent);
+        if let Some(wireframe) = &self.pipelines.wireframe {
+            pass.set_pipeline(wireframe);
+            self.draw_items(&mut pass, &draw_list.opaque);
+            self.draw_items(&mut pass, &draw_list.masked);
+            self.draw_items(&mut pass, &draw_list.transparent);
+        }
+        self.debug_draw_pipeline
+            .draw(&self.device, &mut pass, &self.debug_draw);
+        self.debug_draw_pipeline
+            .draw_on_top(&self.device, &mut pass, &self.gizmo_draw);
+        drop(pass);
+        self.debug_draw.clear();
+        self.gizmo_draw.clear();
+        // Occluding the next frame's objects, a frame late.
+        if self.gpu_culling && self.occlusion_culling {
+            self.culling.depth_pyramid.build(
+                &self.device,
+                &mut encoder,
+                &self.depth_texture,
+                projection * view,
+            );
+        }
+
+        // The outline is an editor's aid, like the gizmos.
+        let selected = scene
+            .selected
+            .filter(|_| scene.layers().intersects(Layers::EDITOR));
+        self.post.selection_outline.enabled = selected.is_some();
+        if let Some(selected) = selected {
+            let items: Vec<_> = draw_list
+                .opaque
+                .iter()
+                .chain(&draw_list.masked)
+                .chain(&draw_list.transparent)
+                .filter(|item| item.entity == selected)
+                .copied()
+                .collect();
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &self.post.selection_outline.mask,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::TRANSPARENT),
+                        store: StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            pass.set_bind_group(0, &uniform_bind_group, &[]);
+            pass.set_bind_group(1, &self.environment_bind_group, &[]);
+            pass.set_pipeline(&self.selection_pipeline);
+            self.draw_items(&mut pass, &items);
+        }
+
+        if let Some(velocity_buffer) = &self.velocity_buffer {
+            let mut pass = velocity_buffer.begin_pass(&mut encoder);
+            pass.set_bind_group(0, &uniform_bind_group, &[]);
+            pass.set_bind_group(1, &self.environment_bind_group, &[]);
+            pass.set_pipeline(&self.velocity_pipelines.background);
+            pass.draw(0..3, 0..1);
+            pass.set_pipeline(&self.velocity_pipelines.geometry);
+            self.draw_items(&mut pass, &draw_list.opaque);
+            self.draw_items(&mut pass, &draw_list.masked);
+        }
+
+        let capture = std::mem::take(&mut self.screenshot_requested).then(|| {
+            screenshot::create_target(
+                &self.device,
+                self.config.format,
+                self.config.width,
+                self.config.height,
+            )
+        });
+        let capture_view = capture
+            .as_ref()
+            .map(|capture| capture.create_view(&TextureViewDescriptor::default()));
+        if let Some(profiler) = &self.profiler {
+            profiler.begin(&mut encoder, Pass::Post);
+        }
+        info_span!("post").in_scope(|| {
+            self.post.run(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                FrameInputs {
+                    depth: &depth_texture_view,
+                    depth_multisampled: self.built.sample_count > 1,
+                    shadow_map: &self.shadow_map.view,
+                    inverse_view_projection: (projection * view).invert().unwrap(),
+                    previous_view_projection: self.previous_view_projection,
+                    camera_position: view.invert().unwrap().w.truncate(),
+                    velocity: self
+                        .velocity_buffer
+                        .as_ref()
+                        .map(|velocity_buffer| &velocity_buffer.view),
+                    frame_index: self.frame_index,
+                    delta_time,
                 },
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: TextureFormat::Depth24Plus,
-                view_formats: &[],
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            }),
+                &surface_texture_view,
+                capture_view.as_ref(),
+            );
+        });
+        let screenshot = capture.and_then(|capture| {
+            Screenshot::capture(&self.device, &mut encoder, &capture)
+                .inspect_err(|error| error!("{error}"))
+                .ok()
+        });
+        self.previous_view_projection = unjittered_projection * view;
+        if let Some(profiler) = &self.profiler {
+            profiler.begin(&mut encoder, Pass::Ui);
+        }
+        let ui_commands = self.ui_pass.draw(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            &surface_texture_view,
+            (self.config.width, self.config.height),
+            ui,
+        );
+        if let Some(profiler) = &mut self.profiler {
+            profiler.end_frame(&mut encoder);
+        }
+        self.staging_belt.finish();
+
+        self.queue
+            .submit(ui_commands.into_iter().chain(Some(encoder.finish())));
+        surface_texture.present();
+        if let Some(screenshot) = screenshot {
+            screenshot.map();
+            self.screenshots.push(screenshot);
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.map();
+        }
+        self.culling.map();
+        self.staging_belt.recall();
+    }
+
+    pub fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        self.config.width = size.width;
+        self.config.height = size.height;
+        self.surface.configure(&self.device, &self.config);
+        self.resize_targets();
+    }
+
+    /// Recreates the targets after the surface or the render scale changed.
+    fn resize_targets(&mut self) {
+        self.rebuild_targets();
+        let (render_width, render_height) = self.render_size();
+        self.post.resize(
+            &self.device,
+            self.config.width,
+            self.config.height,
+            render_width,
+            render_height,
         );
     }
 }
@@ -0,0 +1,722 @@
+use std::collections::HashSet;
+
+use cgmath::{InnerSpace, Matrix3, Matrix4, Rad, SquareMatrix, Vector3, Vector4};
+
+use crate::{
+    animation::AnimationPlayer,
+    camera::{Bookmark, Camera, Projection, SceneCamera},
+    environment::{Background, SkyGradient},
+    gizmo::Gizmo,
+    history::{Edit, History},
+    light::{DayCycle, DirectionalLight, HemisphereLight, PointLight, RectLight, SpotLight},
+    material::{
+        shininess_from_roughness, AlphaMode, Material, MaterialId, Outline, Shading, TextureMapping,
+    },
+    mesh::{MeshData, MeshId},
+    point_cloud::PointCloudId,
+    prefab::{Prefab, PrefabId},
+    render::Renderer,
+    texture,
+    timeline::{Easing, Timeline},
+    world::{Entity, Layers, Light, Name, Renderable, Snapshot, World},
+};
+
+/// Tag of the objects which never move, and so may be merged by `Scene::batch_static`.
+pub const STATIC_TAG: &str = "static";
+
+/// The components of a drawn entity, as imported or authored.
+#[derive(Debug, Clone)]
+pub struct Object {
+    /// Empty if the format has no names.
+    pub name: String,
+    pub transform: Matrix4<f32>,
+    pub mesh: MeshId,
+    pub material: MaterialId,
+}
+
+/// Shapes which can be added while the viewer runs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Primitive {
+    Cube,
+    Sphere,
+    Plane,
+}
+
+impl Primitive {
+    pub fn name(self) -> &'static str {
+        match self {
+            Primitive::Cube => "cube",
+            Primitive::Sphere => "sphere",
+            Primitive::Plane => "plane",
+        }
+    }
+
+    fn mesh_data(self) -> MeshData {
+        match self {
+            Primitive::Cube => MeshData::cube(),
+            Primitive::Sphere => MeshData::sphere(48, 24),
+            Primitive::Plane => MeshData::plane(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Scene {
+    /// Objects and all lights but the directional one.
+    pub world: World,
+    pub point_clouds: Vec<PointCloudId>,
+    pub light: DirectionalLight,
+    /// Drives the light and the sky, if set.
+    pub day_cycle: Option<DayCycle>,
+    /// Whether the point lights orbit around the vertical axis.
+    pub orbit_point_lights: bool,
+    /// Ambient light used instead of the environment's irradiance, if set.
+    pub hemisphere: Option<HemisphereLight>,
+    pub background: Background,
+    pub selected: Option<Entity>,
+    /// Moves, rotates or scales the selected object.
+    pub gizmo: Gizmo,
+    /// Edits of the world.
+    pub history: History,
+    /// Drive the transforms of imported objects, one per model.
+    pub animations: Vec<AnimationPlayer>,
+    pub cameras: Vec<SceneCamera>,
+    /// Index of the camera looked through, or none for the orbit camera.
+    pub camera: Option<usize>,
+    /// Entity the orbit camera is attached to, which it orbits and turns with.
+    pub camera_parent: Option<Entity>,
+    /// Views saved with the scene.
+    pub bookmarks: Vec<Bookmark>,
+    /// Index of the bookmark returned to last.
+    bookmark: Option<usize>,
+    /// One per model, followed by those registered from the selection.
+    pub prefabs: Vec<Prefab>,
+    /// Prefab placed next.
+    pub prefab: Option<PrefabId>,
+    /// Keyframed properties authored in the viewer or by scripts.
+    pub timeline: Timeline,
+    /// Meshes of the primitives added so far, shared by their objects.
+    primitives: Vec<(Primitive, MeshId)>,
+    /// Of the primitives added at runtime.
+    primitive_material: Option<MaterialId>,
+}
+
+impl Scene {
+    /// The colored cube inside a glass shell, flanked by two translucent spheres, a toon-shaded
+    /// and a Blinn-Phong one, above a tiled floor.
+    pub fn demo(renderer: &mut Renderer) -> Self {
+        let cube = renderer.add_mesh(&MeshData::cube());
+        let sphere = renderer.add_mesh(&MeshData::sphere(48, 24));
+        let plane = renderer.add_mesh(&MeshData::plane());
+        let opaque = renderer.add_material(&Material::default());
+        let tiles = renderer.add_texture(
+            &texture::checker(256, 2, [90, 90, 90, 255], [60, 60, 60, 255]),
+            true,
+        );
+        let floor = renderer.add_material(&Material {
+            base_color_texture: Some(tiles),
+            mapping: TextureMapping::Triplanar {
+                scale: 0.5,
+                sharpness: 4.0,
+            },
+            roughness: 0.8,
+            ..Default::default()
+        });
+        let glass = |color: Vector4<f32>| Material {
+            base_color: color,
+            roughness: 0.1,
+            alpha_mode: AlphaMode::Blend,
+            ..Default::default()
+        };
+        let shell = renderer.add_material(&glass(Vector4::new(0.9, 0.9, 1.0, 0.15)));
+        let red = renderer.add_material(&glass(Vector4::new(1.0, 0.2, 0.2, 0.5)));
+        let blue = renderer.add_material(&glass(Vector4::new(0.2, 0.3, 1.0, 0.5)));
+
+        let toon = renderer.add_material(&Material {
+            base_color: Vector4::new(1.0, 0.55, 0.1, 1.0),
+            shading: Shading::Toon { bands: 3, rim: 0.5 },
+            outline: Some(Outline::default()),
+            ..Default::default()
+        });
+
+        let plastic = renderer.add_material(&Material {
+            base_color: Vector4::new(0.8, 0.8, 0.85, 1.0),
+            shading: Shading::BlinnPhong {
+                shininess: shininess_from_roughness(0.3),
+            },
+            ..Default::default()
+        });
+
+        let point_light = |position, color| PointLight {
+            cast_shadows: true,
+            ..PointLight::new(position, color, 8.0)
+        };
+
+        let sphere_at = |name: &str, x: f32, radius: f32, material| Object {
+            name: name.to_string(),
+            transform: Matrix4::from_translation(Vector3::new(x, 0.0, 0.0))
+                * Matrix4::from_scale(radius),
+            mesh: sphere,
+            material,
+        };
+
+        let mut world = World::default();
+        for (object, tags) in [
+            (
+                Object {
+                    name: "cube".to_string(),
+                    transform: Matrix4::identity(),
+                    mesh: cube,
+                    material: opaque,
+                },
+                &[][..],
+            ),
+            (
+                Object {
+                    name: "floor".to_string(),
+                    transform: Matrix4::from_translation(Vector3::new(0.0, -2.0, 0.0))
+                        * Matrix4::from_scale(10.0),
+                    mesh: plane,
+                    material: floor,
+                },
+                &["ground"],
+            ),
+            (sphere_at("shell", 0.0, 1.8, shell), &["glass"]),
+            (sphere_at("red sphere", 2.6, 0.5, red), &["glass"]),
+            (sphere_at("blue sphere", -2.6, 0.5, blue), &["glass"]),
+            (
+                Object {
+                    name: "toon sphere".to_string(),
+                    transform: Matrix4::from_translation(Vector3::new(0.0, 0.0, -3.0))
+                        * Matrix4::from_scale(0.6),
+                    mesh: sphere,
+                    material: toon,
+                },
+                &[],
+            ),
+            (
+                Object {
+                    name: "plastic sphere".to_string(),
+                    transform: Matrix4::from_translation(Vector3::new(0.0, 0.0, 3.0))
+                        * Matrix4::from_scale(0.6),
+                    mesh: sphere,
+                    material: plastic,
+                },
+                &[],
+            ),
+        ] {
+            let entity = world.spawn_object(&object);
+            for tag in tags {
+                world.tag(entity, tag);
+            }
+        }
+        for light in [
+            Light::Point(point_light(
+                Vector3::new(3.0, 1.0, 0.0),
+                Vector3::new(1.0, 0.3, 0.2),
+            )),
+            Light::Point(point_light(
+                Vector3::new(-1.5, 1.0, 2.6),
+                Vector3::new(0.2, 1.0, 0.3),
+            )),
+            Light::Point(point_light(
+                Vector3::new(-1.5, 1.0, -2.6),
+                Vector3::new(0.3, 0.4, 1.0),
+            )),
+            Light::Spot(SpotLight::new(
+                Vector3::new(0.0, 4.0, 4.0),
+                Vector3::new(0.0, -1.0, -0.8),
+                Vector3::new(1.0, 0.9, 0.7),
+                20.0,
+            )),
+            Light::Rect(RectLight::new(
+                Vector3::new(-4.0, 2.5, 0.0),
+                Vector3::new(1.0, -0.6, 0.0),
+                Vector3::new(1.0, 1.0, 1.0),
+                4.0,
+            )),
+        ] {
+            world.spawn_light(light);
+        }
+
+        Scene {
+            world,
+            point_clouds: Vec::new(),
+            light: DirectionalLight::default(),
+            day_cycle: None,
+            orbit_point_lights: false,
+            hemisphere: None,
+            background: Background::default(),
+            selected: None,
+            gizmo: Gizmo::default(),
+            history: History::default(),
+            animations: Vec::new(),
+            cameras: Vec::new(),
+            camera: None,
+            camera_parent: None,
+            bookmarks: Vec::new(),
+            bookmark: None,
+            prefabs: Vec::new(),
+            prefab: None,
+            timeline: Timeline::default(),
+            primitives: vec![
+                (Primitive::Cube, cube),
+                (Primitive::Sphere, sphere),
+                (Primitive::Plane, plane),
+            ],
+            primitive_material: Some(opaque),
+        }
+    }
+
+    /// The view of the active scene camera, if any.
+    pub fn camera_view(&self) -> Option<Matrix4<f32>> {
+        self.camera.map(|camera| self.cameras[camera].view())
+    }
+
+    /// The first object of the name.
+    pub fn find(&self, name: &str) -> Option<Entity> {
+        self.world.find(name)
+    }
+
+    /// The entities tagged with the tag.
+    #[allow(dead_code)]
+    pub fn with_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = Entity> + 'a {
+        self.world.with_tag(tag)
+    }
+
+    /// Transforms from the orbit camera's space, centered on its parent without the parent's
+    /// scale, to the world.
+    pub fn camera_parent_transform(&self) -> Matrix4<f32> {
+        let Some(transform) = self
+            .camera_parent
+            .and_then(|parent| self.world.transforms.get(parent))
+        else {
+            return Matrix4::identity();
+        };
+        let [x, y, z] = [transform.0.x, transform.0.y, transform.0.z]
+            .map(|axis| axis.truncate().normalize().extend(0.0));
+        Matrix4::from_cols(x, y, z, transform.0.w)
+    }
+
+    /// Attaches the orbit camera to the selected object, or detaches it.
+    pub fn toggle_camera_parent(&mut self) {
+        self.camera_parent = match self.camera_parent {
+            None => self.selected,
+            Some(_) => None,
+        };
+        match self.camera_parent {
+            Some(parent) => println!("Camera: following {}", self.world.name(parent)),
+            None => println!("Camera: free orbit"),
+        }
+    }
+
+    /// Layers seen through the active scene camera, or all of them through the orbit camera.
+    pub fn layers(&self) -> Layers {
+        self.camera
+            .map_or(Layers::ALL, |camera| self.cameras[camera].layers)
+    }
+
+    /// The projection of the active scene camera, or the orbit camera's.
+    pub fn projection(&self) -> Projection {
+        self.camera.map_or(Projection::default(), |camera| {
+            self.cameras[camera].projection
+        })
+    }
+
+    /// Switches to the next scene camera, and from the last one back to the orbit camera.
+    pub fn cycle_camera(&mut self) {
+        self.camera = match self.camera {
+            None if !self.cameras.is_empty() => Some(0),
+            Some(camera) if camera + 1 < self.cameras.len() => Some(camera + 1),
+            _ => None,
+        };
+        match self.camera {
+            Some(camera) => println!("Camera: {}", self.cameras[camera].name),
+            None => println!("Camera: orbit"),
+        }
+    }
+
+    /// Saves the current view as a bookmark.
+    pub fn bookmark_view(&mut self, orbit_camera: &Camera) {
+        self.bookmarks.push(Bookmark {
+            orbit_camera: *orbit_camera,
+            camera: self.camera,
+        });
+        self.bookmark = Some(self.bookmarks.len() - 1);
+        println!("Bookmark {}", self.bookmarks.len());
+    }
+
+    /// Returns to the view of the next bookmark, after the last one to the first.
+    pub fn next_bookmark(&mut self, orbit_camera: &mut Camera) {
+        if self.bookmarks.is_empty() {
+            println!("No bookmarks");
+            return;
+        }
+        let index = self
+            .bookmark
+            .map_or(0, |bookmark| (bookmark + 1) % self.bookmarks.len());
+        let bookmark = self.bookmarks[index];
+        *orbit_camera = bookmark.orbit_camera;
+        self.camera = bookmark
+            .camera
+            .filter(|&camera| camera < self.cameras.len());
+        self.bookmark = Some(index);
+        println!("Bookmark {} of {}", index + 1, self.bookmarks.len());
+    }
+
+    /// Radius of the smallest sphere around the origin enclosing the objects' bounds.
+    pub fn bounding_radius(&self, renderer: &Renderer) -> f32 {
+        self.world
+            .renderables()
+            .flat_map(|object| {
+                let bounds = renderer.mesh(object.mesh).bounds;
+                bounds.corners().map(move |corner| {
+                    (object.transform * corner.to_homogeneous())
+                        .truncate()
+                        .magnitude()
+                })
+            })
+            .fold(0.0, f32::max)
+    }
+
+    /// Ends dragging the gizmo, recording the edit.
+    pub fn release_gizmo(&mut self) {
+        if let Some(edit) = self.gizmo.release(&self.world) {
+            self.history.push(edit);
+        }
+    }
+
+    pub fn delete_selected(&mut self) {
+        let Some(selected) = self.selected.take() else {
+            return;
+        };
+        let edit = Edit::begin(
+            format!("delete {}", self.world.name(selected)),
+            &self.world,
+            [selected],
+        );
+        self.world.despawn(selected);
+        self.history.push(edit.finish(&self.world));
+    }
+
+    /// Adds a copy of the selected object in the same place and selects it.
+    pub fn duplicate_selected(&mut self) {
+        let Some(selected) = self.selected else {
+            return;
+        };
+        let Some(snapshot) = self.world.snapshot(selected) else {
+            return;
+        };
+        let name = format!("{} copy", self.world.name(selected));
+        let mut edit = Edit::begin(format!("duplicate as {name}"), &self.world, []);
+        let copy = self.world.spawn();
+        self.world.restore(
+            copy,
+            Some(&Snapshot {
+                name: Some(Name(name)),
+                ..snapshot
+            }),
+        );
+        edit.spawned(copy);
+        self.history.push(edit.finish(&self.world));
+        self.selected = Some(copy);
+    }
+
+    pub fn add_prefab(&mut self, prefab: Prefab) -> PrefabId {
+        self.prefabs.push(prefab);
+        PrefabId(self.prefabs.len() - 1)
+    }
+
+    /// Registers the selected object as a prefab and places that one from now on.
+    pub fn register_selected(&mut self) {
+        let Some(selected) = self.selected else {
+            return;
+        };
+        let name = self.world.name(selected).to_string();
+        let prefab = self.add_prefab(Prefab::from_entities(&name, &self.world, &[selected]));
+        self.prefab = Some(prefab);
+        println!("Prefab: {name}");
+    }
+
+    /// Adds an instance of the prefab and returns its entities.
+    pub fn instantiate(&mut self, id: PrefabId, transform: Matrix4<f32>) -> Vec<Entity> {
+        let prefab = &self.prefabs[id.0];
+        let mut edit = Edit::begin(format!("place {}", prefab.name), &self.world, []);
+        let entities = prefab.instantiate(&mut self.world, id, transform);
+        for &entity in &entities {
+            edit.spawned(entity);
+        }
+        self.history.push(edit.finish(&self.world));
+        entities
+    }
+
+    /// Places an instance of the current prefab where the orbit camera looks, and selects it.
+    pub fn place_prefab(&mut self) {
+        let Some(prefab) = self.prefab else {
+            println!("No prefab to place, select an object to register one");
+            return;
+        };
+        let entities = self.instantiate(prefab, Matrix4::from_translation(self.focus()));
+        self.selected = entities.first().copied().or(self.selected);
+    }
+
+    /// The point the orbit camera turns around.
+    pub fn focus(&self) -> Vector3<f32> {
+        self.camera_parent_transform().w.truncate()
+    }
+
+    /// Adds a primitive at the camera's focus and selects it. Its mesh is uploaded the first
+    /// time, and shared from then on.
+    pub fn spawn_primitive(&mut self, renderer: &mut Renderer, primitive: Primitive) -> Entity {
+        let mesh = match self
+            .primitives
+            .iter()
+            .find(|(other, _)| *other == primitive)
+        {
+            Some(&(_, mesh)) => mesh,
+            None => {
+                let mesh = renderer.add_mesh(&primitive.mesh_data());
+                self.primitives.push((primitive, mesh));
+                mesh
+            }
+        };
+        let material = *self
+            .primitive_material
+            .get_or_insert_with(|| renderer.add_material(&Material::default()));
+        let mut edit = Edit::begin(format!("spawn {}", primitive.name()), &self.world, []);
+        let entity = self.world.spawn_object(&Object {
+            name: String::new(),
+            transform: Matrix4::from_translation(self.focus()) * Matrix4::from_scale(0.5),
+            mesh,
+            material,
+        });
+        let name = format!("{} {}", primitive.name(), entity.0);
+        println!("Spawned {name}");
+        self.world.names.insert(entity, Name(name));
+        edit.spawned(entity);
+        self.history.push(edit.finish(&self.world));
+        self.selected = Some(entity);
+        entity
+    }
+
+    /// Merges the opaque and masked objects tagged as static which share a material and layers
+    /// into one mesh per group, with their transforms baked in, so that they take one draw each.
+    /// Deformed and animated objects, and the one the camera follows, are left alone. The merged
+    /// objects are replaced by an entity per group, which undoing brings back. Returns the number
+    /// of objects merged.
+    pub fn batch_static(&mut self, renderer: &mut Renderer) -> usize {
+        let animated: HashSet<Entity> = self
+            .animations
+            .iter()
+            .flat_map(|animation| animation.attachments.iter().map(|&(entity, _)| entity))
+            .collect();
+        let mut groups: Vec<((MaterialId, Layers), Vec<Renderable>)> = Vec::new();
+        for object in self.world.renderables() {
+            let is_static = self
+                .world
+                .tags
+                .get(object.entity)
+                .is_some_and(|tags| tags.0.contains(STATIC_TAG));
+            if !is_static
+                || animated.contains(&object.entity)
+                || self.camera_parent == Some(object.entity)
+                || renderer.is_deformed(object.mesh)
+                || renderer.material(object.material).alpha_mode == AlphaMode::Blend
+            {
+                continue;
+            }
+            let key = (object.material, object.layers);
+            match groups.iter_mut().find(|(other, _)| *other == key) {
+                Some((_, objects)) => objects.push(object),
+                None => groups.push((key, vec![object])),
+            }
+        }
+        groups.retain(|(_, objects)| objects.len() > 1);
+
+        let mut edit = Edit::begin(
+            "batch static meshes",
+            &self.world,
+            groups
+                .iter()
+                .flat_map(|(_, objects)| objects.iter().map(|object| object.entity))
+                .collect::<Vec<_>>(),
+        );
+        let mut merged = 0;
+        for (batch, ((material, layers), objects)) in groups.iter().enumerate() {
+            let mut data = MeshData::default();
+            for object in objects {
+                data.append(&renderer.mesh(object.mesh).data, object.transform);
+                self.world.despawn(object.entity);
+            }
+            let entity = self.world.spawn_object(&Object {
+                name: format!("static batch {batch}"),
+                transform: Matrix4::identity(),
+                mesh: renderer.add_mesh(&data),
+                material: *material,
+            });
+            self.world.tag(entity, STATIC_TAG);
+            if *layers != Layers::DEFAULT {
+                self.world.layers.insert(entity, *layers);
+            }
+            edit.spawned(entity);
+            merged += objects.len();
+        }
+        self.history.push(edit.finish(&self.world));
+        self.deselect_removed();
+        merged
+    }
+
+    pub fn undo(&mut self) {
+        self.history.undo(&mut self.world);
+        self.deselect_removed();
+    }
+
+    pub fn redo(&mut self) {
+        self.history.redo(&mut self.world);
+        self.deselect_removed();
+    }
+
+    fn deselect_removed(&mut self) {
+        if !self
+            .selected
+            .is_some_and(|selected| self.world.is_alive(selected))
+        {
+            self.selected = None;
+        }
+    }
+
+    /// Switches the ambient light between the environment and a hemisphere light.
+    pub fn toggle_hemisphere(&mut self) {
+        self.hemisphere = match self.hemisphere {
+            Some(_) => None,
+            None => Some(HemisphereLight::default()),
+        };
+        println!(
+            "Ambient: {}",
+            if self.hemisphere.is_some() {
+                "hemisphere"
+            } else {
+                "environment"
+            }
+        );
+    }
+
+    /// Starts or pauses the day-night cycle.
+    pub fn toggle_day_cycle(&mut self) {
+        let cycle = self.day_cycle.get_or_insert(DayCycle {
+            time: 8.0,
+            animate: false,
+        });
+        cycle.animate = !cycle.animate;
+    }
+
+    /// Starts or stops orbiting the point lights.
+    pub fn toggle_light_orbit(&mut self) {
+        self.orbit_point_lights = !self.orbit_point_lights;
+        println!(
+            "Point light orbit: {}",
+            if self.orbit_point_lights { "on" } else { "off" }
+        );
+    }
+
+    /// Moves the time of day, pausing the cycle.
+    pub fn scrub_time_of_day(&mut self, hours: f32) {
+        let cycle = self.day_cycle.get_or_insert(DayCycle {
+            time: 8.0,
+            animate: false,
+        });
+        cycle.animate = false;
+        cycle.scrub(hours);
+        println!("Time of day: {:.1} h", cycle.time);
+    }
+
+    /// Starts the animations and the timeline, or pauses them if any is playing.
+    pub fn toggle_animation(&mut self) {
+        if self.animations.is_empty() && self.timeline.tracks.is_empty() {
+            return;
+        }
+        let playing =
+            !self.timeline.playing && !self.animations.iter().any(|animation| animation.playing);
+        for animation in &mut self.animations {
+            animation.playing = playing;
+        }
+        self.timeline.playing = playing;
+        println!("Animation: {}", if playing { "playing" } else { "paused" });
+    }
+
+    /// Moves the animations' and the timeline's time, pausing them.
+    pub fn scrub_animation(&mut self, seconds: f32) {
+        for animation in &mut self.animations {
+            animation.playing = false;
+            animation.scrub(seconds);
+        }
+        self.timeline.playing = false;
+        self.timeline.scrub(seconds);
+        self.timeline.apply(&mut self.world);
+        match self.animations.first() {
+            Some(animation) => println!("Animation time: {:.2} s", animation.time),
+            None => println!("Timeline: {:.2} s", self.timeline.time),
+        }
+    }
+
+    /// Whether advancing the time changes anything: playing animations, the day cycle or the
+    /// orbiting point lights.
+    pub fn is_animating(&self) -> bool {
+        self.animations.iter().any(|animation| animation.playing)
+            || self.timeline.playing
+            || self.day_cycle.is_some_and(|cycle| cycle.animate)
+            || self.orbit_point_lights
+    }
+
+    /// Advances the animations and the timeline and moves the objects accordingly. The timeline
+    /// only sets its properties while playing, so that they can be edited in between.
+    pub fn update_animation(&mut self, dt: f32) {
+        for animation in &mut self.animations {
+            animation.advance(dt);
+            animation.apply(&mut self.world);
+        }
+        if self.timeline.playing {
+            self.timeline.advance(dt);
+            self.timeline.apply(&mut self.world);
+        }
+    }
+
+    /// Keys the selected object's transform on the timeline at its current time.
+    pub fn key_selected(&mut self) {
+        let Some(selected) = self.selected else {
+            return;
+        };
+        let time = self.timeline.time;
+        self.timeline
+            .key_transform(&self.world, selected, time, Easing::default());
+        println!("Keyed {} at {time:.2} s", self.world.name(selected));
+    }
+
+    /// Advances the day-night cycle and moves the sun accordingly.
+    pub fn update_day_cycle(&mut self, dt: f32) {
+        if let Some(cycle) = &mut self.day_cycle {
+            cycle.advance(dt);
+            self.light = DirectionalLight::sun(cycle.time);
+        }
+    }
+
+    /// The sky matching the time of day.
+    pub fn sky(&self) -> SkyGradient {
+        self.day_cycle.map_or(SkyGradient::default(), |cycle| {
+            SkyGradient::at_time(cycle.time)
+        })
+    }
+
+    /// Orbits the point lights around the vertical axis, if enabled.
+    pub fn animate_point_lights(&mut self, dt: f32) {
+        if !self.orbit_point_lights {
+            return;
+        }
+        let rotation = Matrix3::from_angle_y(Rad(0.5 * dt));
+        for (_, light) in self.world.lights.iter_mut() {
+            if let Light::Point(light) = light {
+                light.position = rotation * light.position;
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use cgmath::{Deg, Euler, Matrix4, Quaternion, Rad, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::{Bookmark, Camera},
+    environment::Background,
+    history::Edit,
+    light::{DayCycle, DirectionalLight, HemisphereLight},
+    material::Material,
+    render::Renderer,
+    scene::Scene,
+    world::{Layers, Light, MaterialRef, Tags, Transform},
+};
+
+/// An object's transform, split up to be edited by hand. Shear is lost.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Placement {
+    pub translation: Vector3<f32>,
+    /// Euler angles about X, Y and Z in degrees.
+    pub rotation: Vector3<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl From<Matrix4<f32>> for Placement {
+    fn from(matrix: Matrix4<f32>) -> Self {
+        let (translation, rotation, scale) = Transform(matrix).decompose();
+        let rotation = Euler::from(rotation);
+        Placement {
+            translation,
+            rotation: Vector3::new(
+                Deg::from(rotation.x).0,
+                Deg::from(rotation.y).0,
+                Deg::from(rotation.z).0,
+            ),
+            scale,
+        }
+    }
+}
+
+impl From<Placement> for Matrix4<f32> {
+    fn from(placement: Placement) -> Self {
+        let rotation = Quaternion::from(Euler::new(
+            Rad::from(Deg(placement.rotation.x)),
+            Rad::from(Deg(placement.rotation.y)),
+            Rad::from(Deg(placement.rotation.z)),
+        ));
+        Transform::compose(placement.translation, rotation, placement.scale).0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedObject {
+    /// Matched against the names of the objects loaded from the models or the demo.
+    pub name: String,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    #[serde(default)]
+    pub layers: Layers,
+    pub placement: Placement,
+    pub material: Material,
+}
+
+/// An arrangement of the scene, saved as a RON file. Meshes and textures are not saved, so the
+/// file lists the models to load, and objects are matched to theirs by name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneFile {
+    /// The demo scene is arranged if there are none.
+    pub models: Vec<PathBuf>,
+    pub objects: Vec<SavedObject>,
+    /// All lights but the directional one.
+    pub lights: Vec<Light>,
+    pub light: DirectionalLight,
+    pub day_cycle: Option<DayCycle>,
+    pub hemisphere: Option<HemisphereLight>,
+    #[serde(default)]
+    pub background: Background,
+    pub orbit_camera: Camera,
+    /// Index of the model's camera looked through, or none for the orbit camera.
+    pub camera: Option<usize>,
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
+}
+
+impl SceneFile {
+    pub fn capture(
+        scene: &Scene,
+        renderer: &Renderer,
+        orbit_camera: &Camera,
+        models: &[PathBuf],
+    ) -> Self {
+        let world = &scene.world;
+        SceneFile {
+            models: models.to_vec(),
+            objects: world
+                .renderables()
+                .filter_map(|object| {
+                    Some(SavedObject {
+                        name: world.names.get(object.entity)?.0.clone(),
+                        tags: world
+                            .tags
+                            .get(object.entity)
+                            .map(|tags| tags.0.clone())
+                            .unwrap_or_default(),
+                        layers: object.layers,
+                        placement: object.transform.into(),
+                        material: *renderer.material(object.material),
+                    })
+                })
+                .collect(),
+            lights: world.lights.iter().map(|(_, light)| *light).collect(),
+            light: scene.light,
+            day_cycle: scene.day_cycle,
+            hemisphere: scene.hemisphere,
+            background: scene.background,
+            orbit_camera: *orbit_camera,
+            camera: scene.camera,
+            bookmarks: scene.bookmarks.clone(),
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        ron::from_str(&text).map_err(|error| error.to_string())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|error| error.to_string())?;
+        std::fs::write(path, text).map_err(|error| error.to_string())
+    }
+
+    /// Moves the objects and replaces the lights and the view. Objects missing from the scene are
+    /// skipped, and objects missing from the file keep their place.
+    pub fn apply(&self, scene: &mut Scene, renderer: &mut Renderer, orbit_camera: &mut Camera) {
+        let world = &mut scene.world;
+        let mut edit = Edit::begin(
+            "load scene",
+            world,
+            world
+                .renderables()
+                .map(|object| object.entity)
+                .chain(world.lights.iter().map(|(entity, _)| entity))
+                .collect::<Vec<_>>(),
+        );
+        let mut objects: Vec<_> = world
+            .renderables()
+            .filter_map(|object| Some((world.names.get(object.entity)?.0.clone(), object)))
+            .collect();
+        for saved in &self.objects {
+            // Objects of the same name are matched in order.
+            let Some(index) = objects.iter().position(|(name, _)| *name == saved.name) else {
+                println!("Cannot find object {}", saved.name);
+                continue;
+            };
+            let (_, object) = objects.remove(index);
+            world
+                .transforms
+                .insert(object.entity, Transform(saved.placement.into()));
+            if saved.tags.is_empty() {
+                world.tags.remove(object.entity);
+            } else {
+                world.tags.insert(object.entity, Tags(saved.tags.clone()));
+            }
+            if saved.layers == Layers::DEFAULT {
+                world.layers.remove(object.entity);
+            } else {
+                world.layers.insert(object.entity, saved.layers);
+            }
+            let current = renderer.material(object.material);
+            let material = Material {
+                base_color_texture: current.base_color_texture,
+                ..saved.material
+            };
+            if material != *current {
+                let material = renderer.add_material(&material);
+                world.materials.insert(object.entity, MaterialRef(material));
+            }
+        }
+
+        let lights: Vec<_> = world.lights.iter().map(|(entity, _)| entity).collect();
+        for entity in lights {
+            world.despawn(entity);
+        }
+        for light in &self.lights {
+            edit.spawned(world.spawn_light(*light));
+        }
+        scene.history.push(edit.finish(world));
+        scene.light = self.light;
+        scene.day_cycle = self.day_cycle;
+        scene.hemisphere = self.hemisphere;
+        scene.background = self.background;
+        *orbit_camera = self.orbit_camera;
+        scene.camera = self.camera.filter(|&camera| camera < scene.cameras.len());
+        scene.bookmarks = self.bookmarks.clone();
+    }
+}
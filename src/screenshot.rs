@@ -0,0 +1,148 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tracing::{error, info};
+use wgpu::*;
+
+/// A frame on its way back from the GPU, written to a PNG once its buffer is mapped.
+#[derive(Debug)]
+pub struct Screenshot {
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    /// Rows are padded to the copy alignment.
+    bytes_per_row: u32,
+    /// The surface's channels are blue, green, red and alpha.
+    bgra: bool,
+    mapped: Arc<AtomicBool>,
+}
+
+/// A target of the surface's format, which the final image is drawn into as well as the surface
+/// when taking a screenshot, since the surface cannot be copied from.
+pub fn create_target(device: &Device, format: TextureFormat, width: u32, height: u32) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: None,
+        size: Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        view_formats: &[],
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+    })
+}
+
+impl Screenshot {
+    /// Records copying the target into a buffer, or returns an error if its format is not 8-bit
+    /// RGBA or BGRA.
+    pub fn capture(
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        target: &Texture,
+    ) -> Result<Self, String> {
+        let bgra = match target.format() {
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+            format => return Err(format!("Cannot save {format:?} as PNG")),
+        };
+        let (width, height) = (target.width(), target.height());
+        let bytes_per_row = (4 * width).next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: (bytes_per_row * height) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            TexelCopyTextureInfo {
+                texture: target,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            target.size(),
+        );
+        Ok(Screenshot {
+            buffer,
+            width,
+            height,
+            bytes_per_row,
+            bgra,
+            mapped: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Starts mapping the buffer, once the copy has been submitted. The device has to be polled
+    /// for it to complete.
+    pub fn map(&self) {
+        let mapped = self.mapped.clone();
+        self.buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| match result {
+                Ok(()) => mapped.store(true, Ordering::Release),
+                Err(error) => error!("Cannot read back the screenshot: {error}"),
+            });
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        self.mapped.load(Ordering::Acquire)
+    }
+
+    /// Copies the pixels out of the mapped buffer and encodes them on another thread, into a
+    /// PNG in the working directory named after the time.
+    pub fn save(self) {
+        let mut pixels = Vec::with_capacity((4 * self.width * self.height) as usize);
+        for row in self
+            .buffer
+            .slice(..)
+            .get_mapped_range()
+            .chunks_exact(self.bytes_per_row as usize)
+        {
+            pixels.extend_from_slice(&row[..(4 * self.width) as usize]);
+        }
+        self.buffer.unmap();
+        if self.bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        // Opaque, as the alpha of the surface is meaningless.
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel[3] = u8::MAX;
+        }
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = PathBuf::from(format!("screenshot-{time}.png"));
+        let (width, height) = (self.width, self.height);
+        std::thread::spawn(move || {
+            let Some(image) = image::RgbaImage::from_raw(width, height, pixels) else {
+                return;
+            };
+            match image.save(&path) {
+                Ok(()) => info!("Saved screenshot to {}", path.display()),
+                Err(error) => error!("Cannot save {}: {error}", path.display()),
+            }
+        });
+    }
+}
@@ -0,0 +1,498 @@
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::SystemTime,
+};
+
+use cgmath::{Deg, InnerSpace, Matrix3, Matrix4, Quaternion, Rotation3, Vector3};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Scope, AST, INT};
+
+use crate::{
+    light::{DirectionalLight, PointLight},
+    prefab::{Prefab, PrefabId},
+    scene::Scene,
+    timeline::{Easing, Key, Property, Timeline, Value},
+    world::{Entity, Light, Transform, World},
+};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// The parts of the scene scripts act on, lent to them while they run.
+#[derive(Debug, Default)]
+struct Context {
+    world: World,
+    prefabs: Vec<Prefab>,
+    light: DirectionalLight,
+    timeline: Timeline,
+    /// Entities spawned by the script, removed when it is reloaded.
+    spawned: Vec<Entity>,
+}
+
+impl Context {
+    fn entity(&self, id: INT) -> ScriptResult<Entity> {
+        let entity = Entity(usize::try_from(id).map_err(|_| format!("No entity {id}"))?);
+        if self.world.is_alive(entity) {
+            Ok(entity)
+        } else {
+            Err(format!("No entity {id}").into())
+        }
+    }
+
+    fn transform(&self, id: INT) -> ScriptResult<(Entity, Matrix4<f32>)> {
+        let entity = self.entity(id)?;
+        let transform = self
+            .world
+            .transforms
+            .get(entity)
+            .ok_or_else(|| format!("Entity {id} has no transform"))?;
+        Ok((entity, transform.0))
+    }
+
+    /// Keys a property of the entity on the timeline.
+    fn key(
+        &mut self,
+        id: INT,
+        property: Property,
+        time: f32,
+        value: Value,
+        easing: &str,
+    ) -> ScriptResult<()> {
+        let entity = self.entity(id)?;
+        let easing = Easing::parse(easing).ok_or_else(|| format!("No easing {easing}"))?;
+        let key = Key {
+            time,
+            value,
+            easing,
+        };
+        self.timeline.key(entity, property, key);
+        Ok(())
+    }
+
+    fn light(&mut self, id: INT) -> ScriptResult<&mut Light> {
+        let entity = self.entity(id)?;
+        self.world
+            .lights
+            .get_mut(entity)
+            .ok_or_else(|| format!("Entity {id} is no light").into())
+    }
+}
+
+/// A Rhai script driving the scene. Its top level runs once the scene is loaded, and its
+/// `update(time, dt)` function, if any, every frame. The script is run again when its file
+/// changes, after removing what it spawned.
+pub struct Script {
+    path: PathBuf,
+    engine: Engine,
+    /// None until the script compiles and runs without errors.
+    ast: Option<AST>,
+    scope: Scope<'static>,
+    context: Rc<RefCell<Context>>,
+    /// Modification time of the file read last.
+    modified: Option<SystemTime>,
+    /// Seconds since the script was run.
+    time: f32,
+}
+
+impl Script {
+    /// Runs the script's top level.
+    pub fn new(path: &Path, scene: &mut Scene) -> Self {
+        let context = Rc::new(RefCell::new(Context::default()));
+        let mut script = Script {
+            path: path.to_path_buf(),
+            engine: engine(&context),
+            ast: None,
+            scope: Scope::new(),
+            context,
+            modified: modified(path),
+            time: 0.0,
+        };
+        script.reload(scene);
+        script
+    }
+
+    /// Runs the script again if its file changed, then calls its update function.
+    pub fn update(&mut self, scene: &mut Scene, dt: f32) {
+        let modified = modified(&self.path);
+        if modified != self.modified {
+            self.modified = modified;
+            self.reload(scene);
+            return;
+        }
+        let Some(ast) = &self.ast else {
+            return;
+        };
+        if !ast
+            .iter_functions()
+            .any(|function| function.name == "update")
+        {
+            return;
+        }
+        self.time += dt;
+        let (time, engine, scope) = (self.time, &self.engine, &mut self.scope);
+        let result = lend(&self.context, scene, || {
+            // The top level ran when the script was loaded.
+            let options = CallFnOptions::new().eval_ast(false);
+            engine.call_fn_with_options::<Dynamic>(options, scope, ast, "update", (time, dt))
+        });
+        if let Err(error) = result {
+            println!("Script error in {}: {error}", self.path.display());
+            self.ast = None;
+        }
+    }
+
+    fn reload(&mut self, scene: &mut Scene) {
+        let spawned = std::mem::take(&mut self.context.borrow_mut().spawned);
+        for entity in spawned {
+            scene.world.despawn(entity);
+        }
+        if scene
+            .selected
+            .is_some_and(|selected| !scene.world.is_alive(selected))
+        {
+            scene.selected = None;
+        }
+        self.ast = None;
+        self.scope = Scope::new();
+        self.time = 0.0;
+        let ast = match self.engine.compile_file(self.path.clone()) {
+            Ok(ast) => ast,
+            Err(error) => {
+                println!("Cannot load script {}: {error}", self.path.display());
+                return;
+            }
+        };
+        let (engine, scope) = (&self.engine, &mut self.scope);
+        match lend(&self.context, scene, || {
+            engine.run_ast_with_scope(scope, &ast)
+        }) {
+            Ok(()) => {
+                println!("Script: {}", self.path.display());
+                self.ast = Some(ast);
+            }
+            Err(error) => println!("Script error in {}: {error}", self.path.display()),
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Hands the scene's parts to the script's functions while running it.
+fn lend<T>(context: &RefCell<Context>, scene: &mut Scene, run: impl FnOnce() -> T) -> T {
+    {
+        let mut context = context.borrow_mut();
+        context.world = std::mem::take(&mut scene.world);
+        context.prefabs = std::mem::take(&mut scene.prefabs);
+        context.light = scene.light;
+        context.timeline = std::mem::take(&mut scene.timeline);
+    }
+    let result = run();
+    let mut context = context.borrow_mut();
+    scene.world = std::mem::take(&mut context.world);
+    scene.prefabs = std::mem::take(&mut context.prefabs);
+    scene.light = context.light;
+    scene.timeline = std::mem::take(&mut context.timeline);
+    result
+}
+
+/// An engine with the functions scripts call on the scene. Entities are passed as integers.
+fn engine(context: &Rc<RefCell<Context>>) -> Engine {
+    let mut engine = Engine::new();
+
+    engine
+        .register_type_with_name::<Vector3<f32>>("Vec3")
+        .register_fn("vec3", |x: f32, y: f32, z: f32| Vector3::new(x, y, z))
+        .register_get_set(
+            "x",
+            |v: &mut Vector3<f32>| v.x,
+            |v: &mut Vector3<f32>, x| v.x = x,
+        )
+        .register_get_set(
+            "y",
+            |v: &mut Vector3<f32>| v.y,
+            |v: &mut Vector3<f32>, y| v.y = y,
+        )
+        .register_get_set(
+            "z",
+            |v: &mut Vector3<f32>| v.z,
+            |v: &mut Vector3<f32>, z| v.z = z,
+        )
+        .register_fn("+", |a: Vector3<f32>, b: Vector3<f32>| a + b)
+        .register_fn("-", |a: Vector3<f32>, b: Vector3<f32>| a - b)
+        .register_fn("*", |a: Vector3<f32>, b: f32| a * b)
+        .register_fn("*", |a: f32, b: Vector3<f32>| a * b)
+        .register_fn("length", |v: Vector3<f32>| v.magnitude())
+        .register_fn("to_string", |v: &mut Vector3<f32>| {
+            format!("({}, {}, {})", v.x, v.y, v.z)
+        })
+        .register_fn("to_debug", |v: &mut Vector3<f32>| format!("{v:?}"));
+
+    let c = context.clone();
+    engine.register_fn("find", move |name: &str| -> Dynamic {
+        c.borrow()
+            .world
+            .find(name)
+            .map_or(Dynamic::UNIT, |entity| Dynamic::from(entity.0 as INT))
+    });
+    let c = context.clone();
+    engine.register_fn("with_tag", move |tag: &str| -> Array {
+        c.borrow()
+            .world
+            .with_tag(tag)
+            .map(|entity| Dynamic::from(entity.0 as INT))
+            .collect()
+    });
+    let c = context.clone();
+    engine.register_fn("name", move |id: INT| -> ScriptResult<String> {
+        let context = c.borrow();
+        Ok(context.world.name(context.entity(id)?).to_string())
+    });
+
+    let c = context.clone();
+    engine.register_fn("position", move |id: INT| -> ScriptResult<Vector3<f32>> {
+        Ok(c.borrow().transform(id)?.1.w.truncate())
+    });
+    let c = context.clone();
+    engine.register_fn(
+        "set_position",
+        move |id: INT, position: Vector3<f32>| -> ScriptResult<()> {
+            let mut context = c.borrow_mut();
+            let (entity, mut transform) = context.transform(id)?;
+            transform.w = position.extend(1.0);
+            context
+                .world
+                .transforms
+                .insert(entity, Transform(transform));
+            Ok(())
+        },
+    );
+    let c = context.clone();
+    engine.register_fn(
+        "rotate",
+        move |id: INT, axis: Vector3<f32>, degrees: f32| -> ScriptResult<()> {
+            let mut context = c.borrow_mut();
+            let (entity, transform) = context.transform(id)?;
+            let rotation = Matrix3::from_axis_angle(axis.normalize(), Deg(degrees));
+            let position = transform.w.truncate();
+            let transform = Matrix4::from_translation(position)
+                * Matrix4::from(rotation)
+                * Matrix4::from_translation(-position)
+                * transform;
+            context
+                .world
+                .transforms
+                .insert(entity, Transform(transform));
+            Ok(())
+        },
+    );
+    let c = context.clone();
+    engine.register_fn("scale", move |id: INT, factor: f32| -> ScriptResult<()> {
+        let mut context = c.borrow_mut();
+        let (entity, transform) = context.transform(id)?;
+        let transform = transform * Matrix4::from_scale(factor);
+        context
+            .world
+            .transforms
+            .insert(entity, Transform(transform));
+        Ok(())
+    });
+
+    let c = context.clone();
+    engine.register_fn(
+        "spawn",
+        move |prefab: &str, position: Vector3<f32>| -> ScriptResult<Array> {
+            let context = &mut *c.borrow_mut();
+            let id = context
+                .prefabs
+                .iter()
+                .position(|other| other.name == prefab)
+                .ok_or_else(|| format!("No prefab {prefab}"))?;
+            let entities = context.prefabs[id].instantiate(
+                &mut context.world,
+                PrefabId(id),
+                Matrix4::from_translation(position),
+            );
+            context.spawned.extend_from_slice(&entities);
+            Ok(entities
+                .into_iter()
+                .map(|entity| Dynamic::from(entity.0 as INT))
+                .collect())
+        },
+    );
+    let c = context.clone();
+    engine.register_fn(
+        "copy",
+        move |id: INT, position: Vector3<f32>| -> ScriptResult<INT> {
+            let mut context = c.borrow_mut();
+            let (entity, mut transform) = context.transform(id)?;
+            let snapshot = context.world.snapshot(entity);
+            let copy = context.world.spawn();
+            context.world.restore(copy, snapshot.as_ref());
+            transform.w = position.extend(1.0);
+            context.world.transforms.insert(copy, Transform(transform));
+            context.spawned.push(copy);
+            Ok(copy.0 as INT)
+        },
+    );
+    let c = context.clone();
+    engine.register_fn("despawn", move |id: INT| -> ScriptResult<()> {
+        let mut context = c.borrow_mut();
+        let entity = context.entity(id)?;
+        context.world.despawn(entity);
+        Ok(())
+    });
+
+    let c = context.clone();
+    engine.register_fn("lights", move || -> Array {
+        c.borrow()
+            .world
+            .lights
+            .iter()
+            .map(|(entity, _)| Dynamic::from(entity.0 as INT))
+            .collect()
+    });
+    let c = context.clone();
+    engine.register_fn(
+        "spawn_point_light",
+        move |position: Vector3<f32>, color: Vector3<f32>, intensity: f32| -> INT {
+            let mut context = c.borrow_mut();
+            let light = PointLight::new(position, color, intensity);
+            let entity = context.world.spawn_light(Light::Point(light));
+            context.spawned.push(entity);
+            entity.0 as INT
+        },
+    );
+    let c = context.clone();
+    engine.register_fn(
+        "light_position",
+        move |id: INT| -> ScriptResult<Vector3<f32>> {
+            Ok(match *c.borrow_mut().light(id)? {
+                Light::Point(light) => light.position,
+                Light::Spot(light) => light.position,
+                Light::Rect(light) => light.position,
+            })
+        },
+    );
+    let c = context.clone();
+    engine.register_fn(
+        "set_light_position",
+        move |id: INT, position: Vector3<f32>| -> ScriptResult<()> {
+            match c.borrow_mut().light(id)? {
+                Light::Point(light) => light.position = position,
+                Light::Spot(light) => light.position = position,
+                Light::Rect(light) => light.position = position,
+            }
+            Ok(())
+        },
+    );
+    let c = context.clone();
+    engine.register_fn(
+        "set_light_color",
+        move |id: INT, color: Vector3<f32>| -> ScriptResult<()> {
+            match c.borrow_mut().light(id)? {
+                Light::Point(light) => light.color = color,
+                Light::Spot(light) => light.color = color,
+                Light::Rect(light) => light.color = color,
+            }
+            Ok(())
+        },
+    );
+    let c = context.clone();
+    engine.register_fn(
+        "set_light_intensity",
+        move |id: INT, intensity: f32| -> ScriptResult<()> {
+            match c.borrow_mut().light(id)? {
+                Light::Point(light) => light.intensity = intensity,
+                Light::Spot(light) => light.intensity = intensity,
+                Light::Rect(light) => light.intensity = intensity,
+            }
+            Ok(())
+        },
+    );
+
+    // The timeline, with easings named `step`, `linear`, `ease_in`, `ease_out` and
+    // `ease_in_out`.
+    let c = context.clone();
+    engine.register_fn(
+        "key_position",
+        move |id: INT, time: f32, position: Vector3<f32>, easing: &str| -> ScriptResult<()> {
+            let value = Value::Vector(position);
+            c.borrow_mut()
+                .key(id, Property::Position, time, value, easing)
+        },
+    );
+    let c = context.clone();
+    engine.register_fn(
+        "key_rotation",
+        move |id: INT, time: f32, axis: Vector3<f32>, degrees: f32, easing: &str| {
+            let value =
+                Value::Rotation(Quaternion::from_axis_angle(axis.normalize(), Deg(degrees)));
+            c.borrow_mut()
+                .key(id, Property::Rotation, time, value, easing)
+        },
+    );
+    let c = context.clone();
+    engine.register_fn(
+        "key_scale",
+        move |id: INT, time: f32, scale: Vector3<f32>, easing: &str| -> ScriptResult<()> {
+            let value = Value::Vector(scale);
+            c.borrow_mut().key(id, Property::Scale, time, value, easing)
+        },
+    );
+    let c = context.clone();
+    engine.register_fn(
+        "key_light_intensity",
+        move |id: INT, time: f32, intensity: f32, easing: &str| -> ScriptResult<()> {
+            let value = Value::Scalar(intensity);
+            c.borrow_mut()
+                .key(id, Property::LightIntensity, time, value, easing)
+        },
+    );
+    let c = context.clone();
+    engine.register_fn(
+        "remove_key",
+        move |id: INT, property: &str, time: f32| -> ScriptResult<()> {
+            let mut context = c.borrow_mut();
+            let entity = context.entity(id)?;
+            let property =
+                Property::parse(property).ok_or_else(|| format!("No property {property}"))?;
+            context.timeline.remove_key(entity, property, time);
+            Ok(())
+        },
+    );
+    let c = context.clone();
+    engine.register_fn("clear_keys", move |id: INT| -> ScriptResult<()> {
+        let mut context = c.borrow_mut();
+        let entity = context.entity(id)?;
+        context.timeline.clear(entity);
+        Ok(())
+    });
+    let c = context.clone();
+    engine.register_fn("play_timeline", move || {
+        c.borrow_mut().timeline.playing = true;
+    });
+    let c = context.clone();
+    engine.register_fn("pause_timeline", move || {
+        c.borrow_mut().timeline.playing = false;
+    });
+
+    // The directional light.
+    let c = context.clone();
+    engine.register_fn("set_sun_direction", move |direction: Vector3<f32>| {
+        c.borrow_mut().light.direction = direction.normalize();
+    });
+    let c = context.clone();
+    engine.register_fn("set_sun_color", move |color: Vector3<f32>| {
+        c.borrow_mut().light.color = color;
+    });
+    let c = context.clone();
+    engine.register_fn("set_sun_intensity", move |intensity: f32| {
+        c.borrow_mut().light.intensity = intensity;
+    });
+
+    engine
+}
@@ -1,32 +1,805 @@
 struct Uniforms {
-    model: mat4x4<f32>,
     view: mat4x4<f32>,
     projection: mat4x4<f32>,
+    environment: mat4x4<f32>,
+    camera_position: vec4<f32>,
+    /// Width and height in pixels, followed by their reciprocals.
+    viewport: vec4<f32>,
+    /// Forces toon shading and outlines on every material.
+    toon: u32,
+    point_light_count: u32,
+    spot_light_count: u32,
+    rect_light_count: u32,
+    /// Maps normalized device coordinates back to world space.
+    inverse_view_projection: mat4x4<f32>,
+    near: f32,
+    far: f32,
+    /// Sub-pixel offset of the projection in normalized device coordinates.
+    jitter: vec2<f32>,
+    /// The previous frame's view-projection, without jitter.
+    previous_view_projection: mat4x4<f32>,
+}
+
+struct Light {
+    /// Direction in which the light travels.
+    direction: vec3<f32>,
+    intensity: f32,
+    color: vec3<f32>,
+    /// Whether the hemisphere colors replace the environment's irradiance.
+    hemisphere: u32,
+    sky_color: vec3<f32>,
+    ground_color: vec3<f32>,
+}
+
+struct PointLight {
+    position: vec3<f32>,
+    radius: f32,
+    /// Premultiplied with the intensity.
+    color: vec3<f32>,
+    attenuation: f32,
+    /// Cube of the light in the point shadow maps, or -1 without shadows.
+    shadow_index: i32,
+}
+
+struct SpotLight {
+    position: vec3<f32>,
+    radius: f32,
+    /// Premultiplied with the intensity.
+    color: vec3<f32>,
+    attenuation: f32,
+    direction: vec3<f32>,
+    cos_inner_angle: f32,
+    cos_outer_angle: f32,
+}
+
+struct RectLight {
+    /// Center of the rectangle.
+    position: vec3<f32>,
+    two_sided: u32,
+    /// Half extent along the horizontal edges.
+    right: vec3<f32>,
+    /// Half extent along the vertical edges.
+    up: vec3<f32>,
+    /// Premultiplied with the intensity.
+    color: vec3<f32>,
+}
+
+struct Shadow {
+    /// The view the cascades are fitted to, whose depth selects them.
+    view: mat4x4<f32>,
+    /// World to light clip space, per cascade.
+    cascades: array<mat4x4<f32>, 4>,
+    /// View-space distance at which each cascade ends.
+    splits: vec4<f32>,
+    /// World-space size of a shadow map texel, per cascade.
+    texel_sizes: vec4<f32>,
+    cascade_count: u32,
+    /// Tints each cascade in a different color.
+    debug: u32,
+    filter_mode: u32,
+    /// Receiver offset along the normal, in shadow map texels.
+    normal_offset: f32,
+}
+
+struct Material {
+    base_color: vec4<f32>,
+    metallic: f32,
+    roughness: f32,
+    alpha_cutoff: f32,
+    mapping: u32,
+    triplanar_scale: f32,
+    triplanar_sharpness: f32,
+    vertex_color_blend: u32,
+    shading: u32,
+    toon_bands: u32,
+    rim: f32,
+    outline_width: f32,
+    /// Procedural pattern used in place of the base color texture, zero for none.
+    pattern: u32,
+    outline_color: vec4<f32>,
+    pattern_scale: f32,
+    shininess: f32,
+    /// Index into the array of textures, for bindless materials.
+    texture: u32,
+}
+
+const PI: f32 = 3.14159265359;
+
+/// Fraction of each cascade over which it is blended into the next one.
+const CASCADE_BLEND: f32 = 0.1;
+/// Receiver offset along the normal for point light shadows, in world units.
+const POINT_SHADOW_NORMAL_OFFSET: f32 = 0.03;
+/// Subtracted from the normalized distance when comparing against point shadow maps.
+const POINT_SHADOW_BIAS: f32 = 0.005;
+
+const SHADOW_FILTER_HARD: u32 = 0u;
+const SHADOW_FILTER_PCF_3X3: u32 = 1u;
+const SHADOW_FILTER_PCF_5X5: u32 = 2u;
+/// Radius of the Poisson disk in shadow map texels.
+const POISSON_RADIUS: f32 = 2.5;
+const POISSON_DISK: array<vec2<f32>, 16> = array<vec2<f32>, 16>(
+    vec2<f32>(-0.94201624, -0.39906216),
+    vec2<f32>(0.94558609, -0.76890725),
+    vec2<f32>(-0.09418410, -0.92938870),
+    vec2<f32>(0.34495938, 0.29387760),
+    vec2<f32>(-0.91588581, 0.45771432),
+    vec2<f32>(-0.81544232, -0.87912464),
+    vec2<f32>(-0.38277543, 0.27676845),
+    vec2<f32>(0.97484398, 0.75648379),
+    vec2<f32>(0.44323325, -0.97511554),
+    vec2<f32>(0.53742981, -0.47373420),
+    vec2<f32>(-0.26496911, -0.41893023),
+    vec2<f32>(0.79197514, 0.19090188),
+    vec2<f32>(-0.24188840, 0.99706507),
+    vec2<f32>(-0.81409955, 0.91437590),
+    vec2<f32>(0.19984126, 0.78641367),
+    vec2<f32>(0.14383161, -0.14100790),
+);
+
+const SHADING_TOON: u32 = 1u;
+const SHADING_BLINN_PHONG: u32 = 2u;
+const DEFAULT_TOON_BANDS: u32 = 3u;
+const DEFAULT_RIM: f32 = 0.3;
+const DEFAULT_OUTLINE_WIDTH: f32 = 2.0;
+
+const MAPPING_TRIPLANAR: u32 = 1u;
+
+/// Must match the size of the LTC lookup tables.
+const LTC_LUT_SIZE: f32 = 64.0;
+
+/// Must match the light culling shader.
+const CLUSTER_COUNT_X: u32 = 16u;
+const CLUSTER_COUNT_Y: u32 = 9u;
+const CLUSTER_COUNT_Z: u32 = 24u;
+const CLUSTER_STRIDE: u32 = 130u;
+
+const VERTEX_COLOR_MULTIPLY: u32 = 0u;
+const VERTEX_COLOR_ADD: u32 = 1u;
+
+struct Object {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+    /// Layer in the lightmap array, or -1 if the object has none.
+    lightmap: i32,
+    /// Index into the array of materials, for bindless materials.
+    material: u32,
+    /// The model matrix of the previous frame.
+    previous_model: mat4x4<f32>,
 }
 
 @group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(0) @binding(1) var<uniform> light: Light;
+@group(0) @binding(2) var<storage, read> point_lights: array<PointLight>;
+@group(0) @binding(3) var<storage, read> spot_lights: array<SpotLight>;
+@group(0) @binding(4) var<uniform> shadow: Shadow;
+@group(0) @binding(5) var shadow_map: texture_depth_2d_array;
+@group(0) @binding(6) var shadow_sampler: sampler_comparison;
+/// Distances to the shadowed point lights, normalized by their radii.
+@group(0) @binding(7) var point_shadow_maps: texture_depth_cube_array;
+@group(0) @binding(8) var hard_shadow_sampler: sampler_comparison;
+/// Per view-space cluster, the number of point and spot lights reaching it followed by their indices.
+@group(0) @binding(9) var<storage, read> clusters: array<u32>;
+@group(0) @binding(10) var<storage, read> rect_lights: array<RectLight>;
+/// Baked irradiance, addressed by the second UV set.
+@group(0) @binding(11) var lightmaps: texture_2d_array<f32>;
+
+/// Per-pixel rotation of the Poisson disk, which trades banding for noise.
+var<private> shadow_rotation: f32;
+/// Offset of the fragment's cluster in the cluster buffer.
+var<private> light_cluster: u32;
+/// Whether masked materials resolve their cutout through alpha-to-coverage instead of discarding.
+override alpha_to_coverage: bool = false;
+
+/// What the fragments show, in the order of the renderer's modes. The wireframe is drawn by its
+/// own entry point over the shaded image.
+override render_mode: u32 = 0u;
+const RENDER_MODE_ALBEDO: u32 = 1u;
+const RENDER_MODE_NORMALS: u32 = 2u;
+const RENDER_MODE_DEPTH: u32 = 3u;
+const RENDER_MODE_UV: u32 = 4u;
+const RENDER_MODE_OVERDRAW: u32 = 6u;
+/// Added per fragment in the overdraw heatmap, going from red over yellow to white.
+const OVERDRAW_STEP: vec3<f32> = vec3<f32>(0.1, 0.05, 0.02);
+const WIREFRAME_COLOR: vec3<f32> = vec3<f32>(0.0, 1.0, 0.8);
+
+@group(1) @binding(0) var environment_texture: texture_cube<f32>;
+@group(1) @binding(1) var environment_sampler: sampler;
+@group(1) @binding(2) var irradiance_texture: texture_cube<f32>;
+@group(1) @binding(3) var specular_texture: texture_cube<f32>;
+@group(1) @binding(4) var brdf_lut: texture_2d<f32>;
+/// Inverse LTC matrices fitted to the GGX lobe, indexed by roughness and `sqrt(1 - n·v)`.
+@group(1) @binding(5) var ltc_matrix_lut: texture_2d<f32>;
+@group(1) @binding(6) var ltc_amplitude_lut: texture_2d<f32>;
 
 struct VertexInput {
-    @location(0) position: vec4<f32>,
-    @location(1) color: vec4<f32>,
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) uv: vec2<f32>,
+    @location(4) lightmap_uv: vec2<f32>,
+    @builtin(instance_index) instance: u32,
 }
 
 struct FragmentInput {
     @builtin(position) position: vec4<f32>,
     @location(0) color: vec4<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) uv: vec2<f32>,
+    @location(4) lightmap_uv: vec2<f32>,
+    @location(5) @interpolate(flat) lightmap: i32,
+    /// Passed on for loading the object's material.
+    @location(6) @interpolate(flat) instance: u32,
+}
+
+struct OutlineInput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) @interpolate(flat) instance: u32,
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    return select(pow((color + 0.055) / 1.055, vec3<f32>(2.4)), color / 12.92, color <= vec3<f32>(0.04045));
 }
 
 @vertex
 fn vertex(in: VertexInput) -> FragmentInput {
+    load_object(in.instance);
     var out: FragmentInput;
-    out.position = uniforms.projection * uniforms.view * uniforms.model * in.position;
-    out.color = in.color;
+    let world_position = object.model * vec4<f32>(in.position, 1.0);
+    out.position = uniforms.projection * uniforms.view * world_position;
+    // Vertex colors are authored in sRGB, but interpolated and shaded in linear space.
+    out.color = vec4<f32>(srgb_to_linear(in.color.rgb), in.color.a);
+    out.world_position = world_position.xyz;
+    out.normal = (object.normal * vec4<f32>(in.normal, 0.0)).xyz;
+    out.uv = in.uv;
+    out.lightmap_uv = in.lightmap_uv;
+    out.lightmap = object.lightmap;
+    out.instance = in.instance;
     return out;
 }
 
+/// Irradiance from the hemisphere light if enabled, otherwise from the environment.
+fn ambient_irradiance(normal: vec3<f32>) -> vec3<f32> {
+    if light.hemisphere != 0u {
+        return mix(light.ground_color, light.sky_color, 0.5 * normal.y + 0.5);
+    }
+    return textureSampleLevel(irradiance_texture, environment_sampler, normal, 0.0).rgb;
+}
+
+/// Irradiance from the object's lightmap if it has one, otherwise from the ambient light.
+fn surface_irradiance(in: FragmentInput, normal: vec3<f32>) -> vec3<f32> {
+    if in.lightmap >= 0 {
+        return textureSampleLevel(lightmaps, environment_sampler, in.lightmap_uv, in.lightmap, 0.0).rgb;
+    }
+    return ambient_irradiance(normal);
+}
+
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+/// Split-sum image-based lighting from the prefiltered environment.
+fn ambient(albedo: vec3<f32>, normal: vec3<f32>, view: vec3<f32>, metallic: f32, roughness: f32, irradiance: vec3<f32>) -> vec3<f32> {
+    let n_dot_v = max(dot(normal, view), 0.0);
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let fresnel = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+
+    let diffuse = (1.0 - fresnel) * (1.0 - metallic) * irradiance * albedo;
+
+    let max_lod = f32(textureNumLevels(specular_texture) - 1);
+    let prefiltered = textureSampleLevel(specular_texture, environment_sampler, reflect(-view, normal), roughness * max_lod).rgb;
+    let brdf = textureSampleLevel(brdf_lut, environment_sampler, vec2<f32>(n_dot_v, roughness), 0.0).rg;
+    let specular = prefiltered * (fresnel * brdf.x + brdf.y);
+
+    return diffuse + specular;
+}
+
+/// Ambient lighting for the Blinn-Phong path, without the split-sum BRDF lookup.
+fn ambient_blinn_phong(albedo: vec3<f32>, normal: vec3<f32>, view: vec3<f32>, metallic: f32, roughness: f32, irradiance: vec3<f32>) -> vec3<f32> {
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let max_lod = f32(textureNumLevels(specular_texture) - 1);
+    let prefiltered = textureSampleLevel(specular_texture, environment_sampler, reflect(-view, normal), roughness * max_lod).rgb;
+    return (1.0 - metallic) * irradiance * albedo + f0 * prefiltered;
+}
+
+/// Lighting from a light in direction `to_light`: Lambertian diffuse,
+/// plus a normalized Blinn-Phong highlight if the shininess is positive.
+fn shade_light(
+    albedo: vec3<f32>,
+    normal: vec3<f32>,
+    view: vec3<f32>,
+    metallic: f32,
+    shininess: f32,
+    to_light: vec3<f32>,
+    radiance: vec3<f32>,
+) -> vec3<f32> {
+    let n_dot_l = max(dot(normal, to_light), 0.0);
+    var color = (1.0 - metallic) * albedo / PI;
+    if shininess > 0.0 {
+        let half_vector = normalize(to_light + view);
+        let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+        color += f0 * (shininess + 8.0) / (8.0 * PI) * pow(max(dot(normal, half_vector), 0.0), shininess);
+    }
+    return color * radiance * n_dot_l;
+}
+
+/// Quadratic falloff, smoothly windowed to reach zero at the light's radius.
+fn distance_falloff(distance: f32, radius: f32, attenuation: f32) -> f32 {
+    let window = saturate(1.0 - pow(distance / radius, 4.0));
+    return window * window / (1.0 + attenuation * distance * distance);
+}
+
+/// Fraction of the directional light reaching the position according to a single cascade.
+fn cascade_shadow(cascade: u32, position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let offset_position = position + normal * shadow.texel_sizes[cascade] * shadow.normal_offset;
+    let clip = shadow.cascades[cascade] * vec4<f32>(offset_position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + 0.5;
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0 {
+        return 1.0;
+    }
+    if shadow.filter_mode == SHADOW_FILTER_HARD {
+        return textureSampleCompareLevel(shadow_map, hard_shadow_sampler, uv, cascade, ndc.z);
+    }
+    let texel = 1.0 / vec2<f32>(textureDimensions(shadow_map));
+    let count = shadow_kernel_size();
+    var sum = 0.0;
+    for (var i = 0u; i < count; i++) {
+        sum += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + shadow_kernel_offset(i) * texel, cascade, ndc.z);
+    }
+    return sum / f32(count);
+}
+
+/// Number of comparisons taken by soft shadow filters.
+fn shadow_kernel_size() -> u32 {
+    switch shadow.filter_mode {
+        case SHADOW_FILTER_PCF_3X3: {
+            return 9u;
+        }
+        case SHADOW_FILTER_PCF_5X5: {
+            return 25u;
+        }
+        default: {
+            return 16u;
+        }
+    }
+}
+
+/// Offset of a comparison of a soft shadow filter, in texels.
+fn shadow_kernel_offset(i: u32) -> vec2<f32> {
+    switch shadow.filter_mode {
+        case SHADOW_FILTER_PCF_3X3: {
+            return vec2<f32>(f32(i % 3u), f32(i / 3u)) - 1.0;
+        }
+        case SHADOW_FILTER_PCF_5X5: {
+            return vec2<f32>(f32(i % 5u), f32(i / 5u)) - 2.0;
+        }
+        default: {
+            let c = cos(shadow_rotation);
+            let s = sin(shadow_rotation);
+            let p = POISSON_DISK[i];
+            return POISSON_RADIUS * vec2<f32>(c * p.x - s * p.y, s * p.x + c * p.y);
+        }
+    }
+}
+
+/// Fraction of a point light reaching the position.
+fn point_shadow(point_light: PointLight, position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    if point_light.shadow_index < 0 {
+        return 1.0;
+    }
+    if RAY_TRACED_SHADOWS {
+        let offset = point_light.position - position;
+        let distance = length(offset);
+        return traced_visibility(position, normal, offset / distance, distance);
+    }
+    let direction = position + normal * POINT_SHADOW_NORMAL_OFFSET - point_light.position;
+    let distance = length(direction);
+    let reference = distance / point_light.radius - POINT_SHADOW_BIAS;
+    if shadow.filter_mode == SHADOW_FILTER_HARD {
+        return textureSampleCompareLevel(point_shadow_maps, hard_shadow_sampler, direction, point_light.shadow_index, reference);
+    }
+
+    // Offset the lookup direction within the plane facing the light, by texels of the cube face.
+    let axis = direction / distance;
+    let helper = select(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0), abs(axis.y) > 0.99);
+    let tangent = normalize(cross(axis, helper));
+    let bitangent = cross(axis, tangent);
+    let texel = 2.0 / f32(textureDimensions(point_shadow_maps).x);
+    let count = shadow_kernel_size();
+    var sum = 0.0;
+    for (var i = 0u; i < count; i++) {
+        let offset = shadow_kernel_offset(i) * texel;
+        let sample_direction = axis + offset.x * tangent + offset.y * bitangent;
+        sum += textureSampleCompareLevel(point_shadow_maps, shadow_sampler, sample_direction, point_light.shadow_index, reference);
+    }
+    return sum / f32(count);
+}
+
+/// Index of the cascade covering the view-space depth, or the cascade count beyond the last one.
+fn cascade_index(depth: f32) -> u32 {
+    var cascade = 0u;
+    while cascade < shadow.cascade_count && depth > shadow.splits[cascade] {
+        cascade++;
+    }
+    return cascade;
+}
+
+/// Fraction of the directional light reaching the position, blending between adjacent cascades
+/// and fading out towards the shadow distance.
+fn directional_shadow(position: vec3<f32>, normal: vec3<f32>, depth: f32) -> f32 {
+    if RAY_TRACED_SHADOWS {
+        return traced_visibility(position, normal, -light.direction, uniforms.far);
+    }
+    let cascade = cascade_index(depth);
+    if cascade >= shadow.cascade_count {
+        return 1.0;
+    }
+    var visibility = cascade_shadow(cascade, position, normal);
+
+    let start = select(0.0, shadow.splits[max(cascade, 1u) - 1u], cascade > 0u);
+    let end = shadow.splits[cascade];
+    let blend = saturate((depth - end) / (CASCADE_BLEND * (end - start)) + 1.0);
+    if blend > 0.0 {
+        var next = 1.0;
+        if cascade + 1u < shadow.cascade_count {
+            next = cascade_shadow(cascade + 1u, position, normal);
+        }
+        visibility = mix(visibility, next, blend);
+    }
+    return visibility;
+}
+
+fn cascade_tint(depth: f32) -> vec3<f32> {
+    switch cascade_index(depth) {
+        case 0u: {
+            return vec3<f32>(1.0, 0.3, 0.3);
+        }
+        case 1u: {
+            return vec3<f32>(0.3, 1.0, 0.3);
+        }
+        case 2u: {
+            return vec3<f32>(0.3, 0.3, 1.0);
+        }
+        case 3u: {
+            return vec3<f32>(1.0, 1.0, 0.3);
+        }
+        default: {
+            return vec3<f32>(1.0);
+        }
+    }
+}
+
+/// Direct lighting from the directional light and the point and spot lights of the fragment's cluster.
+fn direct(
+    albedo: vec3<f32>,
+    normal: vec3<f32>,
+    view: vec3<f32>,
+    position: vec3<f32>,
+    metallic: f32,
+    shininess: f32,
+    visibility: f32,
+) -> vec3<f32> {
+    var color = shade_light(albedo, normal, view, metallic, shininess, -light.direction, light.color * light.intensity * visibility);
+    let point_count = clusters[light_cluster];
+    let spot_count = clusters[light_cluster + 1u];
+    for (var i = 0u; i < point_count; i++) {
+        let point_light = point_lights[clusters[light_cluster + 2u + i]];
+        let offset = point_light.position - position;
+        let distance = length(offset);
+        if distance >= point_light.radius {
+            continue;
+        }
+        let radiance = point_light.color * distance_falloff(distance, point_light.radius, point_light.attenuation)
+            * point_shadow(point_light, position, normal);
+        color += shade_light(albedo, normal, view, metallic, shininess, offset / distance, radiance);
+    }
+    for (var i = 0u; i < spot_count; i++) {
+        let spot_light = spot_lights[clusters[light_cluster + 2u + point_count + i]];
+        let offset = spot_light.position - position;
+        let distance = length(offset);
+        if distance >= spot_light.radius {
+            continue;
+        }
+        let to_light = offset / distance;
+        let cone = smoothstep(spot_light.cos_outer_angle, spot_light.cos_inner_angle, dot(-to_light, spot_light.direction));
+        let radiance = spot_light.color * cone * distance_falloff(distance, spot_light.radius, spot_light.attenuation);
+        color += shade_light(albedo, normal, view, metallic, shininess, to_light, radiance);
+    }
+    return color;
+}
+
+/// Contribution of the great-circle arc between two directions to the cosine-weighted
+/// solid angle, using a rational fit of θ / (2π sin θ).
+fn integrate_edge(v1: vec3<f32>, v2: vec3<f32>) -> f32 {
+    let x = dot(v1, v2);
+    let y = abs(x);
+    let v = (0.8543985 + (0.4965155 + 0.0145206 * y) * y) / (3.4175940 + (4.1616724 + y) * y);
+    var theta_sin_theta = v;
+    if x <= 0.0 {
+        theta_sin_theta = 0.5 * inverseSqrt(max(1.0 - x * x, 1e-7)) - v;
+    }
+    return cross(v1, v2).z * theta_sin_theta;
+}
+
+/// Integrates a clamped cosine over the rectangle as seen from the position,
+/// after transforming its corners from world space into the cosine's space.
+fn ltc_integrate(transform: mat3x3<f32>, position: vec3<f32>, rect_light: RectLight) -> f32 {
+    let center = rect_light.position - position;
+    let corners = array<vec3<f32>, 4>(
+        transform * (center - rect_light.right - rect_light.up),
+        transform * (center - rect_light.right + rect_light.up),
+        transform * (center + rect_light.right + rect_light.up),
+        transform * (center + rect_light.right - rect_light.up),
+    );
+
+    // Clip to the upper hemisphere, which may add a fifth corner.
+    var clipped: array<vec3<f32>, 5>;
+    var count = 0u;
+    for (var i = 0u; i < 4u; i++) {
+        let a = corners[i];
+        let b = corners[(i + 1u) % 4u];
+        if a.z > 0.0 {
+            clipped[count] = a;
+            count++;
+        }
+        if (a.z > 0.0) != (b.z > 0.0) {
+            clipped[count] = mix(a, b, a.z / (a.z - b.z));
+            count++;
+        }
+    }
+    if count < 3u {
+        return 0.0;
+    }
+
+    var sum = 0.0;
+    for (var i = 0u; i < count; i++) {
+        sum += integrate_edge(normalize(clipped[i]), normalize(clipped[(i + 1u) % count]));
+    }
+    // The corners wind counterclockwise when the emitting side faces the position.
+    if rect_light.two_sided != 0u {
+        sum = abs(sum);
+    }
+    return max(sum, 0.0);
+}
+
+/// Lambertian diffuse and GGX specular lighting from the rectangular lights,
+/// evaluated with linearly transformed cosines.
+fn rect_lighting(
+    albedo: vec3<f32>,
+    normal: vec3<f32>,
+    view: vec3<f32>,
+    position: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+) -> vec3<f32> {
+    let n_dot_v = saturate(dot(normal, view));
+    let uv = (vec2<f32>(roughness, sqrt(1.0 - n_dot_v)) * (LTC_LUT_SIZE - 1.0) + 0.5) / LTC_LUT_SIZE;
+    let m = textureSampleLevel(ltc_matrix_lut, environment_sampler, uv, 0.0);
+    let amplitude = textureSampleLevel(ltc_amplitude_lut, environment_sampler, uv, 0.0).rg;
+
+    // Tangent frame with the view direction in the xz-plane, as in the fit.
+    var tangent = view - normal * n_dot_v;
+    if dot(tangent, tangent) < 1e-6 {
+        tangent = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(normal.x) > 0.9);
+        tangent -= normal * dot(tangent, normal);
+    }
+    tangent = normalize(tangent);
+    let frame = transpose(mat3x3<f32>(tangent, cross(normal, tangent), normal));
+    let specular_transform = mat3x3<f32>(
+        vec3<f32>(m.x, 0.0, m.z),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(m.y, 0.0, m.w),
+    ) * frame;
+
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let specular_color = f0 * amplitude.x + amplitude.y;
+    var color = vec3<f32>(0.0);
+    for (var i = 0u; i < uniforms.rect_light_count; i++) {
+        let rect_light = rect_lights[i];
+        let diffuse = ltc_integrate(frame, position, rect_light);
+        let specular = ltc_integrate(specular_transform, position, rect_light);
+        color += rect_light.color * ((1.0 - metallic) * albedo * diffuse + specular_color * specular);
+    }
+    return color;
+}
+
+/// Projects the texture along each world axis and blends the three samples by the normal.
+fn sample_triplanar(position: vec3<f32>, normal: vec3<f32>) -> vec4<f32> {
+    let p = position * material().triplanar_scale;
+    var weights = pow(abs(normal), vec3<f32>(material().triplanar_sharpness));
+    weights /= weights.x + weights.y + weights.z;
+    return sample_material_texture(p.zy) * weights.x
+        + sample_material_texture(p.xz) * weights.y
+        + sample_material_texture(p.xy) * weights.z;
+}
+
+fn sample_base_color(in: FragmentInput, normal: vec3<f32>) -> vec4<f32> {
+    if material().pattern != 0u {
+        // Decoded like the sRGB textures the patterns stand in for.
+        let pattern = procedural_pattern(material().pattern, material().pattern_scale, in.uv, in.world_position);
+        return vec4<f32>(srgb_to_linear(pattern), 1.0);
+    }
+    if material().mapping == MAPPING_TRIPLANAR {
+        return sample_triplanar(in.world_position, normal);
+    }
+    return sample_material_texture(in.uv);
+}
+
+fn blend_vertex_color(texture_color: vec4<f32>, vertex_color: vec4<f32>) -> vec4<f32> {
+    switch material().vertex_color_blend {
+        case VERTEX_COLOR_MULTIPLY: {
+            return texture_color * vertex_color;
+        }
+        case VERTEX_COLOR_ADD: {
+            return vec4<f32>(texture_color.rgb + vertex_color.rgb, texture_color.a * vertex_color.a);
+        }
+        default: {
+            return vertex_color;
+        }
+    }
+}
+
+/// Quantized diffuse bands over a flat ambient term, with a hard rim highlight.
+fn toon(albedo: vec3<f32>, normal: vec3<f32>, view: vec3<f32>, bands: u32, rim: f32, visibility: f32) -> vec3<f32> {
+    let n_dot_l = max(dot(normal, -light.direction), 0.0) * step(0.5, visibility);
+    let levels = f32(max(bands, 1u));
+    let diffuse = ceil(n_dot_l * levels) / levels;
+    let ambient = ambient_irradiance(vec3<f32>(0.0, 1.0, 0.0));
+    let rim_light = smoothstep(0.6, 0.65, 1.0 - max(dot(normal, view), 0.0)) * rim * n_dot_l;
+    return albedo * (ambient + diffuse * light.color) + rim_light * light.color;
+}
+
+fn cluster_offset(pixel: vec2<f32>, depth: f32) -> u32 {
+    let tile = min(
+        vec2<u32>(pixel * uniforms.viewport.zw * vec2<f32>(f32(CLUSTER_COUNT_X), f32(CLUSTER_COUNT_Y))),
+        vec2<u32>(CLUSTER_COUNT_X, CLUSTER_COUNT_Y) - 1u,
+    );
+    let slice = u32(clamp(
+        log(depth / uniforms.near) / log(uniforms.far / uniforms.near) * f32(CLUSTER_COUNT_Z),
+        0.0,
+        f32(CLUSTER_COUNT_Z - 1u),
+    ));
+    return ((slice * CLUSTER_COUNT_Y + tile.y) * CLUSTER_COUNT_X + tile.x) * CLUSTER_STRIDE;
+}
+
+/// Shading inputs shared by the forward and deferred paths.
+struct Surface {
+    albedo: vec3<f32>,
+    normal: vec3<f32>,
+    position: vec3<f32>,
+    metallic: f32,
+    roughness: f32,
+    shading: u32,
+    toon_bands: u32,
+    rim: f32,
+    shininess: f32,
+    /// Diffuse irradiance, from a lightmap or the environment.
+    irradiance: vec3<f32>,
+}
+
+/// Lights the surface with its shading model, or toon shading if forced globally.
+fn shade(surface: Surface, pixel: vec2<f32>) -> vec3<f32> {
+    let view = normalize(uniforms.camera_position.xyz - surface.position);
+    // Interleaved gradient noise.
+    shadow_rotation = 2.0 * PI * fract(52.9829189 * fract(dot(pixel, vec2<f32>(0.06711056, 0.00583715))));
+    let depth = -(uniforms.view * vec4<f32>(surface.position, 1.0)).z;
+    light_cluster = cluster_offset(pixel, depth);
+    // Differs from the depth while the frame is seen through the debug camera.
+    let shadow_depth = -(shadow.view * vec4<f32>(surface.position, 1.0)).z;
+    let visibility = directional_shadow(surface.position, surface.normal, shadow_depth);
+    var color: vec3<f32>;
+    if surface.shading == SHADING_TOON {
+        color = toon(surface.albedo, surface.normal, view, surface.toon_bands, surface.rim, visibility);
+    } else if uniforms.toon != 0u {
+        color = toon(surface.albedo, surface.normal, view, DEFAULT_TOON_BANDS, DEFAULT_RIM, visibility);
+    } else if surface.shading == SHADING_BLINN_PHONG {
+        color = ambient_blinn_phong(surface.albedo, surface.normal, view, surface.metallic, surface.roughness, surface.irradiance)
+            + direct(surface.albedo, surface.normal, view, surface.position, surface.metallic, surface.shininess, visibility)
+            + rect_lighting(surface.albedo, surface.normal, view, surface.position, surface.metallic, surface.roughness);
+    } else {
+        color = ambient(surface.albedo, surface.normal, view, surface.metallic, surface.roughness, surface.irradiance)
+            + direct(surface.albedo, surface.normal, view, surface.position, surface.metallic, 0.0, visibility)
+            + rect_lighting(surface.albedo, surface.normal, view, surface.position, surface.metallic, surface.roughness);
+    }
+    if shadow.debug != 0u {
+        color *= cascade_tint(shadow_depth);
+    }
+    return color;
+}
+
 @fragment
 fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
-    return in.color;
+    load_object(in.instance);
+    let normal = normalize(in.normal);
+    let base_color = material().base_color * blend_vertex_color(sample_base_color(in, normal), in.color);
+    var color: vec3<f32>;
+    switch render_mode {
+        case RENDER_MODE_ALBEDO: {
+            color = base_color.rgb;
+        }
+        case RENDER_MODE_NORMALS: {
+            color = 0.5 * normal + 0.5;
+        }
+        case RENDER_MODE_DEPTH: {
+            // Logarithmic, to tell apart nearby depths as well as distant ones.
+            let depth = -(uniforms.view * vec4<f32>(in.world_position, 1.0)).z;
+            color = vec3<f32>(saturate(log2(1.0 + depth) / log2(1.0 + uniforms.far)));
+        }
+        case RENDER_MODE_UV: {
+            color = vec3<f32>(fract(in.uv), 0.0);
+        }
+        case RENDER_MODE_OVERDRAW: {
+            return vec4<f32>(OVERDRAW_STEP, 1.0);
+        }
+        default: {
+            color = shade(
+                Surface(
+                    base_color.rgb,
+                    normal,
+                    in.world_position,
+                    material().metallic,
+                    material().roughness,
+                    material().shading,
+                    material().toon_bands,
+                    material().rim,
+                    material().shininess,
+                    surface_irradiance(in, normal),
+                ),
+                in.position.xy,
+            );
+        }
+    }
+
+    var alpha = base_color.a;
+    if material().alpha_cutoff > 0.0 {
+        if alpha_to_coverage {
+            // Sharpen the coverage transition to about one pixel around the cutoff.
+            alpha = saturate((alpha - material().alpha_cutoff) / max(fwidth(alpha), 0.0001) + 0.5);
+        } else {
+            if alpha < material().alpha_cutoff {
+                discard;
+            }
+            alpha = 1.0;
+        }
+    }
+    return vec4<f32>(color, alpha);
+}
+
+/// Inverted hull: back faces pushed outwards along the normal by a constant number of pixels.
+@vertex
+fn outline_vertex(in: VertexInput) -> OutlineInput {
+    load_object(in.instance);
+    let view_projection = uniforms.projection * uniforms.view;
+    var position = view_projection * object.model * vec4<f32>(in.position, 1.0);
+    let normal = (view_projection * object.normal * vec4<f32>(in.normal, 0.0)).xy;
+
+    var width = material().outline_width;
+    if width == 0.0 && uniforms.toon != 0u {
+        width = DEFAULT_OUTLINE_WIDTH;
+    }
+    if length(normal) > 0.0 {
+        position += vec4<f32>(normalize(normal) * width * 2.0 * uniforms.viewport.zw * position.w, 0.0, 0.0);
+    }
+    return OutlineInput(position, in.instance);
+}
+
+@fragment
+fn outline_fragment(in: OutlineInput) -> @location(0) vec4<f32> {
+    load_object(in.instance);
+    return material().outline_color;
+}
+
+/// Edges of the triangles, rasterized as lines over the shaded image.
+@fragment
+fn wireframe_fragment() -> @location(0) vec4<f32> {
+    return vec4<f32>(WIREFRAME_COLOR, 1.0);
+}
+
+/// Marks the selected objects in the selection mask.
+@fragment
+fn selection_fragment() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
 }
 
 /// Generates vertices from the vertex index.
@@ -0,0 +1,686 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+use wgpu::*;
+
+use crate::{debug_draw::DebugDraw, mesh::Vertex, render::as_byte_slice};
+
+/// Number of cascades, between 2 and 4.
+pub const CASCADE_COUNT: usize = 3;
+const SHADOW_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+/// View distance up to which shadows are rendered.
+const SHADOW_DISTANCE: f32 = 40.0;
+/// Blend between a uniform (0) and logarithmic (1) distribution of the cascade splits.
+const SPLIT_LAMBDA: f32 = 0.75;
+/// Extends the light frusta towards the light to catch casters outside the view frustum.
+const CASTER_DISTANCE: f32 = 20.0;
+/// Distance between per-cascade and per-face uniforms, satisfying the minimum dynamic offset alignment.
+const UNIFORM_STRIDE: u64 = 256;
+/// Number of point lights that can cast shadows at the same time.
+pub const MAX_SHADOWED_POINT_LIGHTS: usize = 4;
+const POINT_SHADOW_MAP_SIZE: u32 = 512;
+const POINT_SHADOW_NEAR: f32 = 0.05;
+
+/// How shadow maps are filtered, trading softness for samples.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ShadowFilter {
+    /// A single nearest-texel comparison.
+    Hard,
+    /// 3×3 bilinear comparisons.
+    #[default]
+    Pcf3x3,
+    /// 5×5 bilinear comparisons.
+    Pcf5x5,
+    /// 16 comparisons on a Poisson disk rotated per pixel.
+    Poisson,
+}
+
+impl ShadowFilter {
+    pub fn next(self) -> Self {
+        match self {
+            ShadowFilter::Hard => ShadowFilter::Pcf3x3,
+            ShadowFilter::Pcf3x3 => ShadowFilter::Pcf5x5,
+            ShadowFilter::Pcf5x5 => ShadowFilter::Poisson,
+            ShadowFilter::Poisson => ShadowFilter::Hard,
+        }
+    }
+}
+
+/// Resolution of the cascades, trading sharpness for memory and fill rate.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ShadowQuality {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl ShadowQuality {
+    /// Width and height of each cascade.
+    pub fn size(self) -> u32 {
+        match self {
+            ShadowQuality::Low => 1024,
+            ShadowQuality::Medium => 2048,
+            ShadowQuality::High => 4096,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ShadowSettings {
+    pub quality: ShadowQuality,
+    pub filter: ShadowFilter,
+    /// Tints each cascade in a different color.
+    pub cascade_debug: bool,
+    /// Constant depth bias of the cascades, in units of the depth format's precision.
+    pub constant_bias: i32,
+    /// Depth bias of the cascades scaled by the caster's depth slope.
+    pub slope_bias: f32,
+    /// Receiver offset along the normal, in shadow map texels.
+    pub normal_offset: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            quality: ShadowQuality::default(),
+            filter: ShadowFilter::default(),
+            cascade_debug: false,
+            constant_bias: 2,
+            slope_bias: 2.0,
+            normal_offset: 1.5,
+        }
+    }
+}
+
+impl ShadowSettings {
+    pub fn depth_bias(&self) -> DepthBiasState {
+        DepthBiasState {
+            constant: self.constant_bias,
+            slope_scale: self.slope_bias,
+            clamp: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ShadowUniforms {
+    /// The view the cascades are fitted to, whose depth selects them.
+    #[allow(dead_code)]
+    view: Matrix4<f32>,
+    /// World to light clip space, per cascade.
+    #[allow(dead_code)]
+    cascades: [Matrix4<f32>; 4],
+    /// View-space distance at which each cascade ends.
+    #[allow(dead_code)]
+    splits: Vector4<f32>,
+    /// World-space size of a shadow map texel, per cascade.
+    #[allow(dead_code)]
+    texel_sizes: Vector4<f32>,
+    #[allow(dead_code)]
+    cascade_count: u32,
+    /// Tints each cascade in a different color.
+    #[allow(dead_code)]
+    debug: u32,
+    #[allow(dead_code)]
+    filter_mode: u32,
+    #[allow(dead_code)]
+    normal_offset: f32,
+}
+
+/// Depth maps of the directional light, one array layer per cascade fitted to a slice of the view frustum.
+#[derive(Debug)]
+pub struct ShadowMap {
+    /// Width and height of each cascade.
+    size: u32,
+    pub view: TextureView,
+    /// Per-cascade render targets.
+    layer_views: Vec<TextureView>,
+    pub sampler: Sampler,
+    /// Nearest comparison sampler for hard shadows.
+    pub hard_sampler: Sampler,
+    pub uniform_buffer: Buffer,
+    pipeline: RenderPipeline,
+    pipeline_layout: PipelineLayout,
+    shader_module: ShaderModule,
+    cascade_buffer: Buffer,
+    cascade_bind_group: BindGroup,
+}
+
+impl ShadowMap {
+    pub fn new(
+        device: &Device,
+        object_bind_group_layout: &BindGroupLayout,
+        size: u32,
+        depth_bias: DepthBiasState,
+    ) -> Self {
+        let (view, layer_views) = create_cascade_views(device, size);
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            compare: Some(CompareFunction::LessEqual),
+            ..Default::default()
+        });
+        let hard_sampler = device.create_sampler(&SamplerDescriptor {
+            compare: Some(CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            size: std::mem::size_of::<ShadowUniforms>() as u64,
+            mapped_at_creation: false,
+        });
+        let cascade_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            size: CASCADE_COUNT as u64 * UNIFORM_STRIDE,
+            mapped_at_creation: false,
+        });
+
+        let (cascade_bind_group_layout, cascade_bind_group) =
+            create_dynamic_uniform_bind_group::<Matrix4<f32>>(device, &cascade_buffer);
+
+        let shader_module = create_shader_module(device);
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            bind_group_layouts: &[&cascade_bind_group_layout, object_bind_group_layout],
+            ..Default::default()
+        });
+        let pipeline =
+            create_cascade_pipeline(device, &pipeline_layout, &shader_module, depth_bias);
+
+        ShadowMap {
+            size,
+            view,
+            layer_views,
+            sampler,
+            hard_sampler,
+            uniform_buffer,
+            pipeline,
+            pipeline_layout,
+            shader_module,
+            cascade_buffer,
+            cascade_bind_group,
+        }
+    }
+
+    /// Rebuilds the pipeline with a new depth bias, which is fixed per pipeline.
+    pub fn set_depth_bias(&mut self, device: &Device, depth_bias: DepthBiasState) {
+        self.pipeline = create_cascade_pipeline(
+            device,
+            &self.pipeline_layout,
+            &self.shader_module,
+            depth_bias,
+        );
+    }
+
+    /// Reallocates the cascades at the given width and height.
+    pub fn set_size(&mut self, device: &Device, size: u32) {
+        (self.view, self.layer_views) = create_cascade_views(device, size);
+        self.size = size;
+    }
+
+    /// Fits the cascades to the view frustum and writes their matrices.
+    pub fn update(
+        &self,
+        queue: &Queue,
+        view: Matrix4<f32>,
+        projection: Matrix4<f32>,
+        near: f32,
+        light_direction: Vector3<f32>,
+        settings: ShadowSettings,
+    ) {
+        let mut uniforms = ShadowUniforms {
+            view,
+            cascades: [Matrix4::identity(); 4],
+            splits: Vector4::new(0.0, 0.0, 0.0, 0.0),
+            texel_sizes: Vector4::new(0.0, 0.0, 0.0, 0.0),
+            cascade_count: CASCADE_COUNT as u32,
+            debug: settings.cascade_debug as u32,
+            filter_mode: settings.filter as u32,
+            normal_offset: settings.normal_offset,
+        };
+
+        let mut cascade_data = vec![0; (CASCADE_COUNT as u64 * UNIFORM_STRIDE) as usize];
+        let mut start = near;
+        for cascade in 0..CASCADE_COUNT {
+            let end = split_distance(cascade + 1, near);
+            let (matrix, texel_size) =
+                fit_cascade(view, projection, start, end, light_direction, self.size);
+            uniforms.cascades[cascade] = matrix;
+            uniforms.splits[cascade] = end;
+            uniforms.texel_sizes[cascade] = texel_size;
+
+            let offset = cascade * UNIFORM_STRIDE as usize;
+            let bytes = as_byte_slice(std::slice::from_ref(&matrix));
+            cascade_data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            start = end;
+        }
+
+        queue.write_buffer(&self.uniform_buffer, 0, as_byte_slice(&[uniforms]));
+        queue.write_buffer(&self.cascade_buffer, 0, &cascade_data);
+    }
+
+    /// Begins a depth-only pass into the cascade, with the pipeline and light matrix bound.
+    /// Object uniforms are expected at bind group 1.
+    pub fn begin_pass<'a>(
+        &self,
+        encoder: &'a mut CommandEncoder,
+        cascade: usize,
+    ) -> RenderPass<'a> {
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.layer_views[cascade],
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(
+            0,
+            &self.cascade_bind_group,
+            &[(cascade as u64 * UNIFORM_STRIDE) as u32],
+        );
+        pass
+    }
+}
+
+/// A view of all cascades for sampling, and one of each for rendering.
+fn create_cascade_views(device: &Device, size: u32) -> (TextureView, Vec<TextureView>) {
+    let texture = device.create_texture(&TextureDescriptor {
+        label: None,
+        size: Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: CASCADE_COUNT as u32,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: SHADOW_FORMAT,
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+    let layer_views = (0..CASCADE_COUNT as u32)
+        .map(|layer| {
+            texture.create_view(&TextureViewDescriptor {
+                dimension: Some(TextureViewDimension::D2),
+                base_array_layer: layer,
+                array_layer_count: Some(1),
+                ..Default::default()
+            })
+        })
+        .collect();
+    (view, layer_views)
+}
+
+fn create_cascade_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader_module: &ShaderModule,
+    depth_bias: DepthBiasState,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: None,
+        cache: None,
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader_module,
+            entry_point: Some("vertex"),
+            buffers: &[Vertex::LAYOUT],
+            compilation_options: Default::default(),
+        },
+        fragment: None,
+        primitive: PrimitiveState {
+            cull_mode: None,
+            ..Default::default()
+        },
+        multisample: Default::default(),
+        depth_stencil: Some(DepthStencilState {
+            format: SHADOW_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: CompareFunction::LessEqual,
+            stencil: Default::default(),
+            bias: depth_bias,
+        }),
+        multiview: None,
+    })
+}
+
+fn create_shader_module(device: &Device) -> ShaderModule {
+    device.create_shader_module(ShaderModuleDescriptor {
+        label: None,
+        source: ShaderSource::Wgsl(include_str!("shadow.wgsl").into()),
+    })
+}
+
+/// A bind group exposing one `T` of the buffer at a time, selected by a dynamic offset.
+fn create_dynamic_uniform_bind_group<T>(
+    device: &Device,
+    buffer: &Buffer,
+) -> (BindGroupLayout, BindGroup) {
+    let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: None,
+        entries: &[BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::VERTEX_FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+    });
+    let bind_group = device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout: &layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: BindingResource::Buffer(BufferBinding {
+                buffer,
+                offset: 0,
+                size: BufferSize::new(std::mem::size_of::<T>() as u64),
+            }),
+        }],
+    });
+    (layout, bind_group)
+}
+
+#[derive(Debug, Copy, Clone)]
+struct PointShadowFace {
+    #[allow(dead_code)]
+    view_projection: Matrix4<f32>,
+    #[allow(dead_code)]
+    light_position: Vector3<f32>,
+    #[allow(dead_code)]
+    radius: f32,
+}
+
+/// Depth cubemaps of the shadow-casting point lights, storing distances normalized by the light radius.
+#[derive(Debug)]
+pub struct PointShadowMaps {
+    /// Cube array with one cube per shadowed light.
+    pub view: TextureView,
+    /// Per-face render targets, six per light.
+    face_views: Vec<TextureView>,
+    pipeline: RenderPipeline,
+    face_buffer: Buffer,
+    face_bind_group: BindGroup,
+}
+
+impl PointShadowMaps {
+    pub fn new(device: &Device, object_bind_group_layout: &BindGroupLayout) -> Self {
+        let layer_count = 6 * MAX_SHADOWED_POINT_LIGHTS as u32;
+        let texture = device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: POINT_SHADOW_MAP_SIZE,
+                height: POINT_SHADOW_MAP_SIZE,
+                depth_or_array_layers: layer_count,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: SHADOW_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::CubeArray),
+            ..Default::default()
+        });
+        let face_views = (0..layer_count)
+            .map(|layer| {
+                texture.create_view(&TextureViewDescriptor {
+                    dimension: Some(TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let face_buffer = device.create_buffer(&BufferDescriptor {
+            label: None,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            size: layer_count as u64 * UNIFORM_STRIDE,
+            mapped_at_creation: false,
+        });
+        let (face_bind_group_layout, face_bind_group) =
+            create_dynamic_uniform_bind_group::<PointShadowFace>(device, &face_buffer);
+
+        let shader_module = create_shader_module(device);
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            cache: None,
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                bind_group_layouts: &[&face_bind_group_layout, object_bind_group_layout],
+                ..Default::default()
+            })),
+            vertex: VertexState {
+                module: &shader_module,
+                entry_point: Some("point_vertex"),
+                buffers: &[Vertex::LAYOUT],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader_module,
+                entry_point: Some("point_fragment"),
+                targets: &[],
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState {
+                cull_mode: None,
+                ..Default::default()
+            },
+            multisample: Default::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: SHADOW_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multiview: None,
+        });
+
+        PointShadowMaps {
+            view,
+            face_views,
+            pipeline,
+            face_buffer,
+            face_bind_group,
+        }
+    }
+
+    /// Writes the face matrices of the shadowed lights, given by position and radius.
+    pub fn update(&self, queue: &Queue, lights: &[(Vector3<f32>, f32)]) {
+        // Cubemap faces in layer order, following the OpenGL convention of looking down with
+        // -y as up, which is flipped into the top-down texture layout by the projection.
+        let faces = [
+            (Vector3::unit_x(), -Vector3::unit_y()),
+            (-Vector3::unit_x(), -Vector3::unit_y()),
+            (Vector3::unit_y(), Vector3::unit_z()),
+            (-Vector3::unit_y(), -Vector3::unit_z()),
+            (Vector3::unit_z(), -Vector3::unit_y()),
+            (-Vector3::unit_z(), -Vector3::unit_y()),
+        ];
+
+        let mut data = vec![0; self.face_views.len() * UNIFORM_STRIDE as usize];
+        for (light, &(position, radius)) in lights.iter().enumerate() {
+            let projection = Matrix4::from_nonuniform_scale(1.0, -1.0, 1.0)
+                * perspective_90(POINT_SHADOW_NEAR, radius);
+            for (face, (forward, up)) in faces.into_iter().enumerate() {
+                let eye = Point3::from_vec(position);
+                let uniforms = PointShadowFace {
+                    view_projection: projection * Matrix4::look_at_rh(eye, eye + forward, up),
+                    light_position: position,
+                    radius,
+                };
+                let offset = (6 * light + face) * UNIFORM_STRIDE as usize;
+                let bytes = as_byte_slice(std::slice::from_ref(&uniforms));
+                data[offset..offset + bytes.len()].copy_from_slice(bytes);
+            }
+        }
+        queue.write_buffer(&self.face_buffer, 0, &data);
+    }
+
+    /// Begins a pass into one face of a light's cubemap, with the pipeline and face uniforms bound.
+    /// Object uniforms are expected at bind group 1.
+    pub fn begin_pass<'a>(
+        &self,
+        encoder: &'a mut CommandEncoder,
+        light: usize,
+        face: usize,
+    ) -> RenderPass<'a> {
+        let layer = 6 * light + face;
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.face_views[layer],
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(
+            0,
+            &self.face_bind_group,
+            &[(layer as u64 * UNIFORM_STRIDE) as u32],
+        );
+        pass
+    }
+}
+
+/// A perspective projection with a 90° field of view and square aspect, mapping depth onto [0, 1].
+fn perspective_90(near: f32, far: f32) -> Matrix4<f32> {
+    Matrix4::from_cols(
+        Vector4::new(1.0, 0.0, 0.0, 0.0),
+        Vector4::new(0.0, 1.0, 0.0, 0.0),
+        Vector4::new(0.0, 0.0, far / (near - far), -1.0),
+        Vector4::new(0.0, 0.0, near * far / (near - far), 0.0),
+    )
+}
+
+/// Outlines the view frustum, with its near and far planes in white and the ends of the cascades in
+/// their debug tints.
+pub fn draw_frustum(
+    debug_draw: &mut DebugDraw,
+    view: Matrix4<f32>,
+    projection: Matrix4<f32>,
+    near: f32,
+    far: f32,
+) {
+    let inverse_view = view.invert().unwrap();
+    let rays = corner_rays(projection);
+    let slice = |distance: f32| {
+        rays.map(|ray| (inverse_view * (ray * (distance / -ray.z)).extend(1.0)).truncate())
+    };
+    let white = Vector4::new(1.0, 1.0, 1.0, 1.0);
+    let tints = [
+        Vector4::new(1.0, 0.3, 0.3, 1.0),
+        Vector4::new(0.3, 1.0, 0.3, 1.0),
+        Vector4::new(0.3, 0.3, 1.0, 1.0),
+        Vector4::new(1.0, 1.0, 0.3, 1.0),
+    ];
+    let planes = std::iter::once((near, white))
+        .chain(
+            (1..=CASCADE_COUNT).map(|cascade| (split_distance(cascade, near), tints[cascade - 1])),
+        )
+        .chain(std::iter::once((far, white)));
+    for (distance, color) in planes {
+        let corners = slice(distance);
+        // The rays go around the frustum, so each corner joins the next.
+        for i in 0..4 {
+            debug_draw.line(corners[i], corners[(i + 1) % 4], color);
+        }
+    }
+    let (near_corners, far_corners) = (slice(near), slice(far));
+    for i in 0..4 {
+        debug_draw.line(near_corners[i], far_corners[i], white);
+    }
+}
+
+/// View-space points on the rays through the corners of the view, in order around it.
+fn corner_rays(projection: Matrix4<f32>) -> [Vector3<f32>; 4] {
+    let inverse_projection = projection.invert().unwrap();
+    [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(x, y)| {
+        Point3::from_homogeneous(inverse_projection * Vector4::new(x, y, 1.0, 1.0)).to_vec()
+    })
+}
+
+/// View distance at which the cascade `index - 1` ends, with the last split at the shadow distance.
+fn split_distance(index: usize, near: f32) -> f32 {
+    let t = index as f32 / CASCADE_COUNT as f32;
+    let uniform = near + (SHADOW_DISTANCE - near) * t;
+    let logarithmic = near * (SHADOW_DISTANCE / near).powf(t);
+    SPLIT_LAMBDA * logarithmic + (1.0 - SPLIT_LAMBDA) * uniform
+}
+
+/// Returns the light's view-projection covering the frustum slice between `near` and `far`,
+/// and the world-space size of one shadow map texel.
+fn fit_cascade(
+    view: Matrix4<f32>,
+    projection: Matrix4<f32>,
+    near: f32,
+    far: f32,
+    light_direction: Vector3<f32>,
+    size: u32,
+) -> (Matrix4<f32>, f32) {
+    let mut corners = Vec::with_capacity(8);
+    for ray in corner_rays(projection) {
+        for distance in [near, far] {
+            corners.push(Point3::from_vec(ray * (distance / -ray.z)));
+        }
+    }
+
+    // A bounding sphere keeps the cascade size constant as the camera rotates,
+    // which together with texel snapping avoids shimmering edges.
+    let center = Point3::centroid(&corners);
+    let radius = corners
+        .iter()
+        .map(|corner| (corner - center).magnitude())
+        .fold(0.0, f32::max);
+    let radius = (radius * 16.0).ceil() / 16.0;
+    let center = Point3::from_homogeneous(view.invert().unwrap() * center.to_homogeneous());
+
+    let direction = light_direction.normalize();
+    let up = if direction.y.abs() > 0.99 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let eye = center - direction * (radius + CASTER_DISTANCE);
+    let light_view = Matrix4::look_at_rh(eye, center, up);
+    let mut projection = orthographic(radius, 2.0 * radius + CASTER_DISTANCE);
+
+    let size = size as f32;
+    let origin = projection * light_view * Vector4::unit_w() * (0.5 * size);
+    let snap = (origin.x.round() - origin.x, origin.y.round() - origin.y);
+    projection.w.x += snap.0 * 2.0 / size;
+    projection.w.y += snap.1 * 2.0 / size;
+
+    (projection * light_view, 2.0 * radius / size)
+}
+
+/// A symmetric orthographic projection mapping depth onto [0, 1].
+fn orthographic(extent: f32, far: f32) -> Matrix4<f32> {
+    Matrix4::from_cols(
+        Vector4::new(1.0 / extent, 0.0, 0.0, 0.0),
+        Vector4::new(0.0, 1.0 / extent, 0.0, 0.0),
+        Vector4::new(0.0, 0.0, -1.0 / far, 0.0),
+        Vector4::new(0.0, 0.0, 0.0, 1.0),
+    )
+}
@@ -0,0 +1,40 @@
+struct Object {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> light_view_projection: mat4x4<f32>;
+@group(1) @binding(0) var<uniform> object: Object;
+
+@vertex
+fn vertex(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return light_view_projection * object.model * vec4<f32>(position, 1.0);
+}
+
+struct PointShadowFace {
+    view_projection: mat4x4<f32>,
+    light_position: vec3<f32>,
+    radius: f32,
+}
+
+@group(0) @binding(0) var<uniform> face: PointShadowFace;
+
+struct PointFragmentInput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+}
+
+@vertex
+fn point_vertex(@location(0) position: vec3<f32>) -> PointFragmentInput {
+    let world_position = object.model * vec4<f32>(position, 1.0);
+    var out: PointFragmentInput;
+    out.position = face.view_projection * world_position;
+    out.world_position = world_position.xyz;
+    return out;
+}
+
+/// Stores the distance to the light, normalized by its radius, so that all faces agree.
+@fragment
+fn point_fragment(in: PointFragmentInput) -> @builtin(frag_depth) f32 {
+    return length(in.world_position - face.light_position) / face.radius;
+}
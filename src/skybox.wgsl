@@ -0,0 +1,44 @@
+struct Uniforms {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    environment: mat4x4<f32>,
+    camera_position: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(1) @binding(0) var environment_texture: texture_cube<f32>;
+@group(1) @binding(1) var environment_sampler: sampler;
+
+struct FragmentInput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) clip: vec2<f32>,
+}
+
+/// Covers the screen with a single triangle placed on the far plane.
+@vertex
+fn vertex(@builtin(vertex_index) vertex_index: u32) -> FragmentInput {
+    let clip = vec2<f32>(f32(vertex_index & 1u) * 4.0 - 1.0, f32(vertex_index >> 1u) * 4.0 - 1.0);
+    var out: FragmentInput;
+    out.position = vec4<f32>(clip, 1.0, 1.0);
+    out.clip = clip;
+    return out;
+}
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    let direction = uniforms.environment * vec4<f32>(in.clip, 1.0, 1.0);
+    return textureSample(environment_texture, environment_sampler, direction.xyz / direction.w);
+}
+
+struct Gradient {
+    top: vec4<f32>,
+    bottom: vec4<f32>,
+}
+
+@group(2) @binding(0) var<uniform> gradient: Gradient;
+
+/// Blends from the bottom of the screen to the top.
+@fragment
+fn gradient_fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    return mix(gradient.bottom, gradient.top, 0.5 * in.clip.y + 0.5);
+}
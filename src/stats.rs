@@ -0,0 +1,93 @@
+use std::fmt;
+
+use wgpu::core::global::GlobalReport;
+
+/// Counts of the last frame, for logging or an overlay.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SceneStats {
+    pub objects: usize,
+    /// Of the objects on the view's layers.
+    pub triangles_submitted: u64,
+    /// Of those left after frustum culling.
+    pub triangles_drawn: u64,
+    /// The directional light included.
+    pub lights: usize,
+    /// Bytes of meshes, textures, per-object uniforms and the main render targets, roughly.
+    pub gpu_memory: u64,
+}
+
+impl fmt::Display for SceneStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} objects, {} of {} triangles drawn, {} lights, {:.1} MiB GPU memory",
+            self.objects,
+            self.triangles_drawn,
+            self.triangles_submitted,
+            self.lights,
+            self.gpu_memory as f64 / (1024.0 * 1024.0)
+        )
+    }
+}
+
+/// Live GPU resources as tracked by wgpu, for catching leaks such as resources created every
+/// frame and never freed.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct GpuStats {
+    pub buffers: usize,
+    pub textures: usize,
+    pub texture_views: usize,
+    pub samplers: usize,
+    pub bind_groups: usize,
+    pub pipelines: usize,
+    pub shader_modules: usize,
+    /// Bytes of the allocations and of the memory blocks holding them, known where the backend
+    /// sub-allocates.
+    pub allocated: Option<u64>,
+    pub reserved: Option<u64>,
+}
+
+impl GpuStats {
+    /// Counts the resources of the report, which is missing on WebGPU.
+    pub fn new(report: Option<GlobalReport>) -> Self {
+        let mut stats = GpuStats::default();
+        if let Some(report) = report {
+            let hub = report.hub_report();
+            stats.buffers = hub.buffers.num_allocated;
+            stats.textures = hub.textures.num_allocated;
+            stats.texture_views = hub.texture_views.num_allocated;
+            stats.samplers = hub.samplers.num_allocated;
+            stats.bind_groups = hub.bind_groups.num_allocated;
+            stats.pipelines =
+                hub.render_pipelines.num_allocated + hub.compute_pipelines.num_allocated;
+            stats.shader_modules = hub.shader_modules.num_allocated;
+        }
+        stats
+    }
+}
+
+impl fmt::Display for GpuStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} buffers, {} textures, {} texture views, {} samplers, {} bind groups, {} pipelines, \
+             {} shader modules",
+            self.buffers,
+            self.textures,
+            self.texture_views,
+            self.samplers,
+            self.bind_groups,
+            self.pipelines,
+            self.shader_modules
+        )?;
+        if let (Some(allocated), Some(reserved)) = (self.allocated, self.reserved) {
+            write!(
+                f,
+                ", {:.1} of {:.1} MiB allocated",
+                allocated as f64 / (1024.0 * 1024.0),
+                reserved as f64 / (1024.0 * 1024.0)
+            )?;
+        }
+        Ok(())
+    }
+}
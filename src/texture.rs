@@ -0,0 +1,381 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use image::{imageops, RgbaImage};
+use util::{DeviceExt, TextureDataOrder};
+use wgpu::*;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TextureId(pub usize);
+
+/// An 8-bit RGBA image followed by its downsampled levels, as uploaded to a texture.
+#[derive(Debug, Clone)]
+pub struct MipChain<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub level_count: u32,
+    pub srgb: bool,
+    pub data: Cow<'a, [u8]>,
+}
+
+impl MipChain<'_> {
+    /// Downsamples the image on the CPU down to a single texel.
+    pub fn new(image: &RgbaImage, srgb: bool) -> MipChain<'static> {
+        let (width, height) = image.dimensions();
+        let level_count = width.max(height).max(1).ilog2() + 1;
+
+        let mut data = image.as_raw().clone();
+        let mut mip = image.clone();
+        for level in 1..level_count {
+            mip = imageops::resize(
+                &mip,
+                (width >> level).max(1),
+                (height >> level).max(1),
+                imageops::FilterType::Triangle,
+            );
+            data.extend_from_slice(mip.as_raw());
+        }
+        MipChain {
+            width,
+            height,
+            level_count,
+            srgb,
+            data: Cow::Owned(data),
+        }
+    }
+
+    /// Whether the data holds exactly the given number of levels, which at most reach down to a
+    /// single texel.
+    pub fn is_valid(&self) -> bool {
+        let (width, height) = (self.width, self.height);
+        let size: u64 = (0..self.level_count)
+            .map(|level| 4 * ((width >> level).max(1) as u64) * ((height >> level).max(1) as u64))
+            .sum();
+        width > 0
+            && height > 0
+            && self.level_count >= 1
+            && self.level_count <= width.max(height).ilog2() + 1
+            && size == self.data.len() as u64
+    }
+
+    fn format(&self) -> TextureFormat {
+        if self.srgb {
+            TextureFormat::Rgba8UnormSrgb
+        } else {
+            TextureFormat::Rgba8Unorm
+        }
+    }
+
+    /// Identifies the image by its first level.
+    fn key(&self) -> ContentKey {
+        content_key(
+            &self.data[..(4 * self.width * self.height) as usize],
+            self.width,
+            self.height,
+            self.srgb,
+        )
+    }
+}
+
+/// Hash of the pixels, the dimensions and whether the texture is sRGB.
+type ContentKey = (u64, u32, u32, bool);
+
+fn content_key(pixels: &[u8], width: u32, height: u32, srgb: bool) -> ContentKey {
+    let mut hasher = DefaultHasher::new();
+    pixels.hash(&mut hasher);
+    (hasher.finish(), width, height, srgb)
+}
+
+#[derive(Debug)]
+struct CachedTexture {
+    /// The texture and its view, or none while unloaded.
+    uploaded: Option<(Texture, TextureView)>,
+    /// The levels as read back when the texture was first unloaded, to upload it again.
+    levels: Option<MipChain<'static>>,
+    key: ContentKey,
+    references: usize,
+}
+
+/// Uploads each distinct image once, so that models referencing the same image, or a model
+/// reloaded with unchanged images, share one texture. Textures are evicted once every reference
+/// has been released. Bind groups created from an evicted texture keep it alive until they are
+/// dropped themselves.
+///
+/// Textures which are still referenced can be unloaded from the GPU to save memory, keeping their
+/// levels on the CPU until they are uploaded again.
+#[derive(Debug, Default)]
+pub struct TextureCache {
+    /// Evicted slots stay empty, so that ids are never reused.
+    textures: Vec<Option<CachedTexture>>,
+    by_content: HashMap<ContentKey, TextureId>,
+}
+
+impl TextureCache {
+    /// Returns the texture holding the same image, or uploads it. Either way, the caller holds a
+    /// reference until it calls `release`.
+    pub fn add(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        image: &RgbaImage,
+        srgb: bool,
+    ) -> TextureId {
+        let key = content_key(image.as_raw(), image.width(), image.height(), srgb);
+        match self.reference(key) {
+            Some(id) => {
+                self.upload(device, queue, id);
+                id
+            }
+            None => self.insert(device, queue, key, &MipChain::new(image, srgb)),
+        }
+    }
+
+    /// Like `add`, for images which come with their mip chain.
+    pub fn add_mipped(&mut self, device: &Device, queue: &Queue, mips: &MipChain) -> TextureId {
+        let key = mips.key();
+        match self.reference(key) {
+            Some(id) => {
+                self.upload(device, queue, id);
+                id
+            }
+            None => self.insert(device, queue, key, mips),
+        }
+    }
+
+    fn reference(&mut self, key: ContentKey) -> Option<TextureId> {
+        let id = *self.by_content.get(&key)?;
+        self.textures[id.0].as_mut()?.references += 1;
+        Some(id)
+    }
+
+    fn insert(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        key: ContentKey,
+        mips: &MipChain,
+    ) -> TextureId {
+        let texture = create_mipped_texture(device, queue, mips);
+        let view = texture.create_view(&Default::default());
+        self.textures.push(Some(CachedTexture {
+            uploaded: Some((texture, view)),
+            levels: None,
+            key,
+            references: 1,
+        }));
+        let id = TextureId(self.textures.len() - 1);
+        self.by_content.insert(key, id);
+        id
+    }
+
+    /// Panics if the texture has been evicted or unloaded.
+    pub fn view(&self, id: TextureId) -> &TextureView {
+        &self
+            .cached(id)
+            .uploaded
+            .as_ref()
+            .expect("Texture has been unloaded")
+            .1
+    }
+
+    fn is_uploaded(&self, id: TextureId) -> bool {
+        self.textures[id.0]
+            .as_ref()
+            .is_some_and(|texture| texture.uploaded.is_some())
+    }
+
+    /// Uploads the texture again if it has been unloaded.
+    pub fn upload(&mut self, device: &Device, queue: &Queue, id: TextureId) {
+        let Some(cached) = &mut self.textures[id.0] else {
+            return;
+        };
+        if let (None, Some(levels)) = (&cached.uploaded, &cached.levels) {
+            let texture = create_mipped_texture(device, queue, levels);
+            let view = texture.create_view(&Default::default());
+            cached.uploaded = Some((texture, view));
+        }
+    }
+
+    /// Drops the texture from the GPU, reading its levels back the first time. Bind groups
+    /// created from it keep it alive until they are dropped themselves.
+    pub fn unload(&mut self, device: &Device, queue: &Queue, id: TextureId) {
+        if !self.is_uploaded(id) {
+            return;
+        }
+        if self.cached(id).levels.is_none() {
+            let levels = self.read_back(device, queue, id);
+            self.textures[id.0].as_mut().unwrap().levels = Some(levels);
+        }
+        self.textures[id.0].as_mut().unwrap().uploaded = None;
+    }
+
+    /// Ids of the textures held on the GPU.
+    pub fn uploaded(&self) -> impl Iterator<Item = TextureId> + '_ {
+        self.textures
+            .iter()
+            .enumerate()
+            .filter(|(_, texture)| {
+                texture
+                    .as_ref()
+                    .is_some_and(|texture| texture.uploaded.is_some())
+            })
+            .map(|(index, _)| TextureId(index))
+    }
+
+    fn cached(&self, id: TextureId) -> &CachedTexture {
+        self.textures[id.0]
+            .as_ref()
+            .expect("Texture has been evicted")
+    }
+
+    /// Drops a reference, evicting the texture with the last one.
+    pub fn release(&mut self, id: TextureId) {
+        let Some(texture) = &mut self.textures[id.0] else {
+            return;
+        };
+        texture.references -= 1;
+        if texture.references == 0 {
+            self.by_content.remove(&texture.key);
+            self.textures[id.0] = None;
+        }
+    }
+
+    /// Bytes taken by the textures on the GPU.
+    pub fn memory(&self) -> u64 {
+        self.textures
+            .iter()
+            .flatten()
+            .filter_map(|cached| cached.uploaded.as_ref())
+            .map(|(texture, _)| texture_memory(texture))
+            .sum()
+    }
+
+    /// Copies the texture with all its levels back from the GPU, blocking until it arrives. The
+    /// levels of an unloaded texture are at hand already.
+    pub fn read_back(&self, device: &Device, queue: &Queue, id: TextureId) -> MipChain<'static> {
+        let cached = self.cached(id);
+        let Some((texture, _)) = &cached.uploaded else {
+            return cached
+                .levels
+                .clone()
+                .expect("Unloaded textures keep their levels");
+        };
+        let (width, height) = (texture.width(), texture.height());
+        let level_count = texture.mip_level_count();
+        let mut encoder = device.create_command_encoder(&Default::default());
+        let levels: Vec<_> = (0..level_count)
+            .map(|level| {
+                let size = Extent3d {
+                    width: (width >> level).max(1),
+                    height: (height >> level).max(1),
+                    depth_or_array_layers: 1,
+                };
+                let bytes_per_row = (4 * size.width).next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+                let buffer = device.create_buffer(&BufferDescriptor {
+                    label: None,
+                    size: (bytes_per_row * size.height) as u64,
+                    usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                });
+                encoder.copy_texture_to_buffer(
+                    TexelCopyTextureInfo {
+                        texture,
+                        mip_level: level,
+                        origin: Origin3d::ZERO,
+                        aspect: TextureAspect::All,
+                    },
+                    TexelCopyBufferInfo {
+                        buffer: &buffer,
+                        layout: TexelCopyBufferLayout {
+                            offset: 0,
+                            bytes_per_row: Some(bytes_per_row),
+                            rows_per_image: None,
+                        },
+                    },
+                    size,
+                );
+                (buffer, size, bytes_per_row)
+            })
+            .collect();
+        queue.submit([encoder.finish()]);
+        for (buffer, _, _) in &levels {
+            buffer.slice(..).map_async(MapMode::Read, |_| ());
+        }
+        device.poll(Maintain::Wait);
+
+        let mut data = Vec::new();
+        for (buffer, size, bytes_per_row) in &levels {
+            let mapped = buffer.slice(..).get_mapped_range();
+            // Rows are padded to the copy alignment.
+            for row in mapped.chunks_exact(*bytes_per_row as usize) {
+                data.extend_from_slice(&row[..(4 * size.width) as usize]);
+            }
+        }
+        MipChain {
+            width,
+            height,
+            level_count,
+            srgb: texture.format() == TextureFormat::Rgba8UnormSrgb,
+            data: Cow::Owned(data),
+        }
+    }
+}
+
+/// Bytes taken by all levels and layers of a texture, ignoring padding and compression.
+pub fn texture_memory(texture: &Texture) -> u64 {
+    let texel = texture.format().target_pixel_byte_cost().unwrap_or(4) as u64;
+    let samples = texture.sample_count() as u64;
+    (0..texture.mip_level_count())
+        .map(|level| {
+            let size = texture.size().mip_level_size(level, texture.dimension());
+            size.width as u64 * size.height as u64 * size.depth_or_array_layers as u64
+        })
+        .sum::<u64>()
+        * texel
+        * samples
+}
+
+/// Uploads an 8-bit image with a full mip chain, downsampled on the CPU.
+pub fn create_texture(device: &Device, queue: &Queue, image: &RgbaImage, srgb: bool) -> Texture {
+    create_mipped_texture(device, queue, &MipChain::new(image, srgb))
+}
+
+fn create_mipped_texture(device: &Device, queue: &Queue, mips: &MipChain) -> Texture {
+    device.create_texture_with_data(
+        queue,
+        &TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: mips.width,
+                height: mips.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: mips.level_count,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: mips.format(),
+            // Read back when bundling.
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        },
+        TextureDataOrder::LayerMajor,
+        &mips.data,
+    )
+}
+
+/// A two-tone checkerboard, handy as a stand-in texture.
+pub fn checker(size: u32, cells: u32, a: [u8; 4], b: [u8; 4]) -> RgbaImage {
+    let cell = (size / cells).max(1);
+    RgbaImage::from_fn(size, size, |x, y| {
+        if (x / cell + y / cell).is_multiple_of(2) {
+            image::Rgba(a)
+        } else {
+            image::Rgba(b)
+        }
+    })
+}
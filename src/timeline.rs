@@ -0,0 +1,246 @@
+use cgmath::{InnerSpace, Quaternion, Vector3, VectorSpace};
+
+use crate::world::{Entity, Light, Transform, World};
+
+/// How a value moves from one keyframe towards the next.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Easing {
+    /// Holds the value until the next keyframe.
+    Step,
+    Linear,
+    EaseIn,
+    EaseOut,
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "step" => Some(Easing::Step),
+            "linear" => Some(Easing::Linear),
+            "ease_in" => Some(Easing::EaseIn),
+            "ease_out" => Some(Easing::EaseOut),
+            "ease_in_out" => Some(Easing::EaseInOut),
+            _ => None,
+        }
+    }
+
+    /// Maps the position between two keyframes in [0, 1] onto the curve.
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Step => 0.0,
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// A keyframed property of an entity.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Property {
+    Position,
+    Rotation,
+    Scale,
+    /// Of a light entity.
+    LightIntensity,
+}
+
+impl Property {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "position" => Some(Property::Position),
+            "rotation" => Some(Property::Rotation),
+            "scale" => Some(Property::Scale),
+            "light_intensity" => Some(Property::LightIntensity),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Value {
+    Vector(Vector3<f32>),
+    Rotation(Quaternion<f32>),
+    Scalar(f32),
+}
+
+impl Value {
+    /// Spherical for rotations. Values of different kinds do not blend.
+    fn lerp(self, other: Value, t: f32) -> Value {
+        match (self, other) {
+            (Value::Vector(a), Value::Vector(b)) => Value::Vector(a.lerp(b, t)),
+            (Value::Rotation(a), Value::Rotation(b)) => Value::Rotation(a.slerp(b, t).normalize()),
+            (Value::Scalar(a), Value::Scalar(b)) => Value::Scalar(a + (b - a) * t),
+            _ => self,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Key {
+    /// In seconds.
+    pub time: f32,
+    pub value: Value,
+    /// Towards the next keyframe.
+    pub easing: Easing,
+}
+
+/// Keyframes of one property of an entity.
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub entity: Entity,
+    pub property: Property,
+    /// Ascending by time.
+    pub keys: Vec<Key>,
+}
+
+impl Track {
+    /// The value at a point in time, holding the first and last keyframes' beyond the ends.
+    fn sample(&self, time: f32) -> Option<Value> {
+        let next = self.keys.partition_point(|key| key.time <= time);
+        if next == 0 {
+            return Some(self.keys.first()?.value);
+        }
+        let previous = &self.keys[next - 1];
+        let Some(next) = self.keys.get(next) else {
+            return Some(previous.value);
+        };
+        let t = (time - previous.time) / (next.time - previous.time);
+        Some(previous.value.lerp(next.value, previous.easing.apply(t)))
+    }
+}
+
+/// Keyframed properties of entities, played back over time. Unlike the imported animations,
+/// the timeline is authored in the viewer or by scripts.
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    pub tracks: Vec<Track>,
+    /// Seconds since the start.
+    pub time: f32,
+    pub playing: bool,
+}
+
+impl Timeline {
+    /// End of the last keyframe, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.tracks
+            .iter()
+            .filter_map(|track| track.keys.last())
+            .map(|key| key.time)
+            .fold(0.0, f32::max)
+    }
+
+    /// Sets a keyframe, replacing one at the same time.
+    pub fn key(&mut self, entity: Entity, property: Property, key: Key) {
+        let track = match self
+            .tracks
+            .iter_mut()
+            .position(|track| track.entity == entity && track.property == property)
+        {
+            Some(index) => &mut self.tracks[index],
+            None => {
+                self.tracks.push(Track {
+                    entity,
+                    property,
+                    keys: Vec::new(),
+                });
+                self.tracks.last_mut().unwrap()
+            }
+        };
+        let index = track.keys.partition_point(|other| other.time < key.time);
+        match track.keys.get_mut(index) {
+            Some(other) if other.time == key.time => *other = key,
+            _ => track.keys.insert(index, key),
+        }
+    }
+
+    /// Keys the entity's position, rotation and scale as they are now.
+    pub fn key_transform(&mut self, world: &World, entity: Entity, time: f32, easing: Easing) {
+        let Some(transform) = world.transforms.get(entity) else {
+            return;
+        };
+        let (position, rotation, scale) = transform.decompose();
+        for (property, value) in [
+            (Property::Position, Value::Vector(position)),
+            (Property::Rotation, Value::Rotation(rotation)),
+            (Property::Scale, Value::Vector(scale)),
+        ] {
+            let key = Key {
+                time,
+                value,
+                easing,
+            };
+            self.key(entity, property, key);
+        }
+    }
+
+    /// Removes the keyframe at the time, and the track once it has none left.
+    pub fn remove_key(&mut self, entity: Entity, property: Property, time: f32) {
+        for track in &mut self.tracks {
+            if track.entity == entity && track.property == property {
+                track.keys.retain(|key| key.time != time);
+            }
+        }
+        self.tracks.retain(|track| !track.keys.is_empty());
+    }
+
+    /// Removes the entity's tracks.
+    pub fn clear(&mut self, entity: Entity) {
+        self.tracks.retain(|track| track.entity != entity);
+    }
+
+    /// Moves the time by the given number of seconds, possibly past the end to add keyframes
+    /// there.
+    pub fn scrub(&mut self, seconds: f32) {
+        self.time = (self.time + seconds).max(0.0);
+    }
+
+    /// Moves the time while playing, wrapping around the end.
+    pub fn advance(&mut self, dt: f32) {
+        if !self.playing {
+            return;
+        }
+        let duration = self.duration();
+        self.time = if duration > 0.0 {
+            (self.time + dt).rem_euclid(duration)
+        } else {
+            0.0
+        };
+    }
+
+    /// Sets the keyframed properties to their values at the current time. Despawned entities
+    /// are skipped.
+    pub fn apply(&self, world: &mut World) {
+        for track in &self.tracks {
+            let Some(value) = track.sample(self.time) else {
+                continue;
+            };
+            match (track.property, value) {
+                (Property::LightIntensity, Value::Scalar(intensity)) => {
+                    match world.lights.get_mut(track.entity) {
+                        Some(Light::Point(light)) => light.intensity = intensity,
+                        Some(Light::Spot(light)) => light.intensity = intensity,
+                        Some(Light::Rect(light)) => light.intensity = intensity,
+                        None => {}
+                    }
+                }
+                (property, value) => {
+                    let Some(transform) = world.transforms.get_mut(track.entity) else {
+                        continue;
+                    };
+                    let (mut position, mut rotation, mut scale) = transform.decompose();
+                    match (property, value) {
+                        (Property::Position, Value::Vector(value)) => position = value,
+                        (Property::Rotation, Value::Rotation(value)) => rotation = value,
+                        (Property::Scale, Value::Vector(value)) => scale = value,
+                        _ => continue,
+                    }
+                    *transform = Transform::compose(position, rotation, scale);
+                }
+            }
+        }
+    }
+}
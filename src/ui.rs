@@ -0,0 +1,628 @@
+use cgmath::{Matrix4, Vector3};
+use egui_wgpu::ScreenDescriptor;
+use tracing::Level;
+use wgpu::*;
+use winit::{event::WindowEvent, window::Window};
+
+use crate::{
+    console, debug_draw,
+    environment::{Background, BackgroundMode},
+    frame_limiter::FrameLimiter,
+    input,
+    post::Tonemapper,
+    profiler::{GpuTimings, Pass, FRAMES},
+    render::{AntiAliasing, RenderMode, RenderSettings},
+    shadow::{ShadowFilter, ShadowQuality},
+    stats::GpuStats,
+};
+
+/// The UI laid out for a frame, ready to be drawn.
+#[derive(Default)]
+pub struct UiFrame {
+    pub primitives: Vec<egui::ClippedPrimitive>,
+    pub textures: egui::TexturesDelta,
+    pub pixels_per_point: f32,
+}
+
+/// The egui UI, hosting the runtime controls, the help and the log console. It sees the window's
+/// events before the viewer and is laid out once per frame, to be drawn over the final image by
+/// the renderer.
+pub struct Ui {
+    context: egui::Context,
+    state: egui_winit::State,
+    /// Whether the debug window is shown.
+    pub debug: bool,
+    /// Whether the help window is shown.
+    pub help: bool,
+    /// Whether the log console is shown.
+    pub console: bool,
+    /// Most detailed level shown in the console.
+    console_level: Level,
+    /// Whether egui asked to be laid out again right away, such as while animating.
+    repaint: bool,
+}
+
+impl std::fmt::Debug for Ui {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ui")
+            .field("debug", &self.debug)
+            .field("help", &self.help)
+            .field("console", &self.console)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Ui {
+    pub fn new(window: &Window) -> Self {
+        let context = egui::Context::default();
+        let state = egui_winit::State::new(
+            context.clone(),
+            context.viewport_id(),
+            window,
+            Some(window.scale_factor() as f32),
+            window.theme(),
+            None,
+        );
+        Ui {
+            context,
+            state,
+            debug: false,
+            help: false,
+            console: false,
+            console_level: Level::INFO,
+            repaint: false,
+        }
+    }
+
+    /// Passes the event on to egui, returning whether egui takes it, such as a click into one of
+    /// its windows. Nothing is taken while no window is shown.
+    pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        let response = self.state.on_window_event(window, event);
+        match event {
+            // Tab always counts as taken, for moving the focus, but only text fields need keys.
+            WindowEvent::KeyboardInput { .. } => self.context.wants_keyboard_input(),
+            _ => response.consumed,
+        }
+    }
+
+    /// Lays out the windows added by `build`, the help and console if shown, and the debug labels
+    /// placed by the view-projection.
+    pub fn run(
+        &mut self,
+        window: &Window,
+        view_projection: Matrix4<f32>,
+        mut build: impl FnMut(&egui::Context),
+    ) -> UiFrame {
+        let input = self.state.take_egui_input(window);
+        let labels = debug_draw::frame().take_labels();
+        let (mut help, mut console) = (self.help, self.console);
+        let mut console_level = self.console_level;
+        let output = self.context.run(input, |context| {
+            show_labels(context, view_projection, &labels);
+            build(context);
+            show_help(context, &mut help);
+            show_console(context, &mut console, &mut console_level);
+        });
+        self.help = help;
+        self.console = console;
+        self.console_level = console_level;
+        self.repaint = output
+            .viewport_output
+            .get(&self.context.viewport_id())
+            .is_some_and(|viewport| viewport.repaint_delay.is_zero());
+        self.state
+            .handle_platform_output(window, output.platform_output);
+        UiFrame {
+            primitives: self
+                .context
+                .tessellate(output.shapes, output.pixels_per_point),
+            textures: output.textures_delta,
+            pixels_per_point: output.pixels_per_point,
+        }
+    }
+
+    pub fn wants_repaint(&self) -> bool {
+        self.repaint
+    }
+}
+
+/// Paints the labels behind the windows, skipping those behind the camera.
+fn show_labels(
+    context: &egui::Context,
+    view_projection: Matrix4<f32>,
+    labels: &[debug_draw::Label],
+) {
+    let painter = context.layer_painter(egui::LayerId::background());
+    let screen = context.screen_rect();
+    for label in labels {
+        let clip = view_projection * label.position.extend(1.0);
+        if clip.w <= 0.0 {
+            continue;
+        }
+        let position = screen.min
+            + egui::vec2(
+                (0.5 + 0.5 * clip.x / clip.w) * screen.width(),
+                (0.5 - 0.5 * clip.y / clip.w) * screen.height(),
+            );
+        let [r, g, b, a]: [f32; 4] = label.color.into();
+        painter.text(
+            position,
+            egui::Align2::CENTER_CENTER,
+            &label.text,
+            egui::FontId::monospace(12.0),
+            egui::Rgba::from_rgba_unmultiplied(r, g, b, a).into(),
+        );
+    }
+}
+
+/// Width and height of the navigation gizmo.
+const NAVIGATION_SIZE: f32 = 96.0;
+/// Radius of the circles at the ends of its axes.
+const NAVIGATION_BUBBLE: f32 = 9.0;
+
+/// An end of an axis of the navigation gizmo.
+struct AxisEnd {
+    direction: Vector3<f32>,
+    name: &'static str,
+    color: egui::Color32,
+    position: egui::Pos2,
+    /// Towards the viewer, for drawing back to front.
+    depth: f32,
+}
+
+/// The world axes in the top right corner, turned with the view. Returns the direction of the
+/// axis end clicked, to view the scene from.
+pub fn show_navigation(context: &egui::Context, view: Matrix4<f32>) -> Option<Vector3<f32>> {
+    let mut clicked = None;
+    egui::Area::new(egui::Id::new("navigation"))
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 8.0))
+        .show(context, |ui| {
+            let (rect, response) =
+                ui.allocate_exact_size(egui::Vec2::splat(NAVIGATION_SIZE), egui::Sense::click());
+            let center = rect.center();
+            let length = 0.5 * NAVIGATION_SIZE - NAVIGATION_BUBBLE;
+            let mut ends: Vec<_> = [
+                (Vector3::unit_x(), "X", egui::Color32::from_rgb(220, 70, 70)),
+                (
+                    Vector3::unit_y(),
+                    "Y",
+                    egui::Color32::from_rgb(110, 190, 60),
+                ),
+                (
+                    Vector3::unit_z(),
+                    "Z",
+                    egui::Color32::from_rgb(70, 120, 220),
+                ),
+            ]
+            .into_iter()
+            .flat_map(|(axis, name, color)| [(axis, name, color), (-axis, "", color)])
+            .map(|(direction, name, color)| {
+                let turned = view * direction.extend(0.0);
+                AxisEnd {
+                    direction,
+                    name,
+                    color,
+                    position: center + length * egui::vec2(turned.x, -turned.y),
+                    depth: turned.z,
+                }
+            })
+            .collect();
+            ends.sort_by(|a, b| a.depth.total_cmp(&b.depth));
+
+            let painter = ui.painter();
+            if response.hovered() {
+                painter.circle_filled(
+                    center,
+                    0.5 * NAVIGATION_SIZE,
+                    egui::Color32::from_white_alpha(20),
+                );
+            }
+            for end in &ends {
+                if end.name.is_empty() {
+                    painter.circle(
+                        end.position,
+                        NAVIGATION_BUBBLE,
+                        end.color.gamma_multiply(0.3),
+                        egui::Stroke::new(1.0, end.color),
+                    );
+                } else {
+                    painter.line_segment([center, end.position], egui::Stroke::new(2.0, end.color));
+                    painter.circle_filled(end.position, NAVIGATION_BUBBLE, end.color);
+                    painter.text(
+                        end.position,
+                        egui::Align2::CENTER_CENTER,
+                        end.name,
+                        egui::FontId::proportional(11.0),
+                        egui::Color32::BLACK,
+                    );
+                }
+            }
+
+            if let Some(pointer) = response
+                .interact_pointer_pos()
+                .filter(|_| response.clicked())
+            {
+                // The front-most end under the pointer.
+                clicked = ends
+                    .iter()
+                    .rev()
+                    .find(|end| end.position.distance(pointer) <= NAVIGATION_BUBBLE)
+                    .map(|end| end.direction);
+            }
+        });
+    clicked
+}
+
+/// Lists the key bindings and what the mouse does, closed by its button or the key toggling it.
+fn show_help(context: &egui::Context, open: &mut bool) {
+    egui::Window::new("Help")
+        .open(open)
+        .default_width(360.0)
+        .show(context, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("bindings").striped(true).show(ui, |ui| {
+                    for binding in input::KEY_BINDINGS {
+                        ui.monospace(binding.shortcut());
+                        ui.label(binding.description);
+                        ui.end_row();
+                    }
+                    for (input, description) in input::MOUSE_BINDINGS {
+                        ui.monospace(*input);
+                        ui.label(*description);
+                        ui.end_row();
+                    }
+                });
+            });
+        });
+}
+
+/// The recent log lines up to the chosen level, following the newest.
+fn show_console(context: &egui::Context, open: &mut bool, level: &mut Level) {
+    egui::Window::new("Console")
+        .open(open)
+        .default_width(480.0)
+        .default_height(240.0)
+        .show(context, |ui| {
+            ui.horizontal(|ui| {
+                for choice in [
+                    Level::ERROR,
+                    Level::WARN,
+                    Level::INFO,
+                    Level::DEBUG,
+                    Level::TRACE,
+                ] {
+                    ui.selectable_value(level, choice, choice.as_str());
+                }
+            });
+            ui.separator();
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .auto_shrink(false)
+                .show(ui, |ui| {
+                    for line in console::lines(*level) {
+                        let color = match line.level {
+                            Level::ERROR => egui::Color32::LIGHT_RED,
+                            Level::WARN => egui::Color32::YELLOW,
+                            Level::INFO => ui.visuals().text_color(),
+                            _ => egui::Color32::GRAY,
+                        };
+                        ui.monospace(egui::RichText::new(line.text).color(color));
+                    }
+                });
+        });
+}
+
+/// The live GPU resources, collapsed by default.
+pub fn show_gpu_stats(ui: &mut egui::Ui, stats: &GpuStats) {
+    egui::CollapsingHeader::new("GPU resources").show(ui, |ui| {
+        egui::Grid::new("gpu_stats").striped(true).show(ui, |ui| {
+            for (name, count) in [
+                ("Buffers", stats.buffers),
+                ("Textures", stats.textures),
+                ("Texture views", stats.texture_views),
+                ("Samplers", stats.samplers),
+                ("Bind groups", stats.bind_groups),
+                ("Pipelines", stats.pipelines),
+                ("Shader modules", stats.shader_modules),
+            ] {
+                ui.label(name);
+                ui.monospace(count.to_string());
+                ui.end_row();
+            }
+            let mebibytes = |bytes: Option<u64>| {
+                bytes.map_or("unknown".to_string(), |bytes| {
+                    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+                })
+            };
+            ui.label("Allocated");
+            ui.monospace(mebibytes(stats.allocated));
+            ui.end_row();
+            ui.label("Reserved");
+            ui.monospace(mebibytes(stats.reserved));
+            ui.end_row();
+        });
+    });
+}
+
+/// The renderer's options, applied once the UI has run.
+pub fn show_render_settings(ui: &mut egui::Ui, settings: &mut RenderSettings) {
+    egui::CollapsingHeader::new("Render settings").show(ui, |ui| {
+        egui::Grid::new("render_settings").show(ui, |ui| {
+            choose(
+                ui,
+                "Anti-aliasing",
+                &mut settings.anti_aliasing,
+                &[
+                    AntiAliasing::Off,
+                    AntiAliasing::Msaa,
+                    AntiAliasing::Fxaa,
+                    AntiAliasing::Taa,
+                ],
+            );
+            ui.label("Vsync");
+            ui.checkbox(&mut settings.vsync, "");
+            ui.end_row();
+            ui.label("Render scale");
+            ui.add(egui::Slider::new(&mut settings.render_scale, 0.25..=1.0));
+            ui.end_row();
+            ui.label("Dynamic resolution");
+            ui.checkbox(&mut settings.dynamic_resolution, "");
+            ui.end_row();
+            choose(
+                ui,
+                "Shadow quality",
+                &mut settings.shadow_quality,
+                &[
+                    ShadowQuality::Low,
+                    ShadowQuality::Medium,
+                    ShadowQuality::High,
+                ],
+            );
+            choose(
+                ui,
+                "Shadow filter",
+                &mut settings.shadow_filter,
+                &[
+                    ShadowFilter::Hard,
+                    ShadowFilter::Pcf3x3,
+                    ShadowFilter::Pcf5x5,
+                    ShadowFilter::Poisson,
+                ],
+            );
+            choose(
+                ui,
+                "Tone mapping",
+                &mut settings.tonemapper,
+                &[
+                    Tonemapper::Aces,
+                    Tonemapper::Reinhard,
+                    Tonemapper::Neutral,
+                    Tonemapper::Clamp,
+                ],
+            );
+            choose(
+                ui,
+                "Render mode",
+                &mut settings.render_mode,
+                &[
+                    RenderMode::Shaded,
+                    RenderMode::Albedo,
+                    RenderMode::Normals,
+                    RenderMode::Depth,
+                    RenderMode::Uv,
+                    RenderMode::Wireframe,
+                    RenderMode::Overdraw,
+                ],
+            );
+            ui.label("Deferred");
+            ui.checkbox(&mut settings.deferred, "");
+            ui.end_row();
+            ui.label("Indirect draws");
+            ui.checkbox(&mut settings.indirect, "");
+            ui.end_row();
+            ui.label("GPU culling");
+            ui.checkbox(&mut settings.gpu_culling, "");
+            ui.end_row();
+            ui.label("Occlusion culling");
+            ui.add_enabled(
+                settings.gpu_culling,
+                egui::Checkbox::without_text(&mut settings.occlusion_culling),
+            );
+            ui.end_row();
+            ui.label("Parallel encoding");
+            ui.checkbox(&mut settings.parallel_encoding, "");
+            ui.end_row();
+            ui.label("Level of detail");
+            ui.checkbox(&mut settings.lod, "");
+            ui.end_row();
+        });
+    });
+}
+
+/// A grid row picking one of the options, named as they are debug printed.
+fn choose<T: Copy + PartialEq + std::fmt::Debug>(
+    ui: &mut egui::Ui,
+    name: &str,
+    value: &mut T,
+    options: &[T],
+) {
+    ui.label(name);
+    egui::ComboBox::from_id_salt(name)
+        .selected_text(format!("{value:?}"))
+        .show_ui(ui, |ui| {
+            for &option in options {
+                ui.selectable_value(value, option, format!("{option:?}"));
+            }
+        });
+    ui.end_row();
+}
+
+/// The background's mode and colors, saved with the scene.
+pub fn show_background(ui: &mut egui::Ui, background: &mut Background) {
+    egui::CollapsingHeader::new("Background").show(ui, |ui| {
+        ui.horizontal(|ui| {
+            for (mode, name) in [
+                (BackgroundMode::Solid, "Solid"),
+                (BackgroundMode::Gradient, "Gradient"),
+                (BackgroundMode::Skybox, "Skybox"),
+            ] {
+                ui.selectable_value(&mut background.mode, mode, name);
+            }
+        });
+        egui::Grid::new("background").show(ui, |ui| {
+            for (name, color) in [
+                ("Clear color", &mut background.clear_color),
+                ("Gradient top", &mut background.gradient_top),
+                ("Gradient bottom", &mut background.gradient_bottom),
+            ] {
+                ui.label(name);
+                // Linear, as egui's picker expects.
+                let mut rgb: [f32; 3] = (*color).into();
+                if ui.color_edit_button_rgb(&mut rgb).changed() {
+                    *color = rgb.into();
+                }
+                ui.end_row();
+            }
+        });
+    });
+}
+
+pub fn show_frame_limiter(ui: &mut egui::Ui, limiter: &mut FrameLimiter) {
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut limiter.enabled, "Frame limit");
+        ui.add_enabled(
+            limiter.enabled,
+            egui::DragValue::new(&mut limiter.target_fps)
+                .range(10.0..=1000.0)
+                .suffix(" fps"),
+        );
+    });
+}
+
+/// The colors of the passes in the chart.
+const PASS_COLORS: [egui::Color32; Pass::ALL.len()] = [
+    egui::Color32::from_rgb(120, 120, 220),
+    egui::Color32::from_rgb(90, 190, 110),
+    egui::Color32::from_rgb(220, 160, 60),
+    egui::Color32::from_rgb(200, 90, 160),
+];
+
+/// The passes' GPU times of the recent frames stacked as bars, scaled to the slowest frame, and
+/// their percentiles.
+pub fn show_gpu_timings(ui: &mut egui::Ui, timings: Option<&GpuTimings>) {
+    egui::CollapsingHeader::new("GPU passes").show(ui, |ui| {
+        let Some(timings) = timings else {
+            ui.label("Timestamp queries are not supported");
+            return;
+        };
+        let (rect, _) =
+            ui.allocate_exact_size(egui::vec2(ui.available_width(), 80.0), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+        let slowest = timings
+            .frames()
+            .map(|times| times.iter().sum())
+            .fold(0.0, f32::max)
+            .max(1.0);
+        let width = rect.width() / FRAMES as f32;
+        let start = FRAMES.saturating_sub(timings.frames().len());
+        for (index, times) in timings.frames().enumerate() {
+            let x = rect.left() + (start + index) as f32 * width;
+            let mut bottom = rect.bottom();
+            for (time, color) in times.iter().zip(PASS_COLORS) {
+                let top = bottom - time / slowest * rect.height();
+                painter.rect_filled(
+                    egui::Rect::from_min_max(egui::pos2(x, top), egui::pos2(x + width, bottom)),
+                    0.0,
+                    color,
+                );
+                bottom = top;
+            }
+        }
+        painter.text(
+            rect.left_top() + egui::vec2(4.0, 2.0),
+            egui::Align2::LEFT_TOP,
+            format!("{slowest:.2} ms"),
+            egui::FontId::monospace(10.0),
+            ui.visuals().weak_text_color(),
+        );
+
+        egui::Grid::new("gpu_timings").striped(true).show(ui, |ui| {
+            ui.label("ms");
+            for heading in ["p50", "p95", "p99"] {
+                ui.label(heading);
+            }
+            ui.end_row();
+            let passes = Pass::ALL.into_iter().map(Some).chain([None]);
+            for (pass, color) in
+                passes.zip(PASS_COLORS.into_iter().chain([ui.visuals().text_color()]))
+            {
+                ui.colored_label(color, pass.map_or("Total", Pass::name));
+                for fraction in [0.5, 0.95, 0.99] {
+                    ui.monospace(format!("{:.2}", timings.percentile(pass, fraction)));
+                }
+                ui.end_row();
+            }
+        });
+    });
+}
+
+/// Draws the UI over the surface, after post-processing.
+pub struct UiPass {
+    renderer: egui_wgpu::Renderer,
+}
+
+impl std::fmt::Debug for UiPass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UiPass").finish_non_exhaustive()
+    }
+}
+
+impl UiPass {
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        UiPass {
+            renderer: egui_wgpu::Renderer::new(device, format, None, 1, false),
+        }
+    }
+
+    /// Returns the command buffers of paint callbacks, which must be submitted before the encoder.
+    pub fn draw(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        target: &TextureView,
+        size: (u32, u32),
+        frame: &UiFrame,
+    ) -> Vec<CommandBuffer> {
+        for (id, delta) in &frame.textures.set {
+            self.renderer.update_texture(device, queue, *id, delta);
+        }
+        let screen = ScreenDescriptor {
+            size_in_pixels: [size.0, size.1],
+            pixels_per_point: frame.pixels_per_point,
+        };
+        let command_buffers =
+            self.renderer
+                .update_buffers(device, queue, encoder, &frame.primitives, &screen);
+        let mut pass = encoder
+            .begin_render_pass(&RenderPassDescriptor {
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Load,
+                        store: StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            })
+            .forget_lifetime();
+        self.renderer.render(&mut pass, &frame.primitives, &screen);
+        drop(pass);
+        for id in &frame.textures.free {
+            self.renderer.free_texture(id);
+        }
+        command_buffers
+    }
+}
@@ -0,0 +1,150 @@
+use wgpu::*;
+
+use crate::mesh::Vertex;
+
+const VELOCITY_FORMAT: TextureFormat = TextureFormat::Rg16Float;
+
+/// Screen-space motion of every pixel since the previous frame, in UV units.
+/// Has its own single-sampled depth buffer, so that it works with MSAA as well.
+#[derive(Debug)]
+pub struct VelocityBuffer {
+    pub view: TextureView,
+    depth: TextureView,
+}
+
+impl VelocityBuffer {
+    pub fn new(device: &Device, width: u32, height: u32) -> Self {
+        let create = |format| {
+            device
+                .create_texture(&TextureDescriptor {
+                    label: None,
+                    size: Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format,
+                    view_formats: &[],
+                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                })
+                .create_view(&Default::default())
+        };
+        VelocityBuffer {
+            view: create(VELOCITY_FORMAT),
+            depth: create(TextureFormat::Depth24Plus),
+        }
+    }
+
+    pub fn begin_pass<'a>(&'a self, encoder: &'a mut CommandEncoder) -> RenderPass<'a> {
+        encoder.begin_render_pass(&RenderPassDescriptor {
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: &self.depth,
+                depth_ops: Some(Operations {
+                    load: LoadOp::Clear(1.0),
+                    store: StoreOp::Discard,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct VelocityPipelines {
+    /// Fills the whole buffer with the motion of the far plane.
+    pub background: RenderPipeline,
+    /// Writes the motion of the opaque and masked geometry on top.
+    pub geometry: RenderPipeline,
+}
+
+impl VelocityPipelines {
+    /// Both use the forward pipeline layout.
+    pub fn new(device: &Device, shader_module: &ShaderModule, layout: &PipelineLayout) -> Self {
+        let targets = &[Some(ColorTargetState {
+            format: VELOCITY_FORMAT,
+            blend: None,
+            write_mask: ColorWrites::ALL,
+        })];
+
+        let background = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            cache: None,
+            layout: Some(layout),
+            vertex: VertexState {
+                module: shader_module,
+                entry_point: Some("deferred_vertex"),
+                buffers: &[],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(FragmentState {
+                module: shader_module,
+                entry_point: Some("velocity_background_fragment"),
+                targets,
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            multisample: Default::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth24Plus,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::Always,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multiview: None,
+        });
+
+        let geometry = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            cache: None,
+            layout: Some(layout),
+            vertex: VertexState {
+                module: shader_module,
+                entry_point: Some("velocity_vertex"),
+                buffers: &[Vertex::LAYOUT],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(FragmentState {
+                module: shader_module,
+                entry_point: Some("velocity_fragment"),
+                targets,
+                compilation_options: Default::default(),
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                polygon_mode: PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            multisample: Default::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: TextureFormat::Depth24Plus,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::LessEqual,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multiview: None,
+        });
+
+        VelocityPipelines {
+            background,
+            geometry,
+        }
+    }
+}
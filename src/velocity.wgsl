@@ -0,0 +1,62 @@
+struct VelocityInput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) uv: vec2<f32>,
+    /// Clip-space positions without jitter, in this and the previous frame.
+    @location(4) current: vec4<f32>,
+    @location(5) previous: vec4<f32>,
+    @location(6) @interpolate(flat) instance: u32,
+}
+
+/// Motion in UV units from the previous frame's position to the current one.
+fn screen_velocity(current: vec4<f32>, previous: vec4<f32>) -> vec2<f32> {
+    return (current.xy / current.w - previous.xy / previous.w) * vec2<f32>(0.5, -0.5);
+}
+
+@vertex
+fn velocity_vertex(in: VertexInput) -> VelocityInput {
+    load_object(in.instance);
+    var out: VelocityInput;
+    let world_position = object.model * vec4<f32>(in.position, 1.0);
+    out.position = uniforms.projection * uniforms.view * world_position;
+    out.color = in.color;
+    out.world_position = world_position.xyz;
+    out.normal = (object.normal * vec4<f32>(in.normal, 0.0)).xyz;
+    out.uv = in.uv;
+    out.current = out.position - vec4<f32>(uniforms.jitter * out.position.w, 0.0, 0.0);
+    out.previous = uniforms.previous_view_projection * object.previous_model * vec4<f32>(in.position, 1.0);
+    out.instance = in.instance;
+    return out;
+}
+
+@fragment
+fn velocity_fragment(in: VelocityInput) -> @location(0) vec4<f32> {
+    load_object(in.instance);
+    if material().alpha_cutoff > 0.0 {
+        var surface: FragmentInput;
+        surface.position = in.position;
+        surface.color = in.color;
+        surface.world_position = in.world_position;
+        surface.normal = in.normal;
+        surface.uv = in.uv;
+        surface.lightmap = -1;
+        surface.instance = in.instance;
+        let normal = normalize(in.normal);
+        let alpha = material().base_color.a * blend_vertex_color(sample_base_color(surface, normal), in.color).a;
+        if alpha < material().alpha_cutoff {
+            discard;
+        }
+    }
+    return vec4<f32>(screen_velocity(in.current, in.previous), 0.0, 1.0);
+}
+
+/// Motion of the far plane due to the camera alone, drawn behind the geometry.
+@fragment
+fn velocity_background_fragment(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let ndc = vec2<f32>(2.0, -2.0) * position.xy * uniforms.viewport.zw + vec2<f32>(-1.0, 1.0);
+    let world = uniforms.inverse_view_projection * vec4<f32>(ndc, 1.0, 1.0);
+    let current = vec4<f32>(ndc - uniforms.jitter, 1.0, 1.0);
+    return vec4<f32>(screen_velocity(current, uniforms.previous_view_projection * world), 0.0, 1.0);
+}
@@ -0,0 +1,358 @@
+use std::collections::BTreeSet;
+
+use cgmath::{InnerSpace, Matrix3, Matrix4, Quaternion, SquareMatrix, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    light::{PointLight, RectLight, SpotLight},
+    material::MaterialId,
+    mesh::MeshId,
+    prefab::PrefabId,
+    scene::Object,
+};
+
+/// Identifies an entity of the world. Ids of despawned entities are not reused.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity(pub usize);
+
+/// Places an entity in the world.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform(pub Matrix4<f32>);
+
+impl Transform {
+    pub fn compose(
+        translation: Vector3<f32>,
+        rotation: Quaternion<f32>,
+        scale: Vector3<f32>,
+    ) -> Self {
+        Transform(
+            Matrix4::from_translation(translation)
+                * Matrix4::from(rotation)
+                * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z),
+        )
+    }
+
+    /// Splits the transform into translation, rotation and scale. Shear is lost, and mirroring
+    /// becomes a negative scale along X.
+    pub fn decompose(&self) -> (Vector3<f32>, Quaternion<f32>, Vector3<f32>) {
+        let matrix = self.0;
+        let mut axes = [matrix.x, matrix.y, matrix.z].map(|axis| axis.truncate());
+        let mut scale = axes.map(|axis| axis.magnitude());
+        if Matrix3::from_cols(axes[0], axes[1], axes[2]).determinant() < 0.0 {
+            scale[0] = -scale[0];
+        }
+        for (axis, scale) in axes.iter_mut().zip(scale) {
+            if scale != 0.0 {
+                *axis /= scale;
+            }
+        }
+        let rotation = Quaternion::from(Matrix3::from_cols(axes[0], axes[1], axes[2]));
+        (matrix.w.truncate(), rotation, scale.into())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MeshRef(pub MeshId);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MaterialRef(pub MaterialId);
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Light {
+    Point(PointLight),
+    Spot(SpotLight),
+    Rect(RectLight),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Name(pub String);
+
+/// Render layers as a bit mask. Entities are drawn in views whose mask shares a layer with
+/// theirs, and entities without layers are on the default one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Layers(pub u32);
+
+impl Layers {
+    pub const DEFAULT: Layers = Layers(1);
+    /// Aids such as gizmos, only drawn in the editor view, i.e. through the orbit camera.
+    pub const EDITOR: Layers = Layers(1 << 1);
+    /// Debug geometry, which casts no shadows.
+    pub const DEBUG: Layers = Layers(1 << 2);
+    pub const ALL: Layers = Layers(u32::MAX);
+
+    pub const fn without(self, other: Layers) -> Layers {
+        Layers(self.0 & !other.0)
+    }
+
+    pub fn intersects(self, other: Layers) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Layers::DEFAULT
+    }
+}
+
+/// Marks an entity as one of the objects of a prefab's instance, so that it follows the prefab
+/// when its model is reloaded.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PrefabInstance {
+    pub prefab: PrefabId,
+    /// Index into the prefab's objects.
+    pub object: usize,
+}
+
+/// Labels to query entities by.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Tags(pub BTreeSet<String>);
+
+/// The components of one type, indexed by entity.
+#[derive(Debug, Clone)]
+pub struct Components<T> {
+    values: Vec<Option<T>>,
+}
+
+impl<T> Default for Components<T> {
+    fn default() -> Self {
+        Components { values: Vec::new() }
+    }
+}
+
+impl<T> Components<T> {
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        self.values.get(entity.0)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        self.values.get_mut(entity.0)?.as_mut()
+    }
+
+    /// Replaces the entity's component, if it had one.
+    pub fn insert(&mut self, entity: Entity, value: T) {
+        if self.values.len() <= entity.0 {
+            self.values.resize_with(entity.0 + 1, || None);
+        }
+        self.values[entity.0] = Some(value);
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        self.values.get_mut(entity.0)?.take()
+    }
+
+    /// By ascending entity.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(|(index, value)| Some((Entity(index), value.as_ref()?)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.values
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, value)| Some((Entity(index), value.as_mut()?)))
+    }
+}
+
+/// All components of one entity, to restore it later.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub transform: Option<Transform>,
+    pub mesh: Option<MeshRef>,
+    pub material: Option<MaterialRef>,
+    pub light: Option<Light>,
+    pub name: Option<Name>,
+    pub tags: Option<Tags>,
+    pub layers: Option<Layers>,
+    pub instance: Option<PrefabInstance>,
+}
+
+/// An entity drawn with a mesh, as joined from its components.
+#[derive(Debug, Copy, Clone)]
+pub struct Renderable {
+    pub entity: Entity,
+    pub transform: Matrix4<f32>,
+    pub mesh: MeshId,
+    pub material: MaterialId,
+    pub layers: Layers,
+}
+
+/// Entities with components stored in one column per type. Behaviors are systems, i.e. functions
+/// iterating the entities with the components they need, so that new ones do not have to thread
+/// their own state through the scene.
+#[derive(Debug, Default, Clone)]
+pub struct World {
+    /// Whether each entity is alive, by id.
+    alive: Vec<bool>,
+    pub transforms: Components<Transform>,
+    pub meshes: Components<MeshRef>,
+    pub materials: Components<MaterialRef>,
+    pub lights: Components<Light>,
+    pub names: Components<Name>,
+    pub tags: Components<Tags>,
+    pub layers: Components<Layers>,
+    pub instances: Components<PrefabInstance>,
+}
+
+impl World {
+    /// Creates an entity without components.
+    pub fn spawn(&mut self) -> Entity {
+        self.alive.push(true);
+        Entity(self.alive.len() - 1)
+    }
+
+    /// Creates an entity drawing the object, named like it unless its name is empty.
+    pub fn spawn_object(&mut self, object: &Object) -> Entity {
+        let entity = self.spawn();
+        self.transforms.insert(entity, Transform(object.transform));
+        self.meshes.insert(entity, MeshRef(object.mesh));
+        self.materials.insert(entity, MaterialRef(object.material));
+        if !object.name.is_empty() {
+            self.names.insert(entity, Name(object.name.clone()));
+        }
+        entity
+    }
+
+    pub fn spawn_light(&mut self, light: Light) -> Entity {
+        let entity = self.spawn();
+        self.lights.insert(entity, light);
+        entity
+    }
+
+    /// Removes the entity with all its components.
+    pub fn despawn(&mut self, entity: Entity) {
+        if let Some(alive) = self.alive.get_mut(entity.0) {
+            *alive = false;
+        }
+        self.transforms.remove(entity);
+        self.meshes.remove(entity);
+        self.materials.remove(entity);
+        self.lights.remove(entity);
+        self.names.remove(entity);
+        self.tags.remove(entity);
+        self.layers.remove(entity);
+        self.instances.remove(entity);
+    }
+
+    pub fn name(&self, entity: Entity) -> &str {
+        self.names.get(entity).map_or("unnamed", |name| &name.0)
+    }
+
+    /// The first entity of the name.
+    pub fn find(&self, name: &str) -> Option<Entity> {
+        self.names
+            .iter()
+            .find(|(_, other)| other.0 == name)
+            .map(|(entity, _)| entity)
+    }
+
+    pub fn with_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = Entity> + 'a {
+        self.tags
+            .iter()
+            .filter(move |(_, tags)| tags.0.contains(tag))
+            .map(|(entity, _)| entity)
+    }
+
+    pub fn tag(&mut self, entity: Entity, tag: &str) {
+        match self.tags.get_mut(entity) {
+            Some(tags) => {
+                tags.0.insert(tag.to_string());
+            }
+            None => self
+                .tags
+                .insert(entity, Tags(BTreeSet::from([tag.to_string()]))),
+        }
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.alive.get(entity.0).copied().unwrap_or(false)
+    }
+
+    /// The entity's components, or none if it is not alive.
+    pub fn snapshot(&self, entity: Entity) -> Option<Snapshot> {
+        self.is_alive(entity).then(|| Snapshot {
+            transform: self.transforms.get(entity).copied(),
+            mesh: self.meshes.get(entity).copied(),
+            material: self.materials.get(entity).copied(),
+            light: self.lights.get(entity).copied(),
+            name: self.names.get(entity).cloned(),
+            tags: self.tags.get(entity).cloned(),
+            layers: self.layers.get(entity).copied(),
+            instance: self.instances.get(entity).copied(),
+        })
+    }
+
+    /// Brings the entity back to a snapshot, or despawns it for none.
+    pub fn restore(&mut self, entity: Entity, snapshot: Option<&Snapshot>) {
+        self.despawn(entity);
+        let Some(snapshot) = snapshot else {
+            return;
+        };
+        if self.alive.len() <= entity.0 {
+            self.alive.resize(entity.0 + 1, false);
+        }
+        self.alive[entity.0] = true;
+        if let Some(transform) = snapshot.transform {
+            self.transforms.insert(entity, transform);
+        }
+        if let Some(mesh) = snapshot.mesh {
+            self.meshes.insert(entity, mesh);
+        }
+        if let Some(material) = snapshot.material {
+            self.materials.insert(entity, material);
+        }
+        if let Some(light) = snapshot.light {
+            self.lights.insert(entity, light);
+        }
+        if let Some(name) = &snapshot.name {
+            self.names.insert(entity, name.clone());
+        }
+        if let Some(tags) = &snapshot.tags {
+            self.tags.insert(entity, tags.clone());
+        }
+        if let Some(layers) = snapshot.layers {
+            self.layers.insert(entity, layers);
+        }
+        if let Some(instance) = snapshot.instance {
+            self.instances.insert(entity, instance);
+        }
+    }
+
+    /// Entities with a transform, a mesh and a material, by ascending entity.
+    pub fn renderables(&self) -> impl Iterator<Item = Renderable> + '_ {
+        self.meshes.iter().filter_map(|(entity, mesh)| {
+            Some(Renderable {
+                entity,
+                transform: self.transforms.get(entity)?.0,
+                mesh: mesh.0,
+                material: self.materials.get(entity)?.0,
+                layers: self.layers.get(entity).copied().unwrap_or_default(),
+            })
+        })
+    }
+
+    pub fn point_lights(&self) -> impl Iterator<Item = &PointLight> {
+        self.lights.iter().filter_map(|(_, light)| match light {
+            Light::Point(light) => Some(light),
+            _ => None,
+        })
+    }
+
+    pub fn spot_lights(&self) -> impl Iterator<Item = &SpotLight> {
+        self.lights.iter().filter_map(|(_, light)| match light {
+            Light::Spot(light) => Some(light),
+            _ => None,
+        })
+    }
+
+    pub fn rect_lights(&self) -> impl Iterator<Item = &RectLight> {
+        self.lights.iter().filter_map(|(_, light)| match light {
+            Light::Rect(light) => Some(light),
+            _ => None,
+        })
+    }
+}